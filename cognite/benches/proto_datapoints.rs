@@ -0,0 +1,139 @@
+//! Compare converting protobuf datapoint responses to owned datapoints against
+//! iterating over them in place.
+//!
+//! Run with `cargo bench --bench proto_datapoints`.
+
+use cognite::time_series::{
+    DataPointListItem, DataPointListResponse, DatapointView, DatapointsEnumType,
+    DatapointsListResponse, ListDatapointType, NumericDatapoint, NumericDatapoints,
+    StringDatapoint, StringDatapoints,
+};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+const SERIES: i64 = 100;
+const DATAPOINTS_PER_SERIES: i64 = 10_000;
+
+fn numeric_response() -> DataPointListResponse {
+    DataPointListResponse {
+        items: (0..SERIES)
+            .map(|id| DataPointListItem {
+                id,
+                external_id: format!("ts-{id}"),
+                datapoint_type: Some(ListDatapointType::NumericDatapoints(NumericDatapoints {
+                    datapoints: (0..DATAPOINTS_PER_SERIES)
+                        .map(|ts| NumericDatapoint {
+                            timestamp: ts,
+                            value: ts as f64,
+                            ..Default::default()
+                        })
+                        .collect(),
+                })),
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+fn string_response() -> DataPointListResponse {
+    DataPointListResponse {
+        items: (0..SERIES)
+            .map(|id| DataPointListItem {
+                id,
+                external_id: format!("ts-{id}"),
+                is_string: true,
+                datapoint_type: Some(ListDatapointType::StringDatapoints(StringDatapoints {
+                    datapoints: (0..DATAPOINTS_PER_SERIES)
+                        .map(|ts| StringDatapoint {
+                            timestamp: ts,
+                            value: format!("value-{ts}"),
+                            ..Default::default()
+                        })
+                        .collect(),
+                })),
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+fn sum_owned(response: DataPointListResponse) -> f64 {
+    let mut sum = 0.0;
+    for item in DatapointsListResponse::from(response).items {
+        if let DatapointsEnumType::NumericDatapoints(dps) = item.datapoints {
+            sum += dps.iter().filter_map(|dp| dp.value).sum::<f64>();
+        }
+    }
+    sum
+}
+
+fn sum_views(response: &DataPointListResponse) -> f64 {
+    response
+        .datapoints()
+        .filter_map(|(_, dp)| dp.as_f64())
+        .sum()
+}
+
+fn string_len_owned(response: DataPointListResponse) -> usize {
+    let mut len = 0;
+    for item in DatapointsListResponse::from(response).items {
+        if let DatapointsEnumType::StringDatapoints(dps) = item.datapoints {
+            len += dps
+                .iter()
+                .filter_map(|dp| dp.value.as_ref())
+                .map(|v| v.len())
+                .sum::<usize>();
+        }
+    }
+    len
+}
+
+fn string_len_views(response: &DataPointListResponse) -> usize {
+    response
+        .datapoints()
+        .filter_map(|(_, dp)| match dp {
+            DatapointView::String(_, value, _) => value,
+            _ => None,
+        })
+        .map(|v| v.len())
+        .sum()
+}
+
+fn bench_numeric(c: &mut Criterion) {
+    let response = numeric_response();
+    let mut group = c.benchmark_group("numeric");
+    group.throughput(Throughput::Elements(
+        (SERIES * DATAPOINTS_PER_SERIES) as u64,
+    ));
+    // Conversion consumes the response, so clone it outside the measured section.
+    group.bench_function("owned", |b| {
+        b.iter_batched(
+            || response.clone(),
+            |r| black_box(sum_owned(r)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("view", |b| b.iter(|| black_box(sum_views(&response))));
+    group.finish();
+}
+
+fn bench_string(c: &mut Criterion) {
+    let response = string_response();
+    let mut group = c.benchmark_group("string");
+    group.throughput(Throughput::Elements(
+        (SERIES * DATAPOINTS_PER_SERIES) as u64,
+    ));
+    group.bench_function("owned", |b| {
+        b.iter_batched(
+            || response.clone(),
+            |r| black_box(string_len_owned(r)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("view", |b| {
+        b.iter(|| black_box(string_len_views(&response)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_numeric, bench_string);
+criterion_main!(benches);
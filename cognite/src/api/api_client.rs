@@ -13,6 +13,57 @@ use crate::error::{Error, Result};
 
 use super::request_builder::RequestBuilder;
 
+/// Version of the CDF API, sent in the `cdf-version` header. Use this to opt into
+/// preview (alpha or beta) endpoints and fields.
+///
+/// Note that preview features may change or be removed without notice.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ApiVersion {
+    /// Alpha version of the API.
+    Alpha,
+    /// Beta version of the API.
+    Beta,
+    /// Some other version, for example a dated version like `20230101-beta`.
+    Custom(String),
+}
+
+impl ApiVersion {
+    /// Get the value of the `cdf-version` header for this version.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ApiVersion::Alpha => "alpha",
+            ApiVersion::Beta => "beta",
+            ApiVersion::Custom(v) => v,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for ApiVersion {
+    fn from(value: &str) -> Self {
+        match value {
+            "alpha" => ApiVersion::Alpha,
+            "beta" => ApiVersion::Beta,
+            r => ApiVersion::Custom(r.to_owned()),
+        }
+    }
+}
+
+impl From<String> for ApiVersion {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "alpha" => ApiVersion::Alpha,
+            "beta" => ApiVersion::Beta,
+            _ => ApiVersion::Custom(value),
+        }
+    }
+}
+
 /// API client, used to query CDF.
 pub struct ApiClient {
     api_base_url: String,
@@ -55,6 +106,17 @@ impl ApiClient {
         }
     }
 
+    /// Create a new api client without a custom API version, so that
+    /// requests are made against the stable API.
+    pub fn clone_without_api_version(&self) -> ApiClient {
+        ApiClient {
+            api_base_url: self.api_base_url.clone(),
+            app_name: self.app_name.clone(),
+            client: self.client.clone(),
+            api_version: None,
+        }
+    }
+
     /// Perform a get request to the given path, deserializing the result from JSON.
     ///
     /// # Arguments
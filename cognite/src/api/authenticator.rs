@@ -151,7 +151,7 @@ struct AuthenticatorResponse {
     expires_in: Option<MaybeStringU64>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Error)]
+#[derive(Serialize, Deserialize, Debug, Clone, Error)]
 /// Error from an authenticator request.
 pub struct AuthenticatorError {
     /// Error message
@@ -3,3 +3,4 @@ pub(crate) mod events;
 pub(crate) mod files;
 pub(crate) mod sequences;
 pub(crate) mod time_series;
+pub(crate) mod units;
@@ -4,8 +4,9 @@ use std::collections::HashSet;
 use crate::api::resource::*;
 use crate::dto::core::asset::*;
 use crate::error::Result;
+use crate::utils::batch_loader::LoadBatch;
 use crate::utils::lease::CleanResource;
-use crate::{IdentityList, ItemsVec, Patch};
+use crate::{Identity, IdentityList, ItemsVec, Patch};
 
 /// Assets represent objects or groups of objects from the physical world.
 /// Assets are organized in hierarchies. For example, a water pump asset can
@@ -35,6 +36,12 @@ where
 {
 }
 
+impl LoadBatch<Identity, Asset> for AssetsResource {
+    async fn load_batch(&self, keys: &[Identity]) -> Result<Vec<Asset>> {
+        self.retrieve(keys, true, None).await
+    }
+}
+
 impl AssetsResource {
     /// Retrieve a list of assets by their IDs.
     ///
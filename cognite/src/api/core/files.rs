@@ -1,3 +1,10 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod ranged_download;
+#[cfg(not(target_arch = "wasm32"))]
+mod resumable_upload;
+#[cfg(not(target_arch = "wasm32"))]
+mod transfer;
+
 use std::collections::HashSet;
 
 use bytes::Bytes;
@@ -14,6 +21,11 @@ use crate::{
 };
 use crate::{Identity, ItemsVec, Patch};
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use ranged_download::{DownloadProgress, DownloadProgressCallback, RangedDownloadConfig};
+#[cfg(not(target_arch = "wasm32"))]
+pub use resumable_upload::{MultipartUploadState, ResumableUploadConfig};
+
 /// Files store documents, binary blobs, and other file data and relate it to assets.
 pub type Files = Resource<FileMetadata>;
 
@@ -75,7 +87,7 @@ impl<'a> MultipartUploader<'a> {
         }
 
         self.resource
-            .upload_stream_known_size("", &self.urls.upload_urls[0], stream, size)
+            .upload_stream_known_size("", &self.urls.upload_urls[part_no], stream, size)
             .await
     }
 
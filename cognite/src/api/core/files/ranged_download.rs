@@ -0,0 +1,459 @@
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::header::{CONTENT_RANGE, ETAG, RANGE};
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::error::Result;
+use crate::{Error, IdentityOrInstance, RequestBuilder};
+
+use super::transfer::{load_state, save_state, with_retries, with_suffix};
+use super::Files;
+
+/// Progress of a file download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Number of bytes downloaded so far, including bytes downloaded before resuming.
+    pub downloaded: u64,
+    /// Total size of the file in bytes.
+    pub total: u64,
+}
+
+/// Function called with the progress of a download after each chunk.
+pub type DownloadProgressCallback = Arc<dyn Fn(DownloadProgress) + Send + Sync>;
+
+#[derive(Clone)]
+/// Configuration for ranged file downloads.
+pub struct RangedDownloadConfig {
+    /// Size of each chunk fetched with a single range request, in bytes.
+    pub chunk_size: u64,
+    /// Maximum number of chunks downloaded in parallel. Each chunk in flight is held in memory.
+    pub parallelism: usize,
+    /// Maximum number of retries for each chunk. Only transient errors, like server errors
+    /// and timeouts, are retried. This is in addition to any retries done by the client.
+    pub max_chunk_retries: u32,
+    /// Delay before the first retry of a chunk. The delay is doubled for each retry.
+    pub initial_retry_delay: Duration,
+    /// Called with the progress of the download after each chunk.
+    pub on_progress: Option<DownloadProgressCallback>,
+}
+
+impl Default for RangedDownloadConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 8 * 1024 * 1024,
+            parallelism: 4,
+            max_chunk_retries: 3,
+            initial_retry_delay: Duration::from_millis(500),
+            on_progress: None,
+        }
+    }
+}
+
+impl Debug for RangedDownloadConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RangedDownloadConfig")
+            .field("chunk_size", &self.chunk_size)
+            .field("parallelism", &self.parallelism)
+            .field("max_chunk_retries", &self.max_chunk_retries)
+            .field("initial_retry_delay", &self.initial_retry_delay)
+            .field("on_progress", &self.on_progress.as_ref().map(|_| "Fn"))
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// Version of the remote file, taken from a range response.
+struct RemoteFile {
+    /// Total size of the file in bytes.
+    size: u64,
+    /// ETag of the file, if the server returned one.
+    etag: Option<String>,
+}
+
+impl RemoteFile {
+    /// Whether `other` may be the same version of the file. ETags are only compared
+    /// if both are known.
+    fn matches(&self, other: &RemoteFile) -> bool {
+        self.size == other.size
+            && (self.etag.is_none() || other.etag.is_none() || self.etag == other.etag)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Checkpoint of a ranged download, stored next to the partial file so that
+/// the download can be resumed.
+struct DownloadState {
+    /// Version of the file being downloaded.
+    #[serde(flatten)]
+    file: RemoteFile,
+    /// Size of each chunk except the last, in bytes.
+    chunk_size: u64,
+    /// Chunks that have been written to the partial file.
+    completed_chunks: BTreeSet<u64>,
+}
+
+impl DownloadState {
+    fn num_chunks(&self) -> u64 {
+        self.file.size.div_ceil(self.chunk_size)
+    }
+
+    fn chunk_range(&self, chunk: u64) -> (u64, u64) {
+        let offset = chunk * self.chunk_size;
+        let len = self.chunk_size.min(self.file.size.saturating_sub(offset));
+        (offset, len)
+    }
+
+    fn downloaded(&self) -> u64 {
+        self.completed_chunks
+            .iter()
+            .map(|c| self.chunk_range(*c).1)
+            .sum()
+    }
+}
+
+/// Parse the start offset and the remote file from a range response, with a
+/// `Content-Range` header like `bytes 0-1023/4096`.
+fn parse_range_response(response: &Response) -> Result<(u64, RemoteFile)> {
+    let (start, size) = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("bytes "))
+        .and_then(|h| h.split_once('/'))
+        .and_then(|(range, total)| {
+            let (start, _) = range.split_once('-')?;
+            Some((start.parse().ok()?, total.parse().ok()?))
+        })
+        .ok_or_else(|| {
+            Error::Other("Range response is missing a valid Content-Range header".to_owned())
+        })?;
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.to_owned());
+    Ok((start, RemoteFile { size, etag }))
+}
+
+/// A chunk of a file fetched with a range request.
+struct Chunk {
+    data: Bytes,
+    start: u64,
+    file: RemoteFile,
+}
+
+/// Whether an error may be caused by an expired download URL.
+fn is_expired_url_error(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::BadRequest(_) | Error::Unauthorized(_) | Error::Forbidden(_)
+    )
+}
+
+async fn write_at(path: &Path, offset: u64, data: &[u8]) -> Result<()> {
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    file.write_all(data).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+/// Shared download URL, refreshed when it expires.
+struct DownloadUrl<'a> {
+    files: &'a Files,
+    id: &'a IdentityOrInstance,
+    url: Mutex<String>,
+}
+
+impl DownloadUrl<'_> {
+    fn get(&self) -> String {
+        self.url.lock().unwrap().clone()
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let url = self.files.download_url(self.id).await?;
+        *self.url.lock().unwrap() = url;
+        Ok(())
+    }
+
+    async fn send_range(&self, offset: u64, len: u64) -> Result<Response> {
+        RequestBuilder::get(&self.files.api_client, self.get())
+            .omit_auth_headers()
+            .header(RANGE, format!("bytes={}-{}", offset, offset + len - 1))
+            .accept_raw()
+            .send()
+            .await
+    }
+
+    /// Request a range of the file, refreshing the download URL once if it has expired.
+    async fn get_range(&self, offset: u64, len: u64) -> Result<Response> {
+        match self.send_range(offset, len).await {
+            Err(e) if is_expired_url_error(&e) => {
+                self.refresh().await?;
+                self.send_range(offset, len).await
+            }
+            r => r,
+        }
+    }
+
+    async fn fetch_chunk(
+        &self,
+        offset: u64,
+        len: u64,
+        config: &RangedDownloadConfig,
+    ) -> Result<Chunk> {
+        with_retries(
+            config.max_chunk_retries,
+            config.initial_retry_delay,
+            || async {
+                let response = self.get_range(offset, len).await?;
+                if response.status() != StatusCode::PARTIAL_CONTENT {
+                    return Err(Error::Other(
+                        "Server does not support range requests".to_owned(),
+                    ));
+                }
+                let (start, file) = parse_range_response(&response)?;
+                let data = response.bytes().await?;
+                Ok(Chunk { data, start, file })
+            },
+        )
+        .await
+    }
+}
+
+impl Files {
+    async fn download_url(&self, id: &IdentityOrInstance) -> Result<String> {
+        self.download_link(std::slice::from_ref(id))
+            .await?
+            .into_iter()
+            .next()
+            .map(|l| l.download_url)
+            .ok_or_else(|| Error::Other("No download URL returned for file".to_owned()))
+    }
+
+    /// Download the full response body to `part_path`, for servers without range support.
+    async fn download_whole(
+        &self,
+        response: Response,
+        part_path: &Path,
+        config: &RangedDownloadConfig,
+    ) -> Result<u64> {
+        let total = response.content_length();
+        let mut file = tokio::fs::File::create(part_path).await?;
+        let mut body = response.bytes_stream();
+        let mut downloaded = 0;
+        while let Some(data) = body.try_next().await? {
+            file.write_all(&data).await?;
+            downloaded += data.len() as u64;
+            if let Some(cb) = &config.on_progress {
+                cb(DownloadProgress {
+                    downloaded,
+                    total: total.unwrap_or(downloaded),
+                });
+            }
+        }
+        file.flush().await?;
+        if total.is_some_and(|t| t != downloaded) {
+            return Err(Error::Other(format!(
+                "Downloaded {downloaded} bytes, expected {}",
+                total.unwrap_or_default()
+            )));
+        }
+        Ok(downloaded)
+    }
+
+    /// Download a file to disk, fetching chunks in parallel with HTTP range requests.
+    ///
+    /// The file is written to `path` with a `.part` suffix, and the completed chunks are
+    /// recorded in a state file with a `.part.state` suffix. If these exist when this is
+    /// called, the download is resumed, and only missing chunks are fetched. Once all chunks
+    /// are downloaded, the file is moved to `path`.
+    ///
+    /// The size and ETag of the file are recorded when the download starts, and every chunk
+    /// is checked against them. If the file has changed, for example if it was replaced
+    /// before a download was resumed, the partial download is discarded and an error is
+    /// returned. Calling this again downloads the new version from the start.
+    ///
+    /// Expired download URLs are refreshed automatically. If the server does not support
+    /// range requests, the file is downloaded in a single request, and cannot be resumed.
+    ///
+    /// Returns the size of the downloaded file in bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID, external ID, or instance ID of the file to download.
+    /// * `path` - Path to write the file to.
+    /// * `config` - Configuration for the download.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let config = RangedDownloadConfig {
+    ///     on_progress: Some(Arc::new(|p: DownloadProgress| println!("{}/{}", p.downloaded, p.total))),
+    ///     ..Default::default()
+    /// };
+    /// client.files.download_to_path(Identity::from("my-file").into(), "my-file.las", config).await?;
+    /// ```
+    pub async fn download_to_path(
+        &self,
+        id: IdentityOrInstance,
+        path: impl AsRef<Path>,
+        config: RangedDownloadConfig,
+    ) -> Result<u64> {
+        let path = path.as_ref();
+        let part_path = with_suffix(path, ".part");
+        let state_path = with_suffix(path, ".part.state");
+        let chunk_size = config.chunk_size.max(1);
+        let url = DownloadUrl {
+            files: self,
+            id: &id,
+            url: Mutex::new(self.download_url(&id).await?),
+        };
+
+        let existing = match load_state::<DownloadState>(&state_path).await? {
+            Some(state) if tokio::fs::try_exists(&part_path).await? => Some(state),
+            _ => None,
+        };
+        let mut state = match existing {
+            Some(state) => state,
+            None => {
+                // The first chunk tells us the total size, and whether ranges are supported.
+                let response = match url.get_range(0, chunk_size).await {
+                    // Range requests for empty files are not satisfiable.
+                    Err(Error::OtherApiError(e))
+                        if e.code == StatusCode::RANGE_NOT_SATISFIABLE.as_u16() as u32 =>
+                    {
+                        tokio::fs::File::create(path).await?;
+                        return Ok(0);
+                    }
+                    r => r?,
+                };
+                if response.status() != StatusCode::PARTIAL_CONTENT {
+                    let size = self.download_whole(response, &part_path, &config).await?;
+                    tokio::fs::rename(&part_path, path).await?;
+                    return Ok(size);
+                }
+                let (start, file) = parse_range_response(&response)?;
+                let data = response.bytes().await?;
+                let mut state = DownloadState {
+                    file,
+                    chunk_size,
+                    completed_chunks: BTreeSet::new(),
+                };
+                let part = tokio::fs::File::create(&part_path).await?;
+                part.set_len(state.file.size).await?;
+                drop(part);
+                if start == 0 && data.len() as u64 == state.chunk_range(0).1 {
+                    write_at(&part_path, 0, &data).await?;
+                    state.completed_chunks.insert(0);
+                }
+                save_state(&state, &state_path).await?;
+                state
+            }
+        };
+
+        let remaining: Vec<_> = (0..state.num_chunks())
+            .filter(|c| !state.completed_chunks.contains(c))
+            .collect();
+        let ranges: Vec<_> = remaining
+            .into_iter()
+            .map(|c| (c, state.chunk_range(c)))
+            .collect();
+        let url = &url;
+        let part_path_ref = &part_path;
+        let config_ref = &config;
+        let expected = state.file.clone();
+        let expected = &expected;
+        // Yields `None` if the file has changed since the download started.
+        let mut chunks = std::pin::pin!(stream::iter(ranges)
+            .map(|(chunk, (offset, len))| async move {
+                let res = url.fetch_chunk(offset, len, config_ref).await?;
+                if !expected.matches(&res.file) {
+                    return Ok(None);
+                }
+                if res.start != offset || res.data.len() as u64 != len {
+                    return Err(Error::Other(format!(
+                        "Expected {len} bytes at offset {offset}, got {} bytes at offset {}",
+                        res.data.len(),
+                        res.start
+                    )));
+                }
+                write_at(part_path_ref, offset, &res.data).await?;
+                Ok::<_, Error>(Some(chunk))
+            })
+            .buffer_unordered(config.parallelism.max(1)));
+        if let Some(cb) = &config.on_progress {
+            cb(DownloadProgress {
+                downloaded: state.downloaded(),
+                total: state.file.size,
+            });
+        }
+        while let Some(chunk) = chunks.try_next().await? {
+            let Some(chunk) = chunk else {
+                tokio::fs::remove_file(&state_path).await?;
+                tokio::fs::remove_file(&part_path).await?;
+                return Err(Error::Other(
+                    "File has changed since the download started, the partial download was discarded"
+                        .to_owned(),
+                ));
+            };
+            state.completed_chunks.insert(chunk);
+            save_state(&state, &state_path).await?;
+            if let Some(cb) = &config.on_progress {
+                cb(DownloadProgress {
+                    downloaded: state.downloaded(),
+                    total: state.file.size,
+                });
+            }
+        }
+
+        tokio::fs::rename(&part_path, path).await?;
+        tokio::fs::remove_file(&state_path).await?;
+        Ok(state.file.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{DownloadState, RemoteFile};
+
+    #[test]
+    fn test_download_state_chunks() {
+        let state = DownloadState {
+            file: RemoteFile {
+                size: 25,
+                etag: None,
+            },
+            chunk_size: 10,
+            completed_chunks: BTreeSet::from([0, 2]),
+        };
+        assert_eq!(3, state.num_chunks());
+        assert_eq!((20, 5), state.chunk_range(2));
+        assert_eq!(15, state.downloaded());
+    }
+
+    #[test]
+    fn test_remote_file_matches() {
+        let file = |size, etag: Option<&str>| RemoteFile {
+            size,
+            etag: etag.map(|e| e.to_owned()),
+        };
+        assert!(file(10, Some("a")).matches(&file(10, Some("a"))));
+        assert!(file(10, None).matches(&file(10, Some("a"))));
+        assert!(!file(10, Some("a")).matches(&file(10, Some("b"))));
+        assert!(!file(10, Some("a")).matches(&file(11, Some("a"))));
+    }
+}
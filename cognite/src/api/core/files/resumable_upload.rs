@@ -0,0 +1,359 @@
+use std::collections::BTreeSet;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+
+use crate::dto::core::files::*;
+use crate::error::Result;
+use crate::{Error, Identity, IdentityOrInstance};
+
+use super::transfer::{load_state, save_state, with_retries};
+use super::Files;
+
+/// Smallest allowed size of each part except the last, 5 MiB.
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+/// Largest allowed size of each part, 4000 MiB.
+const MAX_PART_SIZE: u64 = 4000 * 1024 * 1024;
+/// Maximum number of parts in a multipart upload.
+const MAX_PARTS: u64 = 250;
+
+#[derive(Debug, Clone)]
+/// Configuration for resumable multipart file uploads.
+pub struct ResumableUploadConfig {
+    /// Size of each part in bytes. If this is not set, the smallest part size that
+    /// fits the file in at most 250 parts is used, rounded up to whole MiB, and at least 5 MiB.
+    pub part_size: Option<u64>,
+    /// Maximum number of parts uploaded in parallel. Each part in flight is held in memory.
+    pub parallelism: usize,
+    /// Maximum number of retries for each part. Only transient errors, like server errors
+    /// and timeouts, are retried. This is in addition to any retries done by the client.
+    pub max_part_retries: u32,
+    /// Delay before the first retry of a part. The delay is doubled for each retry.
+    pub initial_retry_delay: Duration,
+    /// Overwrite an existing file with the same external ID when starting a new upload.
+    pub overwrite: bool,
+}
+
+impl Default for ResumableUploadConfig {
+    fn default() -> Self {
+        Self {
+            part_size: None,
+            parallelism: 4,
+            max_part_retries: 3,
+            initial_retry_delay: Duration::from_millis(500),
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Checkpoint of a multipart upload, stored in the state file so that the upload
+/// can be resumed.
+pub struct MultipartUploadState {
+    /// Metadata of the file being uploaded.
+    pub file: FileMetadata,
+    /// Upload ID and upload URLs for each part.
+    pub urls: MultiUploadUrls,
+    /// Total size of the file in bytes.
+    pub size: u64,
+    /// Size of each part except the last, in bytes.
+    pub part_size: u64,
+    /// Parts that have been uploaded.
+    pub completed_parts: BTreeSet<usize>,
+}
+
+impl MultipartUploadState {
+    /// Load a checkpoint from a state file, returning `None` if the file does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the state file.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        load_state(path.as_ref()).await
+    }
+
+    /// Write this checkpoint to a state file. The state is written to a temporary file
+    /// first, which replaces the state file, so an interrupted write never leaves a
+    /// corrupt state file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the state file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        save_state(self, path.as_ref()).await
+    }
+
+    /// Number of parts in the upload.
+    pub fn num_parts(&self) -> usize {
+        self.urls.upload_urls.len()
+    }
+
+    /// Whether all parts have been uploaded.
+    pub fn is_complete(&self) -> bool {
+        self.completed_parts.len() >= self.num_parts()
+    }
+
+    fn part_range(&self, part: usize) -> (u64, u64) {
+        let offset = part as u64 * self.part_size;
+        let len = self.part_size.min(self.size.saturating_sub(offset));
+        (offset, len)
+    }
+
+    fn remaining_parts(&self) -> Vec<usize> {
+        (0..self.num_parts())
+            .filter(|p| !self.completed_parts.contains(p))
+            .collect()
+    }
+}
+
+fn part_layout(size: u64, part_size: Option<u64>) -> Result<(u64, u32)> {
+    let part_size = match part_size {
+        Some(part_size) => {
+            if part_size == 0 || part_size > MAX_PART_SIZE {
+                return Err(Error::Other(format!(
+                    "Part size must be between 1 and {MAX_PART_SIZE} bytes"
+                )));
+            }
+            part_size
+        }
+        None => {
+            const MIB: u64 = 1024 * 1024;
+            size.div_ceil(MAX_PARTS)
+                .div_ceil(MIB)
+                .saturating_mul(MIB)
+                .max(MIN_PART_SIZE)
+        }
+    };
+    let parts = size.div_ceil(part_size).max(1);
+    if parts > 1 && part_size < MIN_PART_SIZE {
+        return Err(Error::Other(format!(
+            "Part size must be at least {MIN_PART_SIZE} bytes when uploading more than one part"
+        )));
+    }
+    if parts > MAX_PARTS {
+        return Err(Error::Other(format!(
+            "File of {size} bytes needs {parts} parts of {part_size} bytes, the maximum is {MAX_PARTS}"
+        )));
+    }
+    Ok((part_size, parts as u32))
+}
+
+async fn read_file_part(path: &Path, offset: u64, len: u64) -> Result<Bytes> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut buf = vec![0; len as usize];
+    file.read_exact(&mut buf).await?;
+    Ok(buf.into())
+}
+
+impl Files {
+    async fn upload_part_with_retries(
+        &self,
+        url: &str,
+        data: Bytes,
+        config: &ResumableUploadConfig,
+    ) -> Result<()> {
+        with_retries(config.max_part_retries, config.initial_retry_delay, || {
+            self.upload_blob("", url, data.clone())
+        })
+        .await
+    }
+
+    async fn begin_resumable_upload(
+        &self,
+        item: &AddFile,
+        size: u64,
+        state_path: &Path,
+        config: &ResumableUploadConfig,
+    ) -> Result<MultipartUploadState> {
+        if let Some(state) = MultipartUploadState::load(state_path).await? {
+            if state.size != size {
+                return Err(Error::Other(format!(
+                    "State file is for an upload of {} bytes, but the file is {size} bytes",
+                    state.size
+                )));
+            }
+            return Ok(state);
+        }
+        let (part_size, parts) = part_layout(size, config.part_size)?;
+        let res = self
+            .init_multipart_upload(config.overwrite, parts, item)
+            .await?;
+        let state = MultipartUploadState {
+            file: res.metadata,
+            urls: res.extra,
+            size,
+            part_size,
+            completed_parts: BTreeSet::new(),
+        };
+        state.save(state_path).await?;
+        Ok(state)
+    }
+
+    async fn finish_resumable_upload<S>(
+        &self,
+        mut state: MultipartUploadState,
+        state_path: &Path,
+        parts: S,
+        config: &ResumableUploadConfig,
+    ) -> Result<FileMetadata>
+    where
+        S: futures::TryStream<Ok = (usize, Bytes), Error = Error>,
+    {
+        let urls = state.urls.upload_urls.clone();
+        let mut uploads = std::pin::pin!(parts
+            .map_ok(|(part, data)| {
+                let url = &urls[part];
+                async move {
+                    self.upload_part_with_retries(url, data, config).await?;
+                    Ok(part)
+                }
+            })
+            .try_buffer_unordered(config.parallelism.max(1)));
+        while let Some(part) = uploads.try_next().await? {
+            state.completed_parts.insert(part);
+            state.save(state_path).await?;
+        }
+
+        self.complete_multipart_upload(
+            IdentityOrInstance::Identity(Identity::Id { id: state.file.id }),
+            state.urls.upload_id.clone(),
+        )
+        .await?;
+        tokio::fs::remove_file(state_path).await?;
+        Ok(state.file)
+    }
+
+    /// Upload a file from disk in multiple parts, with parallel part uploads, and a
+    /// checkpoint that allows the upload to be resumed.
+    ///
+    /// The upload URLs and completed parts are written to `state_path` after each part.
+    /// If `state_path` exists when this is called, the upload it describes is resumed,
+    /// and only the missing parts are uploaded. Once all parts are uploaded, the upload
+    /// is completed, and the state file is deleted.
+    ///
+    /// Upload URLs expire after some time, so an upload that is resumed too late will fail.
+    /// Delete the state file to start over.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to upload.
+    /// * `item` - Metadata of the file to create. Ignored when resuming an upload.
+    /// * `state_path` - Path to the state file.
+    /// * `config` - Configuration for the upload.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let file = client
+    ///     .files
+    ///     .upload_file_resumable("data.bin", &item, "data.bin.upload", Default::default())
+    ///     .await?;
+    /// ```
+    pub async fn upload_file_resumable(
+        &self,
+        path: impl AsRef<Path>,
+        item: &AddFile,
+        state_path: impl AsRef<Path>,
+        config: ResumableUploadConfig,
+    ) -> Result<FileMetadata> {
+        let path = path.as_ref();
+        let state_path = state_path.as_ref();
+        let size = tokio::fs::metadata(path).await?.len();
+        let state = self
+            .begin_resumable_upload(item, size, state_path, &config)
+            .await?;
+
+        let ranges: Vec<_> = state
+            .remaining_parts()
+            .into_iter()
+            .map(|part| (part, state.part_range(part)))
+            .collect();
+        let parts =
+            stream::iter(ranges.into_iter().map(Ok::<_, Error>)).and_then(
+                |(part, (offset, len))| async move {
+                    Ok((part, read_file_part(path, offset, len).await?))
+                },
+            );
+        self.finish_resumable_upload(state, state_path, parts, &config)
+            .await
+    }
+
+    /// Upload data from a reader in multiple parts, with parallel part uploads, and a
+    /// checkpoint that allows the upload to be resumed.
+    ///
+    /// This works like [upload_file_resumable](Self::upload_file_resumable), except that
+    /// the data is read sequentially from `reader`. When resuming, the reader must produce
+    /// the same data from the start, and data for parts that are already uploaded is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader producing the data to upload.
+    /// * `size` - Total size of the data in bytes.
+    /// * `item` - Metadata of the file to create. Ignored when resuming an upload.
+    /// * `state_path` - Path to the state file.
+    /// * `config` - Configuration for the upload.
+    pub async fn upload_reader_resumable<R>(
+        &self,
+        reader: R,
+        size: u64,
+        item: &AddFile,
+        state_path: impl AsRef<Path>,
+        config: ResumableUploadConfig,
+    ) -> Result<FileMetadata>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let state_path = state_path.as_ref();
+        let state = self
+            .begin_resumable_upload(item, size, state_path, &config)
+            .await?;
+
+        let ranges: Vec<_> = (0..state.num_parts())
+            .map(|part| (part, state.part_range(part).1))
+            .collect();
+        let completed = state.completed_parts.clone();
+        let parts = stream::try_unfold(
+            (reader, ranges.into_iter()),
+            move |(mut reader, mut ranges)| {
+                let completed = completed.clone();
+                async move {
+                    for (part, len) in ranges.by_ref() {
+                        let mut buf = vec![0; len as usize];
+                        reader.read_exact(&mut buf).await?;
+                        if !completed.contains(&part) {
+                            return Ok(Some(((part, Bytes::from(buf)), (reader, ranges))));
+                        }
+                    }
+                    Ok(None)
+                }
+            },
+        );
+        self.finish_resumable_upload(state, state_path, parts, &config)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{part_layout, MIN_PART_SIZE};
+
+    #[test]
+    fn test_part_layout() {
+        const MIB: u64 = 1024 * 1024;
+        assert_eq!((MIN_PART_SIZE, 1), part_layout(0, None).unwrap());
+        assert_eq!((MIN_PART_SIZE, 1), part_layout(100, None).unwrap());
+        assert_eq!((MIN_PART_SIZE, 3), part_layout(12 * MIB, None).unwrap());
+        assert_eq!((8 * MIB, 250), part_layout(2000 * MIB, None).unwrap());
+        assert_eq!((9 * MIB, 223), part_layout(2001 * MIB, None).unwrap());
+        assert_eq!((10, 1), part_layout(10, Some(10)).unwrap());
+        assert!(part_layout(20, Some(10)).is_err());
+        assert!(part_layout(1300 * MIB, Some(MIN_PART_SIZE)).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Helpers shared by resumable uploads and ranged downloads.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::Error;
+
+/// Append `suffix` to the file name in `path`.
+pub(super) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Load a JSON state file, returning `None` if the file does not exist.
+pub(super) async fn load_state<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write a JSON state file. The state is written to a temporary file first, which
+/// replaces the state file, so an interrupted write never leaves a corrupt state file.
+pub(super) async fn save_state<T: Serialize>(state: &T, path: &Path) -> Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(state)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+/// Whether a failed transfer may succeed if retried, like server errors, rate limiting,
+/// and timeouts. Other errors, like a 4xx response for an expired URL, are permanent.
+pub(super) fn is_transient_error(err: &Error) -> bool {
+    match err {
+        Error::OtherApiError(e) => e.code >= 500 || e.code == 408 || e.code == 429,
+        Error::Reqwest(e) => e.is_timeout() || e.is_connect() || e.is_body(),
+        _ => false,
+    }
+}
+
+/// Run `op` until it succeeds, retrying transient errors up to `max_retries` times.
+/// The delay before each retry starts at `initial_delay`, and is doubled for each retry.
+pub(super) async fn with_retries<T, F, Fut>(
+    max_retries: u32,
+    initial_delay: Duration,
+    mut op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut delay = initial_delay;
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(res) => return Ok(res),
+            Err(e) if attempt < max_retries && is_transient_error(&e) => {
+                attempt += 1;
+                futures_timer::Delay::new(delay).await;
+                delay *= 2;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -1,4 +1,13 @@
+mod align;
 mod datapoints_stream;
+mod files;
+mod linked;
+mod replace;
+mod sharded;
+mod subscriptions;
+mod tail;
+mod units;
+mod upload_queue;
 
 use std::collections::HashSet;
 use std::iter::FromIterator;
@@ -23,13 +32,30 @@ use crate::Items;
 use crate::ItemsVec;
 use crate::Patch;
 
-pub use datapoints_stream::{DataPointRef, DatapointsStreamOptions, EitherDataPoint};
+pub use align::*;
+pub use datapoints_stream::{
+    DataPointRef, DatapointsBatch, DatapointsStreamOptions, EitherDataPoint, TimeSeriesRef,
+};
+pub use files::{
+    CsvExportOptions, CsvImportOptions, CsvLayout, DatapointsColumns, WideColumnIdentity,
+};
+#[cfg(feature = "parquet")]
+pub use files::{ParquetExportOptions, ParquetImportOptions};
+pub use linked::{LinkedDatapoints, LinkedTimeSeriesSource};
+pub use replace::ReplaceDatapointsError;
+pub use sharded::{ShardedRetrievalOptions, ShardingStrategy};
+pub use subscriptions::*;
+pub use tail::DatapointsTailOptions;
+pub use upload_queue::*;
 
 /// A time series consists of a sequence of data points connected to a single asset.
 /// For example, a water pump asset can have a temperature time series taht records a data point in
 /// units of °C every second.
 pub type TimeSeriesResource = Resource<TimeSeries>;
 
+/// Maximum number of time series per request for latest datapoints.
+const MAX_LATEST_DATAPOINTS_PER_REQUEST: usize = 100;
+
 impl WithBasePath for TimeSeriesResource {
     const BASE_PATH: &'static str = "timeseries";
 }
@@ -251,7 +277,25 @@ impl TimeSeriesResource {
         datapoints_filter: &DatapointsFilter,
     ) -> Result<Vec<DatapointsResponse>> {
         let datapoints_response = self.retrieve_datapoints_proto(datapoints_filter).await?;
-        Ok(DatapointsListResponse::from(datapoints_response).items)
+        let mut queries = datapoints_filter.items.iter();
+        let items = datapoints_response
+            .items
+            .into_iter()
+            .map(|item| {
+                // Items are returned in the same order as the queries, but queries for
+                // missing time series are skipped.
+                let time_zone = queries
+                    .find(|q| DatapointsStream::equals_identity(&q.id, &item))
+                    .and_then(|q| q.time_zone.as_ref())
+                    .or(datapoints_filter.time_zone.as_ref())
+                    .cloned();
+                DatapointsResponse {
+                    time_zone,
+                    ..item.into()
+                }
+            })
+            .collect();
+        Ok(items)
     }
 
     /// Retrieve datapoints for a collection of time series.
@@ -263,6 +307,7 @@ impl TimeSeriesResource {
         &self,
         datapoints_filter: &DatapointsFilter,
     ) -> Result<DataPointListResponse> {
+        datapoints_filter.validate_time_zone()?;
         let datapoints_response: DataPointListResponse = self
             .api_client
             .post_expect_protobuf("timeseries/data/list", &datapoints_filter)
@@ -289,6 +334,60 @@ impl TimeSeriesResource {
         Ok(datapoints_response.items)
     }
 
+    /// Retrieve the latest datapoint before a given time for a large number of time series.
+    ///
+    /// The queries are split into requests of at most 100 time series, which are sent
+    /// with at most `parallelism` requests running at the same time.
+    /// Each query must refer to a distinct time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Queries for latest datapoint.
+    /// * `ignore_unknown_ids` - Set this to `true` to ignore timeseries that do not exist.
+    ///   Time series that do not exist are then returned in `missing`.
+    /// * `parallelism` - Maximum number of concurrent requests. Must be greater than zero.
+    pub async fn retrieve_latest_datapoints_bulk(
+        &self,
+        items: &[LatestDatapointsQuery],
+        ignore_unknown_ids: bool,
+        parallelism: usize,
+    ) -> Result<LatestDatapointsBulkResponse> {
+        let futures = items
+            .chunks(MAX_LATEST_DATAPOINTS_PER_REQUEST)
+            .map(|chunk| async move {
+                let res = self
+                    .retrieve_latest_datapoints(chunk, ignore_unknown_ids)
+                    .await?;
+                Ok::<_, crate::Error>((chunk, res))
+            });
+        let chunks = execute_with_parallelism(futures, parallelism).await?;
+
+        let mut result = LatestDatapointsBulkResponse::default();
+        for (queries, responses) in chunks {
+            let mut pending: HashSet<&IdentityOrInstance> = queries.iter().map(|q| &q.id).collect();
+            for response in responses {
+                let by_id = IdentityOrInstance::from(response.id);
+                let by_xid = response.external_id.as_ref().map(IdentityOrInstance::from);
+                let by_instance = response.instance_id.clone().map(IdentityOrInstance::from);
+                let Some(key) = [Some(by_id), by_xid, by_instance]
+                    .into_iter()
+                    .flatten()
+                    .find(|key| pending.remove(key))
+                else {
+                    continue;
+                };
+                result.items.insert(key, response);
+            }
+            result.missing.extend(
+                queries
+                    .iter()
+                    .filter(|q| pending.contains(&q.id))
+                    .map(|q| q.id.clone()),
+            );
+        }
+        Ok(result)
+    }
+
     /// Delete ranges of datapoints for a list of time series.
     ///
     /// # Arguments
@@ -366,4 +465,36 @@ impl TimeSeriesResource {
     ) -> impl Stream<Item = Result<DataPointListResponse>> + '_ {
         DatapointsStream::new(self, filter, options).stream_batches()
     }
+
+    /// Stream datapoints for a list of timeseries as batches, like
+    /// [stream_datapoint_batches](Self::stream_datapoint_batches), but with metadata about
+    /// each timeseries attached. Use [`DatapointsBatch::datapoints`] to iterate over
+    /// the datapoints in each batch without converting them to owned datapoints, which
+    /// is considerably cheaper than [stream_datapoints](Self::stream_datapoints) for
+    /// high volume reads.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter describing common filter properties and a list of timeseries to retrieve data from.
+    /// * `options` - Options for controlling the stream.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut stream = pin!(client.time_series.stream_datapoint_views(filter, Default::default()));
+    /// while let Some(batch) = stream.try_next().await? {
+    ///     for (ts, dp) in batch.datapoints() {
+    ///         if let DatapointView::Numeric(timestamp, Some(value), _) = dp {
+    ///             println!("{}: {timestamp} {value}", ts.id());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn stream_datapoint_views(
+        &self,
+        filter: DatapointsFilter,
+        options: DatapointsStreamOptions,
+    ) -> impl Stream<Item = Result<DatapointsBatch>> + '_ {
+        DatapointsStream::new(self, filter, options).stream_views()
+    }
 }
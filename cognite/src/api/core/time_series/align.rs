@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+
+use crate::time_series::{
+    DataPointListItem, DataPointListResponse, DataPointRef, DatapointDouble, DatapointsEnumType,
+    DatapointsResponse, EitherDataPoint, Granularity,
+};
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How to compute the value of a series at a timestamp without a datapoint.
+pub enum FillStrategy {
+    /// Forward fill step series, and linearly interpolate other series.
+    #[default]
+    Auto,
+    /// Only use datapoints exactly at each timestamp.
+    Exact,
+    /// Use the value of the last datapoint at or before each timestamp.
+    ForwardFill,
+    /// Linearly interpolate between the datapoints on either side of each timestamp.
+    Linear,
+    /// Use the value of the datapoint closest to each timestamp.
+    /// Ties are resolved in favor of the earlier datapoint.
+    Nearest,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How to handle datapoints with a bad status code when aligning.
+pub enum BadDatapointHandling {
+    /// Treat bad datapoints as if they do not exist.
+    #[default]
+    Ignore,
+    /// Treat bad datapoints as gaps. Values are not filled forward past, or interpolated across,
+    /// a bad datapoint, and timestamps at a bad datapoint have no value.
+    Gap,
+}
+
+#[derive(Debug, Clone)]
+/// Timestamps to align series onto.
+pub enum AlignIndex {
+    /// The sorted union of the timestamps of all usable datapoints in all series.
+    Union,
+    /// A regular grid from `start`, stepping by `granularity`, up to but not including `end`.
+    /// Calendar granularities step by calendar months.
+    Regular {
+        /// First timestamp in the grid, in milliseconds since epoch.
+        start: i64,
+        /// End of the grid, exclusive.
+        end: i64,
+        /// Distance between timestamps.
+        granularity: Granularity,
+    },
+    /// An explicit list of timestamps. Must be sorted.
+    Timestamps(Vec<i64>),
+}
+
+#[derive(Debug, Clone)]
+/// Options for [`align_datapoints`].
+pub struct AlignOptions {
+    /// How to compute values between datapoints. Default [`FillStrategy::Auto`].
+    pub fill: FillStrategy,
+    /// Maximum distance in milliseconds between a timestamp and the datapoints used to compute
+    /// its value. If `None`, there is no limit.
+    pub tolerance: Option<i64>,
+    /// How to handle datapoints with a bad status code. Default [`BadDatapointHandling::Ignore`].
+    pub bad_datapoints: BadDatapointHandling,
+    /// Treat datapoints with an uncertain status code as bad. Default `true`.
+    pub treat_uncertain_as_bad: bool,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            fill: FillStrategy::Auto,
+            tolerance: None,
+            bad_datapoints: BadDatapointHandling::Ignore,
+            treat_uncertain_as_bad: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A numeric time series with datapoints, used as input to [`align_datapoints`].
+pub struct AlignSeries {
+    /// Time series internal ID.
+    pub id: i64,
+    /// Time series external ID.
+    pub external_id: Option<String>,
+    /// Whether the time series is a step series.
+    pub is_step: bool,
+    /// Datapoints in the time series. These do not need to be sorted.
+    pub datapoints: Vec<DatapointDouble>,
+}
+
+impl AlignSeries {
+    /// Group a sequence of datapoint references, as returned from a datapoints stream,
+    /// into one series per time series. Series are returned in the order they first appear.
+    /// Non-numeric datapoints are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `datapoints` - Datapoints to group.
+    pub fn from_refs(datapoints: impl IntoIterator<Item = DataPointRef>) -> Vec<AlignSeries> {
+        let mut series: Vec<AlignSeries> = Vec::new();
+        let mut index = HashMap::new();
+        for dp in datapoints {
+            let idx = *index.entry(dp.id()).or_insert_with(|| {
+                series.push(AlignSeries {
+                    id: dp.id(),
+                    external_id: dp.external_id().map(|s| s.to_owned()),
+                    is_step: dp.is_step(),
+                    datapoints: Vec::new(),
+                });
+                series.len() - 1
+            });
+            if let EitherDataPoint::Numeric(d) = dp.into_datapoint() {
+                series[idx].datapoints.push(d);
+            }
+        }
+        series
+    }
+
+    /// Convert every item in a protobuf datapoints response.
+    /// Fails if any of the time series are not numeric.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - Datapoints response.
+    pub fn from_list_response(response: DataPointListResponse) -> Result<Vec<Self>, Error> {
+        response.items.into_iter().map(Self::try_from).collect()
+    }
+}
+
+impl TryFrom<DatapointsResponse> for AlignSeries {
+    type Error = Error;
+
+    fn try_from(value: DatapointsResponse) -> Result<Self, Self::Error> {
+        let DatapointsEnumType::NumericDatapoints(datapoints) = value.datapoints else {
+            return Err(Error::Other(format!(
+                "Time series {} does not contain raw numeric datapoints, and cannot be aligned",
+                value.id
+            )));
+        };
+        Ok(Self {
+            id: value.id,
+            external_id: value.external_id,
+            is_step: value.is_step,
+            datapoints,
+        })
+    }
+}
+
+impl TryFrom<DataPointListItem> for AlignSeries {
+    type Error = Error;
+
+    fn try_from(value: DataPointListItem) -> Result<Self, Self::Error> {
+        DatapointsResponse::from(value).try_into()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A single series aligned onto the timestamps of an [`AlignedTable`].
+pub struct AlignedColumn {
+    /// Time series internal ID.
+    pub id: i64,
+    /// Time series external ID.
+    pub external_id: Option<String>,
+    /// Whether the time series is a step series.
+    pub is_step: bool,
+    /// Value at each timestamp in the table, or `None` if there is no value.
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Several series aligned onto a shared time index.
+pub struct AlignedTable {
+    /// Timestamps of each row, in milliseconds since epoch.
+    pub timestamps: Vec<i64>,
+    /// One column per series, in the order they were given.
+    pub columns: Vec<AlignedColumn>,
+}
+
+impl AlignedTable {
+    /// Get the column for a time series by internal ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series internal ID.
+    pub fn column_by_id(&self, id: i64) -> Option<&AlignedColumn> {
+        self.columns.iter().find(|c| c.id == id)
+    }
+
+    /// Get the column for a time series by external ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `external_id` - Time series external ID.
+    pub fn column_by_external_id(&self, external_id: &str) -> Option<&AlignedColumn> {
+        self.columns
+            .iter()
+            .find(|c| c.external_id.as_deref() == Some(external_id))
+    }
+
+    /// Get the values of each column in a single row.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Row index.
+    pub fn row(&self, row: usize) -> Vec<Option<f64>> {
+        self.columns.iter().map(|c| c.values[row]).collect()
+    }
+}
+
+struct Point {
+    timestamp: i64,
+    value: Option<f64>,
+}
+
+/// Sorted datapoints of a series. Points that cannot be used have no value,
+/// and are only kept if they should be treated as gaps.
+fn points(series: &AlignSeries, options: &AlignOptions) -> Vec<Point> {
+    let mut points: Vec<_> = series
+        .datapoints
+        .iter()
+        .filter_map(|dp| {
+            let bad = dp
+                .status
+                .as_ref()
+                .is_some_and(|s| s.is_bad() || s.is_uncertain() && options.treat_uncertain_as_bad);
+            let value = dp.value.filter(|v| v.is_finite() && !bad);
+            match (value, options.bad_datapoints) {
+                (None, BadDatapointHandling::Ignore) => None,
+                _ => Some(Point {
+                    timestamp: dp.timestamp,
+                    value,
+                }),
+            }
+        })
+        .collect();
+    points.sort_by_key(|p| p.timestamp);
+    points
+}
+
+fn within(tolerance: Option<i64>, distance: i64) -> bool {
+    tolerance.is_none_or(|t| distance <= t)
+}
+
+fn value_at(points: &[Point], t: i64, fill: FillStrategy, tolerance: Option<i64>) -> Option<f64> {
+    let idx = points.partition_point(|p| p.timestamp < t);
+    // The last point at `t`, or the last point before it.
+    let exact = points[idx..].iter().take_while(|p| p.timestamp == t).last();
+    if let Some(p) = exact {
+        return p.value;
+    }
+    let prev = idx.checked_sub(1).map(|i| &points[i]);
+    let next = points.get(idx);
+    match fill {
+        FillStrategy::Auto | FillStrategy::Exact => None,
+        FillStrategy::ForwardFill => prev
+            .filter(|p| within(tolerance, t - p.timestamp))
+            .and_then(|p| p.value),
+        FillStrategy::Linear => {
+            let (p, n) = (prev?, next?);
+            if !within(tolerance, t - p.timestamp) || !within(tolerance, n.timestamp - t) {
+                return None;
+            }
+            let (v0, v1) = (p.value?, n.value?);
+            let frac = (t - p.timestamp) as f64 / (n.timestamp - p.timestamp) as f64;
+            Some(v0 + (v1 - v0) * frac)
+        }
+        FillStrategy::Nearest => {
+            let nearest = match (prev, next) {
+                (Some(p), Some(n)) if n.timestamp - t < t - p.timestamp => n,
+                (Some(p), _) => p,
+                (None, Some(n)) => n,
+                (None, None) => return None,
+            };
+            nearest
+                .value
+                .filter(|_| within(tolerance, (nearest.timestamp - t).abs()))
+        }
+    }
+}
+
+/// Align several numeric time series onto a shared time index.
+///
+/// Each series gets one column in the result, with a value for each timestamp in the index
+/// computed according to `options.fill`. With [`FillStrategy::Auto`], step series are
+/// forward filled and other series are linearly interpolated, matching how the API
+/// interprets them. Timestamps before the first datapoint, after the last datapoint
+/// for interpolation, or farther than `options.tolerance` from the datapoints, have no value.
+///
+/// # Arguments
+///
+/// * `series` - Series to align.
+/// * `index` - Timestamps to align onto.
+/// * `options` - Fill strategy, tolerance and status code handling.
+///
+/// # Example
+///
+/// ```ignore
+/// let response = client.time_series.retrieve_datapoints(filter).await?;
+/// let series = response
+///     .into_iter()
+///     .map(AlignSeries::try_from)
+///     .collect::<Result<Vec<_>>>()?;
+/// let table = align_datapoints(
+///     &series,
+///     &AlignIndex::Regular { start, end, granularity: "1m".parse()? },
+///     &AlignOptions::default(),
+/// );
+/// ```
+pub fn align_datapoints(
+    series: &[AlignSeries],
+    index: &AlignIndex,
+    options: &AlignOptions,
+) -> AlignedTable {
+    let points: Vec<_> = series.iter().map(|s| points(s, options)).collect();
+    let timestamps = match index {
+        AlignIndex::Union => {
+            let mut ts: Vec<_> = points
+                .iter()
+                .flatten()
+                .filter(|p| p.value.is_some())
+                .map(|p| p.timestamp)
+                .collect();
+            ts.sort_unstable();
+            ts.dedup();
+            ts
+        }
+        AlignIndex::Regular {
+            start,
+            end,
+            granularity,
+        } => {
+            // Compute each point from the start, so that calendar units
+            // do not drift once the day of month is clamped.
+            let mut ts = Vec::new();
+            let mut current = *start;
+            while current < *end {
+                ts.push(current);
+                current = granularity.add_periods(*start, ts.len() as i64);
+            }
+            ts
+        }
+        AlignIndex::Timestamps(ts) => ts.clone(),
+    };
+
+    let columns = series
+        .iter()
+        .zip(points)
+        .map(|(s, points)| {
+            let fill = match options.fill {
+                FillStrategy::Auto if s.is_step => FillStrategy::ForwardFill,
+                FillStrategy::Auto => FillStrategy::Linear,
+                f => f,
+            };
+            AlignedColumn {
+                id: s.id,
+                external_id: s.external_id.clone(),
+                is_step: s.is_step,
+                values: timestamps
+                    .iter()
+                    .map(|t| value_at(&points, *t, fill, options.tolerance))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    AlignedTable {
+        timestamps,
+        columns,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        align_datapoints, AlignIndex, AlignOptions, AlignSeries, BadDatapointHandling, FillStrategy,
+    };
+    use crate::time_series::{DatapointDouble, StatusCode};
+
+    fn series(id: i64, is_step: bool, dps: &[(i64, f64)]) -> AlignSeries {
+        AlignSeries {
+            id,
+            external_id: None,
+            is_step,
+            datapoints: dps
+                .iter()
+                .map(|(timestamp, value)| DatapointDouble {
+                    timestamp: *timestamp,
+                    value: Some(*value),
+                    status: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_align_datapoints() {
+        let input = vec![
+            series(1, false, &[(0, 0.0), (100, 10.0)]),
+            series(2, true, &[(50, 1.0), (150, 2.0)]),
+        ];
+        let table = align_datapoints(&input, &AlignIndex::Union, &AlignOptions::default());
+        assert_eq!(vec![0, 50, 100, 150], table.timestamps);
+        assert_eq!(
+            vec![Some(0.0), Some(5.0), Some(10.0), None],
+            table.column_by_id(1).unwrap().values
+        );
+        assert_eq!(
+            vec![None, Some(1.0), Some(1.0), Some(2.0)],
+            table.column_by_id(2).unwrap().values
+        );
+        assert_eq!(vec![Some(10.0), Some(1.0)], table.row(2));
+
+        let options = AlignOptions {
+            fill: FillStrategy::Nearest,
+            tolerance: Some(20),
+            ..Default::default()
+        };
+        let table = align_datapoints(&input, &AlignIndex::Timestamps(vec![10, 40, 140]), &options);
+        assert_eq!(
+            vec![Some(0.0), None, None],
+            table.column_by_id(1).unwrap().values
+        );
+        assert_eq!(
+            vec![None, Some(1.0), Some(2.0)],
+            table.column_by_id(2).unwrap().values
+        );
+    }
+
+    #[test]
+    fn test_align_bad_datapoints() {
+        let mut input = series(1, false, &[(0, 0.0), (100, 10.0), (200, 20.0)]);
+        input.datapoints[1].status = Some(StatusCode::try_parse("Bad").unwrap());
+        let index = AlignIndex::Timestamps(vec![0, 50, 100, 150, 200]);
+        let table = align_datapoints(
+            std::slice::from_ref(&input),
+            &index,
+            &AlignOptions::default(),
+        );
+        assert_eq!(
+            vec![Some(0.0), Some(5.0), Some(10.0), Some(15.0), Some(20.0)],
+            table.columns[0].values
+        );
+
+        let options = AlignOptions {
+            bad_datapoints: BadDatapointHandling::Gap,
+            ..Default::default()
+        };
+        let table = align_datapoints(&[input], &index, &options);
+        assert_eq!(
+            vec![Some(0.0), None, None, None, Some(20.0)],
+            table.columns[0].values
+        );
+    }
+
+    #[test]
+    fn test_align_regular_index() {
+        let input = series(1, true, &[(0, 1.0), (2_500, 2.0)]);
+        let index = AlignIndex::Regular {
+            start: 0,
+            end: 4_000,
+            granularity: "1s".parse().unwrap(),
+        };
+        let table = align_datapoints(&[input], &index, &AlignOptions::default());
+        assert_eq!(vec![0, 1_000, 2_000, 3_000], table.timestamps);
+        assert_eq!(
+            vec![Some(1.0), Some(1.0), Some(1.0), Some(2.0)],
+            table.columns[0].values
+        );
+    }
+
+    #[test]
+    fn test_align_regular_index_month_end() {
+        // 2024-01-31 to 2024-05-01, monthly points should stay on the last day of each month.
+        let index = AlignIndex::Regular {
+            start: 1_706_659_200_000,
+            end: 1_714_521_600_000,
+            granularity: "1mo".parse().unwrap(),
+        };
+        let table = align_datapoints(&[], &index, &AlignOptions::default());
+        assert_eq!(
+            vec![
+                1_706_659_200_000, // 2024-01-31
+                1_709_164_800_000, // 2024-02-29
+                1_711_843_200_000, // 2024-03-31
+                1_714_435_200_000, // 2024-04-30
+            ],
+            table.timestamps
+        );
+    }
+}
@@ -4,15 +4,15 @@ use std::{
     sync::Arc,
 };
 
-use futures::{stream::FuturesUnordered, Stream, StreamExt, TryStream};
+use futures::{stream::FuturesUnordered, Stream, StreamExt, TryStream, TryStreamExt};
 use pin_project::pin_project;
 
 use crate::{
     send_helper::CondBoxFuture,
     time_series::{
         DataPointListItem, DataPointListResponse, DatapointAggregate, DatapointDouble,
-        DatapointString, DatapointsFilter, DatapointsQuery, InstanceId, ListDatapointType,
-        TimeSeriesResource,
+        DatapointString, DatapointView, DatapointsFilter, DatapointsQuery, InstanceId,
+        ListDatapointType, TimeSeriesResource, TimeZone,
     },
     Identity, IdentityOrInstance,
 };
@@ -27,7 +27,8 @@ pub enum EitherDataPoint {
     Aggregate(DatapointAggregate),
 }
 
-struct TimeSeriesRef {
+/// Metadata about a timeseries returned from a datapoints stream.
+pub struct TimeSeriesRef {
     id: i64,
     external_id: Option<String>,
     instance_id: Option<InstanceId>,
@@ -36,6 +37,54 @@ struct TimeSeriesRef {
     is_step: bool,
     unit: Option<String>,
     unit_external_id: Option<String>,
+    time_zone: Option<TimeZone>,
+}
+
+impl TimeSeriesRef {
+    /// Get the internal ID of the timeseries.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Get the external ID of the timeseries, if it has one.
+    pub fn external_id(&self) -> Option<&str> {
+        self.external_id.as_deref()
+    }
+
+    /// Get the data modelling instance ID of the timeseries, if it has one.
+    pub fn instance_id(&self) -> Option<&InstanceId> {
+        self.instance_id.as_ref()
+    }
+
+    /// Get the original ID used to identify the timeseries in the request.
+    pub fn original_id(&self) -> &IdentityOrInstance {
+        &self.original_id
+    }
+
+    /// Check if the timeseries is of string type.
+    pub fn is_string(&self) -> bool {
+        self.is_string
+    }
+
+    /// Check if the timeseries is a step timeseries.
+    pub fn is_step(&self) -> bool {
+        self.is_step
+    }
+
+    /// Get the unit of the timeseries, if it has one.
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Get the external ID of the unit of the timeseries, if it has one.
+    pub fn unit_external_id(&self) -> Option<&str> {
+        self.unit_external_id.as_deref()
+    }
+
+    /// Get the time zone aggregates for this timeseries were aligned to, if one was given in the query.
+    pub fn time_zone(&self) -> Option<&TimeZone> {
+        self.time_zone.as_ref()
+    }
 }
 
 /// A datapoint containing a reference to its timeseries metadata.
@@ -88,6 +137,16 @@ impl DataPointRef {
         self.timeseries.unit_external_id.as_deref()
     }
 
+    /// Get the time zone aggregates for this timeseries were aligned to, if one was given in the query.
+    pub fn time_zone(&self) -> Option<&TimeZone> {
+        self.timeseries.time_zone.as_ref()
+    }
+
+    /// Get the metadata of the timeseries this datapoint belongs to.
+    pub fn timeseries(&self) -> &TimeSeriesRef {
+        &self.timeseries
+    }
+
     /// Consume the reference and return the underlying datapoint, to avoid cloning.
     pub fn into_datapoint(self) -> EitherDataPoint {
         self.datapoint
@@ -123,6 +182,88 @@ impl DataPointRef {
     }
 }
 
+/// A batch of datapoints as returned from CDF, paired with metadata about each timeseries.
+///
+/// Use [`datapoints`](Self::datapoints) to iterate over the datapoints without
+/// converting them to owned datapoints first.
+pub struct DatapointsBatch {
+    // One entry per item in `response`.
+    timeseries: Vec<Arc<TimeSeriesRef>>,
+    response: DataPointListResponse,
+    // One entry per item in `response`, `true` if there are no more datapoints
+    // for the query the item belongs to.
+    finished: Vec<bool>,
+}
+
+impl DatapointsBatch {
+    /// Whether each item in this batch is the last for its query.
+    pub(super) fn finished(&self) -> &[bool] {
+        &self.finished
+    }
+
+    /// Get the underlying protobuf response.
+    pub fn response(&self) -> &DataPointListResponse {
+        &self.response
+    }
+
+    /// Consume the batch and return the underlying protobuf response.
+    pub fn into_response(self) -> DataPointListResponse {
+        self.response
+    }
+
+    /// Iterate over the items in this batch, paired with the metadata of their timeseries.
+    pub fn items(&self) -> impl ExactSizeIterator<Item = (&TimeSeriesRef, &DataPointListItem)> {
+        self.timeseries
+            .iter()
+            .map(|ts| ts.as_ref())
+            .zip(self.response.items.iter())
+    }
+
+    /// Iterate over all datapoints in this batch, paired with the metadata of their timeseries,
+    /// without allocating.
+    pub fn datapoints(&self) -> impl Iterator<Item = (&TimeSeriesRef, DatapointView<'_>)> {
+        self.items()
+            .flat_map(|(ts, item)| item.datapoints().map(move |dp| (ts, dp)))
+    }
+
+    /// Consume the batch and convert it into owned datapoints.
+    pub fn into_datapoint_refs(self) -> impl Iterator<Item = DataPointRef> {
+        self.timeseries
+            .into_iter()
+            .zip(self.response.items)
+            .flat_map(|(timeseries, item)| {
+                // Only one of these is set, chaining them avoids boxing the iterator.
+                let (mut numeric, mut string, mut aggregate) = (None, None, None);
+                match item.datapoint_type {
+                    None => (),
+                    Some(ListDatapointType::NumericDatapoints(dps)) => numeric = Some(dps),
+                    Some(ListDatapointType::StringDatapoints(dps)) => string = Some(dps),
+                    Some(ListDatapointType::AggregateDatapoints(dps)) => aggregate = Some(dps),
+                }
+                numeric
+                    .into_iter()
+                    .flat_map(|dps| dps.datapoints)
+                    .map(|dp| EitherDataPoint::Numeric(dp.into()))
+                    .chain(
+                        string
+                            .into_iter()
+                            .flat_map(|dps| dps.datapoints)
+                            .map(|dp| EitherDataPoint::String(dp.into())),
+                    )
+                    .chain(
+                        aggregate
+                            .into_iter()
+                            .flat_map(|dps| dps.datapoints)
+                            .map(|dp| EitherDataPoint::Aggregate(dp.into())),
+                    )
+                    .map(move |datapoint| DataPointRef {
+                        timeseries: timeseries.clone(),
+                        datapoint,
+                    })
+            })
+    }
+}
+
 struct FetchResult {
     query_items: Vec<DatapointsQuery>,
     response: DataPointListResponse,
@@ -218,11 +359,19 @@ impl<'a> DatapointsStream<'a> {
                 } else {
                     None
                 },
+                time_zone: query
+                    .time_zone
+                    .as_ref()
+                    .or(self.filter.time_zone.as_ref())
+                    .cloned(),
             }),
         );
     }
 
-    fn equals_identity(id: &IdentityOrInstance, response_item: &DataPointListItem) -> bool {
+    pub(super) fn equals_identity(
+        id: &IdentityOrInstance,
+        response_item: &DataPointListItem,
+    ) -> bool {
         match id {
             IdentityOrInstance::Identity(Identity::Id { id }) => response_item.id == *id,
             IdentityOrInstance::Identity(Identity::ExternalId { external_id }) => {
@@ -238,7 +387,7 @@ impl<'a> DatapointsStream<'a> {
     async fn stream_batches_inner(
         &mut self,
         maintain_internal_state: bool,
-    ) -> Result<Option<DataPointListResponse>, crate::Error> {
+    ) -> Result<Option<(DataPointListResponse, Vec<bool>)>, crate::Error> {
         // If there's room for more requests, spawn them immediately.
         while self.futures.len() < self.options.parallelism && !self.queries.is_empty() {
             let mut batch = Vec::with_capacity(self.options.batch_size.min(self.queries.len()));
@@ -265,6 +414,7 @@ impl<'a> DatapointsStream<'a> {
         };
         let mut fetch_result = result?;
         let mut query_iter = fetch_result.query_items.into_iter();
+        let mut finished = Vec::with_capacity(fetch_result.response.items.len());
 
         // Update queries from the result, then re-queue them.
         for response_item in &mut fetch_result.response.items {
@@ -299,66 +449,64 @@ impl<'a> DatapointsStream<'a> {
                 // see it or use it.
                 query.cursor = Some(std::mem::take(&mut response_item.next_cursor));
                 self.queries.push_back(query);
+                finished.push(false);
+            } else {
+                finished.push(true);
             }
         }
 
-        Ok(Some(fetch_result.response))
+        Ok(Some((fetch_result.response, finished)))
     }
 
     pub fn stream_batches(
         self,
     ) -> impl Stream<Item = Result<DataPointListResponse, crate::Error>> + 'a {
         futures::stream::try_unfold(self, move |mut state| async move {
-            Ok(state.stream_batches_inner(false).await?.map(|v| (v, state)))
+            Ok(state
+                .stream_batches_inner(false)
+                .await?
+                .map(|(v, _)| (v, state)))
         })
     }
 
-    pub fn stream_datapoints(self) -> impl Stream<Item = Result<DataPointRef, crate::Error>> + 'a {
-        FlatIterStream::new(futures::stream::try_unfold(
-            self,
-            move |mut state| async move {
-                let Some(batch) = state.stream_batches_inner(true).await? else {
-                    return Ok(None);
-                };
-                let mut res = Vec::new();
-
-                for item in batch.items {
-                    let timeseries = state
-                        .known_timeseries
-                        .get(&item.id)
-                        .ok_or_else(|| {
-                            crate::Error::Other(format!(
-                                "Internal logic error: timeseries with id {} not found in known_timeseries",
-                                item.id
-                            ))
-                        })?
-                        .clone();
-                    match item.datapoint_type {
-                        None => continue,
-                        Some(ListDatapointType::AggregateDatapoints(dps)) => {
-                            res.extend(dps.datapoints.into_iter().map(move |dp| DataPointRef {
-                                timeseries: timeseries.clone(),
-                                datapoint: EitherDataPoint::Aggregate(dp.into()),
-                            }));
-                        }
-                        Some(ListDatapointType::StringDatapoints(dps)) => {
-                            res.extend(dps.datapoints.into_iter().map(move |dp| DataPointRef {
-                                timeseries: timeseries.clone(),
-                                datapoint: EitherDataPoint::String(dp.into()),
-                            }));
-                        }
-                        Some(ListDatapointType::NumericDatapoints(dps)) => {
-                            res.extend(dps.datapoints.into_iter().map(move |dp| DataPointRef {
-                                timeseries: timeseries.clone(),
-                                datapoint: EitherDataPoint::Numeric(dp.into()),
-                            }));
-                        }
-                    }
-                }
+    fn make_batch(
+        &self,
+        response: DataPointListResponse,
+        finished: Vec<bool>,
+    ) -> Result<DatapointsBatch, crate::Error> {
+        let timeseries = response
+            .items
+            .iter()
+            .map(|item| {
+                self.known_timeseries.get(&item.id).cloned().ok_or_else(|| {
+                    crate::Error::Other(format!(
+                        "Internal logic error: timeseries with id {} not found in known_timeseries",
+                        item.id
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DatapointsBatch {
+            timeseries,
+            response,
+            finished,
+        })
+    }
 
-                Ok(Some((res.into_iter(), state)))
-            },
-        ))
+    pub fn stream_views(self) -> impl Stream<Item = Result<DatapointsBatch, crate::Error>> + 'a {
+        futures::stream::try_unfold(self, move |mut state| async move {
+            let Some((response, finished)) = state.stream_batches_inner(true).await? else {
+                return Ok(None);
+            };
+            Ok(Some((state.make_batch(response, finished)?, state)))
+        })
+    }
+
+    pub fn stream_datapoints(self) -> impl Stream<Item = Result<DataPointRef, crate::Error>> + 'a {
+        FlatIterStream::new(
+            self.stream_views()
+                .map_ok(DatapointsBatch::into_datapoint_refs),
+        )
     }
 }
 
@@ -435,6 +583,7 @@ mod tests {
             is_step: false,
             unit: Some("°C".to_string()),
             unit_external_id: None,
+            time_zone: None,
         });
         let dp = DataPointRef {
             timeseries: ts_ref.clone(),
@@ -0,0 +1,347 @@
+mod csv;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+use std::collections::HashMap;
+
+pub use self::csv::{CsvExportOptions, CsvImportOptions, CsvLayout};
+#[cfg(feature = "parquet")]
+pub use self::parquet::{ParquetExportOptions, ParquetImportOptions};
+
+use crate::models::instances::InstanceId;
+use crate::time_series::{
+    AddDatapoints, DatapointDouble, DatapointString, DatapointsEnumType, DatapointsFilter,
+    StatusCode, TimeSeriesResource,
+};
+use crate::{Error, Identity, IdentityOrInstance, Result};
+
+/// Maximum number of time series in a single insert request.
+const MAX_SERIES_PER_INSERT: usize = 10_000;
+
+#[derive(Clone, Debug)]
+/// Names of the columns in datapoint files with one row per datapoint.
+///
+/// Identity columns set to `None` are not written on export, and ignored on import.
+/// On import, the time series of each row is taken from the instance ID columns if both are
+/// set, then the external ID column, then the ID column.
+pub struct DatapointsColumns {
+    /// Internal ID of the time series. Default `id`.
+    pub id: Option<String>,
+    /// External ID of the time series. Default `external_id`.
+    pub external_id: Option<String>,
+    /// Space of the time series instance ID. Default `instance_space`.
+    pub instance_space: Option<String>,
+    /// External ID of the time series instance ID. Default `instance_external_id`.
+    pub instance_external_id: Option<String>,
+    /// Timestamp of the datapoint. Default `timestamp`.
+    pub timestamp: String,
+    /// Value of numeric datapoints. Default `value`.
+    pub value: String,
+    /// Value of string datapoints. Default `string_value`.
+    pub string_value: String,
+    /// Status code of the datapoint, empty if the status is `Good`. Default `status_code`.
+    /// On import, this can be either the numeric status code, or a status code symbol.
+    pub status_code: Option<String>,
+}
+
+impl Default for DatapointsColumns {
+    fn default() -> Self {
+        Self {
+            id: Some("id".to_owned()),
+            external_id: Some("external_id".to_owned()),
+            instance_space: Some("instance_space".to_owned()),
+            instance_external_id: Some("instance_external_id".to_owned()),
+            timestamp: "timestamp".to_owned(),
+            value: "value".to_owned(),
+            string_value: "string_value".to_owned(),
+            status_code: Some("status_code".to_owned()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// How the names of time series columns in files with one column per time series
+/// map to time series.
+///
+/// On export, columns are named by the identity in the query, internal IDs as numbers,
+/// and instance IDs as `space:externalId`.
+pub enum WideColumnIdentity {
+    /// Column names are internal IDs.
+    Id,
+    /// Column names are external IDs.
+    #[default]
+    ExternalId,
+    /// Column names are instance IDs, as `space:externalId`.
+    InstanceId,
+}
+
+impl WideColumnIdentity {
+    pub(crate) fn parse(&self, name: &str) -> Result<IdentityOrInstance> {
+        match self {
+            Self::Id => name
+                .parse()
+                .map(|id| Identity::Id { id }.into())
+                .map_err(|_| Error::Other(format!("Column '{name}' is not a valid internal ID"))),
+            Self::ExternalId => Ok(Identity::ExternalId {
+                external_id: name.to_owned(),
+            }
+            .into()),
+            Self::InstanceId => match name.split_once(':') {
+                Some((space, external_id)) => Ok(InstanceId {
+                    space: space.to_owned(),
+                    external_id: external_id.to_owned(),
+                }
+                .into()),
+                None => Err(Error::Other(format!(
+                    "Column '{name}' is not an instance ID on the form 'space:externalId'"
+                ))),
+            },
+        }
+    }
+}
+
+pub(crate) fn wide_column_name(id: &IdentityOrInstance) -> String {
+    match id {
+        IdentityOrInstance::Identity(Identity::Id { id }) => id.to_string(),
+        IdentityOrInstance::Identity(Identity::ExternalId { external_id }) => external_id.clone(),
+        IdentityOrInstance::InstanceId { instance_id } => {
+            format!("{}:{}", instance_id.space, instance_id.external_id)
+        }
+    }
+}
+
+pub(crate) fn check_exportable(filter: &DatapointsFilter) -> Result<()> {
+    if filter.aggregates.is_some() || filter.items.iter().any(|q| q.aggregates.is_some()) {
+        return Err(Error::Other(
+            "Only raw datapoints can be exported".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_status(value: &str) -> Result<Option<StatusCode>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(code) = value.parse::<u32>() {
+        return Ok(Some(StatusCode::from(code)));
+    }
+    StatusCode::try_parse(value)
+        .map(Some)
+        .map_err(|e| Error::Other(format!("Invalid status code '{value}': {e}")))
+}
+
+/// A single datapoint read from a file.
+pub(crate) enum ImportedDatapoint {
+    Numeric(DatapointDouble),
+    String(DatapointString),
+    /// A datapoint without a value, the type is taken from other datapoints in
+    /// the same time series, or numeric if there are none.
+    Null(i64, Option<StatusCode>),
+}
+
+/// A row in a file with one row per datapoint.
+#[derive(Default)]
+pub(crate) struct LongRow<'a> {
+    pub id: Option<i64>,
+    pub external_id: Option<&'a str>,
+    pub instance_space: Option<&'a str>,
+    pub instance_external_id: Option<&'a str>,
+    pub timestamp: i64,
+    pub value: Option<f64>,
+    /// Value of string datapoints, `None` if the value is missing. An empty string is
+    /// a string datapoint with an empty value, unless `value` is set.
+    pub string_value: Option<&'a str>,
+    pub status: Option<StatusCode>,
+}
+
+fn non_empty(s: Option<&str>) -> Option<&str> {
+    s.filter(|s| !s.is_empty())
+}
+
+impl LongRow<'_> {
+    pub fn into_datapoint(self) -> Result<(IdentityOrInstance, ImportedDatapoint)> {
+        let id = match (
+            non_empty(self.instance_space),
+            non_empty(self.instance_external_id),
+            non_empty(self.external_id),
+            self.id,
+        ) {
+            (Some(space), Some(external_id), _, _) => InstanceId {
+                space: space.to_owned(),
+                external_id: external_id.to_owned(),
+            }
+            .into(),
+            (_, _, Some(external_id), _) => Identity::ExternalId {
+                external_id: external_id.to_owned(),
+            }
+            .into(),
+            (_, _, _, Some(id)) => Identity::Id { id }.into(),
+            _ => return Err(Error::Other("Row has no time series identity".to_owned())),
+        };
+        // An empty string value is only a string datapoint if there is no numeric value.
+        let string_value = self
+            .string_value
+            .filter(|s| !s.is_empty() || self.value.is_none());
+        let dp = if let Some(value) = string_value {
+            ImportedDatapoint::String(DatapointString {
+                timestamp: self.timestamp,
+                value: Some(value.to_owned()),
+                status: self.status,
+            })
+        } else if let Some(value) = self.value {
+            ImportedDatapoint::Numeric(DatapointDouble {
+                timestamp: self.timestamp,
+                value: Some(value),
+                status: self.status,
+            })
+        } else {
+            ImportedDatapoint::Null(self.timestamp, self.status)
+        };
+        Ok((id, dp))
+    }
+}
+
+/// Buffer of imported datapoints, inserted in batches.
+///
+/// Datapoints without a value are held back until the first datapoint with a value
+/// for the same time series, since that decides whether the time series is numeric
+/// or string. Any left when the import finishes are inserted as numeric.
+pub(crate) struct ImportBuffer<'a> {
+    resource: &'a TimeSeriesResource,
+    items: Vec<AddDatapoints>,
+    index: HashMap<IdentityOrInstance, usize>,
+    // Whether each time series is a string time series, kept across flushes.
+    is_string: HashMap<IdentityOrInstance, bool>,
+    // Datapoints without a value for time series of unknown type.
+    nulls: HashMap<IdentityOrInstance, Vec<(i64, Option<StatusCode>)>>,
+    count: usize,
+    batch_size: usize,
+    total: usize,
+}
+
+impl<'a> ImportBuffer<'a> {
+    pub fn new(resource: &'a TimeSeriesResource, batch_size: usize) -> Self {
+        Self {
+            resource,
+            items: vec![],
+            index: HashMap::new(),
+            is_string: HashMap::new(),
+            nulls: HashMap::new(),
+            count: 0,
+            batch_size: batch_size.max(1),
+            total: 0,
+        }
+    }
+
+    /// Add a datapoint to the buffer, returning `true` if the buffer should be flushed.
+    pub fn push(&mut self, id: IdentityOrInstance, dp: ImportedDatapoint) -> Result<bool> {
+        let is_string = match &dp {
+            ImportedDatapoint::Numeric(_) => false,
+            ImportedDatapoint::String(_) => true,
+            ImportedDatapoint::Null(timestamp, status) => match self.is_string.get(&id) {
+                Some(is_string) => *is_string,
+                None => {
+                    self.nulls
+                        .entry(id)
+                        .or_default()
+                        .push((*timestamp, *status));
+                    return Ok(false);
+                }
+            },
+        };
+        let nulls = self.nulls.remove(&id).unwrap_or_default();
+        let idx = match self.index.get(&id) {
+            Some(idx) => *idx,
+            None => {
+                let datapoints = if *self.is_string.entry(id.clone()).or_insert(is_string) {
+                    DatapointsEnumType::StringDatapoints(vec![])
+                } else {
+                    DatapointsEnumType::NumericDatapoints(vec![])
+                };
+                self.items.push(AddDatapoints {
+                    id: id.clone(),
+                    datapoints,
+                });
+                self.index.insert(id, self.items.len() - 1);
+                self.items.len() - 1
+            }
+        };
+        let item = &mut self.items[idx];
+        self.count += nulls.len();
+        match &mut item.datapoints {
+            DatapointsEnumType::NumericDatapoints(dps) => dps.extend(nulls.into_iter().map(
+                |(timestamp, status)| DatapointDouble {
+                    timestamp,
+                    value: None,
+                    status,
+                },
+            )),
+            DatapointsEnumType::StringDatapoints(dps) => dps.extend(nulls.into_iter().map(
+                |(timestamp, status)| DatapointString {
+                    timestamp,
+                    value: None,
+                    status,
+                },
+            )),
+            DatapointsEnumType::AggregateDatapoints(_) => (),
+        }
+        match (&mut item.datapoints, dp) {
+            (DatapointsEnumType::NumericDatapoints(dps), ImportedDatapoint::Numeric(dp)) => {
+                dps.push(dp)
+            }
+            (DatapointsEnumType::StringDatapoints(dps), ImportedDatapoint::String(dp)) => {
+                dps.push(dp)
+            }
+            (DatapointsEnumType::NumericDatapoints(dps), ImportedDatapoint::Null(ts, status)) => {
+                dps.push(DatapointDouble {
+                    timestamp: ts,
+                    value: None,
+                    status,
+                })
+            }
+            (DatapointsEnumType::StringDatapoints(dps), ImportedDatapoint::Null(ts, status)) => dps
+                .push(DatapointString {
+                    timestamp: ts,
+                    value: None,
+                    status,
+                }),
+            _ => {
+                return Err(Error::Other(format!(
+                    "Time series {} has both numeric and string datapoints",
+                    wide_column_name(&item.id)
+                )))
+            }
+        }
+        self.count += 1;
+        Ok(self.count >= self.batch_size || self.items.len() >= MAX_SERIES_PER_INSERT)
+    }
+
+    /// Insert all buffered datapoints.
+    pub async fn flush(&mut self) -> Result<()> {
+        if self.items.is_empty() {
+            return Ok(());
+        }
+        let items = std::mem::take(&mut self.items);
+        self.index.clear();
+        self.resource.insert_datapoints(items).await?;
+        self.total += self.count;
+        self.count = 0;
+        Ok(())
+    }
+
+    /// Insert any remaining datapoints, and return the total number of datapoints inserted.
+    pub async fn finish(mut self) -> Result<usize> {
+        for (id, nulls) in std::mem::take(&mut self.nulls) {
+            self.is_string.insert(id.clone(), false);
+            for (timestamp, status) in nulls {
+                if self.push(id.clone(), ImportedDatapoint::Null(timestamp, status))? {
+                    self.flush().await?;
+                }
+            }
+        }
+        self.flush().await?;
+        Ok(self.total)
+    }
+}
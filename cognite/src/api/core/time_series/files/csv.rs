@@ -0,0 +1,566 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+
+use futures::TryStreamExt;
+
+use super::{
+    check_exportable, parse_status, wide_column_name, DatapointsColumns, ImportBuffer,
+    ImportedDatapoint, LongRow, WideColumnIdentity,
+};
+use crate::api::core::time_series::datapoints_stream::{
+    DatapointsBatch, DatapointsStream, TimeSeriesRef,
+};
+use crate::time_series::{
+    DatapointDouble, DatapointString, DatapointView, DatapointsFilter, DatapointsStreamOptions,
+    StatusCode, TimeSeriesResource, TimestampFormat,
+};
+use crate::{Error, IdentityOrInstance, Result};
+
+fn csv_error(e: ::csv::Error) -> Error {
+    Error::Other(format!("CSV error: {e}"))
+}
+
+fn row_error(line: Option<u64>, e: Error) -> Error {
+    match line {
+        Some(line) => Error::Other(format!("Line {line}: {e}")),
+        None => e,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Layout of datapoints in CSV files.
+pub enum CsvLayout {
+    /// One row per datapoint, with columns given by [`DatapointsColumns`].
+    #[default]
+    Long,
+    /// One row per timestamp, with a timestamp column and one column per time series.
+    /// Cells are empty where a time series has no datapoint at a timestamp.
+    Wide,
+}
+
+#[derive(Clone, Debug)]
+/// Options for [`TimeSeriesResource::export_datapoints_csv`].
+pub struct CsvExportOptions {
+    /// Layout of the file. Default [`CsvLayout::Long`].
+    pub layout: CsvLayout,
+    /// Field delimiter. Default `,`.
+    pub delimiter: u8,
+    /// Format of timestamps. Default milliseconds since epoch.
+    pub timestamp_format: TimestampFormat,
+    /// Column names. In wide layout, only the timestamp column is used.
+    pub columns: DatapointsColumns,
+    /// In wide layout, write the status code of each time series to a column named by the
+    /// time series column followed by this suffix. If `None`, status codes are not written.
+    pub wide_status_suffix: Option<String>,
+    /// Options for the datapoints stream.
+    pub stream: DatapointsStreamOptions,
+}
+
+impl Default for CsvExportOptions {
+    fn default() -> Self {
+        Self {
+            layout: CsvLayout::Long,
+            delimiter: b',',
+            timestamp_format: TimestampFormat::EpochMillis,
+            columns: DatapointsColumns::default(),
+            wide_status_suffix: None,
+            stream: DatapointsStreamOptions::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Options for [`TimeSeriesResource::import_datapoints_csv`].
+pub struct CsvImportOptions {
+    /// Layout of the file. Default [`CsvLayout::Long`].
+    pub layout: CsvLayout,
+    /// Field delimiter. Default `,`.
+    pub delimiter: u8,
+    /// Format of timestamps. Default milliseconds since epoch.
+    pub timestamp_format: TimestampFormat,
+    /// Column names. In wide layout, only the timestamp column is used.
+    pub columns: DatapointsColumns,
+    /// In wide layout, how column names map to time series. Default external IDs.
+    pub wide_identity: WideColumnIdentity,
+    /// In wide layout, columns named by a time series column followed by this suffix contain
+    /// the status codes of that time series.
+    pub wide_status_suffix: Option<String>,
+    /// In wide layout, treat all values as strings. By default, values are parsed as numbers.
+    pub wide_string_values: bool,
+    /// Maximum number of datapoints to insert in each request. Default 100 000.
+    pub batch_size: usize,
+}
+
+impl Default for CsvImportOptions {
+    fn default() -> Self {
+        Self {
+            layout: CsvLayout::Long,
+            delimiter: b',',
+            timestamp_format: TimestampFormat::EpochMillis,
+            columns: DatapointsColumns::default(),
+            wide_identity: WideColumnIdentity::ExternalId,
+            wide_status_suffix: None,
+            wide_string_values: false,
+            batch_size: 100_000,
+        }
+    }
+}
+
+fn format_status(status: Option<StatusCode>) -> String {
+    status.map(|s| s.to_string()).unwrap_or_default()
+}
+
+struct LongWriter<W: Write> {
+    writer: ::csv::Writer<W>,
+    columns: DatapointsColumns,
+    timestamp_format: TimestampFormat,
+}
+
+impl<W: Write> LongWriter<W> {
+    fn write_header(&mut self) -> Result<()> {
+        let c = &self.columns;
+        let header = [
+            c.id.as_deref(),
+            c.external_id.as_deref(),
+            c.instance_space.as_deref(),
+            c.instance_external_id.as_deref(),
+            Some(c.timestamp.as_str()),
+            Some(c.value.as_str()),
+            Some(c.string_value.as_str()),
+            c.status_code.as_deref(),
+        ];
+        self.writer
+            .write_record(header.into_iter().flatten())
+            .map_err(csv_error)
+    }
+
+    fn write_row(&mut self, ts: &TimeSeriesRef, dp: DatapointView<'_>) -> Result<()> {
+        let c = &self.columns;
+        let instance_id = ts.instance_id();
+        let value = dp.as_f64().map(|v| v.to_string()).unwrap_or_default();
+        let status = format_status(dp.status());
+        let fields = [
+            c.id.as_ref().map(|_| ts.id().to_string()),
+            c.external_id
+                .as_ref()
+                .map(|_| ts.external_id().unwrap_or_default().to_owned()),
+            c.instance_space
+                .as_ref()
+                .map(|_| instance_id.map(|i| i.space.clone()).unwrap_or_default()),
+            c.instance_external_id.as_ref().map(|_| {
+                instance_id
+                    .map(|i| i.external_id.clone())
+                    .unwrap_or_default()
+            }),
+            Some(self.timestamp_format.format(dp.timestamp())),
+            Some(value),
+            Some(dp.as_str().unwrap_or_default().to_owned()),
+            c.status_code.as_ref().map(|_| status),
+        ];
+        self.writer
+            .write_record(fields.iter().flatten())
+            .map_err(csv_error)
+    }
+}
+
+struct WideColumn {
+    // Buffered datapoints as (timestamp, value, status).
+    buffer: VecDeque<(i64, String, Option<StatusCode>)>,
+    last: Option<i64>,
+    finished: bool,
+}
+
+/// Merges datapoints from each time series into rows. Each time series is returned in
+/// ascending order, so all timestamps up to the lowest last timestamp seen for any
+/// unfinished time series are complete, and can be written.
+struct WideWriter<W: Write> {
+    writer: ::csv::Writer<W>,
+    timestamp_format: TimestampFormat,
+    write_status: bool,
+    columns: Vec<WideColumn>,
+    index: HashMap<IdentityOrInstance, usize>,
+    rows: usize,
+}
+
+impl<W: Write> WideWriter<W> {
+    fn new(
+        writer: ::csv::Writer<W>,
+        filter: &DatapointsFilter,
+        options: &CsvExportOptions,
+    ) -> Result<Self> {
+        let mut res = Self {
+            writer,
+            timestamp_format: options.timestamp_format.clone(),
+            write_status: options.wide_status_suffix.is_some(),
+            columns: vec![],
+            index: HashMap::new(),
+            rows: 0,
+        };
+        let mut header = vec![options.columns.timestamp.clone()];
+        for query in &filter.items {
+            if res.index.contains_key(&query.id) {
+                continue;
+            }
+            let name = wide_column_name(&query.id);
+            if let Some(suffix) = &options.wide_status_suffix {
+                header.push(name.clone());
+                header.push(format!("{name}{suffix}"));
+            } else {
+                header.push(name);
+            }
+            res.index.insert(query.id.clone(), res.columns.len());
+            res.columns.push(WideColumn {
+                buffer: VecDeque::new(),
+                last: None,
+                finished: false,
+            });
+        }
+        res.writer.write_record(&header).map_err(csv_error)?;
+        Ok(res)
+    }
+
+    fn add_batch(&mut self, batch: &DatapointsBatch) -> Result<()> {
+        for ((ts, item), finished) in batch.items().zip(batch.finished()) {
+            let Some(idx) = self.index.get(ts.original_id()) else {
+                continue;
+            };
+            let column = &mut self.columns[*idx];
+            for dp in item.datapoints() {
+                let value = match dp {
+                    DatapointView::String(_, value, _) => value.unwrap_or_default().to_owned(),
+                    dp => dp.as_f64().map(|v| v.to_string()).unwrap_or_default(),
+                };
+                column
+                    .buffer
+                    .push_back((dp.timestamp(), value, dp.status()));
+                column.last = Some(dp.timestamp());
+            }
+            column.finished |= *finished;
+        }
+        let mut watermark = i64::MAX;
+        for column in self.columns.iter().filter(|c| !c.finished) {
+            match column.last {
+                Some(last) => watermark = watermark.min(last),
+                None => return Ok(()),
+            }
+        }
+        self.write_rows(watermark)
+    }
+
+    fn write_rows(&mut self, watermark: i64) -> Result<()> {
+        let mut record = Vec::with_capacity(self.columns.len() * 2 + 1);
+        loop {
+            let Some(timestamp) = self
+                .columns
+                .iter()
+                .filter_map(|c| c.buffer.front().map(|(ts, _, _)| *ts))
+                .min()
+            else {
+                return Ok(());
+            };
+            if timestamp > watermark {
+                return Ok(());
+            }
+            record.clear();
+            record.push(self.timestamp_format.format(timestamp));
+            for column in &mut self.columns {
+                let (value, status) = match column.buffer.front() {
+                    Some((ts, _, _)) if *ts == timestamp => {
+                        let (_, value, status) = column.buffer.pop_front().unwrap();
+                        (value, format_status(status))
+                    }
+                    _ => (String::new(), String::new()),
+                };
+                record.push(value);
+                if self.write_status {
+                    record.push(status);
+                }
+            }
+            self.writer.write_record(&record).map_err(csv_error)?;
+            self.rows += 1;
+        }
+    }
+}
+
+struct WideImportColumn {
+    id: IdentityOrInstance,
+    value: Option<usize>,
+    status: Option<usize>,
+}
+
+fn wide_import_columns(
+    headers: &::csv::StringRecord,
+    options: &CsvImportOptions,
+) -> Result<(usize, Vec<WideImportColumn>)> {
+    let timestamp = headers
+        .iter()
+        .position(|h| h == options.columns.timestamp)
+        .ok_or_else(|| {
+            Error::Other(format!(
+                "Missing timestamp column '{}'",
+                options.columns.timestamp
+            ))
+        })?;
+    let mut columns: Vec<WideImportColumn> = vec![];
+    let mut by_name: HashMap<&str, usize> = HashMap::new();
+    for (idx, name) in headers.iter().enumerate() {
+        if idx == timestamp {
+            continue;
+        }
+        let (name, is_status) = match options
+            .wide_status_suffix
+            .as_deref()
+            .and_then(|s| name.strip_suffix(s))
+        {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let col_idx = match by_name.get(name) {
+            Some(i) => *i,
+            None => {
+                columns.push(WideImportColumn {
+                    id: options.wide_identity.parse(name)?,
+                    value: None,
+                    status: None,
+                });
+                by_name.insert(name, columns.len() - 1);
+                columns.len() - 1
+            }
+        };
+        if is_status {
+            columns[col_idx].status = Some(idx);
+        } else {
+            columns[col_idx].value = Some(idx);
+        }
+    }
+    Ok((timestamp, columns))
+}
+
+fn parse_timestamp(format: &TimestampFormat, value: &str) -> Result<i64> {
+    format
+        .parse(value)
+        .ok_or_else(|| Error::Other(format!("Invalid timestamp '{value}'")))
+}
+
+fn parse_value(value: &str) -> Result<f64> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::Other(format!("Invalid numeric value '{value}'")))
+}
+
+fn find_column(headers: &::csv::StringRecord, name: Option<&str>) -> Option<usize> {
+    let name = name?;
+    headers.iter().position(|h| h == name)
+}
+
+impl TimeSeriesResource {
+    /// Export datapoints to a CSV file.
+    ///
+    /// Datapoints are streamed from CDF and written as they arrive, so memory use is bounded
+    /// regardless of the number of datapoints. In wide layout, datapoints are buffered until
+    /// all time series have been read past their timestamp, so time series that are read
+    /// at very different rates, or do not exist, will increase memory use.
+    ///
+    /// Only raw datapoints can be exported. The writer is written to synchronously.
+    ///
+    /// Returns the number of rows written, excluding the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter describing common filter properties and a list of timeseries to retrieve data from.
+    /// * `writer` - Writer to write the CSV file to.
+    /// * `options` - Options for the layout and format of the file.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let file = std::fs::File::create("datapoints.csv")?;
+    /// let options = CsvExportOptions {
+    ///     layout: CsvLayout::Wide,
+    ///     timestamp_format: TimestampFormat::Rfc3339,
+    ///     ..Default::default()
+    /// };
+    /// client.time_series.export_datapoints_csv(filter, file, options).await?;
+    /// ```
+    pub async fn export_datapoints_csv<W: Write>(
+        &self,
+        filter: DatapointsFilter,
+        writer: W,
+        options: CsvExportOptions,
+    ) -> Result<usize> {
+        check_exportable(&filter)?;
+        let writer = ::csv::WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .from_writer(writer);
+        let mut stream =
+            std::pin::pin!(
+                DatapointsStream::new(self, filter.clone(), options.stream.clone()).stream_views()
+            );
+
+        let rows = match options.layout {
+            CsvLayout::Long => {
+                let mut writer = LongWriter {
+                    writer,
+                    columns: options.columns,
+                    timestamp_format: options.timestamp_format,
+                };
+                writer.write_header()?;
+                let mut rows = 0;
+                while let Some(batch) = stream.try_next().await? {
+                    for (ts, dp) in batch.datapoints() {
+                        writer.write_row(ts, dp)?;
+                        rows += 1;
+                    }
+                }
+                writer.writer.flush()?;
+                rows
+            }
+            CsvLayout::Wide => {
+                let mut writer = WideWriter::new(writer, &filter, &options)?;
+                while let Some(batch) = stream.try_next().await? {
+                    writer.add_batch(&batch)?;
+                }
+                writer.write_rows(i64::MAX)?;
+                writer.writer.flush()?;
+                writer.rows
+            }
+        };
+        Ok(rows)
+    }
+
+    /// Import datapoints from a CSV file, inserting them with
+    /// [insert_datapoints](Self::insert_datapoints).
+    ///
+    /// The file is read as a stream, and datapoints are inserted in batches of
+    /// `batch_size`, so memory use is bounded. If an error occurs, datapoints in earlier
+    /// batches have already been inserted.
+    ///
+    /// In long layout, rows with a value in the string value column become string datapoints,
+    /// rows with a value in the value column become numeric datapoints, and rows with neither
+    /// become datapoints without a value. Rows with an empty string value and a status that
+    /// is not bad become string datapoints with an empty value.
+    ///
+    /// The reader is read from synchronously.
+    ///
+    /// Returns the number of datapoints inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader to read the CSV file from.
+    /// * `options` - Options for the layout and format of the file.
+    pub async fn import_datapoints_csv<R: Read>(
+        &self,
+        reader: R,
+        options: CsvImportOptions,
+    ) -> Result<usize> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .from_reader(reader);
+        let headers = reader.headers().map_err(csv_error)?.clone();
+        let mut buffer = ImportBuffer::new(self, options.batch_size);
+        let mut record = ::csv::StringRecord::new();
+        let format = &options.timestamp_format;
+
+        match options.layout {
+            CsvLayout::Long => {
+                let c = &options.columns;
+                let timestamp_col = find_column(&headers, Some(&c.timestamp)).ok_or_else(|| {
+                    Error::Other(format!("Missing timestamp column '{}'", c.timestamp))
+                })?;
+                let id_col = find_column(&headers, c.id.as_deref());
+                let external_id_col = find_column(&headers, c.external_id.as_deref());
+                let space_col = find_column(&headers, c.instance_space.as_deref());
+                let instance_xid_col = find_column(&headers, c.instance_external_id.as_deref());
+                let value_col = find_column(&headers, Some(&c.value));
+                let string_value_col = find_column(&headers, Some(&c.string_value));
+                let status_col = find_column(&headers, c.status_code.as_deref());
+
+                while reader.read_record(&mut record).map_err(csv_error)? {
+                    let line = record.position().map(|p| p.line());
+                    let field = |col: Option<usize>| col.and_then(|i| record.get(i));
+                    let row = (|| {
+                        let status = parse_status(field(status_col).unwrap_or_default())?;
+                        // Empty fields can't be told apart from missing values in CSV. Datapoints
+                        // without a value always have a bad status, so an empty string value
+                        // without a bad status is read as an empty string.
+                        let string_value = field(string_value_col)
+                            .filter(|s| !s.is_empty() || status.is_none_or(|s| !s.is_bad()));
+                        let row = LongRow {
+                            id: match field(id_col).filter(|s| !s.is_empty()) {
+                                Some(id) => Some(id.trim().parse().map_err(|_| {
+                                    Error::Other(format!("Invalid internal ID '{id}'"))
+                                })?),
+                                None => None,
+                            },
+                            external_id: field(external_id_col),
+                            instance_space: field(space_col),
+                            instance_external_id: field(instance_xid_col),
+                            timestamp: parse_timestamp(
+                                format,
+                                field(Some(timestamp_col)).unwrap_or_default(),
+                            )?,
+                            value: match field(value_col).filter(|s| !s.is_empty()) {
+                                Some(v) => Some(parse_value(v)?),
+                                None => None,
+                            },
+                            string_value,
+                            status,
+                        };
+                        row.into_datapoint()
+                    })()
+                    .map_err(|e| row_error(line, e))?;
+                    if buffer.push(row.0, row.1).map_err(|e| row_error(line, e))? {
+                        buffer.flush().await?;
+                    }
+                }
+            }
+            CsvLayout::Wide => {
+                let (timestamp_col, columns) = wide_import_columns(&headers, &options)?;
+                while reader.read_record(&mut record).map_err(csv_error)? {
+                    let line = record.position().map(|p| p.line());
+                    let timestamp =
+                        parse_timestamp(format, record.get(timestamp_col).unwrap_or_default())
+                            .map_err(|e| row_error(line, e))?;
+                    let mut flush = false;
+                    for column in &columns {
+                        let value = column
+                            .value
+                            .and_then(|i| record.get(i))
+                            .filter(|s| !s.is_empty());
+                        let status = parse_status(
+                            column
+                                .status
+                                .and_then(|i| record.get(i))
+                                .unwrap_or_default(),
+                        )
+                        .map_err(|e| row_error(line, e))?;
+                        let dp = match value {
+                            None if status.is_none() => continue,
+                            None => ImportedDatapoint::Null(timestamp, status),
+                            Some(v) if options.wide_string_values => {
+                                ImportedDatapoint::String(DatapointString {
+                                    timestamp,
+                                    value: Some(v.to_owned()),
+                                    status,
+                                })
+                            }
+                            Some(v) => ImportedDatapoint::Numeric(DatapointDouble {
+                                timestamp,
+                                value: Some(parse_value(v).map_err(|e| row_error(line, e))?),
+                                status,
+                            }),
+                        };
+                        flush |= buffer
+                            .push(column.id.clone(), dp)
+                            .map_err(|e| row_error(line, e))?;
+                    }
+                    if flush {
+                        buffer.flush().await?;
+                    }
+                }
+            }
+        }
+        buffer.finish().await
+    }
+}
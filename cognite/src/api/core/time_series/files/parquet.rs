@@ -0,0 +1,368 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int64Type, TimestampMicrosecondType, TimestampMillisecondType,
+    TimestampNanosecondType, TimestampSecondType, UInt32Type,
+};
+use arrow_array::{
+    Array, ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray,
+    UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use futures::TryStreamExt;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::ChunkReader;
+
+use super::{check_exportable, parse_status, DatapointsColumns, ImportBuffer, LongRow};
+use crate::api::core::time_series::datapoints_stream::{DatapointsBatch, DatapointsStream};
+use crate::time_series::{
+    DatapointsFilter, DatapointsStreamOptions, StatusCode, TimeSeriesResource, TimestampFormat,
+};
+use crate::{Error, Result};
+
+fn parquet_error(e: impl std::fmt::Display) -> Error {
+    Error::Other(format!("Parquet error: {e}"))
+}
+
+#[derive(Clone, Debug, Default)]
+/// Options for [`TimeSeriesResource::export_datapoints_parquet`].
+pub struct ParquetExportOptions {
+    /// Column names.
+    pub columns: DatapointsColumns,
+    /// Options for the datapoints stream.
+    pub stream: DatapointsStreamOptions,
+}
+
+#[derive(Clone, Debug)]
+/// Options for [`TimeSeriesResource::import_datapoints_parquet`].
+pub struct ParquetImportOptions {
+    /// Column names.
+    pub columns: DatapointsColumns,
+    /// Format of timestamps, if the timestamp column is not an Arrow timestamp column.
+    /// Default milliseconds since epoch.
+    pub timestamp_format: TimestampFormat,
+    /// Maximum number of datapoints to insert in each request. Default 100 000.
+    pub batch_size: usize,
+}
+
+impl Default for ParquetImportOptions {
+    fn default() -> Self {
+        Self {
+            columns: DatapointsColumns::default(),
+            timestamp_format: TimestampFormat::EpochMillis,
+            batch_size: 100_000,
+        }
+    }
+}
+
+fn export_schema(columns: &DatapointsColumns) -> SchemaRef {
+    let timestamp_type = DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()));
+    let fields = [
+        (columns.id.as_deref(), DataType::Int64),
+        (columns.external_id.as_deref(), DataType::Utf8),
+        (columns.instance_space.as_deref(), DataType::Utf8),
+        (columns.instance_external_id.as_deref(), DataType::Utf8),
+        (Some(columns.timestamp.as_str()), timestamp_type),
+        (Some(columns.value.as_str()), DataType::Float64),
+        (Some(columns.string_value.as_str()), DataType::Utf8),
+        (columns.status_code.as_deref(), DataType::UInt32),
+    ];
+    Arc::new(Schema::new(
+        fields
+            .into_iter()
+            .filter_map(|(name, data_type)| Some(Field::new(name?, data_type, true)))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+fn export_batch(
+    schema: &SchemaRef,
+    columns: &DatapointsColumns,
+    batch: &DatapointsBatch,
+) -> Result<RecordBatch> {
+    let mut id = vec![];
+    let mut external_id = vec![];
+    let mut instance_space = vec![];
+    let mut instance_external_id = vec![];
+    let mut timestamp = vec![];
+    let mut value = vec![];
+    let mut string_value = vec![];
+    let mut status_code = vec![];
+    for (ts, dp) in batch.datapoints() {
+        id.push(ts.id());
+        external_id.push(ts.external_id());
+        instance_space.push(ts.instance_id().map(|i| i.space.as_str()));
+        instance_external_id.push(ts.instance_id().map(|i| i.external_id.as_str()));
+        timestamp.push(dp.timestamp());
+        value.push(dp.as_f64());
+        string_value.push(dp.as_str());
+        status_code.push(dp.status().map(|s| s.bits()));
+    }
+    let arrays: [(bool, ArrayRef); 8] = [
+        (columns.id.is_some(), Arc::new(Int64Array::from(id))),
+        (
+            columns.external_id.is_some(),
+            Arc::new(StringArray::from(external_id)),
+        ),
+        (
+            columns.instance_space.is_some(),
+            Arc::new(StringArray::from(instance_space)),
+        ),
+        (
+            columns.instance_external_id.is_some(),
+            Arc::new(StringArray::from(instance_external_id)),
+        ),
+        (
+            true,
+            Arc::new(TimestampMillisecondArray::from(timestamp).with_timezone("UTC")),
+        ),
+        (true, Arc::new(Float64Array::from(value))),
+        (true, Arc::new(StringArray::from(string_value))),
+        (
+            columns.status_code.is_some(),
+            Arc::new(UInt32Array::from(status_code)),
+        ),
+    ];
+    RecordBatch::try_new(
+        schema.clone(),
+        arrays
+            .into_iter()
+            .filter_map(|(enabled, array)| enabled.then_some(array))
+            .collect(),
+    )
+    .map_err(parquet_error)
+}
+
+fn find_column<'a>(batch: &'a RecordBatch, name: Option<&str>) -> Option<&'a ArrayRef> {
+    batch.column_by_name(name?)
+}
+
+fn unsupported(name: &str, array: &ArrayRef) -> Error {
+    Error::Other(format!(
+        "Column '{name}' has unsupported type {}",
+        array.data_type()
+    ))
+}
+
+/// Read a string column, which may be either `Utf8` or `LargeUtf8`.
+fn str_value<'a>(name: &str, array: Option<&'a ArrayRef>, row: usize) -> Result<Option<&'a str>> {
+    let Some(array) = array else {
+        return Ok(None);
+    };
+    if array.is_null(row) {
+        return Ok(None);
+    }
+    match array.data_type() {
+        DataType::Utf8 => Ok(Some(array.as_string::<i32>().value(row))),
+        DataType::LargeUtf8 => Ok(Some(array.as_string::<i64>().value(row))),
+        _ => Err(unsupported(name, array)),
+    }
+}
+
+fn timestamp_value(
+    name: &str,
+    array: &ArrayRef,
+    row: usize,
+    format: &TimestampFormat,
+) -> Result<i64> {
+    if array.is_null(row) {
+        return Err(Error::Other("Missing timestamp".to_owned()));
+    }
+    let res = match array.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => array
+            .as_primitive::<TimestampSecondType>()
+            .value(row)
+            .checked_mul(1000),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            Some(array.as_primitive::<TimestampMillisecondType>().value(row))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => Some(
+            array
+                .as_primitive::<TimestampMicrosecondType>()
+                .value(row)
+                .div_euclid(1000),
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => Some(
+            array
+                .as_primitive::<TimestampNanosecondType>()
+                .value(row)
+                .div_euclid(1_000_000),
+        ),
+        DataType::Int64 => {
+            let value = array.as_primitive::<Int64Type>().value(row);
+            format.parse(&value.to_string())
+        }
+        DataType::Float64 => {
+            let value = array.as_primitive::<Float64Type>().value(row);
+            format.parse(&value.to_string())
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            let value = str_value(name, Some(array), row)?.unwrap_or_default();
+            format.parse(value)
+        }
+        _ => return Err(unsupported(name, array)),
+    };
+    res.ok_or_else(|| Error::Other("Invalid timestamp".to_owned()))
+}
+
+fn value(name: &str, array: Option<&ArrayRef>, row: usize) -> Result<Option<f64>> {
+    let Some(array) = array else {
+        return Ok(None);
+    };
+    if array.is_null(row) {
+        return Ok(None);
+    }
+    match array.data_type() {
+        DataType::Float64 => Ok(Some(array.as_primitive::<Float64Type>().value(row))),
+        DataType::Float32 => Ok(Some(array.as_primitive::<Float32Type>().value(row) as f64)),
+        DataType::Int64 => Ok(Some(array.as_primitive::<Int64Type>().value(row) as f64)),
+        _ => Err(unsupported(name, array)),
+    }
+}
+
+fn status(name: &str, array: Option<&ArrayRef>, row: usize) -> Result<Option<StatusCode>> {
+    let Some(array) = array else {
+        return Ok(None);
+    };
+    if array.is_null(row) {
+        return Ok(None);
+    }
+    match array.data_type() {
+        DataType::UInt32 => Ok(Some(array.as_primitive::<UInt32Type>().value(row).into())),
+        DataType::Int64 => StatusCode::try_from(array.as_primitive::<Int64Type>().value(row))
+            .map(Some)
+            .map_err(|e| Error::Other(format!("Invalid status code: {e}"))),
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            parse_status(str_value(name, Some(array), row)?.unwrap_or_default())
+        }
+        _ => Err(unsupported(name, array)),
+    }
+}
+
+impl TimeSeriesResource {
+    /// Export datapoints to a Parquet file.
+    ///
+    /// The file has one row per datapoint, with the columns given in `options`.
+    /// Datapoints are streamed from CDF and written as they arrive, buffering at most
+    /// one row group in memory.
+    ///
+    /// Only raw datapoints can be exported. The writer is written to synchronously.
+    ///
+    /// Returns the number of rows written.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter describing common filter properties and a list of timeseries to retrieve data from.
+    /// * `writer` - Writer to write the Parquet file to.
+    /// * `options` - Options for the columns of the file.
+    pub async fn export_datapoints_parquet<W: Write + Send>(
+        &self,
+        filter: DatapointsFilter,
+        writer: W,
+        options: ParquetExportOptions,
+    ) -> Result<usize> {
+        check_exportable(&filter)?;
+        let schema = export_schema(&options.columns);
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(writer, schema.clone(), Some(props)).map_err(parquet_error)?;
+        let mut stream =
+            std::pin::pin!(DatapointsStream::new(self, filter, options.stream).stream_views());
+        let mut rows = 0;
+        while let Some(batch) = stream.try_next().await? {
+            let batch = export_batch(&schema, &options.columns, &batch)?;
+            rows += batch.num_rows();
+            writer.write(&batch).map_err(parquet_error)?;
+        }
+        writer.close().map_err(parquet_error)?;
+        Ok(rows)
+    }
+
+    /// Import datapoints from a Parquet file with one row per datapoint, inserting them with
+    /// [insert_datapoints](Self::insert_datapoints).
+    ///
+    /// The file is read one record batch at a time, and datapoints are inserted in batches of
+    /// `batch_size`, so memory use is bounded. If an error occurs, datapoints in earlier
+    /// batches have already been inserted.
+    ///
+    /// See [import_datapoints_csv](Self::import_datapoints_csv) for how rows are
+    /// converted to datapoints.
+    ///
+    /// Returns the number of datapoints inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Parquet file to read, for example a `std::fs::File`.
+    /// * `options` - Options for the columns of the file.
+    pub async fn import_datapoints_parquet<R: ChunkReader + 'static>(
+        &self,
+        reader: R,
+        options: ParquetImportOptions,
+    ) -> Result<usize> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(reader)
+            .and_then(|b| b.build())
+            .map_err(parquet_error)?;
+        let c = &options.columns;
+        let mut buffer = ImportBuffer::new(self, options.batch_size);
+        let mut row_offset = 0;
+        for batch in reader {
+            let batch = batch.map_err(parquet_error)?;
+            let timestamp = batch.column_by_name(&c.timestamp).ok_or_else(|| {
+                Error::Other(format!("Missing timestamp column '{}'", c.timestamp))
+            })?;
+            let id = find_column(&batch, c.id.as_deref());
+            let external_id = find_column(&batch, c.external_id.as_deref());
+            let instance_space = find_column(&batch, c.instance_space.as_deref());
+            let instance_external_id = find_column(&batch, c.instance_external_id.as_deref());
+            let value_col = find_column(&batch, Some(&c.value));
+            let string_value = find_column(&batch, Some(&c.string_value));
+            let status_col = find_column(&batch, c.status_code.as_deref());
+
+            for row in 0..batch.num_rows() {
+                let row_error = |e: Error| Error::Other(format!("Row {}: {e}", row_offset + row));
+                let id = match id {
+                    Some(array) if !array.is_null(row) => match array.data_type() {
+                        DataType::Int64 => Some(array.as_primitive::<Int64Type>().value(row)),
+                        _ => return Err(row_error(unsupported("id", array))),
+                    },
+                    _ => None,
+                };
+                let long_row = (|| {
+                    LongRow {
+                        id,
+                        external_id: str_value("external_id", external_id, row)?,
+                        instance_space: str_value("instance_space", instance_space, row)?,
+                        instance_external_id: str_value(
+                            "instance_external_id",
+                            instance_external_id,
+                            row,
+                        )?,
+                        timestamp: timestamp_value(
+                            &c.timestamp,
+                            timestamp,
+                            row,
+                            &options.timestamp_format,
+                        )?,
+                        value: value(&c.value, value_col, row)?,
+                        string_value: str_value(&c.string_value, string_value, row)?,
+                        status: status("status_code", status_col, row)?,
+                    }
+                    .into_datapoint()
+                })()
+                .map_err(row_error)?;
+                if buffer.push(long_row.0, long_row.1).map_err(row_error)? {
+                    buffer.flush().await?;
+                }
+            }
+            row_offset += batch.num_rows();
+        }
+        buffer.finish().await
+    }
+}
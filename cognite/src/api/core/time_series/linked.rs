@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::{Stream, TryStreamExt};
+
+use super::datapoints_stream::DatapointsStream;
+use crate::api::data_modeling::instances::Instances;
+use crate::models::filter::{and, equals};
+use crate::models::instances::{
+    InstanceId, NodeOrEdge, NodesQuery, QueryDirection, QueryInstancesRequest,
+    QueryNodeTableExpression, QueryTableExpression, SelectExpression, Timeseries,
+    ViewPropertyReference, WithView,
+};
+use crate::models::views::ViewReference;
+use crate::time_series::{
+    DatapointsEnumType, DatapointsFilter, DatapointsQuery, DatapointsResponse,
+    DatapointsStreamOptions, TimeSeries, TimeSeriesFilter, TimeSeriesFilterRequest,
+    TimeSeriesResource,
+};
+use crate::{FilterWithRequest, Identity, Result, RetrieveWithIgnoreUnknownIds};
+
+const SOURCE_QUERY: &str = "source";
+const TIMESERIES_QUERY: &str = "timeseries";
+const PAGE_SIZE: usize = 1000;
+
+#[derive(Clone, Debug)]
+/// How to find the time series linked to an asset or instance.
+pub enum LinkedTimeSeriesSource {
+    /// Classic time series with one of these assets in `asset_id`.
+    Assets(Vec<Identity>),
+    /// Classic time series linked to any asset in the subtree of one of these assets,
+    /// including the assets themselves.
+    AssetSubtrees(Vec<Identity>),
+    /// Data modeling time series with a direct relation to an instance.
+    Instance {
+        /// ID of the instance the time series are linked to.
+        instance_id: InstanceId,
+        /// Direct relation property on the time series pointing to the instance.
+        through: ViewPropertyReference,
+    },
+}
+
+impl LinkedTimeSeriesSource {
+    /// Time series in the core data model linked to an asset instance through
+    /// the `assets` property of `CogniteTimeSeries`.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - ID of the asset instance.
+    pub fn cognite_asset(instance_id: InstanceId) -> Self {
+        Self::Instance {
+            instance_id,
+            through: ViewPropertyReference {
+                view: ViewReference {
+                    space: Timeseries::SPACE.to_owned(),
+                    external_id: Timeseries::EXTERNAL_ID.to_owned(),
+                    version: Timeseries::VERSION.to_owned(),
+                }
+                .into(),
+                identifier: "assets".to_owned(),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Datapoints for a single time series linked to an asset or instance.
+pub struct LinkedDatapoints {
+    /// The time series the datapoints belong to.
+    pub timeseries: TimeSeries,
+    /// Retrieved datapoints. When collected with
+    /// [retrieve_linked_datapoints](TimeSeriesResource::retrieve_linked_datapoints),
+    /// this is `None` if no datapoints were returned.
+    pub datapoints: Option<DatapointsEnumType>,
+}
+
+fn extend_datapoints(target: &mut Option<DatapointsEnumType>, new: DatapointsEnumType) {
+    match (target, new) {
+        (
+            Some(DatapointsEnumType::NumericDatapoints(a)),
+            DatapointsEnumType::NumericDatapoints(b),
+        ) => a.extend(b),
+        (
+            Some(DatapointsEnumType::StringDatapoints(a)),
+            DatapointsEnumType::StringDatapoints(b),
+        ) => a.extend(b),
+        (
+            Some(DatapointsEnumType::AggregateDatapoints(a)),
+            DatapointsEnumType::AggregateDatapoints(b),
+        ) => a.extend(b),
+        (target, new) => *target = Some(new),
+    }
+}
+
+impl TimeSeriesResource {
+    async fn filter_linked(
+        &self,
+        filter: TimeSeriesFilter,
+        result: &mut Vec<TimeSeries>,
+        seen: &mut HashSet<i64>,
+    ) -> Result<()> {
+        let items = self
+            .filter_all(TimeSeriesFilterRequest {
+                filter: Some(filter),
+                limit: Some(PAGE_SIZE as i32),
+                ..Default::default()
+            })
+            .await?;
+        result.extend(items.into_iter().filter(|ts| seen.insert(ts.id)));
+        Ok(())
+    }
+
+    async fn query_linked_instances(
+        &self,
+        instance_id: &InstanceId,
+        through: &ViewPropertyReference,
+    ) -> Result<Vec<InstanceId>> {
+        let instances = Instances::new(self.api_client.clone());
+        let mut query = QueryInstancesRequest {
+            with: HashMap::from([
+                (
+                    SOURCE_QUERY.to_owned(),
+                    QueryTableExpression::Node(QueryNodeTableExpression {
+                        limit: Some(1),
+                        nodes: NodesQuery {
+                            filter: Some(and(vec![
+                                equals(["node", "space"], instance_id.space.as_str()),
+                                equals(["node", "externalId"], instance_id.external_id.as_str()),
+                            ])),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+                ),
+                (
+                    TIMESERIES_QUERY.to_owned(),
+                    QueryTableExpression::Node(QueryNodeTableExpression {
+                        limit: Some(PAGE_SIZE as i32),
+                        nodes: NodesQuery {
+                            from: Some(SOURCE_QUERY.to_owned()),
+                            through: Some(through.clone()),
+                            direction: Some(QueryDirection::Inwards),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+                ),
+            ]),
+            cursors: None,
+            select: HashMap::from([(
+                TIMESERIES_QUERY.to_owned(),
+                SelectExpression {
+                    sources: vec![],
+                    sort: None,
+                    limit: None,
+                },
+            )]),
+            parameters: None,
+            include_typing: None,
+        };
+
+        let mut result = vec![];
+        loop {
+            let mut response = instances.query::<serde_json::Value>(query.clone()).await?;
+            let items = response.items.remove(TIMESERIES_QUERY).unwrap_or_default();
+            let count = items.len();
+            result.extend(items.into_iter().filter_map(|item| match item {
+                NodeOrEdge::Node(node) => Some(InstanceId {
+                    space: node.space,
+                    external_id: node.external_id,
+                }),
+                NodeOrEdge::Edge(_) => None,
+            }));
+            let cursor = response
+                .next_cursor
+                .and_then(|mut c| c.remove(TIMESERIES_QUERY));
+            match cursor {
+                Some(cursor) if count > 0 => {
+                    query.cursors = Some(HashMap::from([(TIMESERIES_QUERY.to_owned(), cursor)]))
+                }
+                _ => return Ok(result),
+            }
+        }
+    }
+
+    /// Find all time series linked to an asset or data modeling instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Which asset or instance to find time series for, and how they are linked.
+    pub async fn resolve_linked_time_series(
+        &self,
+        source: &LinkedTimeSeriesSource,
+    ) -> Result<Vec<TimeSeries>> {
+        let mut result = vec![];
+        let mut seen = HashSet::new();
+        match source {
+            LinkedTimeSeriesSource::Assets(assets) => {
+                // The fields in a filter are combined with AND, so internal and external
+                // asset IDs must be queried separately.
+                let mut ids = vec![];
+                let mut external_ids = vec![];
+                for asset in assets {
+                    match asset {
+                        Identity::Id { id } => ids.push(*id),
+                        Identity::ExternalId { external_id } => {
+                            external_ids.push(external_id.clone())
+                        }
+                    }
+                }
+                for chunk in ids.chunks(100) {
+                    let filter = TimeSeriesFilter {
+                        asset_ids: Some(chunk.to_vec()),
+                        ..Default::default()
+                    };
+                    self.filter_linked(filter, &mut result, &mut seen).await?;
+                }
+                for chunk in external_ids.chunks(100) {
+                    let filter = TimeSeriesFilter {
+                        asset_external_ids: Some(chunk.to_vec()),
+                        ..Default::default()
+                    };
+                    self.filter_linked(filter, &mut result, &mut seen).await?;
+                }
+            }
+            LinkedTimeSeriesSource::AssetSubtrees(assets) => {
+                for chunk in assets.chunks(100) {
+                    let filter = TimeSeriesFilter {
+                        asset_subtree_ids: Some(chunk.to_vec()),
+                        ..Default::default()
+                    };
+                    self.filter_linked(filter, &mut result, &mut seen).await?;
+                }
+            }
+            LinkedTimeSeriesSource::Instance {
+                instance_id,
+                through,
+            } => {
+                let instance_ids = self.query_linked_instances(instance_id, through).await?;
+                for chunk in instance_ids.chunks(PAGE_SIZE) {
+                    let items = self.retrieve(chunk, true).await?;
+                    result.extend(items.into_iter().filter(|ts| seen.insert(ts.id)));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn linked_datapoint_batches(
+        &self,
+        timeseries: Vec<TimeSeries>,
+        mut filter: DatapointsFilter,
+        options: DatapointsStreamOptions,
+    ) -> impl Stream<Item = Result<LinkedDatapoints>> + '_ {
+        filter.items = timeseries
+            .iter()
+            .map(|ts| DatapointsQuery {
+                id: Identity::Id { id: ts.id }.into(),
+                ..Default::default()
+            })
+            .collect();
+        let timeseries: HashMap<i64, TimeSeries> =
+            timeseries.into_iter().map(|ts| (ts.id, ts)).collect();
+
+        DatapointsStream::new(self, filter, options)
+            .stream_batches()
+            .map_ok(move |batch| {
+                let items: Vec<_> = batch
+                    .items
+                    .into_iter()
+                    .filter_map(|item| {
+                        let timeseries = timeseries.get(&item.id)?.clone();
+                        Some(Ok(LinkedDatapoints {
+                            timeseries,
+                            datapoints: Some(DatapointsResponse::from(item).datapoints),
+                        }))
+                    })
+                    .collect();
+                futures::stream::iter(items)
+            })
+            .try_flatten()
+    }
+
+    /// Stream datapoints for all time series linked to an asset or data modeling instance.
+    ///
+    /// The linked time series are found with
+    /// [resolve_linked_time_series](Self::resolve_linked_time_series), then datapoints for all
+    /// of them are streamed using the same filter and stream options. Each item contains one
+    /// batch of datapoints for a single time series, with the time series metadata attached.
+    /// A time series with many datapoints is returned over several items, and time series
+    /// without any datapoints are not returned at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Which asset or instance to find time series for, and how they are linked.
+    /// * `filter` - Filter properties shared by all time series. `items` is replaced by one query
+    ///   per linked time series.
+    /// * `options` - Options for controlling the stream.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let source = LinkedTimeSeriesSource::Assets(vec![Identity::from("pump-1")]);
+    /// let mut stream = pin!(client.time_series.stream_linked_datapoints(&source, filter, Default::default()));
+    /// while let Some(batch) = stream.try_next().await? {
+    ///     println!("{:?}: {:?}", batch.timeseries.name, batch.datapoints);
+    /// }
+    /// ```
+    pub fn stream_linked_datapoints<'a>(
+        &'a self,
+        source: &'a LinkedTimeSeriesSource,
+        filter: DatapointsFilter,
+        options: DatapointsStreamOptions,
+    ) -> impl Stream<Item = Result<LinkedDatapoints>> + 'a {
+        futures::stream::once(self.resolve_linked_time_series(source))
+            .map_ok(move |timeseries| {
+                self.linked_datapoint_batches(timeseries, filter.clone(), options.clone())
+            })
+            .try_flatten()
+    }
+
+    /// Retrieve datapoints for all time series linked to an asset or data modeling instance.
+    ///
+    /// This collects the output of [stream_linked_datapoints](Self::stream_linked_datapoints)
+    /// into one entry per linked time series, in the order they were found. Note that all
+    /// datapoints are kept in memory, prefer the stream for large time ranges.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Which asset or instance to find time series for, and how they are linked.
+    /// * `filter` - Filter properties shared by all time series. `items` is replaced by one query
+    ///   per linked time series.
+    /// * `options` - Options for controlling the stream.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let source = LinkedTimeSeriesSource::Assets(vec![Identity::from("pump-1")]);
+    /// let filter = DatapointsFilter {
+    ///     start: Some(TimestampOrRelative::Relative("1d-ago".to_owned())),
+    ///     ..Default::default()
+    /// };
+    /// for series in client.time_series.retrieve_linked_datapoints(&source, filter, Default::default()).await? {
+    ///     println!("{:?}: {:?}", series.timeseries.name, series.datapoints);
+    /// }
+    /// ```
+    pub async fn retrieve_linked_datapoints(
+        &self,
+        source: &LinkedTimeSeriesSource,
+        filter: DatapointsFilter,
+        options: DatapointsStreamOptions,
+    ) -> Result<Vec<LinkedDatapoints>> {
+        let timeseries = self.resolve_linked_time_series(source).await?;
+        let index: HashMap<i64, usize> = timeseries
+            .iter()
+            .enumerate()
+            .map(|(idx, ts)| (ts.id, idx))
+            .collect();
+        let mut result: Vec<_> = timeseries
+            .iter()
+            .cloned()
+            .map(|timeseries| LinkedDatapoints {
+                timeseries,
+                datapoints: None,
+            })
+            .collect();
+
+        let mut stream = std::pin::pin!(self.linked_datapoint_batches(timeseries, filter, options));
+        while let Some(batch) = stream.try_next().await? {
+            let idx = index[&batch.timeseries.id];
+            if let Some(datapoints) = batch.datapoints {
+                extend_datapoints(&mut result[idx].datapoints, datapoints);
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,184 @@
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::time_series::{
+    AddDatapoints, DatapointsEnumType, DeleteDatapointsQuery, TimeSeriesResource,
+    MAX_DATAPOINTS_PER_REQUEST,
+};
+use crate::{Error, IdentityOrInstance};
+
+#[derive(Debug, Error)]
+#[error("Failed to replace datapoints, replaced until {replaced_until}, deleted until {deleted_until}: {source}")]
+/// Error returned when replacing a range of datapoints fails part of the way.
+///
+/// The range is replaced in chronological order, one batch at a time, by first deleting
+/// the part of the range covered by the batch, then inserting the batch. When this fails:
+///
+///  - `[start, replaced_until)` has been fully replaced with the new datapoints.
+///  - `[replaced_until, deleted_until)` has been deleted, but the new datapoints were not inserted.
+///  - `[deleted_until, end)` is unchanged.
+///
+/// Calling `replace_datapoints` again with the same arguments is safe, and will complete the operation.
+pub struct ReplaceDatapointsError {
+    /// End of the part of the range that has been replaced, exclusive.
+    pub replaced_until: i64,
+    /// End of the part of the range that has been deleted, exclusive.
+    pub deleted_until: i64,
+    /// The error that caused the operation to fail.
+    #[source]
+    pub source: Error,
+}
+
+impl ReplaceDatapointsError {
+    fn new(replaced_until: i64, deleted_until: i64, source: Error) -> Self {
+        Self {
+            replaced_until,
+            deleted_until,
+            source,
+        }
+    }
+}
+
+/// Split `range` into consecutive sub-ranges covering at most `batch_size` of the sorted
+/// `timestamps` each. Returns each sub-range along with the indices of its timestamps.
+fn plan_batches(
+    range: Range<i64>,
+    timestamps: &[i64],
+    batch_size: usize,
+) -> Vec<(Range<i64>, Range<usize>)> {
+    if timestamps.is_empty() {
+        return vec![(range, 0..0)];
+    }
+    let mut batches = Vec::new();
+    let mut start = range.start;
+    let mut idx = 0;
+    while idx < timestamps.len() {
+        let next = (idx + batch_size).min(timestamps.len());
+        let end = timestamps.get(next).copied().unwrap_or(range.end);
+        batches.push((start..end, idx..next));
+        start = end;
+        idx = next;
+    }
+    batches
+}
+
+fn timestamps(datapoints: &DatapointsEnumType) -> Result<Vec<i64>, Error> {
+    match datapoints {
+        DatapointsEnumType::NumericDatapoints(d) => Ok(d.iter().map(|d| d.timestamp).collect()),
+        DatapointsEnumType::StringDatapoints(d) => Ok(d.iter().map(|d| d.timestamp).collect()),
+        DatapointsEnumType::AggregateDatapoints(_) => Err(Error::Other(
+            "Aggregate datapoints cannot be inserted".to_owned(),
+        )),
+    }
+}
+
+fn sort_datapoints(datapoints: &mut DatapointsEnumType) {
+    match datapoints {
+        DatapointsEnumType::NumericDatapoints(d) => d.sort_by_key(|d| d.timestamp),
+        DatapointsEnumType::StringDatapoints(d) => d.sort_by_key(|d| d.timestamp),
+        DatapointsEnumType::AggregateDatapoints(_) => (),
+    }
+}
+
+fn slice_datapoints(datapoints: &DatapointsEnumType, idx: Range<usize>) -> DatapointsEnumType {
+    match datapoints {
+        DatapointsEnumType::NumericDatapoints(d) => d[idx].to_vec().into(),
+        DatapointsEnumType::StringDatapoints(d) => d[idx].to_vec().into(),
+        DatapointsEnumType::AggregateDatapoints(d) => d[idx].to_vec().into(),
+    }
+}
+
+impl TimeSeriesResource {
+    /// Replace all datapoints in a time range for a single time series.
+    ///
+    /// Existing datapoints in `range` are deleted, and `datapoints` are inserted in their place.
+    /// Every datapoint must have a timestamp inside `range`, if not, nothing is changed.
+    ///
+    /// The range is processed in chronological order, in batches of at most 100 000 datapoints.
+    /// For each batch, the part of the range covered by the batch is deleted, then the batch is
+    /// inserted. If a request fails, the returned [`ReplaceDatapointsError`] describes how much
+    /// of the range was replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series to replace datapoints in.
+    /// * `range` - Time range to replace, in milliseconds since epoch. The end is exclusive.
+    /// * `datapoints` - New datapoints in the range. Must be numeric or string datapoints.
+    pub async fn replace_datapoints(
+        &self,
+        id: impl Into<IdentityOrInstance>,
+        range: Range<i64>,
+        datapoints: impl Into<DatapointsEnumType>,
+    ) -> Result<(), ReplaceDatapointsError> {
+        self.replace_datapoints_batched(
+            id.into(),
+            range,
+            datapoints.into(),
+            MAX_DATAPOINTS_PER_REQUEST,
+        )
+        .await
+    }
+
+    async fn replace_datapoints_batched(
+        &self,
+        id: IdentityOrInstance,
+        range: Range<i64>,
+        mut datapoints: DatapointsEnumType,
+        batch_size: usize,
+    ) -> Result<(), ReplaceDatapointsError> {
+        let fail = |e| ReplaceDatapointsError::new(range.start, range.start, e);
+        if range.is_empty() {
+            return Err(fail(Error::Other(format!(
+                "Invalid range {}..{}, start must be before end",
+                range.start, range.end
+            ))));
+        }
+        sort_datapoints(&mut datapoints);
+        let timestamps = timestamps(&datapoints).map_err(fail)?;
+        if let Some(ts) = timestamps.iter().find(|ts| !range.contains(ts)) {
+            return Err(fail(Error::Other(format!(
+                "Datapoint with timestamp {ts} is outside the range {}..{}",
+                range.start, range.end
+            ))));
+        }
+
+        for (sub_range, idx) in plan_batches(range, &timestamps, batch_size) {
+            self.delete_datapoints(&[DeleteDatapointsQuery {
+                inclusive_begin: sub_range.start,
+                exclusive_end: sub_range.end,
+                id: id.clone(),
+            }])
+            .await
+            .map_err(|e| ReplaceDatapointsError::new(sub_range.start, sub_range.start, e))?;
+
+            if idx.is_empty() {
+                continue;
+            }
+            self.insert_datapoints(vec![AddDatapoints {
+                id: id.clone(),
+                datapoints: slice_datapoints(&datapoints, idx),
+            }])
+            .await
+            .map_err(|e| ReplaceDatapointsError::new(sub_range.start, sub_range.end, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::plan_batches;
+
+    #[test]
+    fn test_plan_batches() {
+        assert_eq!(vec![(0..100, 0..0)], plan_batches(0..100, &[], 10));
+
+        let timestamps = [5, 10, 20, 30, 40, 50, 60];
+        assert_eq!(
+            vec![(0..30, 0..3), (30..60, 3..6), (60..100, 6..7)],
+            plan_batches(0..100, &timestamps, 3)
+        );
+        assert_eq!(vec![(0..100, 0..7)], plan_batches(0..100, &timestamps, 10));
+    }
+}
@@ -0,0 +1,473 @@
+use futures::channel::mpsc;
+use futures::{future, stream, SinkExt, Stream, StreamExt, TryStreamExt};
+
+use crate::send_helper::CondBoxStream;
+use crate::time_series::{
+    DataPointListItem, DatapointAggregate, DatapointDouble, DatapointString, DatapointsFilter,
+    DatapointsQuery, Granularity, GranularityUnit, ListDatapointType, TimeSeriesResource,
+};
+use crate::{CondBoxedStream, Result};
+
+use super::EitherDataPoint;
+
+/// How to decide where to split a time range into shards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShardingStrategy {
+    /// Use count aggregates for numeric time series, and a density estimate
+    /// for string time series, which do not support aggregates.
+    #[default]
+    Auto,
+    /// Estimate the density of the time series from a sample of datapoints
+    /// at the start of the range, and split the range into equally long shards.
+    DensityEstimate,
+}
+
+/// Options for retrieving the full history of a time series in parallel shards.
+#[derive(Debug, Clone)]
+pub struct ShardedRetrievalOptions {
+    /// Maximum number of shards. Default is 64.
+    pub max_shards: usize,
+    /// Target number of datapoints per shard. Default is 1 000 000.
+    pub datapoints_per_shard: u64,
+    /// Number of shards fetched in parallel. Default is 4.
+    pub parallelism: usize,
+    /// Maximum number of datapoints per request. Default is 100 000.
+    pub limit_per_request: u32,
+    /// Number of pages each shard fetches ahead of the consumer. Default is 1.
+    ///
+    /// Shards are fetched in parallel, but returned in order, so shards after the
+    /// one currently being consumed stop fetching once they are this many pages ahead.
+    /// At most `parallelism * (prefetch_pages + 1)` pages of `limit_per_request`
+    /// datapoints are held in memory.
+    pub prefetch_pages: usize,
+    /// Number of datapoints to sample when estimating density. Default is 10 000.
+    pub sample_size: u32,
+    /// How to split the range into shards.
+    pub strategy: ShardingStrategy,
+}
+
+impl Default for ShardedRetrievalOptions {
+    fn default() -> Self {
+        Self {
+            max_shards: 64,
+            datapoints_per_shard: 1_000_000,
+            parallelism: 4,
+            limit_per_request: 100_000,
+            prefetch_pages: 1,
+            sample_size: 10_000,
+            strategy: ShardingStrategy::default(),
+        }
+    }
+}
+
+/// Maximum number of aggregates returned in a single request.
+const MAX_AGGREGATES: i64 = 10_000;
+
+/// A shard of the time range, along with datapoints already fetched
+/// for the start of the shard.
+struct Shard {
+    start: i64,
+    end: i64,
+    initial: Option<Page>,
+}
+
+enum Page {
+    Numeric(Vec<DatapointDouble>),
+    String(Vec<DatapointString>),
+    Aggregate(Vec<DatapointAggregate>),
+}
+
+struct PageResult {
+    datapoints: Page,
+    is_string: bool,
+    cursor: Option<String>,
+}
+
+impl Page {
+    fn into_iter(self) -> impl Iterator<Item = EitherDataPoint> {
+        let (numeric, string, aggregate) = match self {
+            Page::Numeric(d) => (d, vec![], vec![]),
+            Page::String(d) => (vec![], d, vec![]),
+            Page::Aggregate(d) => (vec![], vec![], d),
+        };
+        numeric
+            .into_iter()
+            .map(EitherDataPoint::Numeric)
+            .chain(string.into_iter().map(EitherDataPoint::String))
+            .chain(aggregate.into_iter().map(EitherDataPoint::Aggregate))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Page::Numeric(d) => d.len(),
+            Page::String(d) => d.len(),
+            Page::Aggregate(d) => d.len(),
+        }
+    }
+
+    fn last_timestamp(&self) -> Option<i64> {
+        match self {
+            Page::Numeric(d) => d.last().map(|d| d.timestamp),
+            Page::String(d) => d.last().map(|d| d.timestamp),
+            Page::Aggregate(d) => d.last().map(|d| d.timestamp),
+        }
+    }
+
+    /// Split the page at `at`, keeping datapoints before `at` and
+    /// returning datapoints at or after `at`.
+    fn split_off(&mut self, at: i64) -> Page {
+        match self {
+            Page::Numeric(d) => Page::Numeric(d.split_off(d.partition_point(|d| d.timestamp < at))),
+            Page::String(d) => Page::String(d.split_off(d.partition_point(|d| d.timestamp < at))),
+            Page::Aggregate(d) => {
+                Page::Aggregate(d.split_off(d.partition_point(|d| d.timestamp < at)))
+            }
+        }
+    }
+}
+
+impl From<Option<DataPointListItem>> for PageResult {
+    fn from(item: Option<DataPointListItem>) -> Self {
+        let Some(item) = item else {
+            return PageResult {
+                datapoints: Page::Numeric(vec![]),
+                is_string: false,
+                cursor: None,
+            };
+        };
+        let datapoints = match item.datapoint_type {
+            Some(ListDatapointType::NumericDatapoints(d)) => {
+                Page::Numeric(d.datapoints.into_iter().map(Into::into).collect())
+            }
+            Some(ListDatapointType::StringDatapoints(d)) => {
+                Page::String(d.datapoints.into_iter().map(Into::into).collect())
+            }
+            Some(ListDatapointType::AggregateDatapoints(d)) => {
+                Page::Aggregate(d.datapoints.into_iter().map(Into::into).collect())
+            }
+            None => Page::Numeric(vec![]),
+        };
+        PageResult {
+            datapoints,
+            is_string: item.is_string,
+            cursor: (!item.next_cursor.is_empty()).then_some(item.next_cursor),
+        }
+    }
+}
+
+/// Pick a granularity that splits `range` into at most `MAX_AGGREGATES` buckets.
+fn count_granularity(range: i64) -> Granularity {
+    let bucket = (range + MAX_AGGREGATES - 1) / MAX_AGGREGATES;
+    const SECOND: i64 = 1000;
+    const MINUTE: i64 = 60 * SECOND;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let (multiple, unit) = if bucket <= 120 * SECOND {
+        (
+            ((bucket + SECOND - 1) / SECOND).max(1),
+            GranularityUnit::Second,
+        )
+    } else if bucket <= 120 * MINUTE {
+        ((bucket + MINUTE - 1) / MINUTE, GranularityUnit::Minute)
+    } else if bucket <= 100_000 * HOUR {
+        ((bucket + HOUR - 1) / HOUR, GranularityUnit::Hour)
+    } else {
+        (
+            ((bucket + DAY - 1) / DAY).min(100_000),
+            GranularityUnit::Day,
+        )
+    };
+    // The multiple is always within the valid range for the unit.
+    Granularity::new(multiple as u32, unit).unwrap()
+}
+
+/// Split `[start, end)` into shards of roughly `per_shard` datapoints each, given
+/// a list of `(timestamp, count)` buckets sorted by timestamp.
+fn shards_from_counts(
+    start: i64,
+    end: i64,
+    counts: &[(i64, f64)],
+    per_shard: u64,
+    max_shards: usize,
+) -> Vec<(i64, i64)> {
+    let total: f64 = counts.iter().map(|c| c.1).sum();
+    let num_shards = ((total / per_shard as f64).ceil() as usize).clamp(1, max_shards);
+    let per_shard = total / num_shards as f64;
+
+    let mut shards = Vec::with_capacity(num_shards);
+    let mut shard_start = start;
+    let mut acc = 0.0;
+    for (ts, count) in counts {
+        if acc >= per_shard && *ts > shard_start && *ts < end && shards.len() + 1 < num_shards {
+            shards.push((shard_start, *ts));
+            shard_start = *ts;
+            acc = 0.0;
+        }
+        acc += count;
+    }
+    shards.push((shard_start, end));
+    shards
+}
+
+/// Split `[start, end)` into `num_shards` equally long shards.
+fn even_shards(start: i64, end: i64, num_shards: usize) -> Vec<(i64, i64)> {
+    let num_shards = (num_shards as i64).clamp(1, (end - start).max(1));
+    let len = (end - start) / num_shards;
+    (0..num_shards)
+        .map(|i| {
+            let shard_end = if i == num_shards - 1 {
+                end
+            } else {
+                start + len * (i + 1)
+            };
+            (start + len * i, shard_end)
+        })
+        .collect()
+}
+
+impl TimeSeriesResource {
+    async fn fetch_page(
+        &self,
+        query: &DatapointsQuery,
+        start: i64,
+        end: i64,
+        limit: u32,
+        cursor: Option<String>,
+    ) -> Result<PageResult> {
+        let filter = DatapointsFilter {
+            items: vec![DatapointsQuery {
+                start: Some(start.into()),
+                end: Some(end.into()),
+                limit: Some(limit),
+                cursor,
+                ..query.clone()
+            }],
+            ..Default::default()
+        };
+        let res = self.retrieve_datapoints_proto(&filter).await?;
+        Ok(res.items.into_iter().next().into())
+    }
+
+    /// Fetch the pages of a shard one by one, sending each page to `sender`.
+    /// This stops once the shard is exhausted, a request fails, or the receiver is dropped.
+    async fn pump_shard(
+        &self,
+        query: &DatapointsQuery,
+        shard: Shard,
+        limit: u32,
+        mut sender: mpsc::Sender<Result<Page>>,
+    ) {
+        if let Some(page) = shard.initial {
+            if sender.send(Ok(page)).await.is_err() {
+                return;
+            }
+        }
+        if shard.start >= shard.end {
+            return;
+        }
+        let mut cursor = None;
+        loop {
+            let page = match self
+                .fetch_page(query, shard.start, shard.end, limit, cursor)
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            if sender.send(Ok(page.datapoints)).await.is_err() {
+                return;
+            }
+            match page.cursor {
+                Some(c) => cursor = Some(c),
+                None => return,
+            }
+        }
+    }
+
+    async fn plan_shards(
+        &self,
+        query: &DatapointsQuery,
+        start: i64,
+        end: i64,
+        options: &ShardedRetrievalOptions,
+    ) -> Result<Vec<Shard>> {
+        // Sample the start of the range. This tells us if the time series is a
+        // string time series, and gives an estimate of its density.
+        let sample = self
+            .fetch_page(query, start, end, options.sample_size.max(1), None)
+            .await?;
+        if sample.cursor.is_none() {
+            // The sample contains everything, no need to shard.
+            return Ok(vec![Shard {
+                start: end,
+                end,
+                initial: Some(sample.datapoints),
+            }]);
+        }
+
+        let mut ranges = None;
+        if !sample.is_string && options.strategy == ShardingStrategy::Auto {
+            let filter = DatapointsFilter {
+                items: vec![DatapointsQuery {
+                    id: query.id.clone(),
+                    start: Some(start.into()),
+                    end: Some(end.into()),
+                    limit: Some(MAX_AGGREGATES as u32),
+                    aggregates: Some(vec!["count".to_owned()]),
+                    granularity: Some(count_granularity(end - start)),
+                    ignore_bad_data_points: query.ignore_bad_data_points,
+                    treat_uncertain_as_bad: query.treat_uncertain_as_bad,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            let res = self.retrieve_datapoints_proto(&filter).await?;
+            let page = PageResult::from(res.items.into_iter().next());
+            if let Page::Aggregate(aggs) = page.datapoints {
+                // Bad datapoints may be excluded from the counts, so this is only an estimate.
+                let counts: Vec<_> = aggs.iter().map(|a| (a.timestamp, a.count)).collect();
+                ranges = Some(shards_from_counts(
+                    start,
+                    end,
+                    &counts,
+                    options.datapoints_per_shard,
+                    options.max_shards,
+                ));
+            }
+        }
+
+        // The sample covers the range up to its last datapoint, since it has a cursor.
+        let mut sample = sample.datapoints;
+        let covered = sample.last_timestamp().map(|t| t + 1).unwrap_or(start);
+        let ranges = ranges.unwrap_or_else(|| {
+            let density = sample.len() as f64 / (covered - start).max(1) as f64;
+            let estimate = density * (end - start) as f64;
+            let num_shards = ((estimate / options.datapoints_per_shard as f64).ceil() as usize)
+                .clamp(1, options.max_shards);
+            even_shards(start, end, num_shards)
+        });
+
+        // Reuse the sample for the shards it covers, instead of fetching it again.
+        Ok(ranges
+            .into_iter()
+            .map(|(shard_start, shard_end)| {
+                if shard_start >= covered {
+                    return Shard {
+                        start: shard_start,
+                        end: shard_end,
+                        initial: None,
+                    };
+                }
+                let rest = sample.split_off(shard_end.min(covered));
+                Shard {
+                    start: shard_end.min(covered),
+                    end: shard_end,
+                    initial: Some(std::mem::replace(&mut sample, rest)),
+                }
+            })
+            .collect())
+    }
+
+    async fn sharded_stream(
+        &self,
+        query: DatapointsQuery,
+        start: i64,
+        end: i64,
+        options: ShardedRetrievalOptions,
+    ) -> Result<CondBoxStream<'_, Result<EitherDataPoint>>> {
+        if end <= start {
+            return Ok(stream::iter(vec![]).boxed_cond());
+        }
+        let shards = self.plan_shards(&query, start, end, &options).await?;
+        let limit = options.limit_per_request.max(1);
+
+        // Each shard sends its pages to a bounded channel. Shards are fetched in
+        // parallel, and pause once their channel is full, while the channels are
+        // read in order.
+        let (senders, receivers): (Vec<_>, Vec<_>) = shards
+            .iter()
+            .map(|_| mpsc::channel(options.prefetch_pages))
+            .unzip();
+        let pumps = stream::iter(shards.into_iter().zip(senders))
+            .map(move |(shard, sender)| {
+                let query = query.clone();
+                async move { self.pump_shard(&query, shard, limit, sender).await }
+            })
+            .buffer_unordered(options.parallelism.max(1))
+            .filter_map(|_| future::ready(None));
+        let pages = stream::iter(receivers).flatten().map(Some);
+
+        Ok(stream::select(pages, pumps)
+            .filter_map(future::ready)
+            .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed_cond())
+    }
+
+    /// Retrieve all datapoints in `[start, end)` for a single time series, splitting the range
+    /// into shards that are fetched in parallel. Each shard follows cursors until it is exhausted.
+    /// Datapoints are returned in order, and may be numeric or string datapoints.
+    ///
+    /// Shards are planned using count aggregates for numeric time series, and a density estimate
+    /// based on a sample of datapoints for string time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Query for the time series. Use this to set the ID, `include_status`,
+    ///   `ignore_bad_data_points` and similar options. `start`, `end`, `limit` and `cursor` are ignored.
+    /// * `start` - Inclusive start of the range, in milliseconds since epoch.
+    /// * `end` - Exclusive end of the range, in milliseconds since epoch.
+    /// * `options` - Options for sharding.
+    pub fn retrieve_datapoints_sharded(
+        &self,
+        query: DatapointsQuery,
+        start: i64,
+        end: i64,
+        options: ShardedRetrievalOptions,
+    ) -> impl Stream<Item = Result<EitherDataPoint>> + '_ {
+        let query = DatapointsQuery {
+            aggregates: None,
+            granularity: None,
+            include_outside_points: None,
+            time_zone: None,
+            ..query
+        };
+        stream::once(self.sharded_stream(query, start, end, options)).try_flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_granularity, even_shards, shards_from_counts};
+
+    #[test]
+    fn test_count_granularity() {
+        assert_eq!("1s", count_granularity(1000).to_string());
+        assert_eq!("60m", count_granularity(10_000 * 3_600_000).to_string());
+        assert_eq!("3h", count_granularity(10_000 * 3 * 3_600_000).to_string());
+        assert_eq!("90s", count_granularity(10_000 * 90_000).to_string());
+        assert_eq!("3m", count_granularity(10_000 * 150_000).to_string());
+    }
+
+    #[test]
+    fn test_shard_planning() {
+        // Uniform density.
+        let counts: Vec<_> = (0..100).map(|i| (i * 10, 10.0)).collect();
+        let shards = shards_from_counts(0, 1000, &counts, 100, 64);
+        assert_eq!(10, shards.len());
+        assert_eq!((0, 100), shards[0]);
+        assert_eq!((900, 1000), shards[9]);
+
+        // All data in the last bucket.
+        let mut counts: Vec<_> = (0..100).map(|i| (i * 10, 0.0)).collect();
+        counts[99].1 = 1000.0;
+        let shards = shards_from_counts(0, 1000, &counts, 100, 4);
+        assert_eq!(vec![(0, 1000)], shards);
+
+        let shards = even_shards(0, 10, 3);
+        assert_eq!(vec![(0, 3), (3, 6), (6, 10)], shards);
+    }
+}
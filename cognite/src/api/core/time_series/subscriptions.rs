@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use futures::{stream, Stream};
+use serde::Serialize;
+
+use crate::time_series::{
+    AddDatapointSubscription, DatapointSubscription, DatapointSubscriptionQuery,
+    ListSubscriptionDataRequest, ListSubscriptionDataResponse, ListSubscriptionMembersQuery,
+    PatchDatapointSubscription, SubscriptionPartition, SubscriptionTimeSeriesId,
+};
+use crate::{
+    Create, Cursor, DeleteWithIgnoreUnknownIds, IdentityList, ItemsVec, List, Patch, Resource,
+    Result, RetrieveWithIgnoreUnknownIds, SetCursor, Update, WithBasePath,
+};
+
+/// Default server side poll timeout used when streaming from a subscription.
+const DEFAULT_POLL_TIMEOUT_SECONDS: u32 = 5;
+/// Delay between polls that return no data, when the server does not long-poll.
+const EMPTY_POLL_DELAY: Duration = Duration::from_secs(1);
+
+/// Datapoint subscriptions let you listen to changes to datapoints in a set of time series,
+/// defined either by a list of time series or by a filter. Changes are read from one or
+/// more partitions, each with its own cursor.
+pub type DatapointSubscriptionsResource = Resource<DatapointSubscription>;
+
+impl WithBasePath for DatapointSubscriptionsResource {
+    const BASE_PATH: &'static str = "timeseries/subscriptions";
+}
+
+impl Create<AddDatapointSubscription, DatapointSubscription> for DatapointSubscriptionsResource {}
+impl List<DatapointSubscriptionQuery, DatapointSubscription> for DatapointSubscriptionsResource {}
+impl Update<Patch<PatchDatapointSubscription>, DatapointSubscription>
+    for DatapointSubscriptionsResource
+{
+}
+impl<R> DeleteWithIgnoreUnknownIds<IdentityList<R>> for DatapointSubscriptionsResource
+where
+    IdentityList<R>: Serialize,
+    R: Send + Sync,
+{
+}
+impl<R> RetrieveWithIgnoreUnknownIds<IdentityList<R>, DatapointSubscription>
+    for DatapointSubscriptionsResource
+where
+    IdentityList<R>: Serialize,
+    R: Send + Sync,
+{
+}
+
+impl DatapointSubscriptionsResource {
+    /// List a page of time series in a subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Subscription external ID, limit, and cursor.
+    pub async fn list_members(
+        &self,
+        query: ListSubscriptionMembersQuery,
+    ) -> Result<ItemsVec<SubscriptionTimeSeriesId, Cursor>> {
+        self.api_client
+            .get_with_params("timeseries/subscriptions/members", Some(query))
+            .await
+    }
+
+    /// List all time series in a subscription, following cursors.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Subscription external ID and page size.
+    pub async fn list_all_members(
+        &self,
+        mut query: ListSubscriptionMembersQuery,
+    ) -> Result<Vec<SubscriptionTimeSeriesId>> {
+        let mut result = Vec::new();
+        loop {
+            let page = self.list_members(query.clone()).await?;
+            result.extend(page.items);
+            match page.extra_fields.next_cursor {
+                Some(cursor) => query.set_cursor(Some(cursor)),
+                None => return Ok(result),
+            }
+        }
+    }
+
+    /// Read a single batch of changes from a subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Partitions and cursors to read from.
+    pub async fn list_data(
+        &self,
+        request: &ListSubscriptionDataRequest,
+    ) -> Result<ListSubscriptionDataResponse> {
+        self.api_client
+            .post("timeseries/subscriptions/data/list", request)
+            .await
+    }
+
+    /// Stream changes from a subscription, following the partition cursors.
+    ///
+    /// Each item in the stream is a batch of upserts and deletes per time series, along with
+    /// the partition cursors pointing past the batch. Store `partitions` from the last processed
+    /// batch and pass them in `request` to resume from that point later.
+    ///
+    /// If `request.partitions` is empty, the subscription is retrieved, and all its partitions
+    /// are read from `request.initialize_cursors`. To distribute the work over several consumers,
+    /// give each stream a subset of the partitions.
+    ///
+    /// Batches with no changes are also yielded, since the cursors may still have advanced.
+    /// If `stop_when_caught_up` is `true`, the stream ends once there is no more data available
+    /// right away, otherwise it keeps long-polling for new data indefinitely. If
+    /// `poll_timeout_seconds` is 0, the stream waits one second after each batch
+    /// with no changes before polling again.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Subscription to read from, with the initial partitions and cursors.
+    /// * `stop_when_caught_up` - Whether to end the stream once all data has been read.
+    pub fn stream_data(
+        &self,
+        mut request: ListSubscriptionDataRequest,
+        stop_when_caught_up: bool,
+    ) -> impl Stream<Item = Result<ListSubscriptionDataResponse>> + '_ {
+        if request.poll_timeout_seconds.is_none() && !stop_when_caught_up {
+            request.poll_timeout_seconds = Some(DEFAULT_POLL_TIMEOUT_SECONDS);
+        }
+        stream::try_unfold(Some((request, false)), move |state| async move {
+            let Some((mut request, delay)) = state else {
+                return Ok(None);
+            };
+            if delay {
+                futures_timer::Delay::new(EMPTY_POLL_DELAY).await;
+            }
+            if request.partitions.is_empty() {
+                let subscription = self
+                    .retrieve(&[request.external_id.as_str()], false)
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| {
+                        crate::Error::Other(format!(
+                            "Subscription {} not found",
+                            request.external_id
+                        ))
+                    })?;
+                request.partitions = (0..subscription.partition_count)
+                    .map(SubscriptionPartition::new)
+                    .collect();
+            }
+            let response = self.list_data(&request).await?;
+            if stop_when_caught_up && !response.has_next {
+                return Ok(Some((response, None)));
+            }
+            request.partitions = response.partitions.clone();
+            // Without long-polling, back off instead of polling continuously.
+            let delay = response.is_empty()
+                && !response.has_next
+                && request.poll_timeout_seconds.unwrap_or(0) == 0;
+            Ok(Some((response, Some((request, delay)))))
+        })
+    }
+}
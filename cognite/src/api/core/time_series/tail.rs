@@ -0,0 +1,307 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use futures::{stream, Stream, StreamExt};
+
+use super::datapoints_stream::DatapointsStream;
+use crate::send_helper::{CondBoxStream, CondBoxedStream};
+use crate::time_series::{
+    DataPointRef, DatapointsFilter, DatapointsQuery, DatapointsStreamOptions, TimeSeriesResource,
+    TimestampOrRelative,
+};
+use crate::{Error, IdentityOrInstance, Result};
+
+/// Options for [`TimeSeriesResource::tail_datapoints`].
+#[derive(Clone, Debug)]
+pub struct DatapointsTailOptions {
+    /// Options for each poll. Default is the default [`DatapointsStreamOptions`].
+    pub stream: DatapointsStreamOptions,
+    /// Time to wait between polls, once all available datapoints have been read.
+    /// Default is 5 seconds.
+    pub poll_interval: Duration,
+    /// How far back from the latest datapoint seen in each time series to query on
+    /// every poll, to pick up datapoints that arrive late. Default is 1 minute.
+    pub overlap: Duration,
+    /// Maximum time to wait before polling again after an error. The wait starts at
+    /// `poll_interval`, and doubles for each consecutive error. Default is 1 minute.
+    pub max_backoff: Duration,
+}
+
+impl Default for DatapointsTailOptions {
+    fn default() -> Self {
+        Self {
+            stream: DatapointsStreamOptions::default(),
+            poll_interval: Duration::from_secs(5),
+            overlap: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+struct SeriesTail {
+    query: DatapointsQuery,
+    latest: Option<i64>,
+    // Timestamps seen within the overlap window before `latest`.
+    recent: BTreeSet<i64>,
+}
+
+impl SeriesTail {
+    fn next_query(&self, overlap: i64) -> DatapointsQuery {
+        let mut query = self.query.clone();
+        if let Some(latest) = self.latest {
+            let mut start = latest.saturating_add(1).saturating_sub(overlap);
+            if let Some(TimestampOrRelative::Timestamp(original)) = &self.query.start {
+                start = start.max(*original);
+            }
+            query.start = Some(TimestampOrRelative::Timestamp(start));
+        }
+        query
+    }
+
+    /// Record a datapoint, returning `false` if it has been seen before.
+    ///
+    /// Only timestamps within the overlap window are kept, so memory use is bounded by
+    /// the number of datapoints in the window, even while catching up on a long history.
+    fn accept(&mut self, timestamp: i64, overlap: i64) -> bool {
+        if !self.recent.insert(timestamp) {
+            return false;
+        }
+        let latest = self.latest.map_or(timestamp, |l| l.max(timestamp));
+        self.latest = Some(latest);
+        let start = latest.saturating_add(1).saturating_sub(overlap);
+        while self.recent.first().is_some_and(|ts| *ts < start) {
+            self.recent.pop_first();
+        }
+        true
+    }
+}
+
+struct TailState<'a> {
+    resource: &'a TimeSeriesResource,
+    filter: DatapointsFilter,
+    series: Vec<SeriesTail>,
+    index: HashMap<IdentityOrInstance, usize>,
+    options: DatapointsTailOptions,
+    current: Option<CondBoxStream<'a, Result<DataPointRef>>>,
+    delay: Option<Duration>,
+    errors: u32,
+}
+
+impl<'a> TailState<'a> {
+    fn new(
+        resource: &'a TimeSeriesResource,
+        mut filter: DatapointsFilter,
+        options: DatapointsTailOptions,
+    ) -> Result<Self> {
+        if filter.aggregates.is_some() || filter.items.iter().any(|q| q.aggregates.is_some()) {
+            return Err(Error::Other("Cannot tail aggregate datapoints".to_owned()));
+        }
+        // Outside points would be seen as new datapoints on every poll.
+        filter.include_outside_points = Some(false);
+        filter.end = None;
+        let series: Vec<_> = std::mem::take(&mut filter.items)
+            .into_iter()
+            .map(|mut query| {
+                query.include_outside_points = Some(false);
+                query.end = None;
+                if query.start.is_none() {
+                    query.start = filter.start.clone();
+                }
+                SeriesTail {
+                    query,
+                    latest: None,
+                    recent: BTreeSet::new(),
+                }
+            })
+            .collect();
+        let index = series
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| (s.query.id.clone(), idx))
+            .collect();
+        Ok(Self {
+            resource,
+            filter,
+            series,
+            index,
+            options,
+            current: None,
+            delay: None,
+            errors: 0,
+        })
+    }
+
+    fn overlap(&self) -> i64 {
+        self.options.overlap.as_millis() as i64
+    }
+
+    fn start_poll(&mut self) {
+        let overlap = self.overlap();
+        let filter = DatapointsFilter {
+            items: self.series.iter().map(|s| s.next_query(overlap)).collect(),
+            ..self.filter.clone()
+        };
+        self.current = Some(
+            DatapointsStream::new(self.resource, filter, self.options.stream.clone())
+                .stream_datapoints()
+                .boxed_cond(),
+        );
+    }
+
+    fn accept(&mut self, dp: &DataPointRef) -> bool {
+        let timestamp = match dp.as_numeric() {
+            Some(d) => d.timestamp,
+            None => match dp.as_string() {
+                Some(d) => d.timestamp,
+                None => return true,
+            },
+        };
+        let overlap = self.overlap();
+        match self.index.get(dp.original_id()) {
+            Some(idx) => self.series[*idx].accept(timestamp, overlap),
+            None => true,
+        }
+    }
+
+    fn finish_poll(&mut self) {
+        self.errors = 0;
+        self.delay = Some(self.options.poll_interval);
+    }
+
+    fn fail_poll(&mut self) {
+        self.errors = self.errors.saturating_add(1);
+        let backoff = self
+            .options
+            .poll_interval
+            .saturating_mul(2u32.saturating_pow(self.errors - 1));
+        self.delay = Some(backoff.min(self.options.max_backoff));
+    }
+
+    async fn next(mut self) -> (Result<DataPointRef>, Self) {
+        loop {
+            if let Some(current) = &mut self.current {
+                match current.next().await {
+                    Some(Ok(dp)) => {
+                        if self.accept(&dp) {
+                            return (Ok(dp), self);
+                        }
+                    }
+                    Some(Err(e)) => {
+                        self.current = None;
+                        self.fail_poll();
+                        return (Err(e), self);
+                    }
+                    None => {
+                        self.current = None;
+                        self.finish_poll();
+                    }
+                }
+                continue;
+            }
+            if let Some(delay) = self.delay.take() {
+                futures_timer::Delay::new(delay).await;
+            }
+            self.start_poll();
+        }
+    }
+}
+
+impl TimeSeriesResource {
+    /// Continuously stream datapoints for a list of time series.
+    ///
+    /// This first reads all datapoints from the start of each query until now, like
+    /// [stream_datapoints](Self::stream_datapoints), then keeps polling for new datapoints
+    /// every `poll_interval`, indefinitely. Each poll reads from `overlap` before the
+    /// latest datapoint seen in each time series, so that datapoints arriving late are
+    /// picked up. Datapoints with a timestamp that has already been returned for a time series
+    /// are skipped, so each timestamp is only returned once, even if the datapoint is updated.
+    ///
+    /// If a poll fails, the error is returned from the stream, and the stream keeps going,
+    /// retrying with exponential backoff. Stop consuming the stream to end it.
+    ///
+    /// Only raw datapoints can be tailed. `end` and `include_outside_points` in the filter
+    /// are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter describing common filter properties and a list of timeseries to retrieve data from.
+    /// * `options` - Options for controlling polling.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut stream = pin!(client.time_series.tail_datapoints(filter, Default::default()));
+    /// while let Some(res) = stream.next().await {
+    ///     match res {
+    ///         Ok(dp) => println!("{}: {:?}", dp.id(), dp.as_numeric()),
+    ///         Err(e) => warn!("Failed to poll datapoints: {e}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn tail_datapoints(
+        &self,
+        filter: DatapointsFilter,
+        options: DatapointsTailOptions,
+    ) -> impl Stream<Item = Result<DataPointRef>> + '_ {
+        let state = TailState::new(self, filter, options);
+        stream::unfold(Some(state), |state| async move {
+            match state? {
+                Ok(state) => {
+                    let (item, state) = state.next().await;
+                    Some((item, Some(Ok(state))))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeriesTail;
+    use crate::time_series::{DatapointsQuery, TimestampOrRelative};
+
+    fn start(tail: &SeriesTail, overlap: i64) -> Option<i64> {
+        match tail.next_query(overlap).start {
+            Some(TimestampOrRelative::Timestamp(ts)) => Some(ts),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_series_tail() {
+        let mut tail = SeriesTail {
+            query: DatapointsQuery {
+                start: Some(TimestampOrRelative::Timestamp(95)),
+                ..Default::default()
+            },
+            latest: None,
+            recent: Default::default(),
+        };
+        assert_eq!(Some(95), start(&tail, 10));
+        assert!(tail.accept(100, 10));
+        assert!(!tail.accept(100, 10));
+        assert!(tail.accept(110, 10));
+        assert_eq!(Some(101), start(&tail, 10));
+        // 100 is outside the overlap window, and was pruned.
+        assert!(tail.recent.contains(&110));
+        assert!(!tail.recent.contains(&100));
+        assert_eq!(Some(95), start(&tail, 100));
+    }
+
+    #[test]
+    fn test_series_tail_bounded_catch_up() {
+        let mut tail = SeriesTail {
+            query: DatapointsQuery::default(),
+            latest: None,
+            recent: Default::default(),
+        };
+        for ts in 0..100_000 {
+            assert!(tail.accept(ts * 1000, 10_000));
+        }
+        // Only timestamps within the last 10 seconds are tracked.
+        assert_eq!(10, tail.recent.len());
+        assert!(!tail.accept(99_999_000, 10_000));
+        assert!(!tail.accept(99_990_000, 10_000));
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use crate::time_series::{DatapointsFilter, TimeSeries, TimeSeriesResource};
+use crate::units::UnitConverter;
+use crate::{IdentityOrInstance, Result, RetrieveWithIgnoreUnknownIds};
+
+/// Maximum number of time series per retrieve request.
+const RETRIEVE_CHUNK_SIZE: usize = 1000;
+
+/// Index time series by each of their ID, external ID, and instance ID.
+fn index_by_identity(timeseries: &[TimeSeries]) -> HashMap<IdentityOrInstance, &TimeSeries> {
+    let mut index = HashMap::with_capacity(timeseries.len());
+    for ts in timeseries {
+        index.insert(IdentityOrInstance::from(ts.id), ts);
+        if let Some(external_id) = &ts.external_id {
+            index.insert(IdentityOrInstance::from(external_id), ts);
+        }
+        if let Some(instance_id) = &ts.instance_id {
+            index.insert(IdentityOrInstance::from(instance_id.clone()), ts);
+        }
+    }
+    index
+}
+
+impl TimeSeriesResource {
+    /// Check that the target unit or target unit system of each query in a datapoints filter
+    /// is compatible with the unit of the queried time series, before retrieving datapoints.
+    ///
+    /// Time series with a target unit or target unit system are retrieved, and their
+    /// `unit_external_id` is checked against the unit catalog. Time series that do not exist
+    /// are ignored if `ignore_unknown_ids` is set on the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Datapoints filter to validate.
+    /// * `units` - Converter created from the unit catalog, see `UnitsResource::converter`.
+    pub async fn validate_target_units(
+        &self,
+        filter: &DatapointsFilter,
+        units: &UnitConverter,
+    ) -> Result<()> {
+        let queries: Vec<_> = filter
+            .items
+            .iter()
+            .filter(|q| q.target_unit.is_some() || q.target_unit_system.is_some())
+            .collect();
+        if queries.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<_> = queries.iter().map(|q| q.id.clone()).collect();
+        let mut timeseries = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(RETRIEVE_CHUNK_SIZE) {
+            timeseries.extend(
+                self.retrieve(chunk, filter.ignore_unknown_ids.unwrap_or(false))
+                    .await?,
+            );
+        }
+        let index = index_by_identity(&timeseries);
+        for query in queries {
+            let Some(ts) = index.get(&query.id) else {
+                continue;
+            };
+            units.validate_query(query, ts.unit_external_id.as_deref())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use futures::task::AtomicWaker;
+use futures::{future, stream, FutureExt, StreamExt};
+
+use crate::time_series::{
+    AddDmOrTimeSeries, DataPointInsertionItem, DataPointInsertionRequest, DatapointDouble,
+    DatapointString, DatapointsCompressor, DatapointsEnumType, TimeSeriesResource,
+};
+use crate::{Error, IdentityOrInstance, Result};
+
+/// Maximum number of datapoints in a single insert request.
+pub const MAX_DATAPOINTS_PER_REQUEST: usize = 100_000;
+/// Maximum number of time series in a single insert request.
+pub const MAX_TIME_SERIES_PER_REQUEST: usize = 10_000;
+
+/// Function called to create time series that do not exist.
+pub type CreateMissingTimeSeries =
+    Arc<dyn Fn(&[IdentityOrInstance]) -> Vec<AddDmOrTimeSeries> + Send + Sync>;
+
+/// Callback called with the result of each flush.
+pub type DatapointsFlushCallback = Arc<dyn Fn(&DatapointsFlushResult) + Send + Sync>;
+
+/// How an upload queue should handle datapoints for time series that do not exist in CDF.
+#[derive(Clone, Default)]
+pub enum MissingTimeSeriesHandling {
+    /// Fail the request if any time series are missing.
+    #[default]
+    Fail,
+    /// Drop datapoints for time series that do not exist, using `insert_datapoints_proto_ignore_missing`.
+    Ignore,
+    /// Create time series that do not exist, using `insert_datapoints_proto_create_missing`.
+    /// The function must return one time series per given identity.
+    Create(CreateMissingTimeSeries),
+}
+
+/// Configuration for a [`DatapointsUploadQueue`].
+#[derive(Clone)]
+pub struct DatapointsUploadQueueConfig {
+    /// Flush the queue once it contains this many datapoints. Default is 1 000 000.
+    pub max_queue_size: usize,
+    /// Flush the queue once the oldest datapoint in the queue has been there this long.
+    /// This requires either calling [`DatapointsUploadQueue::run`], or that datapoints
+    /// are added regularly. Default is 10 seconds.
+    pub max_age: Option<Duration>,
+    /// Maximum number of datapoints per request. Default and maximum is 100 000.
+    pub max_datapoints_per_request: usize,
+    /// Maximum number of time series per request. Default and maximum is 10 000.
+    pub max_time_series_per_request: usize,
+    /// Maximum number of requests in flight during a flush. Default is 4.
+    pub parallelism: usize,
+    /// How to handle datapoints for time series that do not exist.
+    pub missing: MissingTimeSeriesHandling,
+    /// Callback called after each flush.
+    pub on_flush: Option<DatapointsFlushCallback>,
+    /// Compression applied to datapoints as they are added to the queue. The queue keeps
+    /// its own copy of the compressor, datapoints held back by the compressor are
+    /// uploaded when the queue is closed.
+    pub compression: Option<DatapointsCompressor>,
+}
+
+impl Default for DatapointsUploadQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_size: 1_000_000,
+            max_age: Some(Duration::from_secs(10)),
+            max_datapoints_per_request: MAX_DATAPOINTS_PER_REQUEST,
+            max_time_series_per_request: MAX_TIME_SERIES_PER_REQUEST,
+            parallelism: 4,
+            missing: MissingTimeSeriesHandling::default(),
+            on_flush: None,
+            compression: None,
+        }
+    }
+}
+
+/// Result of flushing a [`DatapointsUploadQueue`].
+///
+/// Datapoints in failed requests are removed from the queue, and are not retried.
+/// They are returned in `failed_requests`, so that the caller can retry them, for
+/// example with [`TimeSeriesResource::insert_datapoints_proto`].
+#[derive(Debug, Default)]
+pub struct DatapointsFlushResult {
+    /// Number of datapoints that were uploaded successfully.
+    pub uploaded_datapoints: usize,
+    /// Number of datapoints in failed requests.
+    pub failed_datapoints: usize,
+    /// Number of requests sent to CDF.
+    pub num_requests: usize,
+    /// Errors from failed requests.
+    pub errors: Vec<Error>,
+    /// Requests that failed, in the same order as `errors`.
+    pub failed_requests: Vec<DataPointInsertionRequest>,
+}
+
+impl DatapointsFlushResult {
+    /// Whether all requests in the flush succeeded.
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct QueueBuffer {
+    items: HashMap<IdentityOrInstance, DatapointsEnumType>,
+    num_datapoints: usize,
+    oldest: Option<Instant>,
+}
+
+struct QueueInner {
+    resource: TimeSeriesResource,
+    config: DatapointsUploadQueueConfig,
+    buffer: Mutex<QueueBuffer>,
+    compressor: Option<Mutex<DatapointsCompressor>>,
+    closed: AtomicBool,
+    // Wakes the `run` loop when the first datapoint is added to an empty
+    // buffer, or when the queue is closed.
+    wake: AtomicWaker,
+    woken: AtomicBool,
+}
+
+/// Queue for uploading datapoints to CDF.
+///
+/// Datapoints added to the queue are buffered, and uploaded once the queue
+/// reaches a maximum size or a maximum age. Each flush is split into requests
+/// within the CDF limits of 100 000 datapoints and 10 000 time series per request.
+///
+/// The queue is cheap to clone, all clones share the same buffer, so that
+/// datapoints can be added from many tasks at once.
+///
+/// Requests are retried by the client as usual, but if a request still fails, its
+/// datapoints are not requeued. Use the `on_flush` callback or the result of
+/// [`DatapointsUploadQueue::flush`] to handle the failed requests.
+///
+/// # Example
+///
+/// ```ignore
+/// let queue = DatapointsUploadQueue::new(client.time_series.clone(), Default::default());
+/// let runner = queue.clone();
+/// tokio::spawn(async move { runner.run().await });
+///
+/// queue
+///     .add(IdentityOrInstance::external_id("my-ts"), vec![DatapointDouble { timestamp: 0, value: Some(1.0), status: None }])
+///     .await?;
+/// // Upload any remaining datapoints.
+/// queue.close().await;
+/// ```
+#[derive(Clone)]
+pub struct DatapointsUploadQueue {
+    inner: Arc<QueueInner>,
+}
+
+impl DatapointsUploadQueue {
+    /// Create a new upload queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource` - Time series resource used to upload datapoints.
+    /// * `config` - Queue configuration.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if any of the size limits or the parallelism is 0.
+    pub fn new(resource: TimeSeriesResource, config: DatapointsUploadQueueConfig) -> Self {
+        assert!(
+            config.max_datapoints_per_request > 0
+                && config.max_time_series_per_request > 0
+                && config.max_queue_size > 0,
+            "Size limits must be greater than 0"
+        );
+        assert!(config.parallelism > 0, "Parallelism must be greater than 0");
+        Self {
+            inner: Arc::new(QueueInner {
+                resource,
+                compressor: config.compression.clone().map(Mutex::new),
+                config,
+                buffer: Mutex::new(QueueBuffer::default()),
+                closed: AtomicBool::new(false),
+                wake: AtomicWaker::new(),
+                woken: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Add datapoints for a time series to the queue. If this causes the queue to
+    /// exceed its maximum size or age, the queue is flushed before returning.
+    /// The result of the flush is reported to the `on_flush` callback.
+    ///
+    /// Fails if the datapoints are aggregates, or of a different type than
+    /// datapoints already in the queue for the same time series.
+    ///
+    /// If `compression` is configured, datapoints are compressed before they are added.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series to add datapoints to.
+    /// * `datapoints` - Datapoints to add.
+    pub async fn add(
+        &self,
+        id: IdentityOrInstance,
+        datapoints: impl Into<DatapointsEnumType>,
+    ) -> Result<()> {
+        let mut datapoints = datapoints.into();
+        if let Some(compressor) = &self.inner.compressor {
+            if !matches!(datapoints, DatapointsEnumType::AggregateDatapoints(_)) {
+                datapoints = compressor.lock().unwrap().compress_series(&id, datapoints);
+            }
+        }
+        if self.add_to_buffer(id, datapoints)? {
+            self.flush().await;
+        }
+        Ok(())
+    }
+
+    /// Add datapoints to the buffer, returning whether the queue should be flushed.
+    fn add_to_buffer(
+        &self,
+        id: IdentityOrInstance,
+        datapoints: DatapointsEnumType,
+    ) -> Result<bool> {
+        let (should_flush, was_empty) = {
+            let mut buffer = self.inner.buffer.lock().unwrap();
+            let count = match &datapoints {
+                DatapointsEnumType::NumericDatapoints(d) => d.len(),
+                DatapointsEnumType::StringDatapoints(d) => d.len(),
+                DatapointsEnumType::AggregateDatapoints(_) => {
+                    return Err(Error::Other(
+                        "Cannot insert aggregate datapoints".to_owned(),
+                    ))
+                }
+            };
+            if count == 0 {
+                return Ok(false);
+            }
+            match (buffer.items.get_mut(&id), datapoints) {
+                (None, datapoints) => {
+                    buffer.items.insert(id, datapoints);
+                }
+                (
+                    Some(DatapointsEnumType::NumericDatapoints(existing)),
+                    DatapointsEnumType::NumericDatapoints(new),
+                ) => existing.extend(new),
+                (
+                    Some(DatapointsEnumType::StringDatapoints(existing)),
+                    DatapointsEnumType::StringDatapoints(new),
+                ) => existing.extend(new),
+                _ => {
+                    return Err(Error::Other(format!(
+                        "Datapoints for time series {id:?} do not match the type of datapoints already in the queue"
+                    )))
+                }
+            }
+            buffer.num_datapoints += count;
+            let was_empty = buffer.oldest.is_none();
+            let oldest = *buffer.oldest.get_or_insert_with(Instant::now);
+            let should_flush = buffer.num_datapoints >= self.inner.config.max_queue_size
+                || self
+                    .inner
+                    .config
+                    .max_age
+                    .is_some_and(|a| oldest.elapsed() >= a);
+            (should_flush, was_empty)
+        };
+        if was_empty {
+            self.wake_runner();
+        }
+        Ok(should_flush)
+    }
+
+    /// Add numeric datapoints for a time series to the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series to add datapoints to.
+    /// * `datapoints` - Datapoints to add.
+    pub async fn add_numeric(
+        &self,
+        id: IdentityOrInstance,
+        datapoints: Vec<DatapointDouble>,
+    ) -> Result<()> {
+        self.add(id, datapoints).await
+    }
+
+    /// Add string datapoints for a time series to the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series to add datapoints to.
+    /// * `datapoints` - Datapoints to add.
+    pub async fn add_string(
+        &self,
+        id: IdentityOrInstance,
+        datapoints: Vec<DatapointString>,
+    ) -> Result<()> {
+        self.add(id, datapoints).await
+    }
+
+    /// Number of datapoints currently in the queue.
+    pub fn len(&self) -> usize {
+        self.inner.buffer.lock().unwrap().num_datapoints
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Upload all datapoints currently in the queue. The result is also
+    /// reported to the `on_flush` callback.
+    ///
+    /// Failed requests are not added back to the queue. They are returned in
+    /// [`DatapointsFlushResult::failed_requests`], and must be retried by the caller.
+    pub async fn flush(&self) -> DatapointsFlushResult {
+        let buffer = std::mem::take(&mut *self.inner.buffer.lock().unwrap());
+        let config = &self.inner.config;
+        let requests = build_requests(
+            buffer.items,
+            config.max_datapoints_per_request,
+            config.max_time_series_per_request,
+        );
+
+        let mut result = DatapointsFlushResult {
+            num_requests: requests.len(),
+            ..Default::default()
+        };
+        let results: Vec<_> = stream::iter(requests)
+            .map(|(request, count)| async move { (self.send(&request).await, request, count) })
+            .buffer_unordered(config.parallelism)
+            .collect()
+            .await;
+        for (res, request, count) in results {
+            match res {
+                Ok(()) => result.uploaded_datapoints += count,
+                Err(e) => {
+                    result.failed_datapoints += count;
+                    result.errors.push(e);
+                    result.failed_requests.push(request);
+                }
+            }
+        }
+
+        if result.num_requests > 0 {
+            if let Some(cb) = &config.on_flush {
+                cb(&result);
+            }
+        }
+        result
+    }
+
+    async fn send(&self, request: &DataPointInsertionRequest) -> Result<()> {
+        let resource = &self.inner.resource;
+        match &self.inner.config.missing {
+            MissingTimeSeriesHandling::Fail => resource.insert_datapoints_proto(request).await,
+            MissingTimeSeriesHandling::Ignore => {
+                resource
+                    .insert_datapoints_proto_ignore_missing(request)
+                    .await
+            }
+            MissingTimeSeriesHandling::Create(generator) => {
+                resource
+                    .insert_datapoints_proto_create_missing(request, &|idts| {
+                        generator(idts).into_iter()
+                    })
+                    .await
+            }
+        }
+    }
+
+    /// Periodically flush the queue when the oldest datapoint exceeds `max_age`.
+    /// This runs until [`DatapointsUploadQueue::close`] is called, which wakes the loop and
+    /// makes it return promptly. It should typically be spawned as a separate task.
+    /// If `max_age` is not set, this returns immediately.
+    pub async fn run(&self) {
+        let Some(max_age) = self.inner.config.max_age else {
+            return;
+        };
+        while !self.inner.closed.load(Ordering::Relaxed) {
+            let oldest = self.inner.buffer.lock().unwrap().oldest;
+            let wait = match oldest {
+                Some(t) => max_age.saturating_sub(t.elapsed()),
+                None => max_age,
+            };
+            if !wait.is_zero() {
+                // Sleep until the oldest datapoint is due, or until woken by a datapoint
+                // added to an empty buffer, or by `close`.
+                let mut delay = futures_timer::Delay::new(wait);
+                future::poll_fn(|cx| {
+                    self.inner.wake.register(cx.waker());
+                    if self.inner.woken.swap(false, Ordering::AcqRel) {
+                        return Poll::Ready(());
+                    }
+                    delay.poll_unpin(cx)
+                })
+                .await;
+                continue;
+            }
+            self.flush().await;
+        }
+    }
+
+    fn wake_runner(&self) {
+        self.inner.woken.store(true, Ordering::Release);
+        self.inner.wake.wake();
+    }
+
+    /// Stop any running [`DatapointsUploadQueue::run`] loop, and upload any datapoints
+    /// remaining in the queue, including datapoints held back by compression.
+    pub async fn close(&self) -> DatapointsFlushResult {
+        self.inner.closed.store(true, Ordering::Relaxed);
+        self.wake_runner();
+        if let Some(compressor) = &self.inner.compressor {
+            let held = compressor.lock().unwrap().flush();
+            for item in held {
+                // Held datapoints are never aggregates, and always match the type
+                // of the time series, so this cannot fail.
+                let _ = self.add_to_buffer(item.id, item.datapoints);
+            }
+        }
+        self.flush().await
+    }
+}
+
+/// Split datapoints into requests within the given limits. Returns each request
+/// along with the number of datapoints it contains.
+fn build_requests(
+    items: HashMap<IdentityOrInstance, DatapointsEnumType>,
+    max_datapoints: usize,
+    max_time_series: usize,
+) -> Vec<(DataPointInsertionRequest, usize)> {
+    let mut requests = Vec::new();
+    let mut current = Vec::new();
+    let mut current_count = 0;
+
+    for (id, datapoints) in items {
+        let mut datapoints = Some(datapoints);
+        while let Some(dps) = datapoints.take() {
+            if current.len() >= max_time_series || current_count >= max_datapoints {
+                requests.push((
+                    DataPointInsertionRequest {
+                        items: std::mem::take(&mut current),
+                    },
+                    current_count,
+                ));
+                current_count = 0;
+            }
+            // Split the datapoints if they do not fit in the current request.
+            let space = max_datapoints - current_count;
+            let (chunk, rest) = split_datapoints(dps, space);
+            datapoints = rest;
+            let count = match &chunk {
+                DatapointsEnumType::NumericDatapoints(d) => d.len(),
+                DatapointsEnumType::StringDatapoints(d) => d.len(),
+                DatapointsEnumType::AggregateDatapoints(_) => 0,
+            };
+            current_count += count;
+            current.push(DataPointInsertionItem::from(
+                crate::time_series::AddDatapoints {
+                    id: id.clone(),
+                    datapoints: chunk,
+                },
+            ));
+        }
+    }
+    if !current.is_empty() {
+        requests.push((DataPointInsertionRequest { items: current }, current_count));
+    }
+    requests
+}
+
+fn split_datapoints(
+    datapoints: DatapointsEnumType,
+    at: usize,
+) -> (DatapointsEnumType, Option<DatapointsEnumType>) {
+    match datapoints {
+        DatapointsEnumType::NumericDatapoints(mut d) if d.len() > at => {
+            let rest = d.split_off(at);
+            (d.into(), Some(rest.into()))
+        }
+        DatapointsEnumType::StringDatapoints(mut d) if d.len() > at => {
+            let rest = d.split_off(at);
+            (d.into(), Some(rest.into()))
+        }
+        d => (d, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::build_requests;
+    use crate::time_series::{DatapointDouble, DatapointString, DatapointsEnumType};
+    use crate::IdentityOrInstance;
+
+    fn numeric(n: usize) -> DatapointsEnumType {
+        (0..n)
+            .map(|i| DatapointDouble {
+                timestamp: i as i64,
+                value: Some(i as f64),
+                status: None,
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn test_build_requests_within_limits() {
+        let mut items = HashMap::new();
+        items.insert(IdentityOrInstance::from(1), numeric(250));
+        items.insert(IdentityOrInstance::from(2), numeric(30));
+        items.insert(
+            IdentityOrInstance::from(3),
+            vec![DatapointString {
+                timestamp: 0,
+                value: Some("test".to_owned()),
+                status: None,
+            }]
+            .into(),
+        );
+        for i in 10..20 {
+            items.insert(IdentityOrInstance::from(i), numeric(1));
+        }
+
+        let requests = build_requests(items, 100, 5);
+        let total: usize = requests.iter().map(|r| r.1).sum();
+        assert_eq!(291, total);
+        for (req, count) in requests {
+            assert!(count <= 100);
+            assert!(req.items.len() <= 5);
+            let actual: usize = req
+                .items
+                .iter()
+                .map(|i| match i.datapoint_type.as_ref().unwrap() {
+                    crate::time_series::InsertDatapointType::NumericDatapoints(d) => {
+                        d.datapoints.len()
+                    }
+                    crate::time_series::InsertDatapointType::StringDatapoints(d) => {
+                        d.datapoints.len()
+                    }
+                })
+                .sum();
+            assert_eq!(count, actual);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::api::resource::*;
+use crate::dto::core::units::*;
+use crate::error::Result;
+use crate::{CogniteExternalId, ItemsVec};
+
+/// API resource for the unit catalog.
+pub type UnitsResource = Resource<Unit>;
+
+impl WithBasePath for UnitsResource {
+    const BASE_PATH: &'static str = "units";
+}
+
+impl Retrieve<CogniteExternalId, Unit> for UnitsResource {}
+
+impl UnitsResource {
+    /// List all units in the unit catalog.
+    pub async fn list(&self) -> Result<Vec<Unit>> {
+        let response: ItemsVec<Unit> = self.api_client.get(Self::BASE_PATH).await?;
+        Ok(response.items)
+    }
+
+    /// List all unit systems in the unit catalog.
+    pub async fn list_systems(&self) -> Result<Vec<UnitSystem>> {
+        let response: ItemsVec<UnitSystem> = self
+            .api_client
+            .get(&format!("{}/systems", Self::BASE_PATH))
+            .await?;
+        Ok(response.items)
+    }
+
+    /// Retrieve the full unit catalog, and create a converter for local unit conversion.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let converter = client.units.converter().await?;
+    /// let fahrenheit = converter.convert(20.0, "temperature:deg_c", "temperature:deg_f")?;
+    /// ```
+    pub async fn converter(&self) -> Result<UnitConverter> {
+        let (units, systems) = futures::try_join!(self.list(), self.list_systems())?;
+        Ok(UnitConverter::new(units, systems))
+    }
+}
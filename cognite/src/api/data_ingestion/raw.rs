@@ -1,19 +1,40 @@
-use std::collections::VecDeque;
-
-use futures::stream::{try_unfold, SelectAll};
+use futures::stream::SelectAll;
 use futures::{FutureExt, TryStream, TryStreamExt};
 
+use crate::api::paginator::hash_params;
+use crate::api::resource::follow_cursors_stream;
 use crate::api::resource::Resource;
 use crate::dto::items::Items;
 use crate::error::Result;
-use crate::{raw::*, CondBoxedStream, CondSend, CursorState, CursorStreamState};
-use crate::{Cursor, ItemsVec, LimitCursorQuery};
+use crate::{raw::*, CondBoxedStream, CondSend, CursorState, IntoParams};
+use crate::{Cursor, ItemsVec, LimitCursorQuery, PageSource, Paginator};
 
 /// Raw is a NoSQL JSON store. Each project can have a variable number of databases,
 /// each of which will have a variable number of tables, each of which will have a variable
 /// number of key-value objects. Only queries on key are supported through this API.
 pub type RawResource = Resource<RawRow>;
 
+/// Page source for rows in a single raw table.
+#[derive(Clone, Copy)]
+pub struct RawRowsSource<'a> {
+    resource: &'a RawResource,
+    db_name: &'a str,
+    table_name: &'a str,
+}
+
+impl PageSource<RetrieveRowsQuery, RawRow> for RawRowsSource<'_> {
+    async fn fetch_page(&self, req: &RetrieveRowsQuery) -> Result<ItemsVec<RawRow, Cursor>> {
+        self.resource
+            .retrieve_rows(self.db_name, self.table_name, Some(req.clone()))
+            .await
+    }
+
+    fn request_hash(&self, req: &RetrieveRowsQuery) -> Result<u64> {
+        let path = format!("raw/dbs/{}/tables/{}/rows", self.db_name, self.table_name);
+        Ok(hash_params(&path, req.clone()))
+    }
+}
+
 impl RawResource {
     /// List Raw databases in the project.
     ///
@@ -170,47 +191,49 @@ impl RawResource {
         params: Option<RetrieveRowsQuery>,
     ) -> impl TryStream<Ok = RawRow, Error = crate::Error, Item = Result<RawRow>> + CondSend + 'a
     {
-        let req = params.unwrap_or_default();
-        let initial_state = match &req.cursor {
-            Some(p) => CursorState::Some(p.to_owned()),
+        let mut req = params.unwrap_or_default();
+        let initial_state = match req.cursor.take() {
+            Some(p) => CursorState::Some(p),
             None => CursorState::Initial,
         };
-        let state = CursorStreamState {
-            req,
-            responses: VecDeque::new(),
-            next_cursor: initial_state,
-        };
-
-        try_unfold(state, move |mut state| async move {
-            if let Some(next) = state.responses.pop_front() {
-                Ok(Some((next, state)))
-            } else {
-                let cursor = match std::mem::take(&mut state.next_cursor) {
-                    CursorState::Initial => None,
-                    CursorState::Some(x) => Some(x),
-                    CursorState::End => {
-                        return Ok(None);
-                    }
-                };
-                state.req.cursor = cursor;
-                let response = self
-                    .retrieve_rows(db_name, table_name, Some(state.req.clone()))
-                    .await?;
+        let path = format!("raw/dbs/{db_name}/tables/{table_name}/rows");
 
-                state.responses.extend(response.items);
-                state.next_cursor = match response.extra_fields.next_cursor {
-                    Some(x) => CursorState::Some(x),
-                    None => CursorState::End,
-                };
-                if let Some(next) = state.responses.pop_front() {
-                    Ok(Some((next, state)))
-                } else {
-                    Ok(None)
-                }
-            }
+        follow_cursors_stream(initial_state, move |cursor| {
+            req.cursor = cursor;
+            let builder = self
+                .api_client
+                .get_request(&path)
+                .query(&req.clone().into_params());
+            async move { builder.accept_json_stream().send().await }
         })
     }
 
+    /// Create a paginator retrieving rows from a table page by page. The paginator
+    /// can be checkpointed, and resumed from a checkpoint later.
+    ///
+    /// `limit` in the filter only affects how many rows are returned _per request_.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_name` - Database to retrieve rows from.
+    /// * `table_name` - Table to retrieve rows from.
+    /// * `params` - Optional filter parameters. This can set a cursor to start reading from there.
+    pub fn retrieve_rows_paginator<'a>(
+        &'a self,
+        db_name: &'a str,
+        table_name: &'a str,
+        params: Option<RetrieveRowsQuery>,
+    ) -> Result<Paginator<RawRowsSource<'a>, RetrieveRowsQuery, RawRow>> {
+        let mut req = params.unwrap_or_default();
+        let cursor = req.cursor.take();
+        let source = RawRowsSource {
+            resource: self,
+            db_name,
+            table_name,
+        };
+        Paginator::new_with_cursor(source, req, cursor)
+    }
+
     /// Retrieve all rows from a table, following cursors.
     ///
     /// `limit` in the filter only affects how many rows are returned _per request_.
@@ -13,7 +13,7 @@ use records::RecordsResource;
 use streams::StreamsResource;
 
 use crate::api::data_modeling::{instances::Instances, views::ViewsResource};
-use crate::ApiClient;
+use crate::{ApiClient, ApiVersion};
 
 use self::containers::ContainersResource;
 use self::data_models::DataModelsResource;
@@ -46,8 +46,32 @@ impl Models {
             spaces: SpacesResource::new(api_client.clone()),
             data_models: DataModelsResource::new(api_client.clone()),
             containers: ContainersResource::new(api_client.clone()),
-            streams: StreamsResource::new(Arc::new(api_client.clone_with_api_version("beta"))),
-            records: RecordsResource::new(Arc::new(api_client.clone_with_api_version("beta"))),
+            streams: StreamsResource::new(Arc::new(
+                api_client.clone_with_api_version(ApiVersion::Beta.as_str()),
+            )),
+            records: RecordsResource::new(Arc::new(
+                api_client.clone_with_api_version(ApiVersion::Beta.as_str()),
+            )),
+        }
+    }
+
+    /// Create a copy of the data modeling resources that use the given CDF API version,
+    /// by setting the `cdf-version` header. Use this to access preview (alpha or beta)
+    /// endpoints and fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_version` - API version to use, for example `ApiVersion::Beta` or `"beta"`.
+    pub fn with_api_version(&self, api_version: impl Into<ApiVersion>) -> Self {
+        let api_version: ApiVersion = api_version.into();
+        Models {
+            instances: self.instances.with_api_version(api_version.clone()),
+            views: self.views.with_api_version(api_version.clone()),
+            spaces: self.spaces.with_api_version(api_version.clone()),
+            data_models: self.data_models.with_api_version(api_version.clone()),
+            containers: self.containers.with_api_version(api_version.clone()),
+            streams: self.streams.with_api_version(api_version.clone()),
+            records: self.records.with_api_version(api_version),
         }
     }
 }
@@ -10,6 +10,7 @@ use crate::models::instances::{
 };
 use crate::models::instances::{FromReadable, WithView};
 use crate::models::views::ViewReference;
+use crate::utils::batch_loader::LoadBatch;
 use crate::Result;
 use crate::{DeleteWithResponse, FilterWithRequest, RetrieveWithRequest, UpsertCollection};
 use crate::{Resource, WithBasePath};
@@ -38,6 +39,25 @@ impl<TProperties> UpsertCollection<NodeAndEdgeCreateCollection<TProperties>, Sli
 }
 impl DeleteWithResponse<NodeOrEdgeSpecification, NodeOrEdgeSpecification> for Instances {}
 
+impl<TProperties> LoadBatch<NodeOrEdgeSpecification, NodeOrEdge<TProperties>> for Instances
+where
+    TProperties: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn load_batch(
+        &self,
+        keys: &[NodeOrEdgeSpecification],
+    ) -> Result<Vec<NodeOrEdge<TProperties>>> {
+        let req = NodeAndEdgeRetrieveRequest {
+            sources: None,
+            items: keys.to_vec(),
+            include_typing: None,
+        };
+        let res: NodeAndEdgeRetrieveResponse<TProperties> =
+            RetrieveWithRequest::retrieve(self, &req).await?;
+        Ok(res.items)
+    }
+}
+
 impl Instances {
     /// Filter instances optionally returning type information.
     ///
@@ -0,0 +1,498 @@
+use std::future::Future;
+
+use futures::stream::{self, try_unfold};
+use futures::{TryStream, TryStreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::dto::items::*;
+use crate::{
+    CondSend, CursorState, Error, FilterWithRequest, IntoParams, List, Partition, Result,
+    SetCursor, WithPartition,
+};
+
+/// Serializable checkpoint of a paginated read. This can be persisted, and later
+/// used to resume the read from where it left off using [`Paginator::resume_from`].
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginationCheckpoint {
+    /// Cursor for the next page. This is `None` if the read has not started yet,
+    /// or if it is done.
+    pub cursor: Option<String>,
+    /// Partition this checkpoint belongs to, if the read is partitioned.
+    pub partition: Option<Partition>,
+    /// Hash of the request this checkpoint was created for, ignoring the cursor.
+    /// Used to verify that the read is resumed with the same request.
+    pub filter_hash: String,
+    /// `true` if all pages have been read.
+    pub done: bool,
+}
+
+/// A single page returned from a [`Paginator`].
+#[derive(Debug, Clone)]
+pub struct Page<TResponse> {
+    /// Items in this page.
+    pub items: Vec<TResponse>,
+    /// Checkpoint after this page. Once the items in this page have been processed,
+    /// this checkpoint can be stored to resume from the next page.
+    pub checkpoint: PaginationCheckpoint,
+}
+
+/// Trait for something that can fetch single pages of `TResponse` using a request
+/// of type `TReq`.
+pub trait PageSource<TReq, TResponse> {
+    /// Fetch a single page of results.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - Request for this page, with cursor set.
+    fn fetch_page(
+        &self,
+        req: &TReq,
+    ) -> impl Future<Output = Result<ItemsVec<TResponse, Cursor>>> + CondSend;
+
+    /// Compute a stable hash of `req`. The cursor is cleared before this is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - Request to hash.
+    fn request_hash(&self, req: &TReq) -> Result<u64>;
+}
+
+/// Page source for resources implementing [`List`].
+pub struct ListSource<'a, R>(pub &'a R);
+
+/// Page source for resources implementing [`FilterWithRequest`].
+pub struct FilterSource<'a, R>(pub &'a R);
+
+impl<R> Clone for ListSource<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for ListSource<'_, R> {}
+
+impl<R> Clone for FilterSource<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for FilterSource<'_, R> {}
+
+impl<R, TParams, TResponse> PageSource<TParams, TResponse> for ListSource<'_, R>
+where
+    TParams: IntoParams + Clone + Send + Sync + 'static,
+    TResponse: Serialize + DeserializeOwned + Send + Sync,
+    R: List<TParams, TResponse>,
+{
+    fn fetch_page(
+        &self,
+        req: &TParams,
+    ) -> impl Future<Output = Result<ItemsVec<TResponse, Cursor>>> + CondSend {
+        let req = req.clone();
+        async move {
+            self.0
+                .get_client()
+                .get_with_params(R::BASE_PATH, Some(req))
+                .await
+        }
+    }
+
+    fn request_hash(&self, req: &TParams) -> Result<u64> {
+        Ok(hash_params(R::BASE_PATH, req.clone()))
+    }
+}
+
+impl<R, TFilter, TResponse> PageSource<TFilter, TResponse> for FilterSource<'_, R>
+where
+    TFilter: Serialize + Sync + Send + 'static,
+    TResponse: Serialize + DeserializeOwned,
+    R: FilterWithRequest<TFilter, TResponse>,
+{
+    async fn fetch_page(&self, req: &TFilter) -> Result<ItemsVec<TResponse, Cursor>> {
+        self.0
+            .get_client()
+            .post(&format!("{}/list", R::BASE_PATH), req)
+            .await
+    }
+
+    fn request_hash(&self, req: &TFilter) -> Result<u64> {
+        hash_json(R::BASE_PATH, req)
+    }
+}
+
+/// Hash a request serialized as query parameters. Parameters are sorted first,
+/// so that the hash does not depend on parameter order.
+pub(crate) fn hash_params(path: &str, params: impl IntoParams) -> u64 {
+    let mut params = params.into_params();
+    params.sort();
+    let mut hash = fnv1a(FNV_OFFSET, path.as_bytes());
+    for (key, value) in params {
+        hash = fnv1a(hash, key.as_bytes());
+        hash = fnv1a(hash, b"=");
+        hash = fnv1a(hash, value.as_bytes());
+        hash = fnv1a(hash, b"&");
+    }
+    hash
+}
+
+/// Hash a request serialized as JSON. Object keys are sorted recursively before hashing,
+/// so that the hash does not depend on map iteration order. `serde_json::Value` only
+/// sorts keys while the `preserve_order` feature of `serde_json` is disabled, so we
+/// can't rely on it.
+pub(crate) fn hash_json(path: &str, req: &impl Serialize) -> Result<u64> {
+    let value = serde_json::to_value(req)?;
+    let mut canonical = String::new();
+    write_canonical_json(&value, &mut canonical);
+    let hash = fnv1a(FNV_OFFSET, path.as_bytes());
+    Ok(fnv1a(hash, canonical.as_bytes()))
+}
+
+/// Write `value` as compact JSON, with object keys in sorted order.
+fn write_canonical_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (idx, (key, value)) in entries.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical_json(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(item, out);
+            }
+            out.push(']');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// The hash is persisted in checkpoints, so we need a hash function that is stable
+// across processes and compiler versions, which the std hasher is not.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// A paginated read of some resource, which can be checkpointed and resumed.
+///
+/// Create a paginator using `list_paginator` on [`List`], `filter_paginator` on
+/// [`FilterWithRequest`], or `retrieve_rows_paginator` on the raw resource.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut paginator = client.assets.filter_paginator(FilterAssetsRequest::default())?;
+/// if let Some(checkpoint) = load_checkpoint() {
+///     paginator = paginator.resume_from(checkpoint)?;
+/// }
+/// while let Some(items) = paginator.next_page().await? {
+///     process(items);
+///     store_checkpoint(paginator.checkpoint());
+/// }
+/// ```
+pub struct Paginator<S, TReq, TResponse> {
+    source: S,
+    req: TReq,
+    state: CursorState,
+    partition: Option<Partition>,
+    filter_hash: u64,
+    marker: std::marker::PhantomData<fn() -> TResponse>,
+}
+
+impl<S, TReq, TResponse> Paginator<S, TReq, TResponse>
+where
+    S: PageSource<TReq, TResponse>,
+    TReq: SetCursor,
+{
+    /// Create a new paginator, starting at the first page.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Source of pages.
+    /// * `req` - Request used to fetch each page. Any cursor in this request is ignored.
+    pub fn new(source: S, mut req: TReq) -> Result<Self> {
+        req.set_cursor(None);
+        let filter_hash = source.request_hash(&req)?;
+        Ok(Self {
+            source,
+            req,
+            state: CursorState::Initial,
+            partition: None,
+            filter_hash,
+            marker: std::marker::PhantomData,
+        })
+    }
+
+    /// Create a new paginator, starting at the page given by `cursor`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Source of pages.
+    /// * `req` - Request used to fetch each page.
+    /// * `cursor` - Cursor to start from.
+    pub fn new_with_cursor(source: S, req: TReq, cursor: Option<String>) -> Result<Self> {
+        let mut paginator = Self::new(source, req)?;
+        if let Some(cursor) = cursor {
+            paginator.state = CursorState::Some(cursor);
+        }
+        Ok(paginator)
+    }
+
+    /// Resume this paginator from a checkpoint.
+    ///
+    /// This fails if the checkpoint was created for a different request or partition.
+    ///
+    /// # Arguments
+    ///
+    /// * `checkpoint` - Checkpoint to resume from.
+    pub fn resume_from(mut self, checkpoint: PaginationCheckpoint) -> Result<Self> {
+        if checkpoint.filter_hash != format_hash(self.filter_hash) {
+            return Err(Error::Other(
+                "Checkpoint was created for a different request".to_owned(),
+            ));
+        }
+        if checkpoint.partition != self.partition {
+            return Err(Error::Other(format!(
+                "Checkpoint was created for partition {}, expected {}",
+                format_partition(&checkpoint.partition),
+                format_partition(&self.partition)
+            )));
+        }
+        self.state = match (checkpoint.done, checkpoint.cursor) {
+            (true, _) => CursorState::End,
+            (false, Some(cursor)) => CursorState::Some(cursor),
+            (false, None) => CursorState::Initial,
+        };
+        Ok(self)
+    }
+
+    /// Get a checkpoint of the current state of this paginator.
+    pub fn checkpoint(&self) -> PaginationCheckpoint {
+        let (cursor, done) = match &self.state {
+            CursorState::Initial => (None, false),
+            CursorState::Some(c) => (Some(c.clone()), false),
+            CursorState::End => (None, true),
+        };
+        PaginationCheckpoint {
+            cursor,
+            partition: self.partition.clone(),
+            filter_hash: format_hash(self.filter_hash),
+            done,
+        }
+    }
+
+    /// Return `true` if all pages have been read.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, CursorState::End)
+    }
+
+    /// Get the request used by this paginator, without cursor.
+    pub fn request(&self) -> &TReq {
+        &self.req
+    }
+
+    /// Fetch the next page. Returns `None` once all pages have been read.
+    ///
+    /// If this fails, the state of the paginator is unchanged, so it can be retried.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<TResponse>>> {
+        let cursor = match &self.state {
+            CursorState::Initial => None,
+            CursorState::Some(c) => Some(c.clone()),
+            CursorState::End => return Ok(None),
+        };
+        self.req.set_cursor(cursor);
+        let response = self.source.fetch_page(&self.req).await;
+        self.req.set_cursor(None);
+        let response = response?;
+        self.state = match response.extra_fields.next_cursor {
+            Some(c) => CursorState::Some(c),
+            None => CursorState::End,
+        };
+        Ok(Some(response.items))
+    }
+
+    /// Convert this paginator into a stream of pages, each with a checkpoint
+    /// that can be stored once the page has been processed.
+    ///
+    /// Each item in the stream will be a result, after the first error is returned the
+    /// stream will end.
+    pub fn into_page_stream(
+        self,
+    ) -> impl TryStream<Ok = Page<TResponse>, Error = Error, Item = Result<Page<TResponse>>> + CondSend
+    where
+        Self: CondSend,
+    {
+        try_unfold(self, |mut paginator| async move {
+            match paginator.next_page().await? {
+                Some(items) => {
+                    let checkpoint = paginator.checkpoint();
+                    Ok(Some((Page { items, checkpoint }, paginator)))
+                }
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Convert this paginator into a stream of items, following cursors until they are
+    /// exhausted.
+    ///
+    /// Each item in the stream will be a result, after the first error is returned the
+    /// stream will end.
+    pub fn into_stream(
+        self,
+    ) -> impl TryStream<Ok = TResponse, Error = Error, Item = Result<TResponse>> + CondSend
+    where
+        Self: CondSend,
+        TResponse: CondSend,
+    {
+        self.into_page_stream()
+            .map_ok(|page| stream::iter(page.items.into_iter().map(Ok)))
+            .try_flatten()
+    }
+}
+
+impl<S, TReq, TResponse> Paginator<S, TReq, TResponse>
+where
+    S: PageSource<TReq, TResponse> + Clone,
+    TReq: SetCursor + WithPartition,
+{
+    /// Split this paginator into `num_partitions` paginators, one per partition.
+    /// Each of these can be checkpointed and resumed separately.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_partitions` - Number of partitions.
+    pub fn partitioned(self, num_partitions: u32) -> Result<Vec<Self>> {
+        (0..num_partitions)
+            .map(|p| self.with_partition(Partition::new(p + 1, num_partitions)))
+            .collect()
+    }
+
+    /// Resume a partitioned read from a list of checkpoints, one per partition.
+    ///
+    /// # Arguments
+    ///
+    /// * `checkpoints` - Checkpoints created by paginators returned from `partitioned`.
+    pub fn resume_partitioned(
+        self,
+        checkpoints: impl IntoIterator<Item = PaginationCheckpoint>,
+    ) -> Result<Vec<Self>> {
+        checkpoints
+            .into_iter()
+            .map(|checkpoint| {
+                let Some(partition) = checkpoint.partition.clone() else {
+                    return Err(Error::Other(
+                        "Checkpoint for partitioned read is missing partition".to_owned(),
+                    ));
+                };
+                self.with_partition(partition)?.resume_from(checkpoint)
+            })
+            .collect()
+    }
+
+    fn with_partition(&self, partition: Partition) -> Result<Self> {
+        let mut paginator = Self::new(
+            self.source.clone(),
+            self.req.with_partition(partition.clone()),
+        )?;
+        paginator.partition = Some(partition);
+        Ok(paginator)
+    }
+}
+
+fn format_hash(hash: u64) -> String {
+    format!("{hash:016x}")
+}
+
+fn format_partition(partition: &Option<Partition>) -> String {
+    match partition {
+        Some(p) => p.to_string(),
+        None => "none".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_json, hash_params, write_canonical_json, PaginationCheckpoint};
+    use crate::{assets::FilterAssetsRequest, Partition};
+
+    #[test]
+    fn test_request_hash_stable() {
+        let params = vec![
+            ("limit".to_owned(), "100".to_owned()),
+            ("name".to_owned(), "test".to_owned()),
+        ];
+        let mut reversed = params.clone();
+        reversed.reverse();
+        assert_eq!(
+            hash_params("assets", params),
+            hash_params("assets", reversed)
+        );
+
+        let req = FilterAssetsRequest::default();
+        assert_eq!(
+            hash_json("assets", &req).unwrap(),
+            hash_json("assets", &req).unwrap()
+        );
+        assert_ne!(
+            hash_json("assets", &req).unwrap(),
+            hash_json("events", &req).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_keys() {
+        let mut inner = serde_json::Map::new();
+        inner.insert("z".to_owned(), serde_json::json!([{ "y": 1, "x": "a\"b" }]));
+        inner.insert("c".to_owned(), serde_json::Value::Null);
+        let mut outer = serde_json::Map::new();
+        outer.insert("metadata".to_owned(), serde_json::Value::Object(inner));
+        outer.insert("limit".to_owned(), serde_json::json!(100));
+
+        let mut out = String::new();
+        write_canonical_json(&serde_json::Value::Object(outer), &mut out);
+        assert_eq!(
+            r#"{"limit":100,"metadata":{"c":null,"z":[{"x":"a\"b","y":1}]}}"#,
+            out
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_serde() {
+        let checkpoint = PaginationCheckpoint {
+            cursor: Some("abc".to_owned()),
+            partition: Some(Partition::new(2, 10)),
+            filter_hash: "0123456789abcdef".to_owned(),
+            done: false,
+        };
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        assert_eq!(
+            json,
+            r#"{"cursor":"abc","partition":"2/10","filterHash":"0123456789abcdef","done":false}"#
+        );
+        let back: PaginationCheckpoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(checkpoint, back);
+    }
+}
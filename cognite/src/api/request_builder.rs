@@ -1,5 +1,7 @@
 mod builder;
+mod json_stream;
 mod response;
 
 pub use builder::RequestBuilder;
+pub use json_stream::JsonItemsStream;
 pub use response::*;
@@ -5,6 +5,7 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::ApiClient;
+use crate::ApiVersion;
 use crate::CondSend;
 use crate::CondSync;
 use crate::Error;
@@ -14,8 +15,8 @@ use reqwest::{IntoUrl, Response};
 use crate::Result;
 
 use super::{
-    JsonResponseHandler, NoResponseHandler, ProtoResponseHandler, RawResponseHandler,
-    ResponseHandler,
+    JsonItemsStreamHandler, JsonResponseHandler, NoResponseHandler, ProtoResponseHandler,
+    RawResponseHandler, ResponseHandler,
 };
 
 /// Generic request builder. Used to construct custom requests towards CDF.
@@ -23,6 +24,7 @@ pub struct RequestBuilder<'a, T = ()> {
     inner: reqwest_middleware::RequestBuilder,
     client: &'a ApiClient,
     output: T,
+    api_version: Option<ApiVersion>,
 }
 
 impl<'a> RequestBuilder<'a, ()> {
@@ -37,6 +39,7 @@ impl<'a> RequestBuilder<'a, ()> {
             inner: client.client().post(url),
             client,
             output: (),
+            api_version: None,
         }
     }
 
@@ -51,6 +54,7 @@ impl<'a> RequestBuilder<'a, ()> {
             inner: client.client().get(url),
             client,
             output: (),
+            api_version: None,
         }
     }
 
@@ -65,6 +69,7 @@ impl<'a> RequestBuilder<'a, ()> {
             inner: client.client().delete(url),
             client,
             output: (),
+            api_version: None,
         }
     }
 
@@ -79,6 +84,7 @@ impl<'a> RequestBuilder<'a, ()> {
             inner: client.client().put(url),
             client,
             output: (),
+            api_version: None,
         }
     }
 }
@@ -139,6 +145,17 @@ impl<'a, T> RequestBuilder<'a, T> {
         self
     }
 
+    /// Set the CDF API version used for this request, overriding the API version
+    /// configured on the client. This sets the `cdf-version` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_version` - API version to use, for example `ApiVersion::Beta`.
+    pub fn api_version(mut self, api_version: impl Into<ApiVersion>) -> Self {
+        self.api_version = Some(api_version.into());
+        self
+    }
+
     /// Modify the inner request builder.
     pub fn with_inner<
         R: FnOnce(reqwest_middleware::RequestBuilder) -> reqwest_middleware::RequestBuilder,
@@ -157,6 +174,14 @@ impl<'a> RequestBuilder<'a, ()> {
         self.accept(JsonResponseHandler::new())
     }
 
+    /// Expect the response for a successful request to be a JSON object with a list of `T`
+    /// in `items`. The items are decoded one by one while the response is received.
+    pub fn accept_json_stream<T: DeserializeOwned>(
+        self,
+    ) -> RequestBuilder<'a, JsonItemsStreamHandler<T>> {
+        self.accept(JsonItemsStreamHandler::new())
+    }
+
     /// Expect the response for a successful request to be `T` encoded as protobuf.
     pub fn accept_protobuf<T: Message + Default + CondSend + CondSync>(
         self,
@@ -182,6 +207,7 @@ impl<'a> RequestBuilder<'a, ()> {
                 .header(ACCEPT, const { HeaderValue::from_static(T::ACCEPT_HEADER) }),
             client: self.client,
             output: handler,
+            api_version: self.api_version,
         }
     }
 }
@@ -224,11 +250,15 @@ impl<T: ResponseHandler> RequestBuilder<'_, T> {
                 "x-cdp-app",
                 HeaderValue::from_str(self.client.app_name()).expect("Invalid app name"),
             );
-        if let Some(cdf_version) = self.client.api_version() {
-            self.inner = self.inner.header(
-                "cdf-version",
-                HeaderValue::from_str(cdf_version).expect("Invalid CDF version"),
-            );
+        let cdf_version = self
+            .api_version
+            .as_ref()
+            .map(|v| v.as_str())
+            .or(self.client.api_version());
+        if let Some(cdf_version) = cdf_version {
+            self.inner = self
+                .inner
+                .header("cdf-version", HeaderValue::from_str(cdf_version)?);
         }
 
         match self.inner.send().await {
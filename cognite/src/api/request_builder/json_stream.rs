@@ -0,0 +1,426 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::stream::Stream;
+use serde::de::{DeserializeOwned, Error as _};
+use serde_json::{Map, Value};
+
+use crate::send_helper::CondBoxStream;
+use crate::{Error, Result};
+
+#[derive(Debug)]
+enum State {
+    Start,
+    FirstKeyOrEnd,
+    Key,
+    Colon(String),
+    FieldValue(String),
+    FieldEnd,
+    ItemsStart,
+    FirstItemOrEnd,
+    Item,
+    ItemEnd,
+    End,
+}
+
+#[derive(Default)]
+struct Scan {
+    offset: usize,
+    depth: u32,
+    in_string: bool,
+    escape: bool,
+}
+
+enum ScanResult {
+    Complete(usize),
+    NeedMore,
+}
+
+/// Incremental decoder for JSON objects on the form `{ "items": [...], ... }`.
+///
+/// Bytes are fed to the decoder as they arrive, and items are decoded one at a time
+/// as soon as they are complete. Any other fields in the object are collected and
+/// can be read once the object is complete.
+struct ItemsDecoder {
+    buf: Vec<u8>,
+    pos: usize,
+    state: State,
+    scan: Option<Scan>,
+    extra_fields: Map<String, Value>,
+}
+
+fn syntax_error(msg: &str) -> Error {
+    Error::SerdeJson(serde_json::Error::custom(msg))
+}
+
+impl ItemsDecoder {
+    fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            state: State::Start,
+            scan: None,
+            extra_fields: Map::new(),
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        // Discard consumed data before appending, so that the buffer only
+        // holds roughly one item at a time.
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, State::End)
+    }
+
+    fn skip_whitespace(&mut self) -> Option<u8> {
+        while let Some(c) = self.buf.get(self.pos) {
+            if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                return Some(*c);
+            }
+        }
+        None
+    }
+
+    /// Find the end of the JSON value starting at `self.pos`. This keeps its state between
+    /// calls, so that long values are only scanned once.
+    fn scan_value(&mut self) -> ScanResult {
+        let scan = self.scan.get_or_insert_with(Default::default);
+        let data = &self.buf[self.pos..];
+        let Some(&first) = data.first() else {
+            return ScanResult::NeedMore;
+        };
+        match first {
+            b'{' | b'[' | b'"' => {
+                while scan.offset < data.len() {
+                    let c = data[scan.offset];
+                    scan.offset += 1;
+                    if scan.in_string {
+                        if scan.escape {
+                            scan.escape = false;
+                        } else if c == b'\\' {
+                            scan.escape = true;
+                        } else if c == b'"' {
+                            scan.in_string = false;
+                            if scan.depth == 0 {
+                                return ScanResult::Complete(scan.offset);
+                            }
+                        }
+                        continue;
+                    }
+                    match c {
+                        b'"' => scan.in_string = true,
+                        b'{' | b'[' => scan.depth += 1,
+                        b'}' | b']' => {
+                            scan.depth = scan.depth.saturating_sub(1);
+                            if scan.depth == 0 {
+                                return ScanResult::Complete(scan.offset);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                ScanResult::NeedMore
+            }
+            // Numbers and literals are always followed by a delimiter inside an object.
+            _ => {
+                while scan.offset < data.len() {
+                    let c = data[scan.offset];
+                    if c == b',' || c == b'}' || c == b']' || c.is_ascii_whitespace() {
+                        return ScanResult::Complete(scan.offset);
+                    }
+                    scan.offset += 1;
+                }
+                ScanResult::NeedMore
+            }
+        }
+    }
+
+    fn take_value(&mut self) -> Option<&[u8]> {
+        match self.scan_value() {
+            ScanResult::Complete(len) => {
+                self.scan = None;
+                let start = self.pos;
+                self.pos += len;
+                Some(&self.buf[start..start + len])
+            }
+            ScanResult::NeedMore => None,
+        }
+    }
+
+    fn expect(&mut self, c: u8, expected: &[u8], name: &str) -> Result<u8> {
+        if expected.contains(&c) {
+            self.pos += 1;
+            Ok(c)
+        } else {
+            Err(syntax_error(&format!(
+                "Unexpected character '{}' in JSON response, expected {name}",
+                c as char
+            )))
+        }
+    }
+
+    /// Decode the next item. Returns `None` if more data is needed, or the object is complete.
+    fn next_item<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        loop {
+            let Some(c) = self.skip_whitespace() else {
+                return Ok(None);
+            };
+            match std::mem::replace(&mut self.state, State::End) {
+                State::Start => {
+                    self.expect(c, b"{", "start of object")?;
+                    self.state = State::FirstKeyOrEnd;
+                }
+                State::FirstKeyOrEnd => {
+                    if c == b'}' {
+                        self.pos += 1;
+                        self.state = State::End;
+                    } else {
+                        self.state = State::Key;
+                    }
+                }
+                State::Key => {
+                    if c != b'"' {
+                        return Err(syntax_error("Expected key in JSON response"));
+                    }
+                    match self.take_value() {
+                        Some(raw) => {
+                            let key: String = serde_json::from_slice(raw)?;
+                            self.state = State::Colon(key);
+                        }
+                        None => {
+                            self.state = State::Key;
+                            return Ok(None);
+                        }
+                    }
+                }
+                State::Colon(key) => {
+                    self.expect(c, b":", "':'")?;
+                    self.state = if key == "items" {
+                        State::ItemsStart
+                    } else {
+                        State::FieldValue(key)
+                    };
+                }
+                State::FieldValue(key) => match self.take_value() {
+                    Some(raw) => {
+                        let value: Value = serde_json::from_slice(raw)?;
+                        self.extra_fields.insert(key, value);
+                        self.state = State::FieldEnd;
+                    }
+                    None => {
+                        self.state = State::FieldValue(key);
+                        return Ok(None);
+                    }
+                },
+                State::FieldEnd => {
+                    let c = self.expect(c, b",}", "',' or '}'")?;
+                    self.state = if c == b',' { State::Key } else { State::End };
+                }
+                State::ItemsStart => {
+                    self.expect(c, b"[", "start of items array")?;
+                    self.state = State::FirstItemOrEnd;
+                }
+                State::FirstItemOrEnd => {
+                    if c == b']' {
+                        self.pos += 1;
+                        self.state = State::FieldEnd;
+                    } else {
+                        self.state = State::Item;
+                    }
+                }
+                State::Item => match self.take_value() {
+                    Some(raw) => {
+                        let item: T = serde_json::from_slice(raw)?;
+                        self.state = State::ItemEnd;
+                        return Ok(Some(item));
+                    }
+                    None => {
+                        self.state = State::Item;
+                        return Ok(None);
+                    }
+                },
+                State::ItemEnd => {
+                    let c = self.expect(c, b",]", "',' or ']'")?;
+                    self.state = if c == b',' {
+                        State::Item
+                    } else {
+                        State::FieldEnd
+                    };
+                }
+                State::End => {
+                    return Err(syntax_error("Trailing characters after JSON response"));
+                }
+            }
+        }
+    }
+}
+
+/// A stream of items decoded from a JSON list response while the response body
+/// is still being received, so that only a single item needs to be held in memory
+/// at a time, instead of the entire response.
+///
+/// Fields other than `items`, such as `nextCursor`, are available once the stream
+/// has been exhausted.
+pub struct JsonItemsStream<T> {
+    body: CondBoxStream<'static, reqwest::Result<Bytes>>,
+    decoder: ItemsDecoder,
+    body_done: bool,
+    failed: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonItemsStream<T> {
+    /// Create a new stream of items from a stream of bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - Stream of bytes containing a JSON object with an `items` array.
+    pub fn new(body: CondBoxStream<'static, reqwest::Result<Bytes>>) -> Self {
+        Self {
+            body,
+            decoder: ItemsDecoder::new(),
+            body_done: false,
+            failed: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Whether the full response has been decoded.
+    pub fn is_done(&self) -> bool {
+        self.decoder.is_done()
+    }
+
+    /// Fields in the response other than `items`. Only complete once the stream is exhausted.
+    pub fn extra_fields(&self) -> &Map<String, Value> {
+        &self.decoder.extra_fields
+    }
+
+    /// The `nextCursor` field in the response, if present.
+    /// This is only available once the stream is exhausted.
+    pub fn next_cursor(&self) -> Option<String> {
+        self.decoder
+            .extra_fields
+            .get("nextCursor")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_owned())
+    }
+}
+
+impl<T: DeserializeOwned> Stream for JsonItemsStream<T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None);
+        }
+        loop {
+            match this.decoder.next_item() {
+                Ok(Some(item)) => return Poll::Ready(Some(Ok(item))),
+                Ok(None) => (),
+                Err(e) => {
+                    this.failed = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+            // Once the object is complete, keep reading the body to check that
+            // there is nothing but whitespace left.
+            if this.body_done && this.decoder.is_done() {
+                return Poll::Ready(None);
+            }
+            if this.body_done {
+                this.failed = true;
+                return Poll::Ready(Some(Err(syntax_error("Unexpected end of JSON response"))));
+            }
+            match this.body.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) => this.decoder.feed(&bytes),
+                Poll::Ready(Some(Err(e))) => {
+                    this.failed = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                Poll::Ready(None) => this.body_done = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, StreamExt, TryStreamExt};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::JsonItemsStream;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Item {
+        id: i64,
+        name: String,
+    }
+
+    async fn decode(body: &str, chunk_size: usize) -> crate::Result<(Vec<Item>, Option<String>)> {
+        let chunks: Vec<_> = body
+            .as_bytes()
+            .chunks(chunk_size)
+            .map(|c| Ok(bytes::Bytes::copy_from_slice(c)))
+            .collect();
+        let mut stream = JsonItemsStream::<Item>::new(stream::iter(chunks).boxed());
+        let mut items = Vec::new();
+        while let Some(item) = stream.try_next().await? {
+            items.push(item);
+        }
+        Ok((items, stream.next_cursor()))
+    }
+
+    #[tokio::test]
+    async fn test_decode_items_stream() {
+        let body = json!({
+            "before": { "nested": [1, 2, { "a": "}]" }] },
+            "items": [
+                { "id": 1, "name": "test \"quoted\" ]}" },
+                { "id": 2, "name": "test\\" }
+            ],
+            "nextCursor": "abc",
+            "count": 12.5e3
+        })
+        .to_string();
+        let pretty = serde_json::to_string_pretty(
+            &serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        )
+        .unwrap();
+
+        for chunk_size in [1, 3, 7, 1000] {
+            for body in [&body, &pretty] {
+                let (items, cursor) = decode(body, chunk_size).await.unwrap();
+                assert_eq!(2, items.len());
+                assert_eq!("test \"quoted\" ]}", items[0].name);
+                assert_eq!("test\\", items[1].name);
+                assert_eq!(Some("abc".to_owned()), cursor);
+            }
+        }
+
+        let (items, cursor) = decode(r#"{"items":[]}"#, 2).await.unwrap();
+        assert!(items.is_empty());
+        assert!(cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_decode_items_stream_errors() {
+        assert!(decode(r#"{"items":[{"id":1,"name":"a"}"#, 4).await.is_err());
+        assert!(decode(r#"{"items":[{"id":1,"name":"a"}]}x"#, 4)
+            .await
+            .is_err());
+        assert!(decode(r#"["items"]"#, 4).await.is_err());
+    }
+}
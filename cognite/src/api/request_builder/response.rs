@@ -6,7 +6,9 @@ use serde::de::DeserializeOwned;
 
 use reqwest::Response;
 
-use crate::{CondSend, CondSync, Result};
+use crate::{CondBoxedStream, CondSend, CondSync, Result};
+
+use super::JsonItemsStream;
 
 /// Trait for a type that produces a typed response from a successful
 /// HTTP response message.
@@ -53,6 +55,31 @@ impl<T: DeserializeOwned> ResponseHandler for JsonResponseHandler<T> {
     }
 }
 
+/// Response handler for decoding a JSON list response as a stream of items,
+/// while the response body is still being received.
+pub struct JsonItemsStreamHandler<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for JsonItemsStreamHandler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> JsonItemsStreamHandler<T> {
+    /// Create a new streaming JSON response handler.
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: DeserializeOwned> ResponseHandler for JsonItemsStreamHandler<T> {
+    type Output = JsonItemsStream<T>;
+    const ACCEPT_HEADER: &'static str = "application/json";
+    async fn handle_response(self, response: Response) -> Result<Self::Output> {
+        Ok(JsonItemsStream::new(response.bytes_stream().boxed_cond()))
+    }
+}
+
 /// Response handler for parsing a payload as Protobuf.
 pub struct ProtoResponseHandler<T>(PhantomData<fn() -> T>);
 
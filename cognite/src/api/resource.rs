@@ -4,13 +4,14 @@ use std::{marker::PhantomData, sync::Arc};
 
 use futures::future::try_join_all;
 use futures::stream::{try_unfold, SelectAll};
-use futures::TryStream;
+use futures::{TryStream, TryStreamExt};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::dto::items::*;
 use crate::{
-    ApiClient, CondBoxedStream, CondSend, EqIdentity, Filter, Identity, IntoParams, IntoPatch,
-    Partition, Patch, Result, Search, SetCursor, UpsertOptions, WithPartition,
+    ApiClient, ApiVersion, CondBoxedStream, CondSend, EqIdentity, Filter, FilterSource, Identity,
+    IntoParams, IntoPatch, JsonItemsStream, ListSource, Paginator, Partition, Patch, Result,
+    Search, SetCursor, UpsertOptions, WithPartition,
 };
 
 use super::utils::{get_duplicates_from_result, get_missing_from_result};
@@ -37,6 +38,34 @@ impl<T> Resource<T> {
     }
 }
 
+impl<T> Resource<T> {
+    /// Create a copy of this resource that uses the given CDF API version,
+    /// by setting the `cdf-version` header. Use this to access preview (alpha or beta)
+    /// endpoints and fields. The underlying HTTP client is shared, so this is cheap.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_version` - API version to use, for example `ApiVersion::Beta` or `"beta"`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let beta_instances = client.models.instances.with_api_version(ApiVersion::Beta);
+    /// ```
+    pub fn with_api_version(&self, api_version: impl Into<ApiVersion>) -> Self {
+        let api_version: ApiVersion = api_version.into();
+        Self::new(Arc::new(
+            self.api_client.clone_with_api_version(api_version.as_str()),
+        ))
+    }
+
+    /// Create a copy of this resource that uses the stable CDF API, without
+    /// the `cdf-version` header.
+    pub fn with_stable_api_version(&self) -> Self {
+        Self::new(Arc::new(self.api_client.clone_without_api_version()))
+    }
+}
+
 impl<T> Clone for Resource<T> {
     fn clone(&self) -> Self {
         Self {
@@ -136,42 +165,33 @@ where
         TParams: SetCursor + Clone,
         TResponse: Send + 'static,
     {
-        let state = CursorStreamState {
-            req: params,
-            responses: VecDeque::new(),
-            next_cursor: CursorState::Initial,
-        };
-
-        try_unfold(state, move |mut state| async move {
-            if let Some(next) = state.responses.pop_front() {
-                Ok(Some((next, state)))
-            } else {
-                let cursor = match std::mem::take(&mut state.next_cursor) {
-                    CursorState::Initial => None,
-                    CursorState::Some(x) => Some(x),
-                    CursorState::End => {
-                        return Ok(None);
-                    }
-                };
-                state.req.set_cursor(cursor);
-                let response: ItemsVec<TResponse, Cursor> = self
-                    .get_client()
-                    .get_with_params(Self::BASE_PATH, Some(state.req.clone()))
-                    .await?;
-
-                state.responses.extend(response.items);
-                state.next_cursor = match response.extra_fields.next_cursor {
-                    Some(x) => CursorState::Some(x),
-                    None => CursorState::End,
-                };
-                if let Some(next) = state.responses.pop_front() {
-                    Ok(Some((next, state)))
-                } else {
-                    Ok(None)
-                }
-            }
+        let mut params = params;
+        let client = self.get_client();
+        follow_cursors_stream(CursorState::Initial, move |cursor| {
+            params.set_cursor(cursor);
+            let builder = client
+                .get_request(Self::BASE_PATH)
+                .query(&params.clone().into_params());
+            async move { builder.accept_json_stream().send().await }
         })
     }
+
+    /// Create a paginator listing resources page by page. The paginator can be
+    /// checkpointed, and resumed from a checkpoint later.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Query parameters. Any cursor in the parameters is ignored.
+    fn list_paginator(
+        &self,
+        params: TParams,
+    ) -> Result<Paginator<ListSource<'_, Self>, TParams, TResponse>>
+    where
+        TParams: SetCursor + Clone,
+        Self: Sized,
+    {
+        Paginator::new(ListSource(self), params)
+    }
 }
 
 /// Trait for creating resources with POST / requests.
@@ -724,10 +744,93 @@ pub(crate) enum CursorState {
     End,
 }
 
-pub(crate) struct CursorStreamState<TFilter, TResponse> {
-    pub(crate) req: TFilter,
-    pub(crate) responses: VecDeque<TResponse>,
-    pub(crate) next_cursor: CursorState,
+pub(crate) struct ItemsStreamState<F, TResponse> {
+    fetch: F,
+    current: VecDeque<TResponse>,
+    next_cursor: CursorState,
+}
+
+/// Maximum number of attempts at reading a single page in a cursor-following stream.
+const MAX_PAGE_ATTEMPTS: u32 = 3;
+
+/// Fetch a page and read the entire body, retrying if reading the body fails.
+async fn read_page<F, Fut, TResponse>(
+    fetch: &mut F,
+    cursor: Option<String>,
+) -> Result<(Vec<TResponse>, CursorState)>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<JsonItemsStream<TResponse>>>,
+    TResponse: DeserializeOwned,
+{
+    let mut attempt = 1;
+    loop {
+        let mut page = fetch(cursor.clone()).await?;
+        let mut items = Vec::new();
+        let res: Result<()> = async {
+            while let Some(item) = page.try_next().await? {
+                items.push(item);
+            }
+            Ok(())
+        }
+        .await;
+        match res {
+            Ok(()) => {
+                let next_cursor = match page.next_cursor() {
+                    Some(x) => CursorState::Some(x),
+                    None => CursorState::End,
+                };
+                return Ok((items, next_cursor));
+            }
+            // None of the items in the page have been returned yet, so the request
+            // can safely be sent again if the connection fails while reading the body.
+            Err(crate::Error::Reqwest(_)) if attempt < MAX_PAGE_ATTEMPTS => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Stream items from a list endpoint, following cursors. Each page is decoded while it is
+/// being received, so the raw response body is never held in memory, only the decoded
+/// items of a single page.
+///
+/// Each page is read completely before any of its items are returned, so that the request
+/// timeout does not depend on how quickly the caller consumes the stream, and so that
+/// pages that fail while the body is being read can be retried.
+///
+/// `fetch` is called with the cursor for each page, and should send the request.
+pub(crate) fn follow_cursors_stream<'a, F, Fut, TResponse>(
+    initial: CursorState,
+    fetch: F,
+) -> impl TryStream<Ok = TResponse, Error = crate::Error, Item = Result<TResponse>> + CondSend + 'a
+where
+    F: FnMut(Option<String>) -> Fut + CondSend + 'a,
+    Fut: Future<Output = Result<JsonItemsStream<TResponse>>> + CondSend + 'a,
+    TResponse: DeserializeOwned + Send + 'static,
+{
+    let state = ItemsStreamState {
+        fetch,
+        current: VecDeque::new(),
+        next_cursor: initial,
+    };
+
+    try_unfold(state, move |mut state| async move {
+        loop {
+            if let Some(next) = state.current.pop_front() {
+                return Ok(Some((next, state)));
+            }
+            let cursor = match std::mem::take(&mut state.next_cursor) {
+                CursorState::Initial => None,
+                CursorState::Some(x) => Some(x),
+                CursorState::End => {
+                    return Ok(None);
+                }
+            };
+            let (items, next_cursor) = read_page(&mut state.fetch, cursor).await?;
+            state.current = items.into();
+            state.next_cursor = next_cursor;
+        }
+    })
 }
 
 /// Trait for resource types that allow filtering with a more complex request.
@@ -803,40 +906,14 @@ where
         TFilter: SetCursor,
         TResponse: Send + 'static,
     {
-        let state = CursorStreamState {
-            req: filter,
-            responses: VecDeque::new(),
-            next_cursor: CursorState::Initial,
-        };
-
-        try_unfold(state, move |mut state| async move {
-            if let Some(next) = state.responses.pop_front() {
-                Ok(Some((next, state)))
-            } else {
-                let cursor = match std::mem::take(&mut state.next_cursor) {
-                    CursorState::Initial => None,
-                    CursorState::Some(x) => Some(x),
-                    CursorState::End => {
-                        return Ok(None);
-                    }
-                };
-                state.req.set_cursor(cursor);
-                let response: ItemsVec<TResponse, Cursor> = self
-                    .get_client()
-                    .post(&format!("{}/list", Self::BASE_PATH), &state.req)
-                    .await?;
-
-                state.responses.extend(response.items);
-                state.next_cursor = match response.extra_fields.next_cursor {
-                    Some(x) => CursorState::Some(x),
-                    None => CursorState::End,
-                };
-                if let Some(next) = state.responses.pop_front() {
-                    Ok(Some((next, state)))
-                } else {
-                    Ok(None)
-                }
-            }
+        let mut filter = filter;
+        let client = self.get_client();
+        follow_cursors_stream(CursorState::Initial, move |cursor| {
+            filter.set_cursor(cursor);
+            let builder = client
+                .post_request(&format!("{}/list", Self::BASE_PATH))
+                .json(&filter);
+            async move { builder?.accept_json_stream().send().await }
         })
     }
 
@@ -901,6 +978,24 @@ where
 
         streams
     }
+
+    /// Create a paginator filtering resources page by page. The paginator can be
+    /// checkpointed, and resumed from a checkpoint later. Use `partitioned` on the
+    /// paginator to read partitions in parallel, each with its own checkpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter which items to retrieve. Any cursor in the filter is ignored.
+    fn filter_paginator(
+        &self,
+        filter: TFilter,
+    ) -> Result<Paginator<FilterSource<'_, Self>, TFilter, TResponse>>
+    where
+        TFilter: SetCursor,
+        Self: Sized,
+    {
+        Paginator::new(FilterSource(self), filter)
+    }
 }
 
 /// Trait for resource types that allow filtering with fuzzy search.
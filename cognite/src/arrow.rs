@@ -0,0 +1,47 @@
+mod datapoints;
+mod json;
+#[cfg(feature = "polars")]
+mod polars;
+mod sequences;
+
+use std::sync::Arc;
+
+pub use arrow_array::RecordBatch;
+use arrow_array::{ArrayRef, TimestampMillisecondArray};
+pub use arrow_schema::ArrowError;
+use arrow_schema::{DataType, Field, Schema};
+
+pub use self::json::{instances_to_record_batches, ViewRecordBatch};
+#[cfg(feature = "polars")]
+pub use self::polars::{record_batch_to_data_frame, ToDataFrame};
+
+/// Conversion of a tabular result into an Arrow [`RecordBatch`].
+///
+/// Timestamps are converted to `Timestamp(Millisecond, "UTC")` columns.
+/// Values are appended directly to Arrow builders, so each value is copied once,
+/// from the response into the Arrow buffer. Repeated identifiers, like the external
+/// ID of a time series in datapoints, are dictionary encoded.
+pub trait ToRecordBatch {
+    /// Convert this into a record batch.
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError>;
+}
+
+/// Time zone used for all timestamp columns.
+const UTC: &str = "UTC";
+
+fn timestamp_array(values: impl Into<TimestampMillisecondArray>) -> ArrayRef {
+    Arc::new(values.into().with_timezone(UTC))
+}
+
+/// Build a record batch from a list of named columns. Columns are nullable
+/// if they contain any nulls.
+fn build_batch(columns: Vec<(String, ArrayRef)>) -> Result<RecordBatch, ArrowError> {
+    let (fields, arrays): (Vec<_>, Vec<_>) = columns
+        .into_iter()
+        .map(|(name, array)| {
+            let nullable = array.null_count() > 0 || array.data_type() == &DataType::Null;
+            (Field::new(name, array.data_type().clone(), nullable), array)
+        })
+        .unzip();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    ArrayBuilder, Float64Builder, Int32Builder, Int64Builder, StringBuilder,
+    TimestampMillisecondBuilder, UInt32Builder,
+};
+use arrow_array::types::Int32Type;
+use arrow_array::{ArrayRef, DictionaryArray, RecordBatch};
+use arrow_schema::ArrowError;
+
+use super::{build_batch, ToRecordBatch, UTC};
+use crate::time_series::{
+    DataPointListItem, DataPointListResponse, DatapointAggregate, DatapointsEnumType,
+    DatapointsListResponse, DatapointsResponse, ListDatapointType, StatusCode,
+};
+
+macro_rules! aggregate_columns {
+    ($($field:ident),*) => {
+        /// Aggregate columns, in the order they appear in the record batch.
+        #[derive(Default)]
+        struct AggregateColumns {
+            $($field: Float64Builder,)*
+        }
+
+        impl AggregateColumns {
+            fn push(&mut self, agg: Option<&DatapointAggregate>) {
+                $(self.$field.append_option(agg.map(|a| a.$field));)*
+            }
+
+            fn push_proto(&mut self, agg: &crate::time_series::AggregateDatapoint) {
+                $(self.$field.append_value(agg.$field);)*
+            }
+
+            fn into_columns(mut self) -> Vec<(String, ArrayRef)> {
+                vec![$((
+                    stringify!($field).to_owned(),
+                    Arc::new(self.$field.finish()) as ArrayRef,
+                ),)*]
+            }
+        }
+    };
+}
+
+aggregate_columns!(
+    average,
+    max,
+    min,
+    count,
+    sum,
+    interpolation,
+    step_interpolation,
+    continuous_variance,
+    discrete_variance,
+    total_variation,
+    count_good,
+    count_uncertain,
+    count_bad,
+    duration_good,
+    duration_uncertain,
+    duration_bad
+);
+
+/// Datapoints from any number of time series, in long format.
+///
+/// Columns are written directly into Arrow builders. External IDs are dictionary
+/// encoded, so each distinct external ID is stored once, not once per row.
+#[derive(Default)]
+struct LongFormat {
+    id: Int64Builder,
+    external_id_keys: Int32Builder,
+    external_id_values: StringBuilder,
+    external_id_index: HashMap<String, i32>,
+    timestamp: TimestampMillisecondBuilder,
+    value: Float64Builder,
+    string_value: StringBuilder,
+    status_code: UInt32Builder,
+    aggregates: AggregateColumns,
+    has_aggregates: bool,
+}
+
+impl LongFormat {
+    fn with_capacity(rows: usize) -> Self {
+        Self {
+            id: Int64Builder::with_capacity(rows),
+            external_id_keys: Int32Builder::with_capacity(rows),
+            timestamp: TimestampMillisecondBuilder::with_capacity(rows),
+            value: Float64Builder::with_capacity(rows),
+            status_code: UInt32Builder::with_capacity(rows),
+            ..Default::default()
+        }
+    }
+
+    /// Add the external ID of a time series to the dictionary, if it is not already
+    /// there, returning its key.
+    fn external_id_key(&mut self, external_id: Option<&str>) -> Option<i32> {
+        let external_id = external_id?;
+        if let Some(key) = self.external_id_index.get(external_id) {
+            return Some(*key);
+        }
+        self.external_id_values.append_value(external_id);
+        let key = self.external_id_values.len() as i32 - 1;
+        self.external_id_index.insert(external_id.to_owned(), key);
+        Some(key)
+    }
+
+    fn push_row(&mut self, id: i64, external_id: Option<i32>, timestamp: i64) {
+        self.id.append_value(id);
+        self.external_id_keys.append_option(external_id);
+        self.timestamp.append_value(timestamp);
+    }
+
+    fn push_raw(&mut self, value: Option<f64>, string_value: Option<&str>, status: Option<u32>) {
+        self.value.append_option(value);
+        self.string_value.append_option(string_value);
+        self.status_code.append_option(status);
+        self.aggregates.push(None);
+    }
+
+    /// Push empty raw columns for an aggregate row. The aggregates themselves
+    /// must be pushed separately.
+    fn push_aggregate_row(&mut self) {
+        self.has_aggregates = true;
+        self.value.append_null();
+        self.string_value.append_null();
+        self.status_code.append_null();
+    }
+
+    fn add_response(&mut self, item: &DatapointsResponse) {
+        let id = item.id;
+        let xid = self.external_id_key(item.external_id.as_deref());
+        let status = |s: &Option<StatusCode>| s.map(|s| s.bits());
+        match &item.datapoints {
+            DatapointsEnumType::NumericDatapoints(dps) => {
+                for dp in dps {
+                    self.push_row(id, xid, dp.timestamp);
+                    self.push_raw(dp.value, None, status(&dp.status));
+                }
+            }
+            DatapointsEnumType::StringDatapoints(dps) => {
+                for dp in dps {
+                    self.push_row(id, xid, dp.timestamp);
+                    self.push_raw(None, dp.value.as_deref(), status(&dp.status));
+                }
+            }
+            DatapointsEnumType::AggregateDatapoints(dps) => {
+                for dp in dps {
+                    self.push_row(id, xid, dp.timestamp);
+                    self.push_aggregate_row();
+                    self.aggregates.push(Some(dp));
+                }
+            }
+        }
+    }
+
+    fn add_proto(&mut self, item: &DataPointListItem) {
+        let id = item.id;
+        let xid = self.external_id_key(Some(item.external_id.as_str()).filter(|s| !s.is_empty()));
+        let status = |s| Option::map(s, |s| StatusCode::from(s).bits());
+        match &item.datapoint_type {
+            Some(ListDatapointType::NumericDatapoints(dps)) => {
+                for dp in &dps.datapoints {
+                    self.push_row(id, xid, dp.timestamp);
+                    let value = Some(dp.value).filter(|_| !dp.null_value);
+                    self.push_raw(value, None, status(dp.status.as_ref()));
+                }
+            }
+            Some(ListDatapointType::StringDatapoints(dps)) => {
+                for dp in &dps.datapoints {
+                    self.push_row(id, xid, dp.timestamp);
+                    let value = Some(dp.value.as_str()).filter(|_| !dp.null_value);
+                    self.push_raw(None, value, status(dp.status.as_ref()));
+                }
+            }
+            Some(ListDatapointType::AggregateDatapoints(dps)) => {
+                for dp in &dps.datapoints {
+                    self.push_row(id, xid, dp.timestamp);
+                    self.push_aggregate_row();
+                    self.aggregates.push_proto(dp);
+                }
+            }
+            None => (),
+        }
+    }
+
+    fn into_record_batch(mut self) -> Result<RecordBatch, ArrowError> {
+        let external_id = DictionaryArray::<Int32Type>::try_new(
+            self.external_id_keys.finish(),
+            Arc::new(self.external_id_values.finish()),
+        )?;
+        let mut columns: Vec<(String, ArrayRef)> = vec![
+            ("id".to_owned(), Arc::new(self.id.finish())),
+            ("external_id".to_owned(), Arc::new(external_id)),
+            (
+                "timestamp".to_owned(),
+                Arc::new(self.timestamp.finish().with_timezone(UTC)),
+            ),
+            ("value".to_owned(), Arc::new(self.value.finish())),
+            (
+                "string_value".to_owned(),
+                Arc::new(self.string_value.finish()),
+            ),
+            (
+                "status_code".to_owned(),
+                Arc::new(self.status_code.finish()),
+            ),
+        ];
+        if self.has_aggregates {
+            columns.extend(self.aggregates.into_columns());
+        }
+        build_batch(columns)
+    }
+}
+
+fn proto_len(item: &DataPointListItem) -> usize {
+    match &item.datapoint_type {
+        Some(ListDatapointType::NumericDatapoints(d)) => d.datapoints.len(),
+        Some(ListDatapointType::StringDatapoints(d)) => d.datapoints.len(),
+        Some(ListDatapointType::AggregateDatapoints(d)) => d.datapoints.len(),
+        None => 0,
+    }
+}
+
+fn response_len(item: &DatapointsResponse) -> usize {
+    match &item.datapoints {
+        DatapointsEnumType::NumericDatapoints(d) => d.len(),
+        DatapointsEnumType::StringDatapoints(d) => d.len(),
+        DatapointsEnumType::AggregateDatapoints(d) => d.len(),
+    }
+}
+
+/// Datapoints are converted to long format, with one row per datapoint and the columns
+/// `id`, `external_id`, `timestamp`, `value`, `string_value` and `status_code`.
+/// `external_id` is a `Dictionary(Int32, Utf8)` column, with one dictionary entry per time series.
+/// If any time series contain aggregates, there is one additional column per aggregate,
+/// like `average` and `step_interpolation`. Aggregates that were not requested are 0.
+impl ToRecordBatch for DataPointListResponse {
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let mut builder = LongFormat::with_capacity(self.items.iter().map(proto_len).sum());
+        for item in &self.items {
+            builder.add_proto(item);
+        }
+        builder.into_record_batch()
+    }
+}
+
+/// See [`DataPointListResponse`] for the layout of the record batch.
+impl ToRecordBatch for DataPointListItem {
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let mut builder = LongFormat::with_capacity(proto_len(self));
+        builder.add_proto(self);
+        builder.into_record_batch()
+    }
+}
+
+/// See [`DataPointListResponse`] for the layout of the record batch.
+impl ToRecordBatch for DatapointsListResponse {
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let mut builder = LongFormat::with_capacity(self.items.iter().map(response_len).sum());
+        for item in &self.items {
+            builder.add_response(item);
+        }
+        builder.into_record_batch()
+    }
+}
+
+/// See [`DataPointListResponse`] for the layout of the record batch.
+impl ToRecordBatch for DatapointsResponse {
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let mut builder = LongFormat::with_capacity(response_len(self));
+        builder.add_response(self);
+        builder.into_record_batch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int32Type;
+    use arrow_array::{Array, Float64Array, StringArray};
+
+    use crate::arrow::ToRecordBatch;
+    use crate::time_series::{
+        AggregateDatapoint, AggregateDatapoints, DataPointListItem, DataPointListResponse,
+        ListDatapointType, NumericDatapoint, NumericDatapoints, StringDatapoint, StringDatapoints,
+    };
+
+    #[test]
+    fn test_datapoints_to_record_batch() {
+        let response = DataPointListResponse {
+            items: vec![
+                DataPointListItem {
+                    id: 1,
+                    external_id: "num".to_owned(),
+                    datapoint_type: Some(ListDatapointType::NumericDatapoints(NumericDatapoints {
+                        datapoints: vec![
+                            NumericDatapoint {
+                                timestamp: 1,
+                                value: 1.5,
+                                ..Default::default()
+                            },
+                            NumericDatapoint {
+                                timestamp: 2,
+                                null_value: true,
+                                ..Default::default()
+                            },
+                        ],
+                    })),
+                    ..Default::default()
+                },
+                DataPointListItem {
+                    id: 2,
+                    datapoint_type: Some(ListDatapointType::StringDatapoints(StringDatapoints {
+                        datapoints: vec![StringDatapoint {
+                            timestamp: 3,
+                            value: "hello".to_owned(),
+                            ..Default::default()
+                        }],
+                    })),
+                    ..Default::default()
+                },
+            ],
+        };
+        let batch = response.to_record_batch().unwrap();
+        assert_eq!(3, batch.num_rows());
+        assert_eq!(6, batch.num_columns());
+        let values = batch
+            .column_by_name("value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(1.5, values.value(0));
+        assert!(values.is_null(1));
+        assert!(values.is_null(2));
+        let strings = batch
+            .column_by_name("string_value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!("hello", strings.value(2));
+        let xids = batch
+            .column_by_name("external_id")
+            .unwrap()
+            .as_dictionary::<Int32Type>();
+        // One dictionary entry for the only time series with an external ID.
+        assert_eq!(1, xids.values().len());
+        let values = xids.values().as_string::<i32>();
+        assert_eq!("num", values.value(xids.keys().value(0) as usize));
+        assert_eq!("num", values.value(xids.keys().value(1) as usize));
+        assert!(xids.is_null(2));
+
+        let item = DataPointListItem {
+            id: 3,
+            datapoint_type: Some(ListDatapointType::AggregateDatapoints(
+                AggregateDatapoints {
+                    datapoints: vec![AggregateDatapoint {
+                        timestamp: 0,
+                        average: 2.0,
+                        ..Default::default()
+                    }],
+                },
+            )),
+            ..Default::default()
+        };
+        let batch = item.to_record_batch().unwrap();
+        assert_eq!(22, batch.num_columns());
+        let average = batch
+            .column_by_name("average")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(2.0, average.value(0));
+    }
+
+    #[test]
+    fn test_datapoints_dictionary_dedup() {
+        let item = |timestamp: i64| DataPointListItem {
+            id: 1,
+            external_id: "num".to_owned(),
+            datapoint_type: Some(ListDatapointType::NumericDatapoints(NumericDatapoints {
+                datapoints: vec![NumericDatapoint {
+                    timestamp,
+                    value: 1.0,
+                    ..Default::default()
+                }],
+            })),
+            ..Default::default()
+        };
+        // The same time series may appear in several items, for example when
+        // responses from multiple pages are combined.
+        let response = DataPointListResponse {
+            items: vec![item(1), item(2), item(3)],
+        };
+        let batch = response.to_record_batch().unwrap();
+        let xids = batch
+            .column_by_name("external_id")
+            .unwrap()
+            .as_dictionary::<Int32Type>();
+        assert_eq!(1, xids.values().len());
+        assert_eq!(3, xids.keys().iter().filter(|k| *k == Some(0)).count());
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, NullArray, RecordBatch,
+    StringArray,
+};
+use arrow_schema::ArrowError;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{build_batch, timestamp_array, ToRecordBatch};
+use crate::models::instances::NodeOrEdge;
+use crate::raw::RawRow;
+
+/// Convert a column of JSON values to an arrow array, inferring the type from the values.
+///
+/// Columns with only booleans, integers, numbers, or strings get the corresponding arrow
+/// type. Columns with mixed types or with objects or arrays are written as JSON text.
+fn json_column(values: &[Option<&Value>]) -> ArrayRef {
+    let values: Vec<_> = values.iter().map(|v| v.filter(|v| !v.is_null())).collect();
+    let present = || values.iter().flatten();
+
+    if present().next().is_none() {
+        Arc::new(NullArray::new(values.len()))
+    } else if present().all(|v| v.is_boolean()) {
+        Arc::new(BooleanArray::from_iter(
+            values.iter().map(|v| v.and_then(Value::as_bool)),
+        ))
+    } else if present().all(|v| v.is_i64()) {
+        Arc::new(Int64Array::from_iter(
+            values.iter().map(|v| v.and_then(Value::as_i64)),
+        ))
+    } else if present().all(|v| v.is_number()) {
+        Arc::new(Float64Array::from_iter(
+            values.iter().map(|v| v.and_then(Value::as_f64)),
+        ))
+    } else {
+        Arc::new(StringArray::from_iter(values.iter().map(|v| {
+            v.map(|v| match v {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            })
+        })))
+    }
+}
+
+/// Build JSON columns for the union of keys in a list of JSON objects, sorted by name.
+fn object_columns(objects: &[Option<&serde_json::Map<String, Value>>]) -> Vec<(String, ArrayRef)> {
+    let names: BTreeSet<&String> = objects.iter().flatten().flat_map(|o| o.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let values: Vec<_> = objects
+                .iter()
+                .map(|o| o.and_then(|o| o.get(name)))
+                .collect();
+            (name.clone(), json_column(&values))
+        })
+        .collect()
+}
+
+/// Raw rows are converted to one row per raw row, with the columns `key` and
+/// `last_updated_time`, followed by the union of all raw columns, sorted by name.
+/// Column types are inferred from the values, columns with mixed types are written as JSON text.
+impl ToRecordBatch for [RawRow] {
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let mut columns: Vec<(String, ArrayRef)> = vec![
+            (
+                "key".to_owned(),
+                Arc::new(StringArray::from_iter_values(
+                    self.iter().map(|r| r.key.as_str()),
+                )),
+            ),
+            (
+                "last_updated_time".to_owned(),
+                timestamp_array(self.iter().map(|r| r.last_updated_time).collect::<Vec<_>>()),
+            ),
+        ];
+        let objects: Vec<_> = self.iter().map(|r| r.columns.as_object()).collect();
+        columns.extend(object_columns(&objects));
+        build_batch(columns)
+    }
+}
+
+/// A record batch containing the properties of instances in a single view.
+#[derive(Debug, Clone)]
+pub struct ViewRecordBatch {
+    /// Space of the view.
+    pub space: String,
+    /// View identifier, as `externalId/version`.
+    pub view: String,
+    /// Instances with properties in this view.
+    pub batch: RecordBatch,
+}
+
+struct InstanceRow<'a> {
+    instance_type: &'static str,
+    space: &'a str,
+    external_id: &'a str,
+    version: i32,
+    created_time: i64,
+    last_updated_time: i64,
+    deleted_time: Option<i64>,
+    properties: Option<serde_json::Map<String, Value>>,
+}
+
+/// Convert a list of instances to record batches, with one batch per view.
+///
+/// Each batch has the columns `instance_type`, `space`, `external_id`, `version`,
+/// `created_time`, `last_updated_time`, and `deleted_time`, followed by the properties
+/// of the view, sorted by name. Property types are inferred from the values.
+/// Instances with properties in several views appear in several batches, instances
+/// without any properties are not included.
+///
+/// # Arguments
+///
+/// * `instances` - Instances to convert.
+pub fn instances_to_record_batches<T: Serialize>(
+    instances: &[NodeOrEdge<T>],
+) -> Result<Vec<ViewRecordBatch>, ArrowError> {
+    let mut groups: BTreeMap<(&str, &str), Vec<InstanceRow>> = BTreeMap::new();
+    for instance in instances {
+        let (instance_type, space, external_id, version, created, updated, deleted, props) =
+            match instance {
+                NodeOrEdge::Node(n) => (
+                    "node",
+                    &n.space,
+                    &n.external_id,
+                    n.version,
+                    n.created_time,
+                    n.last_updated_time,
+                    n.deleted_time,
+                    &n.properties,
+                ),
+                NodeOrEdge::Edge(e) => (
+                    "edge",
+                    &e.space,
+                    &e.external_id,
+                    e.version,
+                    e.created_time,
+                    e.last_updated_time,
+                    e.deleted_time,
+                    &e.properties,
+                ),
+            };
+        for (view_space, views) in props.iter().flatten() {
+            for (view, properties) in views {
+                let properties = serde_json::to_value(properties)
+                    .map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
+                let properties = match properties {
+                    Value::Object(o) => Some(o),
+                    _ => None,
+                };
+                groups
+                    .entry((view_space.as_str(), view.as_str()))
+                    .or_default()
+                    .push(InstanceRow {
+                        instance_type,
+                        space,
+                        external_id,
+                        version,
+                        created_time: created,
+                        last_updated_time: updated,
+                        deleted_time: deleted,
+                        properties,
+                    });
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|((space, view), rows)| {
+            let mut columns: Vec<(String, ArrayRef)> = vec![
+                (
+                    "instance_type".to_owned(),
+                    Arc::new(StringArray::from_iter_values(
+                        rows.iter().map(|r| r.instance_type),
+                    )),
+                ),
+                (
+                    "space".to_owned(),
+                    Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.space))),
+                ),
+                (
+                    "external_id".to_owned(),
+                    Arc::new(StringArray::from_iter_values(
+                        rows.iter().map(|r| r.external_id),
+                    )),
+                ),
+                (
+                    "version".to_owned(),
+                    Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.version))),
+                ),
+                (
+                    "created_time".to_owned(),
+                    timestamp_array(rows.iter().map(|r| r.created_time).collect::<Vec<_>>()),
+                ),
+                (
+                    "last_updated_time".to_owned(),
+                    timestamp_array(rows.iter().map(|r| r.last_updated_time).collect::<Vec<_>>()),
+                ),
+                (
+                    "deleted_time".to_owned(),
+                    timestamp_array(rows.iter().map(|r| r.deleted_time).collect::<Vec<_>>()),
+                ),
+            ];
+            let objects: Vec<_> = rows.iter().map(|r| r.properties.as_ref()).collect();
+            columns.extend(object_columns(&objects));
+            Ok(ViewRecordBatch {
+                space: space.to_owned(),
+                view: view.to_owned(),
+                batch: build_batch(columns)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Array, Float64Array, Int64Array, StringArray};
+    use arrow_schema::DataType;
+    use serde_json::json;
+
+    use super::instances_to_record_batches;
+    use crate::arrow::ToRecordBatch;
+    use crate::models::instances::NodeOrEdge;
+    use crate::raw::RawRow;
+
+    #[test]
+    fn test_raw_rows_to_record_batch() {
+        let rows: Vec<RawRow> = serde_json::from_value(json!([
+            { "key": "a", "lastUpdatedTime": 1, "columns": { "int": 1, "mixed": 1, "float": 1 } },
+            { "key": "b", "lastUpdatedTime": 2, "columns": { "int": 2, "mixed": "x", "float": 1.5, "null": null } }
+        ]))
+        .unwrap();
+        let batch = rows.to_record_batch().unwrap();
+        assert_eq!(2, batch.num_rows());
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            vec!["key", "last_updated_time", "float", "int", "mixed", "null"],
+            names
+        );
+        let int = batch.column_by_name("int").unwrap();
+        assert_eq!(
+            2,
+            int.as_any().downcast_ref::<Int64Array>().unwrap().value(1)
+        );
+        let float = batch.column_by_name("float").unwrap();
+        assert_eq!(
+            1.0,
+            float
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .value(0)
+        );
+        let mixed = batch.column_by_name("mixed").unwrap();
+        let mixed = mixed.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!("1", mixed.value(0));
+        assert_eq!("x", mixed.value(1));
+        assert_eq!(
+            &DataType::Null,
+            batch.column_by_name("null").unwrap().data_type()
+        );
+    }
+
+    #[test]
+    fn test_instances_to_record_batches() {
+        let instances: Vec<NodeOrEdge<serde_json::Value>> = serde_json::from_value(json!([
+            {
+                "instanceType": "node", "space": "s", "externalId": "n1", "version": 1,
+                "createdTime": 0, "lastUpdatedTime": 0,
+                "properties": { "s": { "view/1": { "name": "a", "value": 1 } } }
+            },
+            {
+                "instanceType": "node", "space": "s", "externalId": "n2", "version": 2,
+                "createdTime": 0, "lastUpdatedTime": 0,
+                "properties": { "s": { "view/1": { "name": "b" }, "other/1": { "flag": true } } }
+            },
+            {
+                "instanceType": "node", "space": "s", "externalId": "n3", "version": 1,
+                "createdTime": 0, "lastUpdatedTime": 0
+            }
+        ]))
+        .unwrap();
+        let batches = instances_to_record_batches(&instances).unwrap();
+        assert_eq!(2, batches.len());
+        assert_eq!("other/1", batches[0].view);
+        assert_eq!(1, batches[0].batch.num_rows());
+        assert_eq!("view/1", batches[1].view);
+        let batch = &batches[1].batch;
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(9, batch.num_columns());
+        let value = batch.column_by_name("value").unwrap();
+        assert!(value.is_null(1));
+    }
+}
@@ -0,0 +1,140 @@
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    ArrowPrimitiveType, Float64Type as ArrowFloat64, Int32Type as ArrowInt32,
+    Int64Type as ArrowInt64, TimestampMillisecondType, UInt32Type as ArrowUInt32,
+};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{DataType as ArrowDataType, TimeUnit as ArrowTimeUnit};
+use polars_core::prelude::*;
+
+use super::ToRecordBatch;
+
+/// Convert a primitive column. Columns without nulls are copied with a single `memcpy`,
+/// other columns are copied value by value, without any intermediate buffers.
+fn primitive_series<A, P>(name: PlSmallStr, array: &dyn Array) -> Series
+where
+    A: ArrowPrimitiveType,
+    P: PolarsNumericType<Native = A::Native>,
+{
+    let array = array.as_primitive::<A>();
+    if array.null_count() == 0 {
+        ChunkedArray::<P>::from_vec(name, array.values().to_vec()).into_series()
+    } else {
+        ChunkedArray::<P>::from_iter_options(name, array.iter()).into_series()
+    }
+}
+
+fn to_series(name: &str, array: &dyn Array) -> PolarsResult<Series> {
+    let name = PlSmallStr::from_str(name);
+    let series = match array.data_type() {
+        ArrowDataType::Null => Series::full_null(name, array.len(), &DataType::Null),
+        ArrowDataType::Boolean => {
+            BooleanChunked::from_iter_options(name, array.as_boolean().iter()).into_series()
+        }
+        ArrowDataType::Int32 => primitive_series::<ArrowInt32, Int32Type>(name, array),
+        ArrowDataType::Int64 => primitive_series::<ArrowInt64, Int64Type>(name, array),
+        ArrowDataType::UInt32 => primitive_series::<ArrowUInt32, UInt32Type>(name, array),
+        ArrowDataType::Float64 => primitive_series::<ArrowFloat64, Float64Type>(name, array),
+        ArrowDataType::Utf8 => {
+            StringChunked::from_iter_options(name, array.as_string::<i32>().iter()).into_series()
+        }
+        ArrowDataType::Dictionary(key, value)
+            if **key == ArrowDataType::Int32 && **value == ArrowDataType::Utf8 =>
+        {
+            let dict = array.as_dictionary::<ArrowInt32>();
+            let values = dict.values().as_string::<i32>();
+            StringChunked::from_iter_options(
+                name,
+                dict.keys()
+                    .iter()
+                    .map(|k| k.map(|k| values.value(k as usize))),
+            )
+            .into_series()
+        }
+        ArrowDataType::Timestamp(ArrowTimeUnit::Millisecond, _) => {
+            primitive_series::<TimestampMillisecondType, Int64Type>(name, array).cast(
+                &DataType::Datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC)),
+            )?
+        }
+        t => polars_bail!(ComputeError: "unsupported arrow type {} in column {}", t, name),
+    };
+    Ok(series)
+}
+
+/// Convert a record batch produced by [`ToRecordBatch`] into a polars data frame.
+///
+/// Timestamp columns are converted to `Datetime(Milliseconds, UTC)`, and dictionary
+/// encoded string columns are converted to `String`. Polars uses its own Arrow
+/// implementation, so each column is copied once.
+///
+/// # Arguments
+///
+/// * `batch` - Record batch to convert.
+pub fn record_batch_to_data_frame(batch: &RecordBatch) -> PolarsResult<DataFrame> {
+    let columns = batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, array)| to_series(field.name(), array.as_ref()).map(Column::from))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    DataFrame::new(columns)
+}
+
+/// Conversion of a tabular result into a polars [`DataFrame`].
+///
+/// This is implemented for every type implementing [`ToRecordBatch`].
+pub trait ToDataFrame {
+    /// Convert this into a data frame.
+    fn to_data_frame(&self) -> PolarsResult<DataFrame>;
+}
+
+impl<T: ToRecordBatch + ?Sized> ToDataFrame for T {
+    fn to_data_frame(&self) -> PolarsResult<DataFrame> {
+        let batch = self
+            .to_record_batch()
+            .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+        record_batch_to_data_frame(&batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use polars_core::prelude::*;
+
+    use super::ToDataFrame;
+    use crate::time_series::{DatapointDouble, DatapointsEnumType, DatapointsResponse};
+
+    #[test]
+    fn test_datapoints_to_data_frame() {
+        let response = DatapointsResponse {
+            id: 1,
+            external_id: Some("ts".to_owned()),
+            datapoints: DatapointsEnumType::NumericDatapoints(vec![
+                DatapointDouble {
+                    timestamp: 1000,
+                    value: Some(1.0),
+                    status: None,
+                },
+                DatapointDouble {
+                    timestamp: 2000,
+                    value: None,
+                    status: None,
+                },
+            ]),
+            unit: None,
+            unit_external_id: None,
+            is_step: false,
+            is_string: false,
+            next_cursor: None,
+            time_zone: None,
+        };
+        let df = response.to_data_frame().unwrap();
+        assert_eq!((2, 6), df.shape());
+        assert_eq!(
+            &DataType::Datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC)),
+            df.column("timestamp").unwrap().dtype()
+        );
+        assert_eq!(1, df.column("value").unwrap().null_count());
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::ArrowError;
+
+use super::{build_batch, ToRecordBatch};
+use crate::sequences::{RetrieveSequenceRowsResponse, SequenceRowValue, SequenceValueType};
+
+fn cast_error(column: &str, row: i64, expected: &str, value: &SequenceRowValue) -> ArrowError {
+    ArrowError::CastError(format!(
+        "Column '{column}' in row {row} should be {expected}, got {value:?}"
+    ))
+}
+
+/// Sequence rows are converted to one row per sequence row, with a `row_number` column,
+/// followed by one column per sequence column named by its external ID. `DOUBLE` columns
+/// become `Float64`, `LONG` columns become `Int64`, and `STRING` columns become `Utf8`.
+impl ToRecordBatch for RetrieveSequenceRowsResponse {
+    fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let row_numbers: Vec<_> = self.rows.iter().map(|r| r.row_number).collect();
+        let mut columns: Vec<(String, ArrayRef)> = vec![(
+            "row_number".to_owned(),
+            Arc::new(Int64Array::from(row_numbers)),
+        )];
+
+        for (idx, column) in self.columns.iter().enumerate() {
+            let name = column.external_id.as_str();
+            let values = self.rows.iter().map(|r| {
+                (
+                    r.row_number,
+                    r.values.get(idx).unwrap_or(&SequenceRowValue::Null(())),
+                )
+            });
+            let array: ArrayRef = match column.value_type {
+                SequenceValueType::Double => Arc::new(
+                    values
+                        .map(|(row, v)| match v {
+                            SequenceRowValue::Double(d) => Ok(Some(*d)),
+                            // Whole numbers in double columns are deserialized as longs.
+                            SequenceRowValue::Long(l) => Ok(Some(*l as f64)),
+                            SequenceRowValue::Null(_) => Ok(None),
+                            v => Err(cast_error(name, row, "a double", v)),
+                        })
+                        .collect::<Result<Float64Array, _>>()?,
+                ),
+                SequenceValueType::Long => Arc::new(
+                    values
+                        .map(|(row, v)| match v {
+                            SequenceRowValue::Long(l) => Ok(Some(*l)),
+                            SequenceRowValue::Null(_) => Ok(None),
+                            v => Err(cast_error(name, row, "a long", v)),
+                        })
+                        .collect::<Result<Int64Array, _>>()?,
+                ),
+                SequenceValueType::String => Arc::new(
+                    values
+                        .map(|(row, v)| match v {
+                            SequenceRowValue::String(s) => Ok(Some(s.as_str())),
+                            SequenceRowValue::Null(_) => Ok(None),
+                            v => Err(cast_error(name, row, "a string", v)),
+                        })
+                        .collect::<Result<StringArray, _>>()?,
+                ),
+            };
+            columns.push((name.to_owned(), array));
+        }
+        build_batch(columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Array, Float64Array, Int64Array, StringArray};
+    use arrow_schema::DataType;
+
+    use crate::arrow::ToRecordBatch;
+    use crate::sequences::RetrieveSequenceRowsResponse;
+
+    #[test]
+    fn test_sequence_rows_to_record_batch() {
+        let response: RetrieveSequenceRowsResponse = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "columns": [
+                { "externalId": "dbl", "valueType": "DOUBLE" },
+                { "externalId": "lng", "valueType": "LONG" },
+                { "externalId": "str", "valueType": "STRING" }
+            ],
+            "rows": [
+                { "rowNumber": 0, "values": [1.5, 1, "a"] },
+                { "rowNumber": 1, "values": [2, null, "b"] }
+            ]
+        }))
+        .unwrap();
+        let batch = response.to_record_batch().unwrap();
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(&DataType::Float64, batch.column(1).data_type());
+        assert_eq!(&DataType::Int64, batch.column(2).data_type());
+        assert_eq!(&DataType::Utf8, batch.column(3).data_type());
+
+        let dbl = batch.column(1).as_any().downcast_ref::<Float64Array>();
+        assert_eq!(2.0, dbl.unwrap().value(1));
+        let lng = batch.column(2).as_any().downcast_ref::<Int64Array>();
+        assert!(lng.unwrap().is_null(1));
+        let strs = batch.column(3).as_any().downcast_ref::<StringArray>();
+        assert_eq!("b", strs.unwrap().value(1));
+
+        let invalid: RetrieveSequenceRowsResponse = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "columns": [{ "externalId": "lng", "valueType": "LONG" }],
+            "rows": [{ "rowNumber": 0, "values": ["a"] }]
+        }))
+        .unwrap();
+        assert!(invalid.to_record_batch().is_err());
+    }
+}
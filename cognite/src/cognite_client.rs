@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use super::{ApiClient, Error, Result};
 use crate::api::core::sequences::SequencesResource;
+use crate::api::core::units::UnitsResource;
 use crate::api::data_modeling::Models;
 use crate::api::iam::groups::GroupsResource;
 use crate::api::iam::sessions::SessionsResource;
@@ -12,10 +13,16 @@ use crate::auth::AuthenticatorMiddleware;
 use crate::retry::CustomRetryMiddleware;
 use crate::AuthHeaderManager;
 use crate::{
-    assets::AssetsResource, datasets::DataSetsResource, events::EventsResource,
-    extpipes::ExtPipeRunsResource, extpipes::ExtPipesResource, files::Files,
-    labels::LabelsResource, raw::RawResource, relationships::RelationshipsResource,
-    time_series::TimeSeriesResource,
+    assets::AssetsResource,
+    datasets::DataSetsResource,
+    events::EventsResource,
+    extpipes::ExtPipeRunsResource,
+    extpipes::ExtPipesResource,
+    files::Files,
+    labels::LabelsResource,
+    raw::RawResource,
+    relationships::RelationshipsResource,
+    time_series::{DatapointSubscriptionsResource, TimeSeriesResource},
 };
 
 use crate::api::authenticator::{Authenticator, AuthenticatorConfig};
@@ -80,6 +87,8 @@ pub struct CogniteClient {
     pub files: Files,
     /// CDF time series resource.
     pub time_series: TimeSeriesResource,
+    /// CDF datapoint subscriptions resource.
+    pub datapoint_subscriptions: DatapointSubscriptionsResource,
     /// CDF groups resource.
     pub groups: GroupsResource,
     /// CDF raw resource.
@@ -98,6 +107,8 @@ pub struct CogniteClient {
     pub sequences: SequencesResource,
     /// CDF sessions resource.
     pub sessions: SessionsResource,
+    /// CDF unit catalog resource.
+    pub units: UnitsResource,
     /// CDF data modeling resource.
     pub models: Models,
 }
@@ -230,6 +241,7 @@ impl CogniteClient {
             files: Files::new(ac.clone()),
             groups: GroupsResource::new(ac.clone()),
             time_series: TimeSeriesResource::new(ac.clone()),
+            datapoint_subscriptions: DatapointSubscriptionsResource::new(ac.clone()),
             raw: RawResource::new(ac.clone()),
             data_sets: DataSetsResource::new(ac.clone()),
             labels: LabelsResource::new(ac.clone()),
@@ -238,6 +250,7 @@ impl CogniteClient {
             ext_pipe_runs: ExtPipeRunsResource::new(ac.clone()),
             sequences: SequencesResource::new(ac.clone()),
             sessions: SessionsResource::new(ac.clone()),
+            units: UnitsResource::new(ac.clone()),
             models: Models::new(ac),
         })
     }
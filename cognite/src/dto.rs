@@ -3,6 +3,8 @@ pub(crate) mod items;
 pub(crate) mod params;
 #[macro_use]
 pub(crate) mod patch_item;
+#[macro_use]
+pub(crate) mod identity;
 
 pub(crate) mod core;
 pub(crate) mod data_ingestion;
@@ -10,5 +12,4 @@ pub(crate) mod data_modeling;
 pub(crate) mod data_organization;
 pub(crate) mod filter;
 pub(crate) mod iam;
-pub(crate) mod identity;
 pub(crate) mod utils;
@@ -5,3 +5,4 @@ pub(crate) mod event;
 pub(crate) mod files;
 pub(crate) mod sequences;
 pub(crate) mod time_series;
+pub(crate) mod units;
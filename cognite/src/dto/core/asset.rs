@@ -67,6 +67,8 @@ pub struct Asset {
     pub geo_location: Option<GeoLocation>,
 }
 
+impl_with_identity!(Asset);
+
 impl Asset {
     /// Create an asset
     ///
@@ -1,18 +1,35 @@
+mod compression;
 mod filter;
+mod granularity;
+mod line_protocol;
+mod local_aggregates;
 #[allow(clippy::all)]
 #[allow(missing_docs)]
 #[path = "datapoint/generated/com.cognite.v1.timeseries.proto.rs"]
 mod proto;
+mod proto_view;
+mod relative_time;
 mod status_code;
+mod time_zone;
+mod timestamp_format;
 
+use std::collections::HashMap;
 use std::convert::TryFrom;
 
+pub use self::compression::*;
 pub use self::filter::*;
+pub use self::granularity::*;
+pub use self::line_protocol::*;
+pub use self::local_aggregates::*;
 pub use self::proto::data_point_insertion_item::DatapointType as InsertDatapointType;
 pub use self::proto::data_point_insertion_item::TimeSeriesReference;
 pub use self::proto::data_point_list_item::DatapointType as ListDatapointType;
 pub use self::proto::*;
+pub use self::proto_view::*;
+pub use self::relative_time::*;
 pub use self::status_code::*;
+pub use self::time_zone::*;
+pub use self::timestamp_format::*;
 
 use serde::{de::Error, Deserialize, Serialize};
 use serde_json::Value;
@@ -104,6 +121,12 @@ impl StatusCode {
 
 impl From<Status> for StatusCode {
     fn from(value: Status) -> Self {
+        StatusCode::from(&value)
+    }
+}
+
+impl From<&Status> for StatusCode {
+    fn from(value: &Status) -> Self {
         if value.code != 0 {
             StatusCode::try_from(value.code).unwrap_or(StatusCode::Invalid)
         } else if !value.symbol.is_empty() {
@@ -347,6 +370,8 @@ pub struct DatapointsResponse {
     /// nextCursor will be omitted when the next aggregate datapoint
     /// is after the end of the interval. Increase start/end to fetch more data.
     pub next_cursor: Option<String>,
+    /// The time zone aggregates were aligned to, if one was given in the query.
+    pub time_zone: Option<TimeZone>,
 }
 
 #[derive(Debug)]
@@ -383,6 +408,8 @@ pub struct LatestDatapointsResponse {
     pub id: i64,
     /// Time series external ID.
     pub external_id: Option<String>,
+    /// Time series instance ID, if the time series is a data modeling instance.
+    pub instance_id: Option<crate::models::instances::InstanceId>,
     /// Retrieved datapoints.
     pub datapoint: Option<LatestDatapoint>,
     /// The physical unit of the time series (free-text field).
@@ -401,11 +428,22 @@ pub struct LatestDatapointsResponse {
     pub next_cursor: Option<String>,
 }
 
+#[derive(Debug, Default)]
+/// Result of retrieving latest datapoints for a large number of time series.
+pub struct LatestDatapointsBulkResponse {
+    /// Latest datapoints, keyed by the identity used in the query.
+    pub items: HashMap<IdentityOrInstance, LatestDatapointsResponse>,
+    /// Time series that were queried, but do not exist.
+    /// Only populated if unknown IDs were ignored.
+    pub missing: Vec<IdentityOrInstance>,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DatapointsResponsePartial {
     id: i64,
     external_id: Option<String>,
+    instance_id: Option<crate::models::instances::InstanceId>,
     datapoints: Value,
     unit: Option<String>,
     unit_external_id: Option<String>,
@@ -455,6 +493,7 @@ impl<'de> Deserialize<'de> for LatestDatapointsResponse {
         Ok(Self {
             id: r.id,
             external_id: r.external_id,
+            instance_id: r.instance_id,
             datapoint: dps,
             unit: r.unit,
             unit_external_id: r.unit_external_id,
@@ -632,6 +671,7 @@ impl From<DataPointListItem> for DatapointsResponse {
             } else {
                 Some(req.next_cursor)
             },
+            time_zone: None,
         }
     }
 }
@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{AddDatapoints, DatapointDouble, DatapointString, DatapointsEnumType, StatusCode};
+use crate::IdentityOrInstance;
+
+/// Deadband around the last stored value of a numeric time series.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deadband {
+    /// Drop datapoints that differ from the last stored value by at most this amount.
+    Absolute(f64),
+    /// Drop datapoints that differ from the last stored value by at most this fraction
+    /// of the absolute value of the last stored value. `0.01` is a 1% deadband.
+    Relative(f64),
+}
+
+impl Deadband {
+    fn contains(&self, stored: f64, value: f64) -> bool {
+        let diff = (value - stored).abs();
+        match self {
+            Deadband::Absolute(band) => diff <= *band,
+            Deadband::Relative(fraction) => diff <= fraction * stored.abs(),
+        }
+    }
+}
+
+/// Compression settings for a single time series.
+///
+/// Datapoints are always kept if their status code differs from the previous datapoint,
+/// if they are null, or if they follow a null datapoint. If a datapoint is kept because
+/// of a status change, the last datapoint before the change is kept as well.
+///
+/// Numeric datapoints are only dropped if `deadband` or `swinging_door` is set. If both
+/// are set, datapoints within the deadband are dropped before swinging door is applied.
+/// String datapoints are kept when their value changes, or on the `max_interval` heartbeat.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompressionConfig {
+    /// Drop numeric datapoints within this deadband of the last stored value.
+    pub deadband: Option<Deadband>,
+    /// Compression deviation for swinging door trending. Numeric datapoints are dropped
+    /// if linear interpolation between the stored datapoints deviates from them by at most
+    /// this amount.
+    ///
+    /// The last received datapoint is held back until it is known whether it is significant,
+    /// use [`SeriesCompressor::flush`] to release it.
+    pub swinging_door: Option<f64>,
+    /// Always keep a datapoint if this long has passed since the last stored datapoint.
+    pub max_interval: Option<Duration>,
+}
+
+impl CompressionConfig {
+    fn is_enabled(&self) -> bool {
+        self.deadband.is_some() || self.swinging_door.is_some() || self.max_interval.is_some()
+    }
+
+    fn filters_numeric(&self) -> bool {
+        self.deadband.is_some() || self.swinging_door.is_some()
+    }
+
+    fn heartbeat(&self, last: i64, timestamp: i64) -> bool {
+        self.max_interval
+            .is_some_and(|m| timestamp.saturating_sub(last) >= m.as_millis() as i64)
+    }
+}
+
+fn status(status: Option<StatusCode>) -> StatusCode {
+    status.unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Default)]
+struct NumericState {
+    stored: Option<DatapointDouble>,
+    held: Option<DatapointDouble>,
+    // Upper and lower slope of the swinging door, from the last stored datapoint.
+    door: Option<(f64, f64)>,
+}
+
+impl NumericState {
+    fn store(&mut self, dp: DatapointDouble, out: &mut Vec<DatapointDouble>) {
+        self.held = None;
+        self.door = None;
+        self.stored = Some(dp.clone());
+        out.push(dp);
+    }
+
+    fn release_held(&mut self, out: &mut Vec<DatapointDouble>) {
+        if let Some(held) = self.held.take() {
+            self.store(held, out);
+        }
+    }
+
+    fn push(
+        &mut self,
+        config: &CompressionConfig,
+        dp: DatapointDouble,
+        out: &mut Vec<DatapointDouble>,
+    ) {
+        let Some(stored) = &self.stored else {
+            self.store(dp, out);
+            return;
+        };
+        // Datapoints out of order are passed through, without affecting compression.
+        let last_timestamp = self.held.as_ref().unwrap_or(stored).timestamp;
+        if dp.timestamp <= last_timestamp {
+            out.push(dp);
+            return;
+        }
+        let previous = self.held.as_ref().unwrap_or(stored);
+        let (Some(stored_value), Some(value)) = (stored.value, dp.value) else {
+            self.release_held(out);
+            self.store(dp, out);
+            return;
+        };
+        if status(previous.status) != status(dp.status)
+            || config.heartbeat(stored.timestamp, dp.timestamp)
+        {
+            self.release_held(out);
+            self.store(dp, out);
+            return;
+        }
+        if config
+            .deadband
+            .is_some_and(|d| d.contains(stored_value, value))
+        {
+            self.held = Some(dp);
+            return;
+        }
+        let Some(deviation) = config.swinging_door else {
+            self.store(dp, out);
+            return;
+        };
+
+        let slopes = |from: &DatapointDouble, from_value: f64| {
+            let dt = (dp.timestamp - from.timestamp) as f64;
+            (
+                (value + deviation - from_value) / dt,
+                (value - deviation - from_value) / dt,
+            )
+        };
+        let (upper, lower) = slopes(stored, stored_value);
+        let (upper, lower) = match self.door {
+            Some((u, l)) => (u.min(upper), l.max(lower)),
+            None => (upper, lower),
+        };
+        if lower > upper {
+            // The door is open, the previous datapoint can no longer be interpolated.
+            if let Some(held) = self.held.take() {
+                let held_value = held.value.unwrap_or(value);
+                let door = slopes(&held, held_value);
+                self.store(held, out);
+                self.door = Some(door);
+                self.held = Some(dp);
+                return;
+            }
+        }
+        self.door = Some((upper, lower));
+        self.held = Some(dp);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct StringState {
+    stored: Option<DatapointString>,
+}
+
+impl StringState {
+    fn push(
+        &mut self,
+        config: &CompressionConfig,
+        dp: DatapointString,
+        out: &mut Vec<DatapointString>,
+    ) {
+        if let Some(stored) = &self.stored {
+            if dp.timestamp <= stored.timestamp {
+                out.push(dp);
+                return;
+            }
+            if dp.value.is_some()
+                && dp.value == stored.value
+                && status(dp.status) == status(stored.status)
+                && !config.heartbeat(stored.timestamp, dp.timestamp)
+            {
+                return;
+            }
+        }
+        self.stored = Some(dp.clone());
+        out.push(dp);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+enum SeriesState {
+    #[default]
+    Empty,
+    Numeric(NumericState),
+    String(StringState),
+}
+
+/// Stateful compression of the datapoints of a single time series.
+///
+/// The compressor remembers the last stored datapoint between calls, so that datapoints
+/// can be compressed as they arrive.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut compressor = SeriesCompressor::new(CompressionConfig {
+///     deadband: Some(Deadband::Absolute(0.5)),
+///     max_interval: Some(Duration::from_secs(600)),
+///     ..Default::default()
+/// });
+/// let datapoints = compressor.compress(datapoints);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SeriesCompressor {
+    config: CompressionConfig,
+    state: SeriesState,
+}
+
+impl SeriesCompressor {
+    /// Create a new compressor.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Compression settings.
+    pub fn new(config: CompressionConfig) -> Self {
+        Self {
+            config,
+            state: SeriesState::Empty,
+        }
+    }
+
+    /// Get the compression settings of this compressor.
+    pub fn config(&self) -> &CompressionConfig {
+        &self.config
+    }
+
+    /// Compress numeric datapoints, returning the datapoints that should be stored.
+    /// Datapoints must be sorted by timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `datapoints` - Datapoints to compress.
+    pub fn compress_numeric(&mut self, datapoints: Vec<DatapointDouble>) -> Vec<DatapointDouble> {
+        if !self.config.filters_numeric() {
+            return datapoints;
+        }
+        if !matches!(self.state, SeriesState::Numeric(_)) {
+            self.state = SeriesState::Numeric(NumericState::default());
+        }
+        let SeriesState::Numeric(state) = &mut self.state else {
+            unreachable!();
+        };
+        let mut out = Vec::with_capacity(datapoints.len());
+        for dp in datapoints {
+            state.push(&self.config, dp, &mut out);
+        }
+        out
+    }
+
+    /// Compress string datapoints, returning the datapoints that should be stored.
+    /// Datapoints must be sorted by timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `datapoints` - Datapoints to compress.
+    pub fn compress_string(&mut self, datapoints: Vec<DatapointString>) -> Vec<DatapointString> {
+        if !self.config.is_enabled() {
+            return datapoints;
+        }
+        if !matches!(self.state, SeriesState::String(_)) {
+            self.state = SeriesState::String(StringState::default());
+        }
+        let SeriesState::String(state) = &mut self.state else {
+            unreachable!();
+        };
+        let mut out = Vec::with_capacity(datapoints.len());
+        for dp in datapoints {
+            state.push(&self.config, dp, &mut out);
+        }
+        out
+    }
+
+    /// Compress datapoints of either type. Aggregates are returned unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `datapoints` - Datapoints to compress.
+    pub fn compress(&mut self, datapoints: impl Into<DatapointsEnumType>) -> DatapointsEnumType {
+        match datapoints.into() {
+            DatapointsEnumType::NumericDatapoints(d) => self.compress_numeric(d).into(),
+            DatapointsEnumType::StringDatapoints(d) => self.compress_string(d).into(),
+            d => d,
+        }
+    }
+
+    /// Release any datapoint held back by swinging door compression. Call this when
+    /// no more datapoints are expected, or before a datapoint must be stored.
+    pub fn flush(&mut self) -> Option<DatapointDouble> {
+        match &mut self.state {
+            SeriesState::Numeric(state) => {
+                let mut out = Vec::new();
+                state.release_held(&mut out);
+                out.pop()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Compression of datapoints for many time series, with per-series settings.
+///
+/// This can be used on its own, by calling [`compress`](Self::compress) on each
+/// [`AddDatapoints`] before inserting it, or be given to a `DatapointsUploadQueue`.
+#[derive(Debug, Clone, Default)]
+pub struct DatapointsCompressor {
+    default: Option<CompressionConfig>,
+    configs: HashMap<IdentityOrInstance, CompressionConfig>,
+    series: HashMap<IdentityOrInstance, SeriesCompressor>,
+}
+
+impl DatapointsCompressor {
+    /// Create a new compressor.
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - Compression settings for time series without their own settings.
+    ///   If this is `None`, only time series with their own settings are compressed.
+    pub fn new(default: Option<CompressionConfig>) -> Self {
+        Self {
+            default,
+            ..Default::default()
+        }
+    }
+
+    /// Set compression settings for a single time series. This resets any compression
+    /// state for the time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series to configure.
+    /// * `config` - Compression settings for the time series.
+    pub fn with_series(
+        mut self,
+        id: impl Into<IdentityOrInstance>,
+        config: CompressionConfig,
+    ) -> Self {
+        self.set_series(id, config);
+        self
+    }
+
+    /// Set compression settings for a single time series. This resets any compression
+    /// state for the time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series to configure.
+    /// * `config` - Compression settings for the time series.
+    pub fn set_series(&mut self, id: impl Into<IdentityOrInstance>, config: CompressionConfig) {
+        let id = id.into();
+        self.series.remove(&id);
+        self.configs.insert(id, config);
+    }
+
+    /// Compress datapoints for a single time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Time series the datapoints belong to.
+    /// * `datapoints` - Datapoints to compress.
+    pub fn compress_series(
+        &mut self,
+        id: &IdentityOrInstance,
+        datapoints: impl Into<DatapointsEnumType>,
+    ) -> DatapointsEnumType {
+        let datapoints = datapoints.into();
+        let compressor = match self.series.get_mut(id) {
+            Some(c) => c,
+            None => {
+                let Some(config) = self.configs.get(id).or(self.default.as_ref()) else {
+                    return datapoints;
+                };
+                self.series
+                    .entry(id.clone())
+                    .or_insert_with(|| SeriesCompressor::new(config.clone()))
+            }
+        };
+        compressor.compress(datapoints)
+    }
+
+    /// Compress a batch of datapoints.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Datapoints to compress.
+    pub fn compress(&mut self, item: AddDatapoints) -> AddDatapoints {
+        let datapoints = self.compress_series(&item.id, item.datapoints);
+        AddDatapoints {
+            id: item.id,
+            datapoints,
+        }
+    }
+
+    /// Release all datapoints held back by swinging door compression.
+    pub fn flush(&mut self) -> Vec<AddDatapoints> {
+        self.series
+            .iter_mut()
+            .filter_map(|(id, c)| {
+                c.flush().map(|dp| AddDatapoints {
+                    id: id.clone(),
+                    datapoints: vec![dp].into(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CompressionConfig, Deadband, SeriesCompressor};
+    use crate::time_series::{DatapointDouble, DatapointString, StatusCode};
+
+    fn dp(timestamp: i64, value: f64) -> DatapointDouble {
+        DatapointDouble {
+            timestamp,
+            value: Some(value),
+            status: None,
+        }
+    }
+
+    fn timestamps(dps: &[DatapointDouble]) -> Vec<i64> {
+        dps.iter().map(|d| d.timestamp).collect()
+    }
+
+    #[test]
+    fn test_deadband_compression() {
+        let mut compressor = SeriesCompressor::new(CompressionConfig {
+            deadband: Some(Deadband::Absolute(1.0)),
+            max_interval: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let mut bad = dp(4, 1.0);
+        bad.status = Some(StatusCode::Bad);
+        let out = compressor.compress_numeric(vec![
+            dp(0, 0.0),
+            dp(1, 0.5),
+            dp(2, 1.5),
+            dp(3, 1.0),
+            bad,
+            dp(5, 1.0),
+            dp(105, 1.0),
+        ]);
+        // 3 is kept since it is the last datapoint before the status change at 4,
+        // 5 after the status change, 105 on the heartbeat.
+        assert_eq!(vec![0, 2, 3, 4, 5, 105], timestamps(&out));
+
+        // State is kept between calls.
+        let out = compressor.compress_numeric(vec![dp(106, 1.5), dp(107, 3.0)]);
+        assert_eq!(vec![107], timestamps(&out));
+
+        let mut relative = SeriesCompressor::new(CompressionConfig {
+            deadband: Some(Deadband::Relative(0.1)),
+            ..Default::default()
+        });
+        let out = relative.compress_numeric(vec![dp(0, 100.0), dp(1, 109.0), dp(2, 111.0)]);
+        assert_eq!(vec![0, 2], timestamps(&out));
+    }
+
+    #[test]
+    fn test_swinging_door_compression() {
+        let mut compressor = SeriesCompressor::new(CompressionConfig {
+            swinging_door: Some(0.5),
+            ..Default::default()
+        });
+        // A straight line, followed by a jump at 5.
+        let out = compressor.compress_numeric(vec![
+            dp(0, 0.0),
+            dp(1, 1.0),
+            dp(2, 2.0),
+            dp(3, 3.0),
+            dp(4, 4.0),
+            dp(5, 10.0),
+            dp(6, 10.0),
+        ]);
+        assert_eq!(vec![0, 4, 5], timestamps(&out));
+        // The last datapoint is held back until flushed.
+        assert_eq!(Some(6), compressor.flush().map(|d| d.timestamp));
+        assert!(compressor.flush().is_none());
+
+        let mut null = dp(8, 0.0);
+        null.value = None;
+        let out = compressor.compress_numeric(vec![dp(7, 4.0), null, dp(9, 4.0)]);
+        assert_eq!(vec![7, 8, 9], timestamps(&out));
+    }
+
+    #[test]
+    fn test_string_compression() {
+        let mut compressor = SeriesCompressor::new(CompressionConfig {
+            max_interval: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        let s = |timestamp: i64, value: &str| DatapointString {
+            timestamp,
+            value: Some(value.to_owned()),
+            status: None,
+        };
+        let out = compressor.compress_string(vec![s(0, "a"), s(1, "a"), s(2, "b"), s(12, "b")]);
+        let out: Vec<_> = out.iter().map(|d| d.timestamp).collect();
+        assert_eq!(vec![0, 2, 12], out);
+    }
+}
@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
-use crate::{Identity, IdentityOrInstance};
+use crate::IdentityOrInstance;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use super::{Granularity, TimeZone};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 /// Datapoint aggregates. See [aggregates](https://developer.cognite.com/dev/concepts/aggregation/)
 /// for more details.
@@ -62,6 +64,8 @@ pub enum TimestampOrRelative {
     /// The format is 'now' or `N[timeunit]-ago` where timeunit is `w,d,h,m,s`.
     /// Example: `2d-ago` gets data that is up to two days old.
     /// You can also specify time in milliseconds since epoch.
+    ///
+    /// Use [`RelativeTime`](super::RelativeTime) to build or parse relative timestamps.
     Relative(String),
 }
 
@@ -101,14 +105,17 @@ pub struct DatapointsFilter {
     /// Specify the aggregates to return.
     pub aggregates: Option<Vec<Aggregate>>,
     /// The time granularity size and unit to aggregate over.
-    /// Valid entries are 'day, hour, minute, second', or short forms 'd, h, m, s',
-    /// or a multiple of these indicated by a number as a prefix. For 'second' and 'minute',
-    /// the multiple must be an integer between 1 and 120 inclusive; for 'hour'
-    /// and 'day', the multiple must be an integer between 1 and 100000 inclusive.
+    /// For 'second' and 'minute', the multiple must be an integer between 1 and 120 inclusive;
+    /// for other units, the multiple must be an integer between 1 and 100000 inclusive.
     ///
     /// For example, a granularity '5m' means that aggregates are calculated over 5 minutes.
+    /// Parse a [`Granularity`] from a string like `"5m"`, which validates it.
     /// This field is required if aggregates are specified.
-    pub granularity: Option<String>,
+    pub granularity: Option<Granularity>,
+    /// Time zone used to align aggregates with a granularity of an hour or longer.
+    /// Accepts `UTC`, fixed offsets like `+02:00`, and IANA time zones like `Europe/Oslo`.
+    /// See [`TimeZone`] for details.
+    pub time_zone: Option<TimeZone>,
     /// Whether to include the last data points before the requsted time period and the first
     /// one after.
     pub include_outside_points: Option<bool>,
@@ -135,14 +142,17 @@ pub struct DatapointsQuery {
     /// Specify the aggregates to return.
     pub aggregates: Option<Vec<String>>,
     /// The time granularity size and unit to aggregate over.
-    /// Valid entries are 'day, hour, minute, second', or short forms 'd, h, m, s',
-    /// or a multiple of these indicated by a number as a prefix. For 'second' and 'minute',
-    /// the multiple must be an integer between 1 and 120 inclusive; for 'hour'
-    /// and 'day', the multiple must be an integer between 1 and 100000 inclusive.
+    /// For 'second' and 'minute', the multiple must be an integer between 1 and 120 inclusive;
+    /// for other units, the multiple must be an integer between 1 and 100000 inclusive.
     ///
     /// For example, a granularity '5m' means that aggregates are calculated over 5 minutes.
+    /// Parse a [`Granularity`] from a string like `"5m"`, which validates it.
     /// This field is required if aggregates are specified.
-    pub granularity: Option<String>,
+    pub granularity: Option<Granularity>,
+    /// Time zone used to align aggregates with a granularity of an hour or longer.
+    /// Accepts `UTC`, fixed offsets like `+02:00`, and IANA time zones like `Europe/Oslo`.
+    /// See [`TimeZone`] for details.
+    pub time_zone: Option<TimeZone>,
     /// Whether to include the last data points before the requsted time period and the first
     /// one after.
     pub include_outside_points: Option<bool>,
@@ -212,8 +222,8 @@ pub struct LatestDatapointsQuery {
     /// Default `true`
     pub treat_uncertain_as_bad: Option<bool>,
     #[serde(flatten)]
-    /// ID or external ID of time series to retrieve data from.
-    pub id: Identity,
+    /// ID, external ID, or instance ID of time series to retrieve data from.
+    pub id: IdentityOrInstance,
 }
 
 impl LatestDatapointsQuery {
@@ -223,9 +233,12 @@ impl LatestDatapointsQuery {
     ///
     /// * `id` - Time series ID.
     /// * `before` - Get data points before this time.
-    pub fn new(id: Identity, before: impl Into<String>) -> LatestDatapointsQuery {
+    pub fn new(
+        id: impl Into<IdentityOrInstance>,
+        before: impl Into<String>,
+    ) -> LatestDatapointsQuery {
         LatestDatapointsQuery {
-            id,
+            id: id.into(),
             before: Some(before.into()),
             target_unit: Default::default(),
             target_unit_system: Default::default(),
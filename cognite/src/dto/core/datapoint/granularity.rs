@@ -0,0 +1,430 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// Unit of a datapoints aggregate granularity.
+pub enum GranularityUnit {
+    /// Seconds, `s` or `second`.
+    Second,
+    /// Minutes, `m`, `t` or `minute`.
+    Minute,
+    /// Hours, `h` or `hour`.
+    Hour,
+    /// Days, `d` or `day`.
+    Day,
+    /// Weeks, `w` or `week`.
+    Week,
+    /// Calendar months, `mo` or `month`.
+    Month,
+    /// Calendar quarters, `q` or `quarter`.
+    Quarter,
+    /// Calendar years, `y` or `year`.
+    Year,
+}
+
+impl GranularityUnit {
+    /// Short form of the unit, as used when serializing a granularity.
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Self::Second => "s",
+            Self::Minute => "m",
+            Self::Hour => "h",
+            Self::Day => "d",
+            Self::Week => "w",
+            Self::Month => "mo",
+            Self::Quarter => "q",
+            Self::Year => "y",
+        }
+    }
+
+    /// Largest multiple of this unit accepted by the API.
+    pub fn max_multiple(&self) -> u32 {
+        match self {
+            Self::Second | Self::Minute => 120,
+            _ => 100_000,
+        }
+    }
+
+    /// Whether this unit is a calendar unit, with a length that varies
+    /// depending on where in the calendar it is.
+    pub fn is_calendar(&self) -> bool {
+        matches!(self, Self::Month | Self::Quarter | Self::Year)
+    }
+
+    /// The length of this unit in milliseconds, or `None` if it is a calendar unit.
+    pub fn millis(&self) -> Option<i64> {
+        match self {
+            Self::Second => Some(1_000),
+            Self::Minute => Some(60_000),
+            Self::Hour => Some(3_600_000),
+            Self::Day => Some(86_400_000),
+            Self::Week => Some(7 * 86_400_000),
+            _ => None,
+        }
+    }
+
+    /// Round `timestamp` down to the start of this unit in UTC. Weeks start on Monday.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp in milliseconds since epoch.
+    pub fn truncate(&self, timestamp: i64) -> i64 {
+        let days = timestamp.div_euclid(MILLIS_PER_DAY);
+        let day_start = days * MILLIS_PER_DAY;
+        match self {
+            Self::Second | Self::Minute | Self::Hour | Self::Day => {
+                let len = self.millis().unwrap_or(MILLIS_PER_DAY);
+                timestamp.div_euclid(len) * len
+            }
+            // 1970-01-01 was a thursday, so weeks start 3 days before a multiple of 7.
+            Self::Week => day_start - (days + 3).rem_euclid(7) * MILLIS_PER_DAY,
+            Self::Month | Self::Quarter | Self::Year => {
+                let (year, month, _) = civil_from_days(days);
+                let month = match self {
+                    Self::Month => month,
+                    Self::Quarter => month - (month - 1) % 3,
+                    _ => 1,
+                };
+                days_from_civil(year, month, 1) * MILLIS_PER_DAY
+            }
+        }
+    }
+}
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Convert days since epoch to a (year, month, day) date in the proleptic gregorian calendar.
+pub(super) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) date in the proleptic gregorian calendar to days since epoch.
+pub(super) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub(super) fn days_in_month(year: i64, month: i64) -> i64 {
+    let next = if month == 12 {
+        days_from_civil(year + 1, 1, 1)
+    } else {
+        days_from_civil(year, month + 1, 1)
+    };
+    next - days_from_civil(year, month, 1)
+}
+
+impl FromStr for GranularityUnit {
+    type Err = ParseGranularityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "s" | "sec" | "second" | "seconds" => Self::Second,
+            "m" | "t" | "min" | "minute" | "minutes" => Self::Minute,
+            "h" | "hour" | "hours" => Self::Hour,
+            "d" | "day" | "days" => Self::Day,
+            "w" | "week" | "weeks" => Self::Week,
+            "mo" | "month" | "months" => Self::Month,
+            "q" | "quarter" | "quarters" => Self::Quarter,
+            "y" | "year" | "years" => Self::Year,
+            r => return Err(ParseGranularityError::UnknownUnit(r.to_owned())),
+        })
+    }
+}
+
+impl Display for GranularityUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.short_name())
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+/// Error on failure to parse or validate a granularity.
+pub enum ParseGranularityError {
+    /// Granularity is empty.
+    #[error("Granularity is empty")]
+    Empty,
+    /// Unknown granularity unit.
+    #[error("Unknown granularity unit '{0}'")]
+    UnknownUnit(String),
+    /// Multiple is not a valid number.
+    #[error("Invalid granularity multiple '{0}'")]
+    InvalidMultiple(String),
+    /// Multiple is outside of the range allowed for the unit.
+    #[error("Granularity multiple {multiple} is out of range for unit '{unit}', must be between 1 and {}", unit.max_multiple())]
+    OutOfRange {
+        /// Given multiple.
+        multiple: u32,
+        /// Granularity unit.
+        unit: GranularityUnit,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The granularity of datapoint aggregates, a multiple of a time unit, like `5m` or `1mo`.
+///
+/// Parse a granularity from a string, or create one with [`Granularity::new`],
+/// which validates the multiple against the limits in the API:
+/// seconds and minutes allow multiples from 1 to 120, the other units from 1 to 100 000.
+///
+/// # Example
+///
+/// ```
+/// use cognite::time_series::{Granularity, GranularityUnit};
+///
+/// let g: Granularity = "15minute".parse().unwrap();
+/// assert_eq!(g, Granularity::new(15, GranularityUnit::Minute).unwrap());
+/// assert_eq!("15m", g.to_string());
+/// assert!("121m".parse::<Granularity>().is_err());
+/// ```
+pub struct Granularity {
+    multiple: u32,
+    unit: GranularityUnit,
+}
+
+impl Granularity {
+    /// Create a new granularity, validating the multiple.
+    ///
+    /// # Arguments
+    ///
+    /// * `multiple` - Number of `unit` in each aggregate period.
+    /// * `unit` - Granularity unit.
+    pub fn new(multiple: u32, unit: GranularityUnit) -> Result<Self, ParseGranularityError> {
+        if multiple == 0 || multiple > unit.max_multiple() {
+            return Err(ParseGranularityError::OutOfRange { multiple, unit });
+        }
+        Ok(Self { multiple, unit })
+    }
+
+    /// Multiple of the unit.
+    pub fn multiple(&self) -> u32 {
+        self.multiple
+    }
+
+    /// Granularity unit.
+    pub fn unit(&self) -> GranularityUnit {
+        self.unit
+    }
+
+    /// The length of each aggregate period in milliseconds,
+    /// or `None` if the granularity uses a calendar unit.
+    pub fn millis(&self) -> Option<i64> {
+        self.unit.millis().map(|m| m * self.multiple as i64)
+    }
+
+    /// Start of the first aggregate period for a query starting at `start`.
+    /// Like the API, this rounds down to a whole granularity unit in UTC, not to a multiple of
+    /// the granularity, so `2h` aggregates starting at 01:30 are aligned to 01:00, 03:00, and so on.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start of the query in milliseconds since epoch.
+    pub fn align(&self, start: i64) -> i64 {
+        self.unit.truncate(start)
+    }
+
+    /// Start of the aggregate period following the one starting at `timestamp`.
+    /// Calendar units keep the day of month, clamped to the length of the month.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Start of an aggregate period in milliseconds since epoch.
+    pub fn next_period(&self, timestamp: i64) -> i64 {
+        self.add_periods(timestamp, 1)
+    }
+
+    /// Add `periods` aggregate periods to `timestamp`. Calendar units keep the day of month
+    /// of `timestamp`, clamped to the length of the resulting month, so adding periods to
+    /// the same start does not drift the way repeated calls to `next_period` do.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp in milliseconds since epoch.
+    /// * `periods` - Number of periods to add, may be negative.
+    pub fn add_periods(&self, timestamp: i64, periods: i64) -> i64 {
+        if let Some(len) = self.millis() {
+            return timestamp.saturating_add(len.saturating_mul(periods));
+        }
+        let months = i64::from(self.multiple)
+            * periods
+            * match self.unit {
+                GranularityUnit::Quarter => 3,
+                GranularityUnit::Year => 12,
+                _ => 1,
+            };
+        let days = timestamp.div_euclid(MILLIS_PER_DAY);
+        let time_of_day = timestamp.rem_euclid(MILLIS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let total = year * 12 + month - 1 + months;
+        let (year, month) = (total.div_euclid(12), total.rem_euclid(12) + 1);
+        let day = day.min(days_in_month(year, month));
+        days_from_civil(year, month, day)
+            .saturating_mul(MILLIS_PER_DAY)
+            .saturating_add(time_of_day)
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = ParseGranularityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseGranularityError::Empty);
+        }
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (multiple, unit) = s.split_at(split);
+        let unit: GranularityUnit = unit.parse()?;
+        let multiple = if multiple.is_empty() {
+            1
+        } else {
+            multiple
+                .parse()
+                .map_err(|_| ParseGranularityError::InvalidMultiple(multiple.to_owned()))?
+        };
+        Self::new(multiple, unit)
+    }
+}
+
+impl TryFrom<&str> for Granularity {
+    type Error = ParseGranularityError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for Granularity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.multiple, self.unit)
+    }
+}
+
+impl Serialize for Granularity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Granularity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Granularity> for chrono::Duration {
+    type Error = Granularity;
+
+    fn try_from(value: Granularity) -> Result<Self, Self::Error> {
+        value
+            .millis()
+            .map(chrono::Duration::milliseconds)
+            .ok_or(value)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Granularity> for time::Duration {
+    type Error = Granularity;
+
+    fn try_from(value: Granularity) -> Result<Self, Self::Error> {
+        value
+            .millis()
+            .map(time::Duration::milliseconds)
+            .ok_or(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Granularity, GranularityUnit, ParseGranularityError};
+
+    #[test]
+    fn test_parse_granularity() {
+        for (input, multiple, unit) in [
+            ("1s", 1, GranularityUnit::Second),
+            ("120m", 120, GranularityUnit::Minute),
+            ("minute", 1, GranularityUnit::Minute),
+            ("2hour", 2, GranularityUnit::Hour),
+            ("100000d", 100_000, GranularityUnit::Day),
+            ("3mo", 3, GranularityUnit::Month),
+            ("1q", 1, GranularityUnit::Quarter),
+            ("y", 1, GranularityUnit::Year),
+        ] {
+            let g: Granularity = input.parse().unwrap();
+            assert_eq!(multiple, g.multiple());
+            assert_eq!(unit, g.unit());
+            assert_eq!(g, g.to_string().parse().unwrap());
+        }
+
+        assert_eq!(
+            Err(ParseGranularityError::OutOfRange {
+                multiple: 121,
+                unit: GranularityUnit::Minute
+            }),
+            "121m".parse::<Granularity>()
+        );
+        assert!("0h".parse::<Granularity>().is_err());
+        assert!("5mon".parse::<Granularity>().is_err());
+        assert!("".parse::<Granularity>().is_err());
+        assert!("100001d".parse::<Granularity>().is_err());
+        assert_eq!(Some(300_000), "5m".parse::<Granularity>().unwrap().millis());
+        assert_eq!(None, "5mo".parse::<Granularity>().unwrap().millis());
+    }
+
+    #[test]
+    fn test_granularity_alignment() {
+        // 2023-05-17T13:45:30.500Z, a wednesday.
+        let ts = 1_684_331_130_500;
+        let hour = 3_600_000;
+        let day = 24 * hour;
+        for (granularity, aligned, next) in [
+            ("2s", 1_684_331_130_000, 1_684_331_132_000),
+            ("2h", 1_684_328_400_000, 1_684_328_400_000 + 2 * hour),
+            ("1d", 1_684_281_600_000, 1_684_281_600_000 + day),
+            // Monday 2023-05-15.
+            ("1w", 1_684_108_800_000, 1_684_108_800_000 + 7 * day),
+            // 2023-05-01 to 2023-06-01.
+            ("1mo", 1_682_899_200_000, 1_685_577_600_000),
+            // 2023-04-01 to 2023-10-01.
+            ("2q", 1_680_307_200_000, 1_696_118_400_000),
+            // 2023-01-01 to 2024-01-01.
+            ("1y", 1_672_531_200_000, 1_704_067_200_000),
+        ] {
+            let g: Granularity = granularity.parse().unwrap();
+            assert_eq!(aligned, g.align(ts), "{granularity}");
+            assert_eq!(next, g.next_period(aligned), "{granularity}");
+        }
+
+        // Before epoch, 1969-12-31T12:00Z aligns to monday 1969-12-29.
+        let g: Granularity = "1w".parse().unwrap();
+        assert_eq!(-3 * day, g.align(-12 * hour));
+        let g: Granularity = "1mo".parse().unwrap();
+        assert_eq!(-31 * day, g.align(-12 * hour));
+        // 2024-01-31 + 1mo is clamped to 2024-02-29.
+        assert_eq!(1_709_164_800_000, g.next_period(1_706_659_200_000));
+    }
+}
@@ -0,0 +1,717 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Debug, Write};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{AddDatapoints, DatapointDouble, DatapointString, DatapointsEnumType};
+use crate::models::instances::InstanceId;
+use crate::{Identity, IdentityOrInstance};
+
+/// Field name used for OpenTSDB datapoints, which only have a single value.
+const OPENTSDB_FIELD: &str = "value";
+/// OpenTSDB timestamps larger than this are in milliseconds, smaller are in seconds.
+const OPENTSDB_MAX_SECONDS: i64 = 9_999_999_999;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Invalid datapoint on line {line}: {message}")]
+/// Error on failure to parse an InfluxDB line protocol or OpenTSDB payload.
+pub struct ParseLineProtocolError {
+    /// 1-based line number in line protocol payloads, or 1-based index of the
+    /// datapoint in OpenTSDB JSON payloads.
+    pub line: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl ParseLineProtocolError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Precision of timestamps in InfluxDB line protocol.
+pub enum TimePrecision {
+    /// Nanoseconds since epoch, the InfluxDB default.
+    #[default]
+    Nanoseconds,
+    /// Microseconds since epoch.
+    Microseconds,
+    /// Milliseconds since epoch.
+    Milliseconds,
+    /// Seconds since epoch.
+    Seconds,
+}
+
+impl TimePrecision {
+    /// Convert a timestamp in this precision to milliseconds since epoch,
+    /// rounding down. Returns `None` on overflow.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp in this precision.
+    pub fn to_millis(&self, timestamp: i64) -> Option<i64> {
+        match self {
+            Self::Nanoseconds => Some(timestamp.div_euclid(1_000_000)),
+            Self::Microseconds => Some(timestamp.div_euclid(1_000)),
+            Self::Milliseconds => Some(timestamp),
+            Self::Seconds => timestamp.checked_mul(1000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The parts of a datapoint identifying which time series it belongs to.
+pub struct SeriesKey<'a> {
+    /// InfluxDB measurement, or OpenTSDB metric.
+    pub measurement: &'a str,
+    /// Tags, sorted by key.
+    pub tags: &'a [(String, String)],
+    /// InfluxDB field key. Always `value` for OpenTSDB datapoints.
+    pub field: &'a str,
+}
+
+impl SeriesKey<'_> {
+    /// Get the value of a tag.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key of the tag.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn render(&self, template: &str) -> Result<String, String> {
+        let mut res = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            res.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                return Err(format!("Unterminated placeholder in template '{template}'"));
+            };
+            let name = &rest[start + 1..start + end];
+            match name {
+                "measurement" => res.push_str(self.measurement),
+                "field" => res.push_str(self.field),
+                "tags" => {
+                    for (k, v) in self.tags {
+                        let _ = write!(res, ",{k}={v}");
+                    }
+                }
+                _ => match name.strip_prefix("tag:") {
+                    Some(tag) => match self.tag(tag) {
+                        Some(value) => res.push_str(value),
+                        None => return Err(format!("Missing tag '{tag}'")),
+                    },
+                    None => {
+                        return Err(format!(
+                            "Unknown placeholder '{{{name}}}' in template '{template}'"
+                        ))
+                    }
+                },
+            }
+            rest = &rest[start + end + 1..];
+        }
+        res.push_str(rest);
+        Ok(res)
+    }
+}
+
+/// Function mapping a series key to a time series. Datapoints are skipped if it returns `None`.
+pub type SeriesMapper = Arc<dyn Fn(&SeriesKey<'_>) -> Option<IdentityOrInstance> + Send + Sync>;
+
+#[derive(Clone)]
+/// How datapoints are mapped to time series in CDF.
+///
+/// Templates may contain the placeholders `{measurement}`, `{field}`, `{tag:<key>}`,
+/// which is the value of the tag `<key>`, and `{tags}`, which is every tag, sorted by key,
+/// as `,key=value`. Datapoints missing a tag referenced with `{tag:<key>}` are rejected.
+pub enum SeriesMapping {
+    /// Map datapoints to the time series with the external ID given by this template.
+    ExternalId(String),
+    /// Map datapoints to the time series with this instance ID.
+    InstanceId {
+        /// Space of the time series instance.
+        space: String,
+        /// Template for the external ID of the time series instance.
+        external_id: String,
+    },
+    /// Map datapoints using a custom function.
+    Custom(SeriesMapper),
+}
+
+impl Default for SeriesMapping {
+    /// External ID on the form `measurement,tag1=a,tag2=b.field`.
+    fn default() -> Self {
+        Self::ExternalId("{measurement}{tags}.{field}".to_owned())
+    }
+}
+
+impl Debug for SeriesMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExternalId(template) => f.debug_tuple("ExternalId").field(template).finish(),
+            Self::InstanceId { space, external_id } => f
+                .debug_struct("InstanceId")
+                .field("space", space)
+                .field("external_id", external_id)
+                .finish(),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl SeriesMapping {
+    fn map(&self, key: &SeriesKey<'_>) -> Result<Option<IdentityOrInstance>, String> {
+        Ok(match self {
+            Self::ExternalId(template) => Some(
+                Identity::ExternalId {
+                    external_id: key.render(template)?,
+                }
+                .into(),
+            ),
+            Self::InstanceId { space, external_id } => Some(
+                InstanceId {
+                    space: space.clone(),
+                    external_id: key.render(external_id)?,
+                }
+                .into(),
+            ),
+            Self::Custom(mapper) => mapper(key),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Options for parsing InfluxDB line protocol and OpenTSDB payloads.
+pub struct LineProtocolOptions {
+    /// Precision of line protocol timestamps. OpenTSDB timestamps are always
+    /// seconds, or milliseconds if they are longer than 10 digits.
+    pub precision: TimePrecision,
+    /// How datapoints are mapped to time series.
+    pub mapping: SeriesMapping,
+    /// Timestamp in milliseconds since epoch used for line protocol datapoints without
+    /// a timestamp. If this is not set, the current time is used.
+    pub default_timestamp: Option<i64>,
+}
+
+/// Parser for datapoints in InfluxDB line protocol and OpenTSDB JSON payloads.
+///
+/// Float, integer, and unsigned fields become numeric datapoints. Boolean fields become
+/// numeric datapoints with value `1` or `0`, and string fields become string datapoints.
+/// Datapoints are grouped by time series in the order they first appear. The result can
+/// be inserted with `insert_datapoints`, or converted to a `DataPointInsertionRequest`.
+///
+/// # Example
+///
+/// ```ignore
+/// let parser = LineProtocolParser::new(LineProtocolOptions {
+///     precision: TimePrecision::Seconds,
+///     mapping: SeriesMapping::ExternalId("{tag:site}.{measurement}.{field}".to_owned()),
+///     ..Default::default()
+/// });
+/// let items = parser.parse("pump,site=oslo pressure=4.2,state=\"running\" 1700000000")?;
+/// client.time_series.insert_datapoints(items).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LineProtocolParser {
+    options: LineProtocolOptions,
+}
+
+#[derive(Default)]
+struct DatapointGroups {
+    items: Vec<AddDatapoints>,
+    index: HashMap<IdentityOrInstance, usize>,
+}
+
+enum FieldValue {
+    Numeric(f64),
+    String(String),
+}
+
+impl DatapointGroups {
+    fn push(
+        &mut self,
+        id: IdentityOrInstance,
+        timestamp: i64,
+        value: FieldValue,
+    ) -> Result<(), String> {
+        let idx = match self.index.get(&id) {
+            Some(idx) => *idx,
+            None => {
+                let datapoints = match value {
+                    FieldValue::Numeric(_) => DatapointsEnumType::NumericDatapoints(vec![]),
+                    FieldValue::String(_) => DatapointsEnumType::StringDatapoints(vec![]),
+                };
+                self.items.push(AddDatapoints {
+                    id: id.clone(),
+                    datapoints,
+                });
+                self.index.insert(id, self.items.len() - 1);
+                self.items.len() - 1
+            }
+        };
+        match (&mut self.items[idx].datapoints, value) {
+            (DatapointsEnumType::NumericDatapoints(dps), FieldValue::Numeric(value)) => {
+                dps.push(DatapointDouble {
+                    timestamp,
+                    value: Some(value),
+                    status: None,
+                })
+            }
+            (DatapointsEnumType::StringDatapoints(dps), FieldValue::String(value)) => {
+                dps.push(DatapointString {
+                    timestamp,
+                    value: Some(value),
+                    status: None,
+                })
+            }
+            _ => return Err("Time series has both numeric and string datapoints".to_owned()),
+        }
+        Ok(())
+    }
+}
+
+/// Scanner over a single line of line protocol.
+struct Scanner<'a> {
+    line: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.line.as_bytes().get(self.pos).copied()
+    }
+
+    /// Read until one of `stops` that is not escaped with a backslash.
+    /// Backslashes followed by one of `escapes` are removed.
+    fn read_until(&mut self, stops: &[u8], escapes: &[u8]) -> Cow<'a, str> {
+        let bytes = self.line.as_bytes();
+        let start = self.pos;
+        let mut escaped = false;
+        while let Some(b) = bytes.get(self.pos) {
+            if *b == b'\\' && bytes.get(self.pos + 1).is_some_and(|n| escapes.contains(n)) {
+                escaped = true;
+                self.pos += 2;
+                continue;
+            }
+            if stops.contains(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        let raw = &self.line[start..self.pos];
+        if !escaped {
+            return Cow::Borrowed(raw);
+        }
+        let mut res = String::with_capacity(raw.len());
+        let mut chars = raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(next) = chars
+                    .peek()
+                    .filter(|n| n.is_ascii() && escapes.contains(&(**n as u8)))
+                {
+                    res.push(*next);
+                    chars.next();
+                    continue;
+                }
+            }
+            res.push(c);
+        }
+        Cow::Owned(res)
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        // Skip opening quote.
+        self.pos += 1;
+        let mut res = String::new();
+        let mut chars = self.line[self.pos..].char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += idx + 1;
+                    return Ok(res);
+                }
+                '\\' => match chars.next() {
+                    Some((_, n @ ('"' | '\\'))) => res.push(n),
+                    Some((_, n)) => {
+                        res.push('\\');
+                        res.push(n);
+                    }
+                    None => res.push('\\'),
+                },
+                c => res.push(c),
+            }
+        }
+        Err("Unterminated string field value".to_owned())
+    }
+
+    fn skip_spaces(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+}
+
+fn parse_field_value(raw: &str) -> Result<FieldValue, String> {
+    let value = match raw {
+        "t" | "T" | "true" | "True" | "TRUE" => return Ok(FieldValue::Numeric(1.0)),
+        "f" | "F" | "false" | "False" | "FALSE" => return Ok(FieldValue::Numeric(0.0)),
+        _ => {
+            if let Some(int) = raw.strip_suffix('i') {
+                int.parse::<i64>().ok().map(|v| v as f64)
+            } else if let Some(uint) = raw.strip_suffix('u') {
+                uint.parse::<u64>().ok().map(|v| v as f64)
+            } else if raw.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) {
+                raw.parse::<f64>().ok().filter(|v| v.is_finite())
+            } else {
+                None
+            }
+        }
+    };
+    value
+        .map(FieldValue::Numeric)
+        .ok_or_else(|| format!("Invalid field value '{raw}'"))
+}
+
+type ParsedLine = (
+    String,
+    Vec<(String, String)>,
+    Vec<(String, FieldValue)>,
+    Option<i64>,
+);
+
+fn parse_line(line: &str) -> Result<ParsedLine, String> {
+    let mut scanner = Scanner { line, pos: 0 };
+    let measurement = scanner.read_until(b", ", b", ");
+    if measurement.is_empty() {
+        return Err("Missing measurement".to_owned());
+    }
+
+    let mut tags = vec![];
+    while scanner.peek() == Some(b',') {
+        scanner.pos += 1;
+        let key = scanner.read_until(b",= ", b",= ");
+        if scanner.peek() != Some(b'=') || key.is_empty() {
+            return Err("Invalid tag, must be on the form 'key=value'".to_owned());
+        }
+        scanner.pos += 1;
+        let value = scanner.read_until(b", ", b",= ");
+        if value.is_empty() {
+            return Err(format!("Missing value for tag '{key}'"));
+        }
+        tags.push((key.into_owned(), value.into_owned()));
+    }
+    tags.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if !scanner.skip_spaces() {
+        return Err("Missing fields".to_owned());
+    }
+    let mut fields = vec![];
+    loop {
+        let key = scanner.read_until(b",= ", b",= ");
+        if scanner.peek() != Some(b'=') || key.is_empty() {
+            return Err("Invalid field, must be on the form 'key=value'".to_owned());
+        }
+        scanner.pos += 1;
+        let value = if scanner.peek() == Some(b'"') {
+            FieldValue::String(scanner.read_string()?)
+        } else {
+            parse_field_value(&scanner.read_until(b", ", b""))?
+        };
+        fields.push((key.into_owned(), value));
+        if scanner.peek() == Some(b',') {
+            scanner.pos += 1;
+        } else {
+            break;
+        }
+    }
+
+    scanner.skip_spaces();
+    let rest = scanner.line[scanner.pos..].trim_end();
+    let timestamp = if rest.is_empty() {
+        None
+    } else {
+        Some(
+            rest.parse::<i64>()
+                .map_err(|_| format!("Invalid timestamp '{rest}'"))?,
+        )
+    };
+    Ok((measurement.into_owned(), tags, fields, timestamp))
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+impl LineProtocolParser {
+    /// Create a new parser.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Timestamp precision and time series mapping.
+    pub fn new(options: LineProtocolOptions) -> Self {
+        Self { options }
+    }
+
+    /// Get the options used by this parser.
+    pub fn options(&self) -> &LineProtocolOptions {
+        &self.options
+    }
+
+    /// Parse a payload in InfluxDB line protocol.
+    ///
+    /// Each line is on the form `measurement[,tag=value...] field=value[,field=value...] [timestamp]`.
+    /// Each field of each line is one datapoint. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - Line protocol payload, one datapoint per line.
+    pub fn parse(&self, payload: &str) -> Result<Vec<AddDatapoints>, ParseLineProtocolError> {
+        let mut groups = DatapointGroups::default();
+        let mut now = None;
+        for (idx, line) in payload.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |e: String| ParseLineProtocolError::new(line_no, e);
+            let (measurement, tags, fields, timestamp) = parse_line(line).map_err(err)?;
+            let timestamp = match timestamp {
+                Some(ts) => self
+                    .options
+                    .precision
+                    .to_millis(ts)
+                    .ok_or_else(|| err(format!("Timestamp {ts} is out of range")))?,
+                None => match self.options.default_timestamp {
+                    Some(ts) => ts,
+                    None => *now.get_or_insert_with(now_millis),
+                },
+            };
+            for (field, value) in fields {
+                let key = SeriesKey {
+                    measurement: &measurement,
+                    tags: &tags,
+                    field: &field,
+                };
+                if let Some(id) = self.options.mapping.map(&key).map_err(err)? {
+                    groups.push(id, timestamp, value).map_err(err)?;
+                }
+            }
+        }
+        Ok(groups.items)
+    }
+
+    /// Parse a payload in the OpenTSDB JSON format, as sent to `/api/put`.
+    ///
+    /// The payload is either a single datapoint or a list of datapoints, each on the form
+    /// `{"metric": "sys.cpu.nice", "timestamp": 1346846400, "value": 18, "tags": {"host": "web01"}}`.
+    /// The metric is used as measurement, and the field is always `value`. String values that
+    /// are valid numbers become numeric datapoints, other strings become string datapoints.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - OpenTSDB JSON payload.
+    pub fn parse_opentsdb_json(
+        &self,
+        payload: &str,
+    ) -> Result<Vec<AddDatapoints>, ParseLineProtocolError> {
+        let payload: Value = serde_json::from_str(payload)
+            .map_err(|e| ParseLineProtocolError::new(1, format!("Invalid JSON: {e}")))?;
+        let entries = match payload {
+            Value::Array(entries) => entries,
+            entry => vec![entry],
+        };
+        let mut groups = DatapointGroups::default();
+        for (idx, entry) in entries.iter().enumerate() {
+            let err = |e: String| ParseLineProtocolError::new(idx + 1, e);
+            let Some(metric) = entry.get("metric").and_then(|m| m.as_str()) else {
+                return Err(err("Missing metric".to_owned()));
+            };
+            let timestamp = entry
+                .get("timestamp")
+                .and_then(|t| t.as_i64())
+                .ok_or_else(|| err("Missing or invalid timestamp".to_owned()))?;
+            let timestamp = if timestamp.abs() > OPENTSDB_MAX_SECONDS {
+                timestamp
+            } else {
+                timestamp * 1000
+            };
+            let value = match entry.get("value") {
+                Some(Value::Number(n)) => n
+                    .as_f64()
+                    .map(FieldValue::Numeric)
+                    .ok_or_else(|| err(format!("Invalid value {n}")))?,
+                Some(Value::String(s)) => match s.trim().parse::<f64>() {
+                    Ok(v) if v.is_finite() => FieldValue::Numeric(v),
+                    _ => FieldValue::String(s.clone()),
+                },
+                _ => return Err(err("Missing or invalid value".to_owned())),
+            };
+            let mut tags = vec![];
+            if let Some(Value::Object(raw)) = entry.get("tags") {
+                for (k, v) in raw {
+                    let v = match v {
+                        Value::String(s) => s.clone(),
+                        v => v.to_string(),
+                    };
+                    tags.push((k.clone(), v));
+                }
+            }
+            tags.sort_by(|a, b| a.0.cmp(&b.0));
+            let key = SeriesKey {
+                measurement: metric,
+                tags: &tags,
+                field: OPENTSDB_FIELD,
+            };
+            if let Some(id) = self.options.mapping.map(&key).map_err(err)? {
+                groups.push(id, timestamp, value).map_err(err)?;
+            }
+        }
+        Ok(groups.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{LineProtocolOptions, LineProtocolParser, SeriesMapping, TimePrecision};
+    use crate::models::instances::InstanceId;
+    use crate::time_series::{AddDatapoints, DatapointsEnumType};
+    use crate::{Identity, IdentityOrInstance};
+
+    fn numeric(item: &AddDatapoints) -> Vec<(i64, f64)> {
+        match &item.datapoints {
+            DatapointsEnumType::NumericDatapoints(dps) => dps
+                .iter()
+                .map(|d| (d.timestamp, d.value.unwrap()))
+                .collect(),
+            _ => panic!("Expected numeric datapoints"),
+        }
+    }
+
+    #[test]
+    fn test_parse_line_protocol() {
+        let parser = LineProtocolParser::default();
+        let items = parser
+            .parse(
+                "# comment\n\
+                 weather,location=us\\ west,host=a temperature=82.5,humidity=71i 1465839830100400200\n\
+                 \n\
+                 weather,host=a,location=us\\ west temperature=83,ok=t,note=\"say \\\"hi\\\", ok\" 1465839831000000000\n",
+            )
+            .unwrap();
+        assert_eq!(4, items.len());
+        assert_eq!(
+            IdentityOrInstance::from(Identity::from(
+                "weather,host=a,location=us west.temperature"
+            )),
+            items[0].id
+        );
+        assert_eq!(
+            vec![(1465839830100, 82.5), (1465839831000, 83.0)],
+            numeric(&items[0])
+        );
+        assert_eq!(vec![(1465839830100, 71.0)], numeric(&items[1]));
+        assert_eq!(vec![(1465839831000, 1.0)], numeric(&items[2]));
+        match &items[3].datapoints {
+            DatapointsEnumType::StringDatapoints(dps) => {
+                assert_eq!(Some("say \"hi\", ok"), dps[0].value.as_deref())
+            }
+            _ => panic!("Expected string datapoints"),
+        }
+
+        let err = parser.parse("cpu value=1\ncpu value=\"a\"").unwrap_err();
+        assert_eq!(2, err.line);
+        let err = parser.parse("cpu value=abc").unwrap_err();
+        assert_eq!("Invalid field value 'abc'", err.message);
+        assert!(parser.parse("cpu,host value=1").is_err());
+        assert!(parser.parse("cpu").is_err());
+    }
+
+    #[test]
+    fn test_line_protocol_mapping() {
+        let parser = LineProtocolParser::new(LineProtocolOptions {
+            precision: TimePrecision::Seconds,
+            mapping: SeriesMapping::InstanceId {
+                space: "sp".to_owned(),
+                external_id: "{tag:site}_{measurement}_{field}".to_owned(),
+            },
+            default_timestamp: Some(5),
+        });
+        let items = parser
+            .parse("pump,site=oslo pressure=4.2 10\npump,site=oslo pressure=4.3")
+            .unwrap();
+        assert_eq!(
+            IdentityOrInstance::from(InstanceId {
+                space: "sp".to_owned(),
+                external_id: "oslo_pump_pressure".to_owned()
+            }),
+            items[0].id
+        );
+        assert_eq!(vec![(10_000, 4.2), (5, 4.3)], numeric(&items[0]));
+        let err = parser.parse("pump pressure=1").unwrap_err();
+        assert_eq!("Missing tag 'site'", err.message);
+
+        let parser = LineProtocolParser::new(LineProtocolOptions {
+            mapping: SeriesMapping::Custom(Arc::new(|key| {
+                (key.field != "skip").then(|| Identity::from(key.field).into())
+            })),
+            ..Default::default()
+        });
+        let items = parser.parse("m a=1,skip=2 0").unwrap();
+        assert_eq!(1, items.len());
+    }
+
+    #[test]
+    fn test_parse_opentsdb_json() {
+        let parser = LineProtocolParser::new(LineProtocolOptions {
+            mapping: SeriesMapping::ExternalId("{measurement}{tags}".to_owned()),
+            ..Default::default()
+        });
+        let items = parser
+            .parse_opentsdb_json(
+                r#"[
+                    {"metric": "sys.cpu", "timestamp": 1346846400, "value": 18, "tags": {"host": "web01", "dc": "lga"}},
+                    {"metric": "sys.cpu", "timestamp": 1346846401000, "value": "19.5", "tags": {"dc": "lga", "host": "web01"}},
+                    {"metric": "sys.state", "timestamp": 1346846400, "value": "up"}
+                ]"#,
+            )
+            .unwrap();
+        assert_eq!(2, items.len());
+        assert_eq!(
+            IdentityOrInstance::from(Identity::from("sys.cpu,dc=lga,host=web01")),
+            items[0].id
+        );
+        assert_eq!(
+            vec![(1346846400000, 18.0), (1346846401000, 19.5)],
+            numeric(&items[0])
+        );
+        assert!(matches!(
+            items[1].datapoints,
+            DatapointsEnumType::StringDatapoints(_)
+        ));
+        let err = parser
+            .parse_opentsdb_json(r#"{"metric": "a", "value": 1}"#)
+            .unwrap_err();
+        assert_eq!(1, err.line);
+    }
+}
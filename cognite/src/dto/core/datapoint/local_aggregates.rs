@@ -0,0 +1,402 @@
+use super::{DatapointAggregate, DatapointDouble, Granularity};
+
+#[derive(Debug, Clone)]
+/// Options for computing aggregates locally with [`compute_aggregates`].
+pub struct LocalAggregateOptions {
+    /// Granularity of the aggregates.
+    pub granularity: Granularity,
+    /// Whether the time series is a step series. Step series are constant between
+    /// datapoints, other series are linearly interpolated.
+    pub is_step: bool,
+    /// Treat datapoints with a bad status code as if they do not exist. If `false`,
+    /// the period between a bad datapoint and the next good datapoint is undefined,
+    /// and the period between a good datapoint and the next bad datapoint is constant.
+    /// Default `true`.
+    pub ignore_bad_data_points: bool,
+    /// Treat datapoints with an uncertain status code as bad. Default `true`.
+    pub treat_uncertain_as_bad: bool,
+}
+
+impl LocalAggregateOptions {
+    /// Create options for aggregating a non-step time series, with default status code handling.
+    ///
+    /// # Arguments
+    ///
+    /// * `granularity` - Granularity of the aggregates.
+    pub fn new(granularity: Granularity) -> Self {
+        Self {
+            granularity,
+            is_step: false,
+            ignore_bad_data_points: true,
+            treat_uncertain_as_bad: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Quality {
+    Good,
+    Uncertain,
+    Bad,
+}
+
+struct Point {
+    timestamp: i64,
+    value: f64,
+    quality: Quality,
+    /// Whether the value of this point is used in aggregates.
+    usable: bool,
+}
+
+/// A piece of the underlying function, from `start` to `end`, going from `v0` to `v1`.
+/// Step series stay at `v0` until `end`.
+struct Segment {
+    start: i64,
+    end: i64,
+    v0: f64,
+    v1: f64,
+}
+
+impl Segment {
+    fn eval(&self, t: i64, step: bool) -> f64 {
+        if step || self.end == self.start {
+            self.v0
+        } else {
+            self.v0 + (self.v1 - self.v0) * (t - self.start) as f64 / (self.end - self.start) as f64
+        }
+    }
+
+    fn end_value(&self, step: bool) -> f64 {
+        if step {
+            self.v0
+        } else {
+            self.v1
+        }
+    }
+}
+
+fn points(datapoints: &[DatapointDouble], options: &LocalAggregateOptions) -> Vec<Point> {
+    let mut points: Vec<_> = datapoints
+        .iter()
+        .map(|dp| {
+            let quality = match &dp.status {
+                Some(s) if s.is_bad() => Quality::Bad,
+                Some(s) if s.is_uncertain() => Quality::Uncertain,
+                _ => Quality::Good,
+            };
+            let bad = quality == Quality::Bad
+                || quality == Quality::Uncertain && options.treat_uncertain_as_bad;
+            Point {
+                timestamp: dp.timestamp,
+                value: dp.value.unwrap_or(f64::NAN),
+                quality,
+                usable: !bad && dp.value.is_some_and(f64::is_finite),
+            }
+        })
+        .collect();
+    points.sort_by_key(|p| p.timestamp);
+    points
+}
+
+fn segments(points: &[Point], options: &LocalAggregateOptions) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    if options.ignore_bad_data_points {
+        let mut usable = points.iter().filter(|p| p.usable).peekable();
+        while let (Some(p), Some(q)) = (usable.next(), usable.peek()) {
+            segments.push(Segment {
+                start: p.timestamp,
+                end: q.timestamp,
+                v0: p.value,
+                v1: q.value,
+            });
+        }
+    } else {
+        for pair in points.windows(2) {
+            let (p, q) = (&pair[0], &pair[1]);
+            if !p.usable {
+                continue;
+            }
+            segments.push(Segment {
+                start: p.timestamp,
+                end: q.timestamp,
+                v0: p.value,
+                v1: if q.usable { q.value } else { p.value },
+            });
+        }
+    }
+    segments.retain(|s| s.end > s.start);
+    segments
+}
+
+/// Value of the underlying function at `t`, or `NaN` if it is not defined there.
+fn value_at(points: &[Point], segments: &[Segment], t: i64, step: bool) -> f64 {
+    let idx = points.partition_point(|p| p.timestamp < t);
+    if let Some(p) = points[idx..].iter().take_while(|p| p.timestamp == t).last() {
+        return if p.usable { p.value } else { f64::NAN };
+    }
+    let idx = segments.partition_point(|s| s.end <= t);
+    match segments.get(idx) {
+        Some(s) if s.start <= t => s.eval(t, step),
+        _ => f64::NAN,
+    }
+}
+
+fn aggregate_period(
+    points: &[Point],
+    segments: &[Segment],
+    start: i64,
+    end: i64,
+    options: &LocalAggregateOptions,
+) -> DatapointAggregate {
+    let from = points.partition_point(|p| p.timestamp < start);
+    let to = points.partition_point(|p| p.timestamp < end);
+    let in_period = &points[from..to];
+
+    let mut count = 0.0;
+    let mut sum = 0.0;
+    let mut min = f64::NAN;
+    let mut max = f64::NAN;
+    let (mut count_good, mut count_uncertain, mut count_bad) = (0.0, 0.0, 0.0);
+    for p in in_period {
+        match p.quality {
+            Quality::Good => count_good += 1.0,
+            Quality::Uncertain => count_uncertain += 1.0,
+            Quality::Bad => count_bad += 1.0,
+        }
+        if p.usable {
+            count += 1.0;
+            sum += p.value;
+            min = min.min(p.value);
+            max = max.max(p.value);
+        }
+    }
+    let average = if count > 0.0 { sum / count } else { f64::NAN };
+    let discrete_variance = if count > 0.0 {
+        in_period
+            .iter()
+            .filter(|p| p.usable)
+            .map(|p| (p.value - average).powi(2))
+            .sum::<f64>()
+            / count
+    } else {
+        f64::NAN
+    };
+
+    // Each datapoint defines the status until the next datapoint.
+    let (mut duration_good, mut duration_uncertain, mut duration_bad) = (0.0, 0.0, 0.0);
+    let first = from.saturating_sub(1);
+    for pair in points[first..(to + 1).min(points.len())].windows(2) {
+        let duration = (pair[1].timestamp.min(end) - pair[0].timestamp.max(start)).max(0) as f64;
+        match pair[0].quality {
+            Quality::Good => duration_good += duration,
+            Quality::Uncertain => duration_uncertain += duration,
+            Quality::Bad => duration_bad += duration,
+        }
+    }
+
+    // Integrate the underlying function over the part of the period where it is defined.
+    let step = options.is_step;
+    let (mut length, mut integral, mut integral_sq, mut total_variation) = (0.0, 0.0, 0.0, 0.0);
+    let mut prev: Option<(i64, f64)> = None;
+    let seg_from = segments.partition_point(|s| s.end <= start);
+    for s in segments[seg_from..].iter().take_while(|s| s.start < end) {
+        let (t0, t1) = (s.start.max(start), s.end.min(end));
+        let (a, b) = (s.eval(t0, step), s.eval(t1, step));
+        let d = (t1 - t0) as f64;
+        length += d;
+        integral += d * (a + b) / 2.0;
+        integral_sq += d * (a * a + a * b + b * b) / 3.0;
+        total_variation += (b - a).abs();
+        if let Some((prev_end, prev_value)) = prev {
+            if prev_end == s.start {
+                total_variation += (a - prev_value).abs();
+            }
+        }
+        prev = Some((s.end, s.end_value(step)));
+    }
+    let (continuous_variance, total_variation) = if length > 0.0 {
+        let mean = integral / length;
+        (
+            (integral_sq / length - mean * mean).max(0.0),
+            total_variation,
+        )
+    } else {
+        (f64::NAN, f64::NAN)
+    };
+
+    let step_interpolation = value_at(points, segments, start, true);
+    let interpolation = if step {
+        step_interpolation
+    } else {
+        value_at(points, segments, start, false)
+    };
+
+    DatapointAggregate {
+        timestamp: start,
+        average,
+        max,
+        min,
+        count,
+        sum,
+        interpolation,
+        step_interpolation,
+        continuous_variance,
+        discrete_variance,
+        total_variation,
+        count_good,
+        count_uncertain,
+        count_bad,
+        duration_good,
+        duration_uncertain,
+        duration_bad,
+    }
+}
+
+/// Compute datapoint aggregates locally, following the same rules as the API.
+///
+/// Aggregate periods are aligned like in the API: `start` is rounded down to a whole granularity
+/// unit in UTC, see [`Granularity::align`]. Only periods starting before `end` and containing at
+/// least one datapoint are returned.
+///
+/// Datapoints before and after the periods are used for interpolation, so include the datapoints
+/// surrounding the range to get correct `interpolation`, `step_interpolation`, `continuous_variance`,
+/// `total_variation`, and durations at the edges.
+///
+/// The underlying function is linear between datapoints, or constant for step series, and is not
+/// defined after the last datapoint. Aggregates that are not defined in a period, like `min`
+/// in a period with only bad datapoints, are `NaN`. `discrete_variance` is the population variance.
+///
+/// # Arguments
+///
+/// * `datapoints` - Datapoints to aggregate. They do not need to be sorted.
+/// * `start` - Start of the range to aggregate, in milliseconds since epoch.
+/// * `end` - End of the range to aggregate, exclusive.
+/// * `options` - Granularity, interpolation and status code handling.
+pub fn compute_aggregates(
+    datapoints: &[DatapointDouble],
+    start: i64,
+    end: i64,
+    options: &LocalAggregateOptions,
+) -> Vec<DatapointAggregate> {
+    let points = points(datapoints, options);
+    let segments = segments(&points, options);
+    let granularity = &options.granularity;
+
+    let mut result = Vec::new();
+    let mut period_start = granularity.align(start);
+    let mut idx = points.partition_point(|p| p.timestamp < period_start);
+    while period_start < end {
+        let Some(next) = points.get(idx) else {
+            break;
+        };
+        // Skip ahead to the period containing the next datapoint.
+        if let Some(len) = granularity.millis() {
+            period_start += (next.timestamp - period_start).div_euclid(len) * len;
+        }
+        let mut period_end = granularity.next_period(period_start);
+        while period_end <= next.timestamp {
+            period_start = period_end;
+            period_end = granularity.next_period(period_start);
+        }
+        if period_start >= end {
+            break;
+        }
+        result.push(aggregate_period(
+            &points,
+            &segments,
+            period_start,
+            period_end,
+            options,
+        ));
+        idx = points.partition_point(|p| p.timestamp < period_end);
+        period_start = period_end;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_aggregates, LocalAggregateOptions};
+    use crate::time_series::{DatapointDouble, StatusCode};
+
+    fn dp(timestamp: i64, value: f64) -> DatapointDouble {
+        DatapointDouble {
+            timestamp,
+            value: Some(value),
+            status: None,
+        }
+    }
+
+    #[test]
+    fn test_compute_aggregates() {
+        let datapoints = vec![dp(0, 0.0), dp(500, 10.0), dp(1000, 0.0), dp(2500, 5.0)];
+        let options = LocalAggregateOptions::new("1s".parse().unwrap());
+        let aggs = compute_aggregates(&datapoints, 0, 5000, &options);
+
+        // 1000-2000 has a datapoint at 1000, 2000-3000 has one at 2500.
+        assert_eq!(3, aggs.len());
+        let first = &aggs[0];
+        assert_eq!(0, first.timestamp);
+        assert_eq!(2.0, first.count);
+        assert_eq!(5.0, first.average);
+        assert_eq!(10.0, first.max);
+        assert_eq!(0.0, first.min);
+        assert_eq!(25.0, first.discrete_variance);
+        assert_eq!(20.0, first.total_variation);
+        // Triangle from 0 to 10 to 0, mean 5, mean square 100/3.
+        assert!((first.continuous_variance - (100.0 / 3.0 - 25.0)).abs() < 1e-9);
+        assert_eq!(1000.0, first.duration_good);
+
+        let second = &aggs[1];
+        assert_eq!(1000, second.timestamp);
+        assert_eq!(0.0, second.interpolation);
+        // 0 to 5 over 1500ms, so 10/3 at 2000.
+        assert!((aggs[2].interpolation - 10.0 / 3.0).abs() < 1e-9);
+        assert_eq!(0.0, aggs[2].step_interpolation);
+        // Not defined after the last datapoint.
+        assert_eq!(500.0, aggs[2].duration_good);
+    }
+
+    #[test]
+    fn test_compute_aggregates_status_codes() {
+        let bad = StatusCode::try_parse("Bad").unwrap();
+        let uncertain = StatusCode::try_parse("Uncertain").unwrap();
+        let datapoints = vec![
+            dp(0, 1.0),
+            DatapointDouble {
+                timestamp: 100,
+                value: Some(100.0),
+                status: Some(bad),
+            },
+            DatapointDouble {
+                timestamp: 200,
+                value: Some(3.0),
+                status: Some(uncertain),
+            },
+            dp(300, 3.0),
+        ];
+        let mut options = LocalAggregateOptions::new("1s".parse().unwrap());
+        let aggs = compute_aggregates(&datapoints, 0, 1000, &options);
+        let agg = &aggs[0];
+        assert_eq!(2.0, agg.count);
+        assert_eq!(2.0, agg.average);
+        assert_eq!(2.0, agg.count_good);
+        assert_eq!(1.0, agg.count_uncertain);
+        assert_eq!(1.0, agg.count_bad);
+        assert_eq!(100.0, agg.duration_good);
+        assert_eq!(100.0, agg.duration_bad);
+        assert_eq!(100.0, agg.duration_uncertain);
+        // Linear from 1 at 0 to 3 at 300.
+        assert_eq!(2.0, agg.total_variation);
+
+        options.treat_uncertain_as_bad = false;
+        options.ignore_bad_data_points = false;
+        let aggs = compute_aggregates(&datapoints, 0, 1000, &options);
+        let agg = &aggs[0];
+        assert_eq!(3.0, agg.count);
+        // Constant 1 until the bad datapoint, undefined until the uncertain datapoint,
+        // then constant 3.
+        assert_eq!(0.0, agg.total_variation);
+        assert!((agg.continuous_variance - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,306 @@
+use std::slice;
+
+use super::{
+    AggregateDatapoint, DataPointListItem, DataPointListResponse, DatapointAggregate,
+    DatapointDouble, DatapointString, ListDatapointType, NumericDatapoint, StatusCode,
+    StringDatapoint,
+};
+
+/// A datapoint borrowed from a protobuf datapoints response.
+///
+/// Numeric and string datapoints are given as `(timestamp, value, status)`, where
+/// `value` is `None` if the datapoint is explicitly null, and `status` is `None` if
+/// the datapoint has no status, meaning it is `Good`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatapointView<'a> {
+    /// A numeric datapoint.
+    Numeric(i64, Option<f64>, Option<StatusCode>),
+    /// A string datapoint.
+    String(i64, Option<&'a str>, Option<StatusCode>),
+    /// An aggregate datapoint.
+    Aggregate(&'a AggregateDatapoint),
+}
+
+impl<'a> DatapointView<'a> {
+    /// Get the timestamp of this datapoint, in milliseconds since epoch.
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            Self::Numeric(ts, _, _) | Self::String(ts, _, _) => *ts,
+            Self::Aggregate(dp) => dp.timestamp,
+        }
+    }
+
+    /// Get the value of this datapoint if it is numeric and not null.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Numeric(_, value, _) => *value,
+            _ => None,
+        }
+    }
+
+    /// Get the value of this datapoint if it is a string and not null.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            Self::String(_, value, _) => *value,
+            _ => None,
+        }
+    }
+
+    /// Get the status code of this datapoint. Aggregates have no status.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Numeric(_, _, status) | Self::String(_, _, status) => *status,
+            Self::Aggregate(_) => None,
+        }
+    }
+}
+
+impl From<DatapointView<'_>> for DatapointDouble {
+    /// Convert a datapoint view to an owned numeric datapoint. Non-numeric datapoints
+    /// become null datapoints.
+    fn from(view: DatapointView<'_>) -> Self {
+        DatapointDouble {
+            timestamp: view.timestamp(),
+            value: view.as_f64(),
+            status: view.status(),
+        }
+    }
+}
+
+impl From<DatapointView<'_>> for DatapointString {
+    /// Convert a datapoint view to an owned string datapoint. Non-string datapoints
+    /// become null datapoints.
+    fn from(view: DatapointView<'_>) -> Self {
+        DatapointString {
+            timestamp: view.timestamp(),
+            value: view.as_str().map(|s| s.to_owned()),
+            status: view.status(),
+        }
+    }
+}
+
+fn numeric_view(dp: &NumericDatapoint) -> (i64, Option<f64>, Option<StatusCode>) {
+    (
+        dp.timestamp,
+        if dp.null_value { None } else { Some(dp.value) },
+        dp.status.as_ref().map(StatusCode::from),
+    )
+}
+
+fn string_view(dp: &StringDatapoint) -> (i64, Option<&str>, Option<StatusCode>) {
+    (
+        dp.timestamp,
+        if dp.null_value {
+            None
+        } else {
+            Some(dp.value.as_str())
+        },
+        dp.status.as_ref().map(StatusCode::from),
+    )
+}
+
+#[derive(Clone, Debug)]
+enum DatapointViewIterInner<'a> {
+    Numeric(slice::Iter<'a, NumericDatapoint>),
+    String(slice::Iter<'a, StringDatapoint>),
+    Aggregate(slice::Iter<'a, AggregateDatapoint>),
+}
+
+/// Iterator over the datapoints in a protobuf datapoints response item,
+/// created by [`DataPointListItem::datapoints`].
+#[derive(Clone, Debug)]
+pub struct DatapointViewIter<'a> {
+    inner: DatapointViewIterInner<'a>,
+}
+
+impl<'a> Iterator for DatapointViewIter<'a> {
+    type Item = DatapointView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            DatapointViewIterInner::Numeric(it) => it.next().map(|dp| {
+                let (ts, value, status) = numeric_view(dp);
+                DatapointView::Numeric(ts, value, status)
+            }),
+            DatapointViewIterInner::String(it) => it.next().map(|dp| {
+                let (ts, value, status) = string_view(dp);
+                DatapointView::String(ts, value, status)
+            }),
+            DatapointViewIterInner::Aggregate(it) => it.next().map(DatapointView::Aggregate),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for DatapointViewIter<'_> {
+    fn len(&self) -> usize {
+        match &self.inner {
+            DatapointViewIterInner::Numeric(it) => it.len(),
+            DatapointViewIterInner::String(it) => it.len(),
+            DatapointViewIterInner::Aggregate(it) => it.len(),
+        }
+    }
+}
+
+impl DataPointListItem {
+    /// Iterate over the numeric datapoints in this item as `(timestamp, value, status)`,
+    /// without allocating. Empty if this item does not contain numeric datapoints.
+    pub fn numeric_datapoints(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (i64, Option<f64>, Option<StatusCode>)> + '_ {
+        let dps: &[NumericDatapoint] = match &self.datapoint_type {
+            Some(ListDatapointType::NumericDatapoints(dps)) => &dps.datapoints,
+            _ => &[],
+        };
+        dps.iter().map(numeric_view)
+    }
+
+    /// Iterate over the string datapoints in this item as `(timestamp, value, status)`,
+    /// borrowing the string values. Empty if this item does not contain string datapoints.
+    pub fn string_datapoints(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (i64, Option<&str>, Option<StatusCode>)> + '_ {
+        let dps: &[StringDatapoint] = match &self.datapoint_type {
+            Some(ListDatapointType::StringDatapoints(dps)) => &dps.datapoints,
+            _ => &[],
+        };
+        dps.iter().map(string_view)
+    }
+
+    /// Get the aggregate datapoints in this item. Empty if this item does not contain
+    /// aggregate datapoints.
+    pub fn aggregate_datapoints(&self) -> &[AggregateDatapoint] {
+        match &self.datapoint_type {
+            Some(ListDatapointType::AggregateDatapoints(dps)) => &dps.datapoints,
+            _ => &[],
+        }
+    }
+
+    /// Iterate over the datapoints in this item, of any type, without allocating.
+    pub fn datapoints(&self) -> DatapointViewIter<'_> {
+        let inner = match &self.datapoint_type {
+            Some(ListDatapointType::NumericDatapoints(dps)) => {
+                DatapointViewIterInner::Numeric(dps.datapoints.iter())
+            }
+            Some(ListDatapointType::StringDatapoints(dps)) => {
+                DatapointViewIterInner::String(dps.datapoints.iter())
+            }
+            Some(ListDatapointType::AggregateDatapoints(dps)) => {
+                DatapointViewIterInner::Aggregate(dps.datapoints.iter())
+            }
+            None => DatapointViewIterInner::Numeric([].iter()),
+        };
+        DatapointViewIter { inner }
+    }
+}
+
+impl DataPointListResponse {
+    /// Iterate over all datapoints in this response, paired with the item they belong to,
+    /// without allocating.
+    pub fn datapoints(&self) -> impl Iterator<Item = (&DataPointListItem, DatapointView<'_>)> {
+        self.items
+            .iter()
+            .flat_map(|item| item.datapoints().map(move |dp| (item, dp)))
+    }
+
+    /// Total number of datapoints in this response.
+    pub fn datapoint_count(&self) -> usize {
+        self.items.iter().map(|item| item.datapoints().len()).sum()
+    }
+}
+
+impl From<&AggregateDatapoint> for DatapointAggregate {
+    fn from(dp: &AggregateDatapoint) -> Self {
+        DatapointAggregate::from(*dp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time_series::{
+        AggregateDatapoint, AggregateDatapoints, DataPointListItem, DataPointListResponse,
+        DatapointView, ListDatapointType, NumericDatapoint, NumericDatapoints, Status, StatusCode,
+        StringDatapoint, StringDatapoints,
+    };
+
+    #[test]
+    fn test_datapoint_views() {
+        let response = DataPointListResponse {
+            items: vec![
+                DataPointListItem {
+                    id: 1,
+                    datapoint_type: Some(ListDatapointType::NumericDatapoints(NumericDatapoints {
+                        datapoints: vec![
+                            NumericDatapoint {
+                                timestamp: 1,
+                                value: 1.5,
+                                ..Default::default()
+                            },
+                            NumericDatapoint {
+                                timestamp: 2,
+                                null_value: true,
+                                status: Some(Status {
+                                    code: 0,
+                                    symbol: "Bad".to_owned(),
+                                }),
+                                ..Default::default()
+                            },
+                        ],
+                    })),
+                    ..Default::default()
+                },
+                DataPointListItem {
+                    id: 2,
+                    datapoint_type: Some(ListDatapointType::StringDatapoints(StringDatapoints {
+                        datapoints: vec![StringDatapoint {
+                            timestamp: 3,
+                            value: "hello".to_owned(),
+                            ..Default::default()
+                        }],
+                    })),
+                    ..Default::default()
+                },
+                DataPointListItem {
+                    id: 3,
+                    datapoint_type: Some(ListDatapointType::AggregateDatapoints(
+                        AggregateDatapoints {
+                            datapoints: vec![AggregateDatapoint {
+                                timestamp: 4,
+                                average: 2.0,
+                                ..Default::default()
+                            }],
+                        },
+                    )),
+                    ..Default::default()
+                },
+                DataPointListItem {
+                    id: 4,
+                    ..Default::default()
+                },
+            ],
+        };
+
+        assert_eq!(4, response.datapoint_count());
+        let numeric: Vec<_> = response.items[0].numeric_datapoints().collect();
+        assert_eq!(
+            vec![(1, Some(1.5), None), (2, None, Some(StatusCode::Bad))],
+            numeric
+        );
+        assert_eq!(0, response.items[0].string_datapoints().len());
+        let strings: Vec<_> = response.items[1].string_datapoints().collect();
+        assert_eq!(vec![(3, Some("hello"), None)], strings);
+        assert_eq!(2.0, response.items[2].aggregate_datapoints()[0].average);
+
+        let all: Vec<_> = response
+            .datapoints()
+            .map(|(item, dp)| (item.id, dp.timestamp()))
+            .collect();
+        assert_eq!(vec![(1, 1), (1, 2), (2, 3), (3, 4)], all);
+        let (_, last) = response.datapoints().last().unwrap();
+        assert!(matches!(last, DatapointView::Aggregate(a) if a.average == 2.0));
+    }
+}
@@ -0,0 +1,302 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{GranularityUnit, TimestampOrRelative};
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+/// Error on failure to parse a relative time.
+pub enum ParseRelativeTimeError {
+    /// Invalid format, must be `now`, `N[timeunit]-ago` or `N[timeunit]-ahead`.
+    #[error(
+        "Invalid relative time '{0}', must be 'now', 'N[timeunit]-ago' or 'N[timeunit]-ahead'"
+    )]
+    InvalidFormat(String),
+    /// Unknown or unsupported time unit.
+    #[error("Unsupported time unit '{0}', must be one of w, d, h, m, s")]
+    UnsupportedUnit(String),
+    /// The resolved timestamp does not fit in an i64.
+    #[error("Relative time overflowed")]
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A time relative to the current time, as accepted by the API, like `now`, `2d-ago` or `3h-ahead`.
+///
+/// Relative times only support units with a fixed length: weeks, days, hours, minutes and seconds.
+pub enum RelativeTime {
+    /// The current time.
+    Now,
+    /// A time in the past.
+    Ago {
+        /// Number of units.
+        amount: u64,
+        /// Time unit.
+        unit: GranularityUnit,
+    },
+    /// A time in the future.
+    Ahead {
+        /// Number of units.
+        amount: u64,
+        /// Time unit.
+        unit: GranularityUnit,
+    },
+}
+
+impl RelativeTime {
+    /// Create a relative time in the past.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - Number of units.
+    /// * `unit` - Time unit. Must not be a calendar unit.
+    pub fn ago(amount: u64, unit: GranularityUnit) -> Result<Self, ParseRelativeTimeError> {
+        check_unit(unit)?;
+        Ok(Self::Ago { amount, unit })
+    }
+
+    /// Create a relative time in the future.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - Number of units.
+    /// * `unit` - Time unit. Must not be a calendar unit.
+    pub fn ahead(amount: u64, unit: GranularityUnit) -> Result<Self, ParseRelativeTimeError> {
+        check_unit(unit)?;
+        Ok(Self::Ahead { amount, unit })
+    }
+
+    /// Offset from the current time in milliseconds.
+    pub fn offset_millis(&self) -> Result<i64, ParseRelativeTimeError> {
+        let (amount, unit, sign) = match self {
+            Self::Now => return Ok(0),
+            Self::Ago { amount, unit } => (*amount, *unit, -1),
+            Self::Ahead { amount, unit } => (*amount, *unit, 1),
+        };
+        let unit = check_unit(unit)?;
+        i64::try_from(amount)
+            .ok()
+            .and_then(|a| a.checked_mul(unit))
+            .map(|o| o * sign)
+            .ok_or(ParseRelativeTimeError::Overflow)
+    }
+
+    /// Resolve this relative time to milliseconds since epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time in milliseconds since epoch.
+    pub fn to_timestamp(&self, now: i64) -> Result<i64, ParseRelativeTimeError> {
+        now.checked_add(self.offset_millis()?)
+            .ok_or(ParseRelativeTimeError::Overflow)
+    }
+}
+
+fn check_unit(unit: GranularityUnit) -> Result<i64, ParseRelativeTimeError> {
+    unit.millis()
+        .ok_or_else(|| ParseRelativeTimeError::UnsupportedUnit(unit.to_string()))
+}
+
+impl FromStr for RelativeTime {
+    type Err = ParseRelativeTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "now" {
+            return Ok(Self::Now);
+        }
+        let invalid = || ParseRelativeTimeError::InvalidFormat(s.to_owned());
+        let (value, ago) = if let Some(v) = s.strip_suffix("-ago") {
+            (v, true)
+        } else if let Some(v) = s.strip_suffix("-ahead") {
+            (v, false)
+        } else {
+            return Err(invalid());
+        };
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (amount, unit) = value.split_at(split);
+        let amount = amount.parse().map_err(|_| invalid())?;
+        let unit: GranularityUnit = unit
+            .parse()
+            .map_err(|_| ParseRelativeTimeError::UnsupportedUnit(unit.to_owned()))?;
+        if ago {
+            Self::ago(amount, unit)
+        } else {
+            Self::ahead(amount, unit)
+        }
+    }
+}
+
+impl Display for RelativeTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Now => write!(f, "now"),
+            Self::Ago { amount, unit } => write!(f, "{amount}{unit}-ago"),
+            Self::Ahead { amount, unit } => write!(f, "{amount}{unit}-ahead"),
+        }
+    }
+}
+
+impl Serialize for RelativeTime {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for RelativeTime {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<RelativeTime> for TimestampOrRelative {
+    fn from(value: RelativeTime) -> Self {
+        Self::Relative(value.to_string())
+    }
+}
+
+impl TimestampOrRelative {
+    /// Parse the relative time, if this is a relative timestamp.
+    pub fn as_relative(&self) -> Option<Result<RelativeTime, ParseRelativeTimeError>> {
+        match self {
+            Self::Timestamp(_) => None,
+            Self::Relative(r) => Some(r.parse()),
+        }
+    }
+
+    /// Resolve this to an absolute timestamp in milliseconds since epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time in milliseconds since epoch.
+    pub fn to_timestamp(&self, now: i64) -> Result<i64, ParseRelativeTimeError> {
+        match self {
+            Self::Timestamp(t) => Ok(*t),
+            Self::Relative(r) => r.parse::<RelativeTime>()?.to_timestamp(now),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for TimestampOrRelative {
+    fn from(value: chrono::DateTime<Tz>) -> Self {
+        Self::Timestamp(value.timestamp_millis())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl RelativeTime {
+    /// Resolve this relative time to a UTC date time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn to_chrono(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<chrono::DateTime<chrono::Utc>, ParseRelativeTimeError> {
+        now.checked_add_signed(chrono::Duration::milliseconds(self.offset_millis()?))
+            .ok_or(ParseRelativeTimeError::Overflow)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for TimestampOrRelative {
+    fn from(value: time::OffsetDateTime) -> Self {
+        Self::Timestamp((value.unix_timestamp_nanos() / 1_000_000) as i64)
+    }
+}
+
+#[cfg(feature = "time")]
+impl RelativeTime {
+    /// Resolve this relative time to an offset date time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn to_offset_date_time(
+        &self,
+        now: time::OffsetDateTime,
+    ) -> Result<time::OffsetDateTime, ParseRelativeTimeError> {
+        now.checked_add(time::Duration::milliseconds(self.offset_millis()?))
+            .ok_or(ParseRelativeTimeError::Overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseRelativeTimeError, RelativeTime};
+    use crate::time_series::{GranularityUnit, TimestampOrRelative};
+
+    #[test]
+    fn test_parse_relative_time() {
+        for (input, expected) in [
+            ("now", RelativeTime::Now),
+            (
+                "2d-ago",
+                RelativeTime::Ago {
+                    amount: 2,
+                    unit: GranularityUnit::Day,
+                },
+            ),
+            (
+                "15m-ahead",
+                RelativeTime::Ahead {
+                    amount: 15,
+                    unit: GranularityUnit::Minute,
+                },
+            ),
+            (
+                "1w-ago",
+                RelativeTime::Ago {
+                    amount: 1,
+                    unit: GranularityUnit::Week,
+                },
+            ),
+        ] {
+            let parsed: RelativeTime = input.parse().unwrap();
+            assert_eq!(expected, parsed);
+            assert_eq!(input, parsed.to_string());
+        }
+
+        assert!(matches!(
+            "2mo-ago".parse::<RelativeTime>(),
+            Err(ParseRelativeTimeError::UnsupportedUnit(_))
+        ));
+        assert!("d-ago".parse::<RelativeTime>().is_err());
+        assert!("2d".parse::<RelativeTime>().is_err());
+        assert!("2x-ago".parse::<RelativeTime>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_relative_time() {
+        let now = 1_000_000_000;
+        assert_eq!(
+            Ok(now - 2 * 3_600_000),
+            TimestampOrRelative::from("2h-ago").to_timestamp(now)
+        );
+        assert_eq!(
+            Ok(now + 30_000),
+            TimestampOrRelative::from("30s-ahead").to_timestamp(now)
+        );
+        assert_eq!(Ok(123), TimestampOrRelative::from(123).to_timestamp(now));
+        assert_eq!(
+            Err(ParseRelativeTimeError::Overflow),
+            RelativeTime::Ago {
+                amount: u64::MAX,
+                unit: GranularityUnit::Second
+            }
+            .to_timestamp(now)
+        );
+    }
+}
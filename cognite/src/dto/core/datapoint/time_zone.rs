@@ -0,0 +1,295 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{DatapointsFilter, Granularity, GranularityUnit};
+
+/// Largest offset from UTC accepted by the API, in minutes.
+const MAX_OFFSET_MINUTES: i32 = 14 * 60;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+/// Error on failure to parse or validate a time zone.
+pub enum TimeZoneError {
+    /// Time zone is not `UTC`, a fixed offset, or an IANA time zone name.
+    #[error("Invalid time zone '{0}', must be 'UTC', an offset like '+02:00', or an IANA time zone like 'Europe/Oslo'")]
+    InvalidFormat(String),
+    /// Fixed offset is out of range, or not a multiple of 15 minutes.
+    #[error("Invalid time zone offset {0} minutes, must be a multiple of 15 minutes between -14:00 and +14:00")]
+    InvalidOffset(i32),
+    /// The granularity cannot be aligned to a time zone.
+    #[error("Granularity '{granularity}' cannot be used with time zone '{time_zone}', time zones only apply to granularities of an hour or longer")]
+    UnsupportedGranularity {
+        /// Granularity of the query.
+        granularity: Granularity,
+        /// Time zone of the query.
+        time_zone: TimeZone,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Time zone used to align datapoint aggregates.
+///
+/// Aggregates with a granularity of an hour or longer are aligned to the start of
+/// the hour, day, week, month, quarter, or year in this time zone. For IANA time zones,
+/// the length of each aggregate period may vary, typically due to daylight saving time.
+///
+/// Parse from `UTC` or `Z`, a fixed offset like `+02:00` or `UTC-05:30`,
+/// or an IANA time zone name like `Europe/Oslo`.
+pub enum TimeZone {
+    /// Coordinated universal time.
+    Utc,
+    /// Fixed offset from UTC in minutes. Must be a multiple of 15 minutes.
+    FixedOffset(i32),
+    /// IANA time zone name, like `Europe/Oslo`.
+    Iana(String),
+}
+
+impl TimeZone {
+    /// Create a fixed offset time zone, validating the offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `minutes` - Offset from UTC in minutes.
+    pub fn fixed_offset(minutes: i32) -> Result<Self, TimeZoneError> {
+        if minutes % 15 != 0 || minutes.abs() > MAX_OFFSET_MINUTES {
+            return Err(TimeZoneError::InvalidOffset(minutes));
+        }
+        Ok(Self::FixedOffset(minutes))
+    }
+
+    /// Create a time zone from an IANA time zone name, like `Europe/Oslo`.
+    /// This only validates the format of the name, not that the time zone exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - IANA time zone name.
+    pub fn iana(name: impl Into<String>) -> Result<Self, TimeZoneError> {
+        let name = name.into();
+        let valid_part = |p: &str| {
+            !p.is_empty()
+                && p.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        };
+        let mut parts = name.split('/');
+        let first = parts.next().unwrap_or_default();
+        let mut rest = parts.peekable();
+        if !first.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !valid_part(first)
+            || rest.peek().is_none()
+            || !rest.all(valid_part)
+        {
+            return Err(TimeZoneError::InvalidFormat(name));
+        }
+        Ok(Self::Iana(name))
+    }
+
+    /// Whether this time zone is always equal to UTC.
+    pub fn is_utc(&self) -> bool {
+        matches!(self, Self::Utc | Self::FixedOffset(0))
+    }
+
+    /// Check that `granularity` can be used for aggregates in this time zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `granularity` - Granularity of the aggregates.
+    pub fn validate_granularity(&self, granularity: &Granularity) -> Result<(), TimeZoneError> {
+        if !self.is_utc()
+            && matches!(
+                granularity.unit(),
+                GranularityUnit::Second | GranularityUnit::Minute
+            )
+        {
+            return Err(TimeZoneError::UnsupportedGranularity {
+                granularity: *granularity,
+                time_zone: self.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn parse_offset(s: &str) -> Option<i32> {
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+impl FromStr for TimeZone {
+    type Err = TimeZoneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "UTC" || s == "Z" {
+            return Ok(Self::Utc);
+        }
+        let offset = s.strip_prefix("UTC").unwrap_or(s);
+        if offset.starts_with(['+', '-']) {
+            let minutes =
+                parse_offset(offset).ok_or_else(|| TimeZoneError::InvalidFormat(s.to_owned()))?;
+            return Self::fixed_offset(minutes);
+        }
+        Self::iana(s)
+    }
+}
+
+impl Display for TimeZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Utc => write!(f, "UTC"),
+            Self::FixedOffset(minutes) => {
+                let sign = if *minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.abs();
+                write!(f, "{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+            }
+            Self::Iana(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl Serialize for TimeZone {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeZone {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::FixedOffset> for TimeZone {
+    type Error = TimeZoneError;
+
+    fn try_from(value: chrono::FixedOffset) -> Result<Self, Self::Error> {
+        let seconds = value.local_minus_utc();
+        if seconds % 60 != 0 {
+            return Err(TimeZoneError::InvalidOffset(seconds / 60));
+        }
+        Self::fixed_offset(seconds / 60)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<time::UtcOffset> for TimeZone {
+    type Error = TimeZoneError;
+
+    fn try_from(value: time::UtcOffset) -> Result<Self, Self::Error> {
+        let seconds = value.whole_seconds();
+        if seconds % 60 != 0 {
+            return Err(TimeZoneError::InvalidOffset(seconds / 60));
+        }
+        Self::fixed_offset(seconds / 60)
+    }
+}
+
+impl DatapointsFilter {
+    /// Check that the time zone and granularity of the filter and each query can be combined.
+    /// Time zone and granularity on individual queries override those on the filter.
+    pub fn validate_time_zone(&self) -> Result<(), TimeZoneError> {
+        if let (Some(tz), Some(g)) = (&self.time_zone, &self.granularity) {
+            tz.validate_granularity(g)?;
+        }
+        for item in &self.items {
+            let tz = item.time_zone.as_ref().or(self.time_zone.as_ref());
+            let g = item.granularity.as_ref().or(self.granularity.as_ref());
+            if let (Some(tz), Some(g)) = (tz, g) {
+                tz.validate_granularity(g)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeZone, TimeZoneError};
+    use crate::time_series::{DatapointsFilter, DatapointsQuery};
+
+    #[test]
+    fn test_parse_time_zone() {
+        for (input, expected, formatted) in [
+            ("UTC", TimeZone::Utc, "UTC"),
+            ("Z", TimeZone::Utc, "UTC"),
+            ("+02:00", TimeZone::FixedOffset(120), "+02:00"),
+            ("UTC-05:30", TimeZone::FixedOffset(-330), "-05:30"),
+            ("UTC+14:00", TimeZone::FixedOffset(840), "+14:00"),
+            (
+                "Europe/Oslo",
+                TimeZone::Iana("Europe/Oslo".to_owned()),
+                "Europe/Oslo",
+            ),
+            (
+                "America/Argentina/Buenos_Aires",
+                TimeZone::Iana("America/Argentina/Buenos_Aires".to_owned()),
+                "America/Argentina/Buenos_Aires",
+            ),
+        ] {
+            let tz: TimeZone = input.parse().unwrap();
+            assert_eq!(expected, tz);
+            assert_eq!(formatted, tz.to_string());
+            assert_eq!(tz, tz.to_string().parse().unwrap());
+        }
+
+        assert_eq!(
+            Err(TimeZoneError::InvalidOffset(130)),
+            "+02:10".parse::<TimeZone>()
+        );
+        assert!("+15:00".parse::<TimeZone>().is_err());
+        assert!("+2:00".parse::<TimeZone>().is_err());
+        assert!("Oslo".parse::<TimeZone>().is_err());
+        assert!("Europe/".parse::<TimeZone>().is_err());
+        assert!("".parse::<TimeZone>().is_err());
+    }
+
+    #[test]
+    fn test_validate_time_zone() {
+        let mut filter = DatapointsFilter {
+            items: vec![DatapointsQuery {
+                id: 1.into(),
+                granularity: Some("1mo".parse().unwrap()),
+                ..Default::default()
+            }],
+            granularity: Some("5m".parse().unwrap()),
+            time_zone: Some("UTC".parse().unwrap()),
+            ..Default::default()
+        };
+        filter.validate_time_zone().unwrap();
+
+        filter.time_zone = Some("Europe/Oslo".parse().unwrap());
+        assert!(matches!(
+            filter.validate_time_zone(),
+            Err(TimeZoneError::UnsupportedGranularity { .. })
+        ));
+
+        filter.granularity = Some("1d".parse().unwrap());
+        filter.validate_time_zone().unwrap();
+
+        filter.items[0].granularity = Some("30s".parse().unwrap());
+        filter.time_zone = None;
+        filter.items[0].time_zone = Some("+01:00".parse().unwrap());
+        assert!(filter.validate_time_zone().is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt::{Debug, Write};
+use std::sync::Arc;
+
+use super::granularity::{civil_from_days, days_from_civil, days_in_month};
+use super::time_zone::parse_offset;
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Function parsing a timestamp into milliseconds since epoch.
+pub type TimestampParser = Arc<dyn Fn(&str) -> Option<i64> + Send + Sync>;
+
+#[derive(Clone, Default)]
+/// Format of timestamps when reading and writing datapoints to files.
+pub enum TimestampFormat {
+    /// Integer milliseconds since epoch.
+    #[default]
+    EpochMillis,
+    /// Seconds since epoch. Fractional seconds are allowed when parsing.
+    EpochSeconds,
+    /// RFC 3339 timestamps, like `2024-01-31T12:00:00.000Z`. Timestamps are written in UTC,
+    /// and parsed with any offset, like `2024-01-31T13:00:00+01:00`.
+    Rfc3339,
+    /// Parse timestamps using a custom function. Only used when parsing,
+    /// timestamps are written as milliseconds since epoch.
+    Custom(TimestampParser),
+}
+
+impl Debug for TimestampFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EpochMillis => write!(f, "EpochMillis"),
+            Self::EpochSeconds => write!(f, "EpochSeconds"),
+            Self::Rfc3339 => write!(f, "Rfc3339"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl TimestampFormat {
+    /// Parse a timestamp into milliseconds since epoch.
+    /// Returns `None` if the timestamp is not valid for this format.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Timestamp to parse.
+    pub fn parse(&self, value: &str) -> Option<i64> {
+        let value = value.trim();
+        match self {
+            Self::EpochMillis => value.parse().ok(),
+            Self::EpochSeconds => match value.parse::<i64>() {
+                Ok(seconds) => seconds.checked_mul(1000),
+                Err(_) => {
+                    let seconds: f64 = value.parse().ok()?;
+                    let millis = (seconds * 1000.0).round();
+                    millis.is_finite().then_some(millis as i64)
+                }
+            },
+            Self::Rfc3339 => parse_rfc3339(value),
+            Self::Custom(parser) => parser(value),
+        }
+    }
+
+    /// Format a timestamp in milliseconds since epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp to format.
+    pub fn format(&self, timestamp: i64) -> String {
+        match self {
+            Self::EpochMillis | Self::Custom(_) => timestamp.to_string(),
+            Self::EpochSeconds => {
+                if timestamp % 1000 == 0 {
+                    (timestamp / 1000).to_string()
+                } else {
+                    (timestamp as f64 / 1000.0).to_string()
+                }
+            }
+            Self::Rfc3339 => format_rfc3339(timestamp),
+        }
+    }
+}
+
+fn format_rfc3339(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(MILLIS_PER_DAY);
+    let millis = timestamp.rem_euclid(MILLIS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let mut res = String::with_capacity(24);
+    let _ = write!(
+        res,
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    );
+    res
+}
+
+fn parse_number(value: &str, len: usize) -> Option<i64> {
+    if value.len() != len || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn parse_rfc3339(value: &str) -> Option<i64> {
+    let (date, time) = value.split_once(['T', 't', ' '])?;
+    let mut date_parts = date.splitn(3, '-');
+    let year = parse_number(date_parts.next()?, 4)?;
+    let month = parse_number(date_parts.next()?, 2)?;
+    let day = parse_number(date_parts.next()?, 2)?;
+
+    let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let idx = time.rfind(['+', '-'])?;
+        (&time[..idx], parse_offset(&time[idx..])?)
+    };
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut time_parts = time.splitn(3, ':');
+    let hour = parse_number(time_parts.next()?, 2)?;
+    let minute = parse_number(time_parts.next()?, 2)?;
+    let second = parse_number(time_parts.next()?, 2)?;
+    let millis = match fraction {
+        Some(f) if !f.is_empty() && f.bytes().all(|b| b.is_ascii_digit()) => {
+            // Only millisecond precision is kept.
+            let digits = &f[..f.len().min(3)];
+            digits.parse::<i64>().ok()? * 10i64.pow(3 - digits.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    Some(
+        days * MILLIS_PER_DAY + hour * 3_600_000 + minute * 60_000 + second * 1000 + millis
+            - i64::from(offset) * 60_000,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::TimestampFormat;
+
+    #[test]
+    fn test_timestamp_format() {
+        let ts = 1_706_702_400_123;
+        assert_eq!(
+            "2024-01-31T12:00:00.123Z",
+            TimestampFormat::Rfc3339.format(ts)
+        );
+        assert_eq!(
+            Some(ts),
+            TimestampFormat::Rfc3339.parse("2024-01-31T12:00:00.123Z")
+        );
+        assert_eq!(
+            Some(ts),
+            TimestampFormat::Rfc3339.parse("2024-01-31 13:30:00.123456+01:30")
+        );
+        assert_eq!(
+            Some(-1000),
+            TimestampFormat::Rfc3339.parse("1969-12-31T23:59:59Z")
+        );
+        assert_eq!(
+            "1969-12-31T23:59:59.000Z",
+            TimestampFormat::Rfc3339.format(-1000)
+        );
+        assert_eq!(None, TimestampFormat::Rfc3339.parse("2024-02-30T00:00:00Z"));
+        assert_eq!(None, TimestampFormat::Rfc3339.parse("2024-01-31T12:00:00"));
+        assert_eq!(
+            Some(1_709_164_800_000),
+            TimestampFormat::Rfc3339.parse("2024-02-29T00:00:00Z")
+        );
+
+        assert_eq!(Some(1500), TimestampFormat::EpochSeconds.parse("1.5"));
+        assert_eq!(Some(2000), TimestampFormat::EpochSeconds.parse("2"));
+        assert_eq!("1.5", TimestampFormat::EpochSeconds.format(1500));
+        assert_eq!(Some(5), TimestampFormat::EpochMillis.parse(" 5 "));
+        assert_eq!(None, TimestampFormat::EpochMillis.parse("5.5"));
+
+        let custom = TimestampFormat::Custom(Arc::new(|s| s.strip_prefix("t")?.parse().ok()));
+        assert_eq!(Some(7), custom.parse("t7"));
+        assert_eq!("7", custom.format(7));
+    }
+}
@@ -48,6 +48,8 @@ pub struct Event {
     pub last_updated_time: i64,
 }
 
+impl_with_identity!(Event);
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
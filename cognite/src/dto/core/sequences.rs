@@ -76,6 +76,8 @@ pub struct Sequence {
     pub data_set_id: Option<i64>,
 }
 
+impl_with_identity!(Sequence);
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -1,15 +1,22 @@
 mod filter;
+mod subscription;
 mod synthetic;
+mod synthetic_expression;
 
 pub use self::filter::*;
+pub use self::subscription::*;
 pub use self::synthetic::*;
+pub use self::synthetic_expression::*;
 
 use crate::models::instances::CogniteTimeseries;
 use crate::models::instances::InstanceId;
 use crate::IntoPatch;
 use crate::IntoPatchItem;
 use crate::UpsertOptions;
-use crate::{EqIdentity, Identity, Patch, UpdateList, UpdateMap, UpdateSet, UpdateSetNull};
+use crate::{
+    EqIdentity, Identity, IdentityOrInstance, Patch, UpdateList, UpdateMap, UpdateSet,
+    UpdateSetNull, WithIdentity,
+};
 
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
@@ -54,6 +61,21 @@ pub struct TimeSeries {
     pub data_set_id: Option<i64>,
 }
 
+impl_with_identity!(TimeSeries);
+
+impl WithIdentity<IdentityOrInstance> for TimeSeries {
+    fn identities(&self) -> Vec<IdentityOrInstance> {
+        let mut res: Vec<IdentityOrInstance> = WithIdentity::<Identity>::identities(self)
+            .into_iter()
+            .map(IdentityOrInstance::Identity)
+            .collect();
+        if let Some(instance_id) = &self.instance_id {
+            res.push(IdentityOrInstance::instance_id(instance_id.clone()));
+        }
+        res
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
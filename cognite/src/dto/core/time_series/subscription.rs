@@ -0,0 +1,402 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::models::instances::InstanceId;
+use crate::time_series::{DatapointDouble, DatapointString};
+use crate::{
+    to_query, AdvancedFilter, Identity, IdentityOrInstance, IntoParams, Patch, SetCursor,
+    UpdateList, UpdateSet, UpdateSetNull,
+};
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A datapoint subscription, which lets you listen to changes to datapoints
+/// in a set of time series.
+pub struct DatapointSubscription {
+    /// Subscription external ID.
+    pub external_id: String,
+    /// Subscription name.
+    pub name: Option<String>,
+    /// Subscription description.
+    pub description: Option<String>,
+    /// ID of the data set this subscription belongs to.
+    pub data_set_id: Option<i64>,
+    /// Number of time series in the subscription.
+    pub time_series_count: Option<i64>,
+    /// Filter defining which time series are in the subscription.
+    /// If this is not set, the time series are listed explicitly.
+    pub filter: Option<AdvancedFilter>,
+    /// Number of partitions the subscription is split into. Each partition
+    /// can be read independently, with its own cursor.
+    pub partition_count: u32,
+    /// Time this subscription was created, in milliseconds since epoch.
+    pub created_time: i64,
+    /// Time this subscription was last updated, in milliseconds since epoch.
+    pub last_updated_time: i64,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Create a datapoint subscription. The subscription is either defined by
+/// a list of time series, or by a filter.
+pub struct AddDatapointSubscription {
+    /// Subscription external ID. Must be unique.
+    pub external_id: String,
+    /// Subscription name.
+    pub name: Option<String>,
+    /// Subscription description.
+    pub description: Option<String>,
+    /// ID of the data set this subscription belongs to.
+    pub data_set_id: Option<i64>,
+    /// Number of partitions to split the subscription into, between 1 and 100.
+    pub partition_count: u32,
+    /// External IDs of time series in the subscription. Not compatible with `filter`.
+    pub time_series_ids: Option<Vec<String>>,
+    /// Instance IDs of time series in the subscription. Not compatible with `filter`.
+    pub instance_ids: Option<Vec<InstanceId>>,
+    /// Filter on time series properties defining the subscription.
+    /// Not compatible with `time_series_ids` or `instance_ids`.
+    pub filter: Option<AdvancedFilter>,
+}
+
+impl AddDatapointSubscription {
+    /// Create a subscription listening to a list of time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `external_id` - Subscription external ID.
+    /// * `partition_count` - Number of partitions.
+    /// * `time_series` - Time series in the subscription, identified by external ID or instance ID.
+    ///   Internal IDs are not supported by the API.
+    pub fn from_time_series(
+        external_id: impl Into<String>,
+        partition_count: u32,
+        time_series: impl IntoIterator<Item = IdentityOrInstance>,
+    ) -> crate::Result<Self> {
+        let mut time_series_ids = Vec::new();
+        let mut instance_ids = Vec::new();
+        for ts in time_series {
+            match ts {
+                IdentityOrInstance::Identity(Identity::ExternalId { external_id }) => {
+                    time_series_ids.push(external_id)
+                }
+                IdentityOrInstance::InstanceId { instance_id } => instance_ids.push(instance_id),
+                IdentityOrInstance::Identity(Identity::Id { id }) => {
+                    return Err(crate::Error::Other(format!(
+                        "Datapoint subscriptions do not support internal IDs, got {id}"
+                    )))
+                }
+            }
+        }
+        Ok(Self {
+            external_id: external_id.into(),
+            partition_count,
+            time_series_ids: (!time_series_ids.is_empty()).then_some(time_series_ids),
+            instance_ids: (!instance_ids.is_empty()).then_some(instance_ids),
+            ..Default::default()
+        })
+    }
+
+    /// Create a subscription listening to all time series matching a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `external_id` - Subscription external ID.
+    /// * `partition_count` - Number of partitions.
+    /// * `filter` - Filter on time series properties.
+    pub fn from_filter(
+        external_id: impl Into<String>,
+        partition_count: u32,
+        filter: AdvancedFilter,
+    ) -> Self {
+        Self {
+            external_id: external_id.into(),
+            partition_count,
+            filter: Some(filter),
+            ..Default::default()
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Update a datapoint subscription.
+pub struct PatchDatapointSubscription {
+    /// Subscription name.
+    pub name: Option<UpdateSetNull<String>>,
+    /// Subscription description.
+    pub description: Option<UpdateSetNull<String>>,
+    /// ID of the data set this subscription belongs to.
+    pub data_set_id: Option<UpdateSetNull<i64>>,
+    /// External IDs of time series in the subscription.
+    pub time_series_ids: Option<UpdateList<String, String>>,
+    /// Instance IDs of time series in the subscription.
+    pub instance_ids: Option<UpdateList<InstanceId, InstanceId>>,
+    /// Filter defining the subscription. Only for subscriptions defined by a filter.
+    pub filter: Option<UpdateSet<AdvancedFilter>>,
+}
+
+impl From<DatapointSubscription> for Patch<PatchDatapointSubscription> {
+    fn from(value: DatapointSubscription) -> Self {
+        Patch {
+            id: Identity::ExternalId {
+                external_id: value.external_id,
+            },
+            update: PatchDatapointSubscription {
+                name: value.name.map(|set| UpdateSetNull::Set { set }),
+                description: value.description.map(|set| UpdateSetNull::Set { set }),
+                data_set_id: value.data_set_id.map(|set| UpdateSetNull::Set { set }),
+                filter: value.filter.map(UpdateSet::set),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Query for listing datapoint subscriptions.
+pub struct DatapointSubscriptionQuery {
+    /// Maximum number of subscriptions to return, default 100, maximum 100.
+    pub limit: Option<u32>,
+    /// Cursor for pagination.
+    pub cursor: Option<String>,
+}
+
+impl SetCursor for DatapointSubscriptionQuery {
+    fn set_cursor(&mut self, cursor: Option<String>) {
+        self.cursor = cursor;
+    }
+}
+
+impl IntoParams for DatapointSubscriptionQuery {
+    fn into_params(self) -> Vec<(String, String)> {
+        let mut params = vec![];
+        to_query("limit", &self.limit, &mut params);
+        to_query("cursor", &self.cursor, &mut params);
+        params
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Query for listing the time series in a datapoint subscription.
+pub struct ListSubscriptionMembersQuery {
+    /// Subscription external ID.
+    pub external_id: String,
+    /// Maximum number of results to return.
+    pub limit: Option<i32>,
+    /// Cursor for pagination.
+    pub cursor: Option<String>,
+}
+
+impl ListSubscriptionMembersQuery {
+    /// Create a query for the members of the subscription with the given external ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `external_id` - Subscription external ID.
+    pub fn new(external_id: impl Into<String>) -> Self {
+        Self {
+            external_id: external_id.into(),
+            limit: None,
+            cursor: None,
+        }
+    }
+}
+
+impl IntoParams for ListSubscriptionMembersQuery {
+    fn into_params(self) -> Vec<(String, String)> {
+        let mut params = vec![("externalId".to_owned(), self.external_id)];
+        to_query("limit", &self.limit, &mut params);
+        to_query("cursor", &self.cursor, &mut params);
+        params
+    }
+}
+
+impl SetCursor for ListSubscriptionMembersQuery {
+    fn set_cursor(&mut self, cursor: Option<String>) {
+        self.cursor = cursor;
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+/// Identifiers of a time series in a datapoint subscription.
+pub struct SubscriptionTimeSeriesId {
+    /// Time series internal ID. Missing if the time series does not exist yet.
+    pub id: Option<i64>,
+    /// Time series external ID.
+    pub external_id: Option<String>,
+    /// Time series instance ID.
+    pub instance_id: Option<InstanceId>,
+}
+
+impl SubscriptionTimeSeriesId {
+    /// Get the identity of the time series, preferring instance ID, then external ID,
+    /// then internal ID.
+    pub fn identity(&self) -> Option<IdentityOrInstance> {
+        if let Some(instance_id) = &self.instance_id {
+            Some(instance_id.clone().into())
+        } else if let Some(external_id) = &self.external_id {
+            Some(external_id.clone().into())
+        } else {
+            self.id.map(Into::into)
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// A partition of a datapoint subscription, with a cursor marking how far it has been read.
+/// Store the partitions returned from the API to resume reading later.
+pub struct SubscriptionPartition {
+    /// Partition index, from 0 to `partition_count - 1`.
+    pub index: u32,
+    /// Cursor to resume reading from. If this is not set, reading starts according
+    /// to `initialize_cursors`.
+    #[serde(alias = "nextCursor")]
+    pub cursor: Option<String>,
+}
+
+impl SubscriptionPartition {
+    /// Create a partition without a cursor.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Partition index.
+    pub fn new(index: u32) -> Self {
+        Self {
+            index,
+            cursor: None,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Request for reading data from a datapoint subscription.
+pub struct ListSubscriptionDataRequest {
+    /// Subscription external ID.
+    pub external_id: String,
+    /// Partitions to read from, with cursors.
+    pub partitions: Vec<SubscriptionPartition>,
+    /// Maximum number of datapoints to return per partition.
+    pub limit: Option<u32>,
+    /// Where to start reading partitions without a cursor, `now` or a
+    /// relative time like `2d-ago`. Defaults to `now`.
+    pub initialize_cursors: Option<String>,
+    /// Include status codes in the response.
+    pub include_status: Option<bool>,
+    /// Ignore datapoints with bad status codes.
+    pub ignore_bad_data_points: Option<bool>,
+    /// Treat datapoints with uncertain status codes as bad.
+    pub treat_uncertain_as_bad: Option<bool>,
+    /// How long the server waits for new data, if there is none, in seconds. Between 0 and 60.
+    pub poll_timeout_seconds: Option<u32>,
+}
+
+impl ListSubscriptionDataRequest {
+    /// Create a request for reading the given partitions of a subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `external_id` - Subscription external ID.
+    /// * `partitions` - Partitions to read from.
+    pub fn new(external_id: impl Into<String>, partitions: Vec<SubscriptionPartition>) -> Self {
+        Self {
+            external_id: external_id.into(),
+            partitions,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+/// A datapoint inserted or updated in a datapoint subscription.
+pub enum SubscriptionDatapoint {
+    /// Numeric datapoint.
+    Numeric(DatapointDouble),
+    /// String datapoint.
+    String(DatapointString),
+}
+
+impl SubscriptionDatapoint {
+    /// Timestamp of the datapoint, in milliseconds since epoch.
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            Self::Numeric(d) => d.timestamp,
+            Self::String(d) => d.timestamp,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// A range of deleted datapoints.
+pub struct SubscriptionDeleteRange {
+    /// Start of the deleted range, inclusive.
+    pub inclusive_begin: i64,
+    /// End of the deleted range, exclusive. If not set, only the datapoint at
+    /// `inclusive_begin` was deleted.
+    pub exclusive_end: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Changes to datapoints in a single time series.
+pub struct DatapointSubscriptionUpdate {
+    /// The time series that changed.
+    pub time_series: SubscriptionTimeSeriesId,
+    /// Datapoints that were inserted or updated.
+    #[serde(default)]
+    pub upserts: Vec<SubscriptionDatapoint>,
+    /// Ranges of datapoints that were deleted.
+    #[serde(default)]
+    pub deletes: Vec<SubscriptionDeleteRange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Time series added to or removed from a subscription.
+pub struct SubscriptionChanges {
+    /// Time series added to the subscription.
+    #[serde(default)]
+    pub added: Vec<SubscriptionTimeSeriesId>,
+    /// Time series removed from the subscription.
+    #[serde(default)]
+    pub removed: Vec<SubscriptionTimeSeriesId>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Response from reading data from a datapoint subscription.
+pub struct ListSubscriptionDataResponse {
+    /// Changes to datapoints, per time series.
+    #[serde(default)]
+    pub updates: Vec<DatapointSubscriptionUpdate>,
+    /// Changes to the set of time series in the subscription.
+    pub subscription_changes: Option<SubscriptionChanges>,
+    /// Partitions with cursors pointing past the data in this response.
+    /// Store these to resume reading later.
+    pub partitions: Vec<SubscriptionPartition>,
+    /// Whether there is more data available right away.
+    pub has_next: bool,
+}
+
+impl ListSubscriptionDataResponse {
+    /// Whether the response contains no datapoint changes or subscription changes.
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+            && self
+                .subscription_changes
+                .as_ref()
+                .is_none_or(|c| c.added.is_empty() && c.removed.is_empty())
+    }
+}
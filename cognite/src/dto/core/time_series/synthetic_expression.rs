@@ -0,0 +1,1051 @@
+use std::{
+    fmt::{Display, Write},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::models::instances::InstanceId;
+use crate::time_series::{Aggregate, Granularity, SyntheticTimeSeriesQuery};
+use crate::{Identity, IdentityOrInstance};
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Invalid synthetic time series expression at position {position}: {message}")]
+/// Error on failure to parse or validate a synthetic time series expression.
+pub struct ParseExpressionError {
+    /// Byte offset into the expression where the error was found.
+    pub position: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl ParseExpressionError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Reference to a time series in a synthetic time series expression,
+/// like `ts{externalId='my-ts', aggregate='average', granularity='1h'}`.
+pub struct SyntheticTimeSeriesRef {
+    /// ID, external ID, or instance ID of the time series.
+    pub id: IdentityOrInstance,
+    /// Aggregate to use instead of raw datapoints. Requires `granularity`.
+    pub aggregate: Option<Aggregate>,
+    /// Granularity of the aggregate.
+    pub granularity: Option<Granularity>,
+    /// Alignment of aggregates, in milliseconds since epoch.
+    pub alignment: Option<i64>,
+    /// Unit to convert the datapoints to, from the unit catalog.
+    pub target_unit: Option<String>,
+    /// Unit system to convert the datapoints to, from the unit catalog.
+    pub target_unit_system: Option<String>,
+}
+
+impl SyntheticTimeSeriesRef {
+    /// Create a reference to a time series with raw datapoints.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID, external ID, or instance ID of the time series.
+    pub fn new(id: impl Into<IdentityOrInstance>) -> Self {
+        Self {
+            id: id.into(),
+            aggregate: None,
+            granularity: None,
+            alignment: None,
+            target_unit: None,
+            target_unit_system: None,
+        }
+    }
+
+    /// Use an aggregate instead of raw datapoints.
+    ///
+    /// # Arguments
+    ///
+    /// * `aggregate` - Aggregate to use.
+    /// * `granularity` - Granularity of the aggregate.
+    pub fn aggregate(mut self, aggregate: Aggregate, granularity: Granularity) -> Self {
+        self.aggregate = Some(aggregate);
+        self.granularity = Some(granularity);
+        self
+    }
+
+    /// Convert datapoints to a unit from the unit catalog.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - External ID of the target unit.
+    pub fn target_unit(mut self, unit: impl Into<String>) -> Self {
+        self.target_unit = Some(unit.into());
+        self
+    }
+
+    /// Convert datapoints to a unit system from the unit catalog.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit_system` - Name of the target unit system.
+    pub fn target_unit_system(mut self, unit_system: impl Into<String>) -> Self {
+        self.target_unit_system = Some(unit_system.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Binary operator in a synthetic time series expression.
+pub enum BinaryOperator {
+    /// Addition, `+`.
+    Add,
+    /// Subtraction, `-`.
+    Sub,
+    /// Multiplication, `*`.
+    Mul,
+    /// Division, `/`.
+    Div,
+    /// Exponentiation, `^`.
+    Pow,
+}
+
+impl BinaryOperator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Pow => "^",
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Add | Self::Sub => 1,
+            Self::Mul | Self::Div => 2,
+            Self::Pow => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Function available in synthetic time series expressions.
+pub enum SyntheticFunction {
+    /// Sine, `sin(x)`.
+    Sin,
+    /// Cosine, `cos(x)`.
+    Cos,
+    /// The constant pi, `pi()`.
+    Pi,
+    /// Natural logarithm, `ln(x)`.
+    Ln,
+    /// Power, `pow(x, y)`.
+    Pow,
+    /// Square root, `sqrt(x)`.
+    Sqrt,
+    /// Exponential function, `exp(x)`.
+    Exp,
+    /// Absolute value, `abs(x)`.
+    Abs,
+    /// Round to a number of decimals, `round(x, decimals)`.
+    Round,
+    /// Replace errors with a default value, `on_error(x, default)`.
+    OnError,
+    /// Maximum of the arguments, `max(x1, x2, ...)`.
+    Max,
+    /// Minimum of the arguments, `min(x1, x2, ...)`.
+    Min,
+    /// Average of the arguments, `avg(x1, x2, ...)`.
+    Avg,
+}
+
+impl SyntheticFunction {
+    /// Name of the function in the expression syntax.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+            Self::Pi => "pi",
+            Self::Ln => "ln",
+            Self::Pow => "pow",
+            Self::Sqrt => "sqrt",
+            Self::Exp => "exp",
+            Self::Abs => "abs",
+            Self::Round => "round",
+            Self::OnError => "on_error",
+            Self::Max => "max",
+            Self::Min => "min",
+            Self::Avg => "avg",
+        }
+    }
+
+    /// Minimum and maximum number of arguments. `None` means there is no upper limit.
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::Pi => (0, Some(0)),
+            Self::Pow | Self::Round | Self::OnError => (2, Some(2)),
+            Self::Max | Self::Min | Self::Avg => (1, None),
+            _ => (1, Some(1)),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "pi" => Self::Pi,
+            "ln" => Self::Ln,
+            "pow" => Self::Pow,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "abs" => Self::Abs,
+            "round" => Self::Round,
+            "on_error" => Self::OnError,
+            "max" => Self::Max,
+            "min" => Self::Min,
+            "avg" => Self::Avg,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A synthetic time series expression.
+///
+/// Expressions can be built using constructors and arithmetic operators, and are
+/// formatted to the API syntax using `Display`, with strings correctly escaped.
+/// They can also be parsed from strings, to validate stored expressions.
+///
+/// # Example
+///
+/// ```ignore
+/// let expr = (SyntheticExpression::ts(
+///     SyntheticTimeSeriesRef::new("a").aggregate(Aggregate::Average, "1h".parse()?),
+/// ) + SyntheticExpression::ts(123))
+///     / 2.0;
+/// assert_eq!(
+///     "(ts{externalId='a', aggregate='average', granularity='1h'} + ts{id=123}) / 2",
+///     expr.to_string()
+/// );
+/// ```
+pub enum SyntheticExpression {
+    /// A number literal.
+    Number(f64),
+    /// A time series.
+    TimeSeries(SyntheticTimeSeriesRef),
+    /// Negation, `-x`.
+    Negate(Box<SyntheticExpression>),
+    /// A binary operation.
+    Binary {
+        /// Operator.
+        op: BinaryOperator,
+        /// Left operand.
+        left: Box<SyntheticExpression>,
+        /// Right operand.
+        right: Box<SyntheticExpression>,
+    },
+    /// A function call.
+    Function {
+        /// Function to call.
+        function: SyntheticFunction,
+        /// Function arguments.
+        args: Vec<SyntheticExpression>,
+    },
+    /// Map string values to numbers, `map(x, ['a', 'b'], [1, 2], default)`.
+    Map {
+        /// Input expression, typically a string time series.
+        input: Box<SyntheticExpression>,
+        /// Values to map from.
+        from: Vec<String>,
+        /// Values to map to, must have the same length as `from`.
+        to: Vec<f64>,
+        /// Value used if the input is not in `from`.
+        default: f64,
+    },
+}
+
+impl SyntheticExpression {
+    /// Create a time series reference expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ts` - Time series reference.
+    pub fn ts(ts: impl Into<SyntheticTimeSeriesRef>) -> Self {
+        Self::TimeSeries(ts.into())
+    }
+
+    /// Create a function call expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `function` - Function to call.
+    /// * `args` - Function arguments.
+    pub fn call(function: SyntheticFunction, args: impl IntoIterator<Item = Self>) -> Self {
+        Self::Function {
+            function,
+            args: args.into_iter().collect(),
+        }
+    }
+
+    /// Create a map expression, mapping string values to numbers.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Input expression.
+    /// * `mapping` - Pairs of values to map from and to.
+    /// * `default` - Value used if the input is not in the mapping.
+    pub fn map(
+        input: Self,
+        mapping: impl IntoIterator<Item = (String, f64)>,
+        default: f64,
+    ) -> Self {
+        let (from, to) = mapping.into_iter().unzip();
+        Self::Map {
+            input: Box::new(input),
+            from,
+            to,
+            default,
+        }
+    }
+
+    /// Raise this expression to a power, `self ^ exponent`.
+    ///
+    /// # Arguments
+    ///
+    /// * `exponent` - Exponent.
+    pub fn pow(self, exponent: impl Into<Self>) -> Self {
+        self.binary(BinaryOperator::Pow, exponent.into())
+    }
+
+    /// Wrap this expression in `on_error`, replacing errors with `default`.
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - Value used where the expression fails to evaluate.
+    pub fn on_error(self, default: impl Into<Self>) -> Self {
+        Self::call(SyntheticFunction::OnError, [self, default.into()])
+    }
+
+    fn binary(self, op: BinaryOperator, right: Self) -> Self {
+        Self::Binary {
+            op,
+            left: Box::new(self),
+            right: Box::new(right),
+        }
+    }
+
+    /// Validate the expression, checking function arities, map lengths, and that
+    /// aggregates have a granularity.
+    pub fn validate(&self) -> Result<(), ParseExpressionError> {
+        match self {
+            Self::Number(n) => {
+                if !n.is_finite() {
+                    return Err(ParseExpressionError::new(0, "Numbers must be finite"));
+                }
+            }
+            Self::TimeSeries(ts) => {
+                if ts.aggregate.is_some() != ts.granularity.is_some() {
+                    return Err(ParseExpressionError::new(
+                        0,
+                        "Aggregate and granularity must be specified together",
+                    ));
+                }
+            }
+            Self::Negate(e) => e.validate()?,
+            Self::Binary { left, right, .. } => {
+                left.validate()?;
+                right.validate()?;
+            }
+            Self::Function { function, args } => {
+                let (min, max) = function.arity();
+                if args.len() < min || max.is_some_and(|m| args.len() > m) {
+                    return Err(ParseExpressionError::new(
+                        0,
+                        format!(
+                            "Wrong number of arguments to {}: {}",
+                            function.name(),
+                            args.len()
+                        ),
+                    ));
+                }
+                for arg in args {
+                    arg.validate()?;
+                }
+            }
+            Self::Map {
+                input, from, to, ..
+            } => {
+                if from.len() != to.len() {
+                    return Err(ParseExpressionError::new(
+                        0,
+                        "Map must have the same number of values to map from and to",
+                    ));
+                }
+                input.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Number(n) if n.is_sign_negative() => 3,
+            Self::Negate(_) => 3,
+            Self::Binary { op, .. } => op.precedence(),
+            _ => 5,
+        }
+    }
+}
+
+impl From<f64> for SyntheticExpression {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<SyntheticTimeSeriesRef> for SyntheticExpression {
+    fn from(value: SyntheticTimeSeriesRef) -> Self {
+        Self::TimeSeries(value)
+    }
+}
+
+macro_rules! impl_ts_ref_from {
+    ($($type:ty),*) => {
+        $(
+            impl From<$type> for SyntheticTimeSeriesRef {
+                fn from(value: $type) -> Self {
+                    Self::new(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_ts_ref_from!(i64, &str, String, Identity, InstanceId, IdentityOrInstance);
+
+macro_rules! impl_binary_op {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl std::ops::$trait for SyntheticExpression {
+            type Output = SyntheticExpression;
+
+            fn $method(self, rhs: Self) -> Self::Output {
+                self.binary($op, rhs)
+            }
+        }
+
+        impl std::ops::$trait<f64> for SyntheticExpression {
+            type Output = SyntheticExpression;
+
+            fn $method(self, rhs: f64) -> Self::Output {
+                self.binary($op, rhs.into())
+            }
+        }
+    };
+}
+
+impl_binary_op!(Add, add, BinaryOperator::Add);
+impl_binary_op!(Sub, sub, BinaryOperator::Sub);
+impl_binary_op!(Mul, mul, BinaryOperator::Mul);
+impl_binary_op!(Div, div, BinaryOperator::Div);
+
+impl std::ops::Neg for SyntheticExpression {
+    type Output = SyntheticExpression;
+
+    fn neg(self) -> Self::Output {
+        Self::Negate(Box::new(self))
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    f.write_char('\'')?;
+    for c in value.chars() {
+        if c == '\'' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('\'')
+}
+
+fn aggregate_name(aggregate: &Aggregate) -> String {
+    serde_json::to_value(aggregate)
+        .ok()
+        .and_then(|v| v.as_str().map(|s| s.to_owned()))
+        .unwrap_or_default()
+}
+
+impl Display for SyntheticTimeSeriesRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ts{{")?;
+        match &self.id {
+            IdentityOrInstance::Identity(Identity::Id { id }) => write!(f, "id={id}")?,
+            IdentityOrInstance::Identity(Identity::ExternalId { external_id }) => {
+                write!(f, "externalId=")?;
+                write_string(f, external_id)?;
+            }
+            IdentityOrInstance::InstanceId { instance_id } => {
+                write!(f, "space=")?;
+                write_string(f, &instance_id.space)?;
+                write!(f, ", externalId=")?;
+                write_string(f, &instance_id.external_id)?;
+            }
+        }
+        if let Some(aggregate) = &self.aggregate {
+            write!(f, ", aggregate=")?;
+            write_string(f, &aggregate_name(aggregate))?;
+        }
+        if let Some(granularity) = &self.granularity {
+            write!(f, ", granularity=")?;
+            write_string(f, &granularity.to_string())?;
+        }
+        if let Some(alignment) = &self.alignment {
+            write!(f, ", alignment={alignment}")?;
+        }
+        if let Some(unit) = &self.target_unit {
+            write!(f, ", targetUnit=")?;
+            write_string(f, unit)?;
+        }
+        if let Some(unit_system) = &self.target_unit_system {
+            write!(f, ", targetUnitSystem=")?;
+            write_string(f, unit_system)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+struct Operand<'a>(&'a SyntheticExpression, bool);
+
+impl Display for Operand<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.1 {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl Display for SyntheticExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{n}"),
+            Self::TimeSeries(ts) => write!(f, "{ts}"),
+            Self::Negate(e) => write!(f, "-{}", Operand(e, e.precedence() <= 3)),
+            Self::Binary { op, left, right } => {
+                let prec = op.precedence();
+                // Exponentiation is right associative, the other operators are left associative.
+                let (left_parens, right_parens) = if *op == BinaryOperator::Pow {
+                    (left.precedence() <= prec, right.precedence() < prec)
+                } else {
+                    (left.precedence() < prec, right.precedence() <= prec)
+                };
+                write!(
+                    f,
+                    "{} {} {}",
+                    Operand(left, left_parens),
+                    op.symbol(),
+                    Operand(right, right_parens)
+                )
+            }
+            Self::Function { function, args } => {
+                write!(f, "{}(", function.name())?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Map {
+                input,
+                from,
+                to,
+                default,
+            } => {
+                write!(f, "map({input}, [")?;
+                for (idx, value) in from.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_string(f, value)?;
+                }
+                write!(f, "], [")?;
+                for (idx, value) in to.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "], {default})")
+            }
+        }
+    }
+}
+
+impl From<SyntheticExpression> for SyntheticTimeSeriesQuery {
+    fn from(value: SyntheticExpression) -> Self {
+        Self {
+            expression: value.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    String(String),
+    Symbol(char),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = pos;
+            let mut prev = c;
+            while let Some(&(i, c)) = chars.peek() {
+                let is_exp_sign = (c == '+' || c == '-') && (prev == 'e' || prev == 'E');
+                if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || is_exp_sign {
+                    end = i + c.len_utf8();
+                    prev = c;
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let text = &input[pos..end];
+            let value = text
+                .parse()
+                .map_err(|_| ParseExpressionError::new(pos, format!("Invalid number '{text}'")))?;
+            tokens.push((pos, Token::Number(value)));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = pos;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    end = i + 1;
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push((pos, Token::Ident(input[pos..end].to_owned())));
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut value = String::new();
+            let mut closed = false;
+            while let Some((_, next)) = chars.next() {
+                if next == '\\' {
+                    match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => break,
+                    }
+                } else if next == c {
+                    closed = true;
+                    break;
+                } else {
+                    value.push(next);
+                }
+            }
+            if !closed {
+                return Err(ParseExpressionError::new(pos, "Unterminated string"));
+            }
+            tokens.push((pos, Token::String(value)));
+        } else if "+-*/^(){}[],=".contains(c) {
+            chars.next();
+            tokens.push((pos, Token::Symbol(c)));
+        } else {
+            return Err(ParseExpressionError::new(
+                pos,
+                format!("Unexpected character '{c}'"),
+            ));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.1)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|t| t.0).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|t| t.1.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseExpressionError {
+        ParseExpressionError::new(self.position(), message)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseExpressionError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{symbol}'")))
+        }
+    }
+
+    fn expression(&mut self) -> Result<SyntheticExpression, ParseExpressionError> {
+        let mut left = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOperator::Add
+            } else if self.eat('-') {
+                BinaryOperator::Sub
+            } else {
+                return Ok(left);
+            };
+            left = left.binary(op, self.term()?);
+        }
+    }
+
+    fn term(&mut self) -> Result<SyntheticExpression, ParseExpressionError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinaryOperator::Mul
+            } else if self.eat('/') {
+                BinaryOperator::Div
+            } else {
+                return Ok(left);
+            };
+            left = left.binary(op, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> Result<SyntheticExpression, ParseExpressionError> {
+        if self.eat('-') {
+            return Ok(match self.unary()? {
+                SyntheticExpression::Number(n) => SyntheticExpression::Number(-n),
+                e => -e,
+            });
+        }
+        let base = self.primary()?;
+        if self.eat('^') {
+            return Ok(base.binary(BinaryOperator::Pow, self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn signed_number(&mut self) -> Result<f64, ParseExpressionError> {
+        let negative = self.eat('-');
+        match self.next() {
+            Some(Token::Number(n)) => Ok(if negative { -n } else { n }),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("Expected number"))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseExpressionError> {
+        match self.next() {
+            Some(Token::String(s)) => Ok(s),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("Expected string"))
+            }
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, ParseExpressionError>,
+    ) -> Result<Vec<T>, ParseExpressionError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        if self.eat(']') {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(']') {
+                return Ok(items);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn primary(&mut self) -> Result<SyntheticExpression, ParseExpressionError> {
+        let start = self.position();
+        match self.next() {
+            Some(Token::Number(n)) => Ok(SyntheticExpression::Number(n)),
+            Some(Token::Symbol('(')) => {
+                let inner = self.expression()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("ts") => self.time_series(),
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("map") => {
+                self.expect('(')?;
+                let input = self.expression()?;
+                self.expect(',')?;
+                let from = self.list(Self::string)?;
+                self.expect(',')?;
+                let to = self.list(Self::signed_number)?;
+                self.expect(',')?;
+                let default = self.signed_number()?;
+                self.expect(')')?;
+                if from.len() != to.len() {
+                    return Err(ParseExpressionError::new(
+                        start,
+                        "Map must have the same number of values to map from and to",
+                    ));
+                }
+                Ok(SyntheticExpression::Map {
+                    input: Box::new(input),
+                    from,
+                    to,
+                    default,
+                })
+            }
+            Some(Token::Ident(name)) => {
+                let function = SyntheticFunction::from_name(&name).ok_or_else(|| {
+                    ParseExpressionError::new(start, format!("Unknown function '{name}'"))
+                })?;
+                self.expect('(')?;
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expression()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                let (min, max) = function.arity();
+                if args.len() < min || max.is_some_and(|m| args.len() > m) {
+                    return Err(ParseExpressionError::new(
+                        start,
+                        format!("Wrong number of arguments to {}: {}", name, args.len()),
+                    ));
+                }
+                Ok(SyntheticExpression::Function { function, args })
+            }
+            _ => Err(ParseExpressionError::new(start, "Expected expression")),
+        }
+    }
+
+    fn time_series(&mut self) -> Result<SyntheticExpression, ParseExpressionError> {
+        let start = self.position();
+        self.expect('{')?;
+        let mut id = None;
+        let mut external_id = None;
+        let mut space = None;
+        let mut ts = SyntheticTimeSeriesRef::new(0);
+        loop {
+            let key_pos = self.position();
+            let key = match self.next() {
+                Some(Token::Ident(key)) => key,
+                _ => {
+                    return Err(ParseExpressionError::new(
+                        key_pos,
+                        "Expected parameter name",
+                    ))
+                }
+            };
+            self.expect('=')?;
+            match key.as_str() {
+                "id" => match self.next() {
+                    Some(Token::Number(n)) if n.fract() == 0.0 => id = Some(n as i64),
+                    _ => return Err(ParseExpressionError::new(key_pos, "Expected integer id")),
+                },
+                "externalId" => external_id = Some(self.string()?),
+                "space" => space = Some(self.string()?),
+                "aggregate" => {
+                    let name = self.string()?;
+                    ts.aggregate = Some(
+                        serde_json::from_value(serde_json::Value::String(name.clone())).map_err(
+                            |_| {
+                                ParseExpressionError::new(
+                                    key_pos,
+                                    format!("Unknown aggregate '{name}'"),
+                                )
+                            },
+                        )?,
+                    );
+                }
+                "granularity" => {
+                    ts.granularity = Some(
+                        self.string()?
+                            .parse()
+                            .map_err(|e| ParseExpressionError::new(key_pos, format!("{e}")))?,
+                    );
+                }
+                "alignment" => ts.alignment = Some(self.signed_number()? as i64),
+                "targetUnit" => ts.target_unit = Some(self.string()?),
+                "targetUnitSystem" => ts.target_unit_system = Some(self.string()?),
+                _ => {
+                    return Err(ParseExpressionError::new(
+                        key_pos,
+                        format!("Unknown time series parameter '{key}'"),
+                    ))
+                }
+            }
+            if self.eat('}') {
+                break;
+            }
+            self.expect(',')?;
+        }
+        ts.id = match (id, external_id, space) {
+            (Some(id), None, None) => IdentityOrInstance::from(id),
+            (None, Some(external_id), None) => IdentityOrInstance::from(external_id),
+            (None, Some(external_id), Some(space)) => {
+                IdentityOrInstance::from(InstanceId { space, external_id })
+            }
+            _ => {
+                return Err(ParseExpressionError::new(
+                    start,
+                    "Time series must have either id, externalId, or space and externalId",
+                ))
+            }
+        };
+        if ts.aggregate.is_some() != ts.granularity.is_some() {
+            return Err(ParseExpressionError::new(
+                start,
+                "Aggregate and granularity must be specified together",
+            ));
+        }
+        Ok(SyntheticExpression::TimeSeries(ts))
+    }
+}
+
+impl FromStr for SyntheticExpression {
+    type Err = ParseExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            end: s.len(),
+        };
+        let expr = parser.expression()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("Unexpected trailing input"));
+        }
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyntheticExpression, SyntheticFunction, SyntheticTimeSeriesRef};
+    use crate::models::instances::InstanceId;
+    use crate::time_series::Aggregate;
+
+    #[test]
+    fn test_build_expression() {
+        let expr = (SyntheticExpression::ts(
+            SyntheticTimeSeriesRef::new("a").aggregate(Aggregate::Average, "1h".parse().unwrap()),
+        ) + SyntheticExpression::ts(123))
+            / 2.0;
+        assert_eq!(
+            "(ts{externalId='a', aggregate='average', granularity='1h'} + ts{id=123}) / 2",
+            expr.to_string()
+        );
+
+        let expr = SyntheticExpression::ts("it's \\ quoted").on_error(0.0);
+        assert_eq!(
+            r"on_error(ts{externalId='it\'s \\ quoted'}, 0)",
+            expr.to_string()
+        );
+        assert_eq!(expr, expr.to_string().parse().unwrap());
+
+        let expr = SyntheticExpression::ts(
+            SyntheticTimeSeriesRef::new(InstanceId {
+                space: "sp".to_owned(),
+                external_id: "x".to_owned(),
+            })
+            .target_unit("temperature:deg_c"),
+        ) - (SyntheticExpression::Number(1.0) - SyntheticExpression::Number(2.0));
+        assert_eq!(
+            "ts{space='sp', externalId='x', targetUnit='temperature:deg_c'} - (1 - 2)",
+            expr.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_expression_round_trip() {
+        for input in [
+            "(ts{externalId='a', aggregate='average', granularity='1h'} + ts{id=123}) / 2",
+            "sin(ts{id=1}) * pi() ^ 2 ^ 0.5",
+            "(2 ^ 3) ^ 4",
+            "-ts{id=1} ^ 2",
+            "(-2) ^ 2",
+            "max(ts{id=1}, ts{id=2}, 3) - -(ts{id=3} + 1)",
+            "map(ts{externalId='state'}, ['OPEN', 'CLOSED'], [1, 0], -1)",
+            "on_error(ts{id=1, aggregate='stepInterpolation', granularity='5m'} / ts{id=2}, 0)",
+        ] {
+            let expr: SyntheticExpression = input.parse().unwrap();
+            expr.validate().unwrap();
+            let formatted = expr.to_string();
+            assert_eq!(expr, formatted.parse().unwrap(), "{input} -> {formatted}");
+        }
+
+        let expr: SyntheticExpression = "TS{id=1} + SQRT(2)".parse().unwrap();
+        assert_eq!("ts{id=1} + sqrt(2)", expr.to_string());
+        let expr: SyntheticExpression = "1 - 2 - 3".parse().unwrap();
+        assert_eq!("1 - 2 - 3", expr.to_string());
+        let expr: SyntheticExpression = "1 - (2 - 3)".parse().unwrap();
+        assert_eq!("1 - (2 - 3)", expr.to_string());
+        assert!(matches!(
+            "pow(1)".parse::<SyntheticExpression>(),
+            Err(e) if e.position == 0
+        ));
+        assert!(SyntheticExpression::call(SyntheticFunction::Sin, [])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_expression_errors() {
+        for input in [
+            "ts{externalId='a}",
+            "ts{externalId='a', aggregate='average'}",
+            "ts{externalId='a', granularity='121m', aggregate='average'}",
+            "ts{name='a'}",
+            "ts{id=1, externalId='a'}",
+            "foo(1)",
+            "1 +",
+            "(1",
+            "1 2",
+            "map(ts{id=1}, ['a'], [1, 2], 0)",
+            "1 # 2",
+        ] {
+            assert!(
+                input.parse::<SyntheticExpression>().is_err(),
+                "{input} should fail to parse"
+            );
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use thiserror::Error;
+
+use crate::time_series::{DatapointDouble, DatapointsQuery};
+use crate::{Identity, IdentityOrInstance};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// Linear conversion of values, `value * multiplier + offset`.
+pub struct UnitConversion {
+    /// Value to multiply by.
+    pub multiplier: f64,
+    /// Value to add after multiplying.
+    pub offset: f64,
+}
+
+impl Default for UnitConversion {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl UnitConversion {
+    /// Apply this conversion to a value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value to convert.
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.multiplier + self.offset
+    }
+
+    /// Get the conversion that reverses this conversion.
+    pub fn inverse(&self) -> Self {
+        Self {
+            multiplier: 1.0 / self.multiplier,
+            offset: -self.offset / self.multiplier,
+        }
+    }
+
+    /// Get the conversion applying this conversion, then `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Conversion to apply after this one.
+    pub fn then(&self, other: &UnitConversion) -> Self {
+        Self {
+            multiplier: self.multiplier * other.multiplier,
+            offset: self.offset * other.multiplier + other.offset,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+/// A unit in the Cognite unit catalog.
+pub struct Unit {
+    /// External ID of the unit, like `temperature:deg_c`.
+    pub external_id: String,
+    /// Name of the unit, like `DEG_C`.
+    pub name: String,
+    /// Descriptive name of the unit, like `degree Celsius`.
+    pub long_name: Option<String>,
+    /// Symbol of the unit, like `°C`.
+    pub symbol: Option<String>,
+    /// Alternative names of the unit.
+    #[serde(default)]
+    pub alias_names: Vec<String>,
+    /// The physical quantity measured by this unit, like `Temperature`.
+    pub quantity: String,
+    /// Conversion from this unit to the base unit of its quantity.
+    #[serde(default)]
+    pub conversion: UnitConversion,
+    /// Source of the unit definition.
+    pub source: Option<String>,
+    /// Reference to the source of the unit definition.
+    pub source_reference: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+/// The unit used for a quantity in a unit system.
+pub struct UnitSystemQuantity {
+    /// Name of the quantity, like `Temperature`.
+    pub name: String,
+    /// External ID of the unit used for the quantity.
+    pub unit_external_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+/// A unit system, like `SI` or `Imperial`, defining which unit to use for each quantity.
+pub struct UnitSystem {
+    /// Name of the unit system.
+    pub name: String,
+    /// Units used for each quantity in the system.
+    #[serde(default)]
+    pub quantities: Vec<UnitSystemQuantity>,
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+/// Error on failure to convert between units.
+pub enum UnitConversionError {
+    /// The unit is not in the unit catalog.
+    #[error("Unknown unit '{0}'")]
+    UnknownUnit(String),
+    /// The unit system is not in the unit catalog.
+    #[error("Unknown unit system '{0}'")]
+    UnknownUnitSystem(String),
+    /// The units measure different quantities.
+    #[error("Cannot convert from '{from}' ({from_quantity}) to '{to}' ({to_quantity})")]
+    IncompatibleUnits {
+        /// External ID of the source unit.
+        from: String,
+        /// Quantity of the source unit.
+        from_quantity: String,
+        /// External ID of the target unit.
+        to: String,
+        /// Quantity of the target unit.
+        to_quantity: String,
+    },
+    /// The unit system has no unit for the quantity.
+    #[error("Unit system '{system}' has no unit for quantity '{quantity}'")]
+    NoUnitInSystem {
+        /// Name of the unit system.
+        system: String,
+        /// Quantity missing from the unit system.
+        quantity: String,
+    },
+    /// A target unit was requested for a time series without a unit.
+    #[error("Time series {0} has no unit external ID, and cannot be converted")]
+    MissingUnit(String),
+    /// Both target unit and target unit system were set.
+    #[error("Target unit and target unit system cannot both be set for time series {0}")]
+    AmbiguousTarget(String),
+}
+
+fn describe_series(id: &IdentityOrInstance) -> String {
+    match id {
+        IdentityOrInstance::Identity(Identity::Id { id }) => format!("with ID {id}"),
+        IdentityOrInstance::Identity(Identity::ExternalId { external_id }) => {
+            format!("'{external_id}'")
+        }
+        IdentityOrInstance::InstanceId { instance_id } => {
+            format!("'{}:{}'", instance_id.space, instance_id.external_id)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Local converter between units in the Cognite unit catalog.
+///
+/// Each unit in the catalog has a conversion to the base unit of its quantity. Converting
+/// between two units of the same quantity converts to the base unit, then from the base
+/// unit to the target unit.
+pub struct UnitConverter {
+    units: HashMap<String, Unit>,
+    systems: HashMap<String, HashMap<String, String>>,
+}
+
+impl UnitConverter {
+    /// Create a new unit converter from the unit catalog.
+    ///
+    /// # Arguments
+    ///
+    /// * `units` - Units in the unit catalog.
+    /// * `systems` - Unit systems in the unit catalog.
+    pub fn new(
+        units: impl IntoIterator<Item = Unit>,
+        systems: impl IntoIterator<Item = UnitSystem>,
+    ) -> Self {
+        Self {
+            units: units
+                .into_iter()
+                .map(|u| (u.external_id.clone(), u))
+                .collect(),
+            systems: systems
+                .into_iter()
+                .map(|s| {
+                    let quantities = s
+                        .quantities
+                        .into_iter()
+                        .map(|q| (q.name, q.unit_external_id))
+                        .collect();
+                    (s.name, quantities)
+                })
+                .collect(),
+        }
+    }
+
+    /// Get a unit by external ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `external_id` - External ID of the unit.
+    pub fn unit(&self, external_id: &str) -> Result<&Unit, UnitConversionError> {
+        self.units
+            .get(external_id)
+            .ok_or_else(|| UnitConversionError::UnknownUnit(external_id.to_owned()))
+    }
+
+    /// Iterate over all units measuring a quantity.
+    ///
+    /// # Arguments
+    ///
+    /// * `quantity` - Name of the quantity, like `Temperature`.
+    pub fn units_for_quantity<'a>(&'a self, quantity: &'a str) -> impl Iterator<Item = &'a Unit> {
+        self.units.values().filter(move |u| u.quantity == quantity)
+    }
+
+    /// Get the unit used for the quantity of a unit in a unit system.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - External ID of the unit.
+    /// * `system` - Name of the unit system.
+    pub fn unit_in_system(&self, from: &str, system: &str) -> Result<&Unit, UnitConversionError> {
+        let unit = self.unit(from)?;
+        let quantities = self
+            .systems
+            .get(system)
+            .ok_or_else(|| UnitConversionError::UnknownUnitSystem(system.to_owned()))?;
+        let target =
+            quantities
+                .get(&unit.quantity)
+                .ok_or_else(|| UnitConversionError::NoUnitInSystem {
+                    system: system.to_owned(),
+                    quantity: unit.quantity.clone(),
+                })?;
+        self.unit(target)
+    }
+
+    /// Get the conversion from one unit to another.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - External ID of the source unit.
+    /// * `to` - External ID of the target unit.
+    pub fn conversion(&self, from: &str, to: &str) -> Result<UnitConversion, UnitConversionError> {
+        let from_unit = self.unit(from)?;
+        let to_unit = self.unit(to)?;
+        if from_unit.quantity != to_unit.quantity {
+            return Err(UnitConversionError::IncompatibleUnits {
+                from: from.to_owned(),
+                from_quantity: from_unit.quantity.clone(),
+                to: to.to_owned(),
+                to_quantity: to_unit.quantity.clone(),
+            });
+        }
+        if from == to {
+            return Ok(UnitConversion::default());
+        }
+        Ok(from_unit.conversion.then(&to_unit.conversion.inverse()))
+    }
+
+    /// Convert a value from one unit to another.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value to convert.
+    /// * `from` - External ID of the source unit.
+    /// * `to` - External ID of the target unit.
+    pub fn convert(&self, value: f64, from: &str, to: &str) -> Result<f64, UnitConversionError> {
+        Ok(self.conversion(from, to)?.apply(value))
+    }
+
+    /// Convert numeric datapoints from one unit to another in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `datapoints` - Datapoints to convert.
+    /// * `from` - External ID of the source unit.
+    /// * `to` - External ID of the target unit.
+    pub fn convert_datapoints(
+        &self,
+        datapoints: &mut [DatapointDouble],
+        from: &str,
+        to: &str,
+    ) -> Result<(), UnitConversionError> {
+        let conversion = self.conversion(from, to)?;
+        for dp in datapoints {
+            dp.value = dp.value.map(|v| conversion.apply(v));
+        }
+        Ok(())
+    }
+
+    /// Check that the target unit or target unit system of a datapoints query
+    /// can be used with a time series with the given unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Datapoints query.
+    /// * `unit_external_id` - Unit external ID of the queried time series.
+    pub fn validate_query(
+        &self,
+        query: &DatapointsQuery,
+        unit_external_id: Option<&str>,
+    ) -> Result<(), UnitConversionError> {
+        let target_unit = query.target_unit.as_deref();
+        let target_unit_system = query.target_unit_system.as_deref();
+        if target_unit.is_none() && target_unit_system.is_none() {
+            return Ok(());
+        }
+        let series = || describe_series(&query.id);
+        if target_unit.is_some() && target_unit_system.is_some() {
+            return Err(UnitConversionError::AmbiguousTarget(series()));
+        }
+        let Some(unit) = unit_external_id else {
+            return Err(UnitConversionError::MissingUnit(series()));
+        };
+        if let Some(target) = target_unit {
+            self.conversion(unit, target)?;
+        }
+        if let Some(system) = target_unit_system {
+            self.unit_in_system(unit, system)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Unit, UnitConversion, UnitConversionError, UnitConverter, UnitSystem, UnitSystemQuantity,
+    };
+
+    fn unit(external_id: &str, quantity: &str, multiplier: f64, offset: f64) -> Unit {
+        Unit {
+            external_id: external_id.to_owned(),
+            name: external_id.to_owned(),
+            quantity: quantity.to_owned(),
+            conversion: UnitConversion { multiplier, offset },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unit_conversion() {
+        let converter = UnitConverter::new(
+            [
+                unit("temperature:k", "Temperature", 1.0, 0.0),
+                unit("temperature:deg_c", "Temperature", 1.0, 273.15),
+                unit(
+                    "temperature:deg_f",
+                    "Temperature",
+                    5.0 / 9.0,
+                    255.3722222222222,
+                ),
+                unit("length:m", "Length", 1.0, 0.0),
+                unit("length:ft", "Length", 0.3048, 0.0),
+            ],
+            [UnitSystem {
+                name: "Imperial".to_owned(),
+                quantities: vec![UnitSystemQuantity {
+                    name: "Temperature".to_owned(),
+                    unit_external_id: "temperature:deg_f".to_owned(),
+                }],
+            }],
+        );
+        let f = converter
+            .convert(100.0, "temperature:deg_c", "temperature:deg_f")
+            .unwrap();
+        assert!((f - 212.0).abs() < 1e-9);
+        let c = converter
+            .convert(32.0, "temperature:deg_f", "temperature:deg_c")
+            .unwrap();
+        assert!(c.abs() < 1e-9);
+        let ft = converter.convert(3.048, "length:m", "length:ft").unwrap();
+        assert!((ft - 10.0).abs() < 1e-9);
+
+        assert!(matches!(
+            converter.convert(1.0, "length:m", "temperature:k"),
+            Err(UnitConversionError::IncompatibleUnits { .. })
+        ));
+        assert_eq!(
+            Err(UnitConversionError::UnknownUnit("length:mi".to_owned())),
+            converter.convert(1.0, "length:m", "length:mi")
+        );
+        assert_eq!(
+            "temperature:deg_f",
+            converter
+                .unit_in_system("temperature:k", "Imperial")
+                .unwrap()
+                .external_id
+        );
+        assert!(matches!(
+            converter.unit_in_system("length:m", "Imperial"),
+            Err(UnitConversionError::NoUnitInSystem { .. })
+        ));
+    }
+}
@@ -6,7 +6,7 @@ use serde_with::skip_serializing_none;
 
 use crate::{models::views::ViewReference, PropertyIdentifier};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
 /// ID of a non-versioned resource in the data modeling API.
 pub struct ItemId {
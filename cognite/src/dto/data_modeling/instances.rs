@@ -6,7 +6,7 @@ use serde_with::skip_serializing_none;
 
 use crate::{
     models::{ItemId, PropertySort, SourceReference, TaggedViewReference},
-    AdvancedFilter, RawValue, SetCursor,
+    AdvancedFilter, RawValue, SetCursor, WithIdentity,
 };
 
 use super::views::ViewCorePropertyType;
@@ -179,6 +179,21 @@ pub enum NodeOrEdge<TProperties> {
     Edge(EdgeDefinition<TProperties>),
 }
 
+impl<TProperties> WithIdentity<NodeOrEdgeSpecification> for NodeOrEdge<TProperties> {
+    fn identities(&self) -> Vec<NodeOrEdgeSpecification> {
+        match self {
+            NodeOrEdge::Node(n) => vec![NodeOrEdgeSpecification::Node(ItemId {
+                space: n.space.clone(),
+                external_id: n.external_id.clone(),
+            })],
+            NodeOrEdge::Edge(e) => vec![NodeOrEdgeSpecification::Edge(ItemId {
+                space: e.space.clone(),
+                external_id: e.external_id.clone(),
+            })],
+        }
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Derivative, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -291,7 +306,7 @@ pub struct NodeAndEdgeRetrieveResponse<TProperties> {
     pub typing: Option<TypeInformation>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase", tag = "instanceType")]
 /// ID of a node or an edge.
 pub enum NodeOrEdgeSpecification {
@@ -7,7 +7,7 @@ use crate::{
     Identity, IntoPatch, IntoPatchItem, Patch, Range, UpdateMap, UpdateSetNull, UpsertOptions,
 };
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 /// A data set grouping data in CDF.
 pub struct DataSet {
@@ -32,6 +32,8 @@ pub struct DataSet {
     pub write_protected: bool,
 }
 
+impl_with_identity!(DataSet);
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
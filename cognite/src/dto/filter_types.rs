@@ -104,7 +104,7 @@ pub enum LabelsFilter {
     ContainsAll(Vec<CogniteExternalId>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// A wrapper around a partition, with custom serializer and deserializer
 /// for converting to the `[a]/[b]` format used by CDF.
 pub struct Partition {
@@ -167,8 +167,8 @@ impl Visitor<'_> for PartitionVisitor {
                 .map_err(|_| E::custom("Expected a string on the form u32/u32"))?;
 
             Ok(Partition {
-                num_partitions: lh_v,
-                partition_number: rh_v,
+                partition_number: lh_v,
+                num_partitions: rh_v,
             })
         }
     }
@@ -257,3 +257,22 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Partition;
+
+    #[test]
+    fn test_partition_round_trip() {
+        let partition: Partition = serde_json::from_str("\"2/10\"").unwrap();
+        assert_eq!(2, partition.partition_number);
+        assert_eq!(10, partition.num_partitions);
+        assert_eq!("\"2/10\"", serde_json::to_string(&partition).unwrap());
+
+        let partition = Partition::new(3, 5);
+        let round_trip: Partition =
+            serde_json::from_str(&serde_json::to_string(&partition).unwrap()).unwrap();
+        assert_eq!(3, round_trip.partition_number);
+        assert_eq!(5, round_trip.num_partitions);
+    }
+}
@@ -149,6 +149,29 @@ pub trait EqIdentity {
     fn eq(&self, id: &Identity) -> bool;
 }
 
+/// Trait for resources retrieved from CDF that can be referred to by identities of type `TIdt`.
+/// This is used to match retrieved items with the identities they were requested by.
+pub trait WithIdentity<TIdt> {
+    /// Return all identities that refer to self.
+    fn identities(&self) -> Vec<TIdt>;
+}
+
+/// Macro to implement `WithIdentity<Identity>` for a resource with an `id`
+/// and an optional `external_id`.
+macro_rules! impl_with_identity {
+    ($t:ty) => {
+        impl $crate::WithIdentity<$crate::Identity> for $t {
+            fn identities(&self) -> Vec<$crate::Identity> {
+                let mut res = vec![$crate::Identity::id(self.id)];
+                if let Some(external_id) = &self.external_id {
+                    res.push($crate::Identity::external_id(external_id));
+                }
+                res
+            }
+        }
+    };
+}
+
 impl From<String> for CogniteExternalId {
     fn from(external_id: String) -> Self {
         CogniteExternalId { external_id }
@@ -14,7 +14,7 @@ pub struct ApiErrorWrapper {
     pub error: ApiErrorMessage,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 /// Value that is either an integer or a string.
 pub enum IntegerStringOrObject {
@@ -52,7 +52,7 @@ impl IntegerStringOrObject {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 /// Details about API errors.
 pub struct ApiErrorDetail(pub Vec<HashMap<String, Box<IntegerStringOrObject>>>);
@@ -106,7 +106,7 @@ impl ApiErrorDetail {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 /// An Error from the CDF API.
 pub struct CdfApiError {
     /// HTTP status code.
@@ -242,6 +242,12 @@ pub enum Error {
     #[error("Unexpected protobuf error: {0}")]
     /// Prost (protobuf deserializer) error
     Prost(#[from] ::prost::DecodeError),
+    #[error("Time zone error: {0}")]
+    /// Invalid time zone, or time zone combined with an unsupported granularity.
+    TimeZone(#[from] crate::time_series::TimeZoneError),
+    #[error("Unit conversion error: {0}")]
+    /// Target unit or unit system cannot be used with the unit of a time series.
+    UnitConversion(#[from] crate::units::UnitConversionError),
     #[error("{0}")]
     /// Something else went wrong.
     Other(String),
@@ -38,6 +38,14 @@ pub mod time_series {
     pub use super::dto::core::{datapoint::*, time_series::*};
 }
 
+/// The unit catalog contains units of measurement, grouped by the quantity they measure,
+/// and unit systems, like `SI` and `Imperial`. Time series can reference a unit in the
+/// catalog, and datapoints can be converted to other units of the same quantity.
+pub mod units {
+    pub use super::api::core::units::*;
+    pub use super::dto::core::units::*;
+}
+
 /// Event objects store complex information about multiple assets over a time period.
 /// Typical types of events might include Alarms, Process Data, and Logs.
 ///
@@ -160,8 +168,15 @@ pub mod iam {
     pub use super::dto::iam::{group::*, security_category::*, session::*};
 }
 
+#[cfg(feature = "arrow")]
+/// Conversion of tabular results, like datapoints, sequence rows, raw rows, and
+/// instances, to Apache Arrow record batches, and optionally polars data frames.
+pub mod arrow;
+
 pub use self::{
-    api::{api_client::*, authenticator::*, request_builder::*, resource::*, utils::*},
+    api::{
+        api_client::*, authenticator::*, paginator::*, request_builder::*, resource::*, utils::*,
+    },
     auth::*,
     cognite_client::*,
     dto::{filter::*, filter_types::*, identity::*, items::*, params::*, patch_item::*, utils::*},
@@ -23,6 +23,7 @@ mod imp {
     impl<T: Stream + Sized + Send> CondBoxedStream for T {}
 
     pub type CondBoxFuture<'a, T> = futures::future::BoxFuture<'a, T>;
+    pub type CondBoxStream<'a, T> = BoxStream<'a, T>;
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -45,6 +46,7 @@ mod imp {
     impl<T: Stream + Sized> CondBoxedStream for T {}
 
     pub type CondBoxFuture<'a, T> = futures::future::LocalBoxFuture<'a, T>;
+    pub type CondBoxStream<'a, T> = LocalBoxStream<'a, T>;
 }
 
 pub(crate) use imp::*;
@@ -1,3 +1,5 @@
+pub mod batch_loader;
+pub mod cache;
 pub mod lease;
 
 use std::collections::HashMap;
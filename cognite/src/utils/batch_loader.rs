@@ -0,0 +1,422 @@
+//! Utility for coalescing concurrent retrieve-by-id calls into batched requests.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use futures::future::{select, Either, Shared};
+use futures::FutureExt;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::send_helper::CondBoxFuture;
+use crate::{
+    CondSend, CondSync, Error, Identity, IdentityList, IdentityOrInstance, IdentityOrInstanceList,
+    Result, RetrieveWithIgnoreUnknownIds, WithIdentity,
+};
+
+/// Trait for types that can retrieve a batch of items by key, for use with [`BatchLoader`].
+///
+/// This is implemented for all resources implementing [`RetrieveWithIgnoreUnknownIds`] with
+/// either [`IdentityList`] or [`IdentityOrInstanceList`].
+pub trait LoadBatch<TKey, TValue> {
+    /// Retrieve items for the given keys. Keys that do not exist in CDF should
+    /// be left out of the result, instead of causing the request to fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Keys to retrieve.
+    fn load_batch(&self, keys: &[TKey]) -> impl Future<Output = Result<Vec<TValue>>> + CondSend;
+}
+
+impl<R, TValue> LoadBatch<Identity, TValue> for R
+where
+    R: for<'a> RetrieveWithIgnoreUnknownIds<IdentityList<&'a [Identity]>, TValue>,
+    TValue: Serialize + DeserializeOwned,
+{
+    fn load_batch(
+        &self,
+        keys: &[Identity],
+    ) -> impl Future<Output = Result<Vec<TValue>>> + CondSend {
+        self.retrieve(keys, true)
+    }
+}
+
+impl<R, TValue> LoadBatch<IdentityOrInstance, TValue> for R
+where
+    R: for<'a> RetrieveWithIgnoreUnknownIds<
+        IdentityOrInstanceList<&'a [IdentityOrInstance]>,
+        TValue,
+    >,
+    TValue: Serialize + DeserializeOwned,
+{
+    fn load_batch(
+        &self,
+        keys: &[IdentityOrInstance],
+    ) -> impl Future<Output = Result<Vec<TValue>>> + CondSend {
+        self.retrieve(keys, true)
+    }
+}
+
+/// Configuration for a [`BatchLoader`].
+#[derive(Debug, Clone)]
+pub struct BatchLoaderConfig {
+    /// Maximum time to wait for more keys after the first key in a batch
+    /// is requested. Defaults to 10 milliseconds.
+    pub max_delay: Duration,
+    /// Maximum number of keys per request. A batch is sent immediately once it
+    /// reaches this size. Defaults to 1000, which is the limit for most `byids` endpoints.
+    pub max_batch_size: usize,
+}
+
+impl Default for BatchLoaderConfig {
+    fn default() -> Self {
+        Self {
+            max_delay: Duration::from_millis(10),
+            max_batch_size: 1000,
+        }
+    }
+}
+
+/// Create a copy of an error shared by all callers waiting on the same batch.
+///
+/// API errors keep their variant, so callers can still match on `NotFound` or `Forbidden`.
+/// Errors wrapping types that cannot be cloned, such as `reqwest::Error`, are copied
+/// as [`Error::Other`] with the same message.
+fn copy_error(err: &Error) -> Error {
+    match err {
+        Error::BadRequest(e) => Error::BadRequest(e.clone()),
+        Error::Unauthorized(e) => Error::Unauthorized(e.clone()),
+        Error::Forbidden(e) => Error::Forbidden(e.clone()),
+        Error::NotFound(e) => Error::NotFound(e.clone()),
+        Error::Conflict(e) => Error::Conflict(e.clone()),
+        Error::UnprocessableEntity(e) => Error::UnprocessableEntity(e.clone()),
+        Error::OtherApiError(e) => Error::OtherApiError(e.clone()),
+        Error::EnvironmentVariableMissing(e) => Error::EnvironmentVariableMissing(e.clone()),
+        Error::Authenticator(e) => Error::Authenticator(e.clone()),
+        Error::IOError(e) => Error::IOError(std::io::Error::new(e.kind(), e.to_string())),
+        Error::StreamError(e) => Error::StreamError(anyhow::anyhow!("{e:#}")),
+        Error::Middleware(e) => Error::Middleware(anyhow::anyhow!("{e:#}")),
+        Error::Config(e) => Error::Config(e.clone()),
+        Error::TimeZone(e) => Error::TimeZone(e.clone()),
+        Error::UnitConversion(e) => Error::UnitConversion(e.clone()),
+        Error::Other(e) => Error::Other(e.clone()),
+        Error::InvalidHeader(_) | Error::Reqwest(_) | Error::SerdeJson(_) | Error::Prost(_) => {
+            Error::Other(err.to_string())
+        }
+    }
+}
+
+type BatchResult<TKey, TValue> = std::result::Result<Arc<HashMap<TKey, TValue>>, Arc<Error>>;
+type BatchFuture<TKey, TValue> = Shared<CondBoxFuture<'static, BatchResult<TKey, TValue>>>;
+
+struct OpenBatch<TKey, TValue> {
+    id: u64,
+    keys: Arc<Mutex<Vec<TKey>>>,
+    // Keys already in the batch, to deduplicate without scanning `keys`.
+    seen: HashSet<TKey>,
+    full: Option<oneshot::Sender<()>>,
+    future: BatchFuture<TKey, TValue>,
+}
+
+struct LoaderState<TKey, TValue> {
+    next_id: u64,
+    current: Option<OpenBatch<TKey, TValue>>,
+}
+
+/// Loader that coalesces concurrent lookups of single items into batched `byids` requests,
+/// also known as the DataLoader pattern.
+///
+/// Keys requested within `max_delay` of each other are collected into a single request
+/// of at most `max_batch_size` keys. Results are then distributed to each caller.
+/// Missing items are returned as `None`, and do not cause other lookups in the same
+/// batch to fail.
+///
+/// The loader is cheap to clone, all clones share the same batches.
+///
+/// # Example
+///
+/// ```ignore
+/// let loader = BatchLoader::new(client.time_series.clone(), Default::default());
+/// // These are sent as a single request.
+/// let (a, b) = futures::join!(
+///     loader.load(IdentityOrInstance::external_id("a")),
+///     loader.load(IdentityOrInstance::external_id("b")),
+/// );
+/// ```
+pub struct BatchLoader<TKey, TValue, TSource> {
+    source: Arc<TSource>,
+    config: BatchLoaderConfig,
+    state: Arc<Mutex<LoaderState<TKey, TValue>>>,
+}
+
+impl<TKey, TValue, TSource> Clone for BatchLoader<TKey, TValue, TSource> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            config: self.config.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<TKey, TValue, TSource> BatchLoader<TKey, TValue, TSource>
+where
+    TKey: Hash + Eq + Clone + CondSend + CondSync + 'static,
+    TValue: WithIdentity<TKey> + Clone + CondSend + CondSync + 'static,
+    TSource: LoadBatch<TKey, TValue> + CondSend + CondSync + 'static,
+{
+    /// Create a new batch loader.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Source used to retrieve batches, typically a resource such as `client.time_series`.
+    /// * `config` - Batching configuration.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_batch_size` is 0.
+    pub fn new(source: TSource, config: BatchLoaderConfig) -> Self {
+        assert!(
+            config.max_batch_size > 0,
+            "Max batch size must be greater than 0"
+        );
+        Self {
+            source: Arc::new(source),
+            config,
+            state: Arc::new(Mutex::new(LoaderState {
+                next_id: 0,
+                current: None,
+            })),
+        }
+    }
+
+    /// Load a single item. Returns `None` if the item does not exist in CDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key of the item to load.
+    pub async fn load(&self, key: TKey) -> Result<Option<TValue>> {
+        let future = self.enqueue(key.clone());
+        let result = future.await.map_err(|e| copy_error(&e))?;
+        Ok(result.get(&key).cloned())
+    }
+
+    /// Load a list of items. The returned list contains one entry per key, in the same order,
+    /// which is `None` if the item does not exist in CDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Keys of the items to load.
+    pub async fn load_many(
+        &self,
+        keys: impl IntoIterator<Item = TKey>,
+    ) -> Result<Vec<Option<TValue>>> {
+        let pending: Vec<_> = keys
+            .into_iter()
+            .map(|key| (self.enqueue(key.clone()), key))
+            .collect();
+        let mut res = Vec::with_capacity(pending.len());
+        for (future, key) in pending {
+            let result = future.await.map_err(|e| copy_error(&e))?;
+            res.push(result.get(&key).cloned());
+        }
+        Ok(res)
+    }
+
+    fn enqueue(&self, key: TKey) -> BatchFuture<TKey, TValue> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(batch) = &mut state.current {
+            let mut keys = batch.keys.lock().unwrap();
+            if batch.seen.insert(key.clone()) {
+                keys.push(key);
+            }
+            let is_full = keys.len() >= self.config.max_batch_size;
+            drop(keys);
+            let future = batch.future.clone();
+            if is_full {
+                // Close the batch and send it right away.
+                if let Some(full) = batch.full.take() {
+                    let _ = full.send(());
+                }
+                state.current = None;
+            }
+            return future;
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        let seen = HashSet::from([key.clone()]);
+        let keys = Arc::new(Mutex::new(vec![key]));
+        let (full_tx, full_rx) = oneshot::channel();
+        let future = self
+            .run_batch(id, keys.clone(), full_rx)
+            .boxed_cond()
+            .shared();
+        let mut batch = OpenBatch {
+            id,
+            keys,
+            seen,
+            full: Some(full_tx),
+            future: future.clone(),
+        };
+        if self.config.max_batch_size == 1 {
+            if let Some(full) = batch.full.take() {
+                let _ = full.send(());
+            }
+        } else {
+            state.current = Some(batch);
+        }
+        future
+    }
+
+    fn run_batch(
+        &self,
+        id: u64,
+        keys: Arc<Mutex<Vec<TKey>>>,
+        full: oneshot::Receiver<()>,
+    ) -> impl Future<Output = BatchResult<TKey, TValue>> + CondSend + 'static {
+        let state = self.state.clone();
+        let source = self.source.clone();
+        let delay = futures_timer::Delay::new(self.config.max_delay);
+        async move {
+            // Wait until either the delay expires, or the batch is full.
+            // The channel is also closed if the batch is closed early.
+            if let Either::Left(_) = select(delay, full).await {
+                let mut state = state.lock().unwrap();
+                if state.current.as_ref().is_some_and(|b| b.id == id) {
+                    state.current = None;
+                }
+            }
+            let keys = std::mem::take(&mut *keys.lock().unwrap());
+            let items = source.load_batch(&keys).await.map_err(Arc::new)?;
+            let mut res = HashMap::with_capacity(items.len());
+            for item in items {
+                for idt in item.identities() {
+                    res.insert(idt, item.clone());
+                }
+            }
+            Ok(Arc::new(res))
+        }
+    }
+}
+
+trait BoxedCond<'a, T> {
+    fn boxed_cond(self) -> CondBoxFuture<'a, T>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a, T, F: Future<Output = T> + Send + 'a> BoxedCond<'a, T> for F {
+    fn boxed_cond(self) -> CondBoxFuture<'a, T> {
+        self.boxed()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<'a, T, F: Future<Output = T> + 'a> BoxedCond<'a, T> for F {
+    fn boxed_cond(self) -> CondBoxFuture<'a, T> {
+        self.boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{BatchLoader, BatchLoaderConfig, LoadBatch};
+    use crate::{CdfApiError, Error, Identity, Result, WithIdentity};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Item {
+        id: i64,
+    }
+
+    impl WithIdentity<Identity> for Item {
+        fn identities(&self) -> Vec<Identity> {
+            vec![Identity::id(self.id)]
+        }
+    }
+
+    struct Source {
+        requests: AtomicUsize,
+    }
+
+    impl LoadBatch<Identity, Item> for Arc<Source> {
+        async fn load_batch(&self, keys: &[Identity]) -> Result<Vec<Item>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            // Odd IDs are missing.
+            Ok(keys
+                .iter()
+                .filter_map(|k| k.as_id())
+                .filter(|id| id % 2 == 0)
+                .map(|id| Item { id })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_loader_coalesces() {
+        let source = Arc::new(Source {
+            requests: AtomicUsize::new(0),
+        });
+        let loader = BatchLoader::new(
+            source.clone(),
+            BatchLoaderConfig {
+                max_delay: Duration::from_millis(50),
+                max_batch_size: 4,
+            },
+        );
+
+        let futures = (0..10).map(|i| {
+            let loader = loader.clone();
+            async move { loader.load(Identity::id(i)).await }
+        });
+        let res = futures::future::join_all(futures).await;
+        for (i, r) in res.into_iter().enumerate() {
+            let r = r.unwrap();
+            if i % 2 == 0 {
+                assert_eq!(Some(Item { id: i as i64 }), r);
+            } else {
+                assert_eq!(None, r);
+            }
+        }
+        // 10 keys with batch size 4 is 3 requests.
+        assert_eq!(3, source.requests.load(Ordering::Relaxed));
+
+        let res = loader
+            .load_many([Identity::id(2), Identity::id(3), Identity::id(2)])
+            .await
+            .unwrap();
+        assert_eq!(vec![Some(Item { id: 2 }), None, Some(Item { id: 2 })], res);
+        assert_eq!(4, source.requests.load(Ordering::Relaxed));
+    }
+
+    struct ForbiddenSource;
+
+    impl LoadBatch<Identity, Item> for ForbiddenSource {
+        async fn load_batch(&self, _keys: &[Identity]) -> Result<Vec<Item>> {
+            Err(Error::Forbidden(CdfApiError {
+                code: 403,
+                message: "Forbidden".to_owned(),
+                ..Default::default()
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_loader_keeps_error_variant() {
+        let loader = BatchLoader::new(ForbiddenSource, BatchLoaderConfig::default());
+
+        let (a, b) = futures::join!(loader.load(Identity::id(1)), loader.load(Identity::id(2)));
+        for res in [a, b] {
+            match res {
+                Err(Error::Forbidden(e)) => assert_eq!(403, e.code),
+                r => panic!("Expected forbidden error, got {r:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,433 @@
+//! Cache for metadata retrieved from CDF, such as time series, assets and instances.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::utils::batch_loader::LoadBatch;
+use crate::{CondSend, CondSync, Result, WithIdentity};
+
+/// Configuration for a [`RetrieveCache`].
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// How long a retrieved item is kept in the cache. Defaults to 5 minutes.
+    pub ttl: Duration,
+    /// How long an ID that was not found in CDF is remembered as missing.
+    /// If this is `None`, missing IDs are not cached. Defaults to 30 seconds.
+    pub negative_ttl: Option<Duration>,
+    /// Maximum number of entries in the cache. Each identity of an item is a separate
+    /// entry, so a time series with both ID and external ID uses two entries.
+    /// When the cache is full, expired entries are removed first,
+    /// then the least recently used entries. Defaults to 100 000.
+    pub max_entries: usize,
+    /// Maximum number of IDs per request to CDF. Defaults to 1000.
+    pub max_batch_size: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(300),
+            negative_ttl: Some(Duration::from_secs(30)),
+            max_entries: 100_000,
+            max_batch_size: 1000,
+        }
+    }
+}
+
+/// Result of looking up a key in a [`RetrieveCache`] without contacting CDF.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheLookup<TValue> {
+    /// The item is in the cache.
+    Hit(TValue),
+    /// The item is known to not exist in CDF.
+    Missing,
+    /// The cache has no valid entry for this key.
+    Miss,
+}
+
+struct CacheEntry<TValue> {
+    // `None` means the key is known to be missing from CDF.
+    value: Option<Arc<TValue>>,
+    expires: Instant,
+    last_used: u64,
+}
+
+struct CacheState<TKey, TValue> {
+    entries: HashMap<TKey, CacheEntry<TValue>>,
+    counter: u64,
+}
+
+impl<TKey: Hash + Eq + Clone, TValue: WithIdentity<TKey>> CacheState<TKey, TValue> {
+    fn get(&mut self, key: &TKey, now: Instant) -> CacheLookup<Arc<TValue>> {
+        self.counter += 1;
+        let counter = self.counter;
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.expires > now => {
+                entry.last_used = counter;
+                match &entry.value {
+                    Some(v) => CacheLookup::Hit(v.clone()),
+                    None => CacheLookup::Missing,
+                }
+            }
+            Some(_) => {
+                self.entries.remove(key);
+                CacheLookup::Miss
+            }
+            None => CacheLookup::Miss,
+        }
+    }
+
+    fn insert(&mut self, key: TKey, value: Option<Arc<TValue>>, expires: Instant) {
+        self.counter += 1;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                expires,
+                last_used: self.counter,
+            },
+        );
+    }
+
+    fn invalidate(&mut self, key: &TKey) {
+        if let Some(CacheEntry {
+            value: Some(value), ..
+        }) = self.entries.remove(key)
+        {
+            // Remove the item under all its other identities as well.
+            for idt in value.identities() {
+                self.entries.remove(&idt);
+            }
+        }
+    }
+
+    fn evict(&mut self, max_entries: usize, now: Instant) {
+        if self.entries.len() <= max_entries {
+            return;
+        }
+        self.entries.retain(|_, e| e.expires > now);
+        if self.entries.len() <= max_entries {
+            return;
+        }
+        // Evict down to 90% of capacity, so that we don't need to do this on every insert.
+        let target = max_entries - max_entries / 10;
+        let mut by_use: Vec<_> = self.entries.values().map(|e| e.last_used).collect();
+        let to_remove = self.entries.len() - target;
+        let (_, threshold, _) = by_use.select_nth_unstable(to_remove - 1);
+        let threshold = *threshold;
+        self.entries.retain(|_, e| e.last_used > threshold);
+    }
+}
+
+/// Read-through cache for retrieving items by ID from CDF.
+///
+/// Items are retrieved using a [`LoadBatch`] source, which is implemented for
+/// resources such as `client.time_series`, `client.assets`, `client.events`,
+/// and `client.models.instances`. Note that instances are retrieved without any
+/// properties. To cache properties from specific views, implement [`LoadBatch`]
+/// for a custom source.
+///
+/// The cache supports time to live, a maximum size, negative caching of
+/// IDs that do not exist in CDF, and explicit invalidation. When writing to
+/// CDF, call [`RetrieveCache::invalidate`] or [`RetrieveCache::insert`] with the
+/// affected items, as the cache has no way to know about changes made elsewhere.
+///
+/// The cache is cheap to clone, all clones share the same cached data.
+///
+/// # Example
+///
+/// ```ignore
+/// let cache = RetrieveCache::new(client.time_series.clone(), CacheConfig::default());
+/// let ts = cache.retrieve(&[IdentityOrInstance::external_id("my-ts")]).await?;
+/// ```
+pub struct RetrieveCache<TKey, TValue, TSource> {
+    source: Arc<TSource>,
+    config: CacheConfig,
+    state: Arc<Mutex<CacheState<TKey, TValue>>>,
+}
+
+impl<TKey, TValue, TSource> Clone for RetrieveCache<TKey, TValue, TSource> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            config: self.config.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<TKey, TValue, TSource> RetrieveCache<TKey, TValue, TSource>
+where
+    TKey: Hash + Eq + Clone + CondSend + CondSync,
+    TValue: WithIdentity<TKey> + CondSend + CondSync,
+    TSource: LoadBatch<TKey, TValue> + CondSend + CondSync,
+{
+    /// Create a new cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Source used to retrieve items, typically a resource such as `client.time_series`.
+    /// * `config` - Cache configuration.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_batch_size` or `max_entries` is 0.
+    pub fn new(source: TSource, config: CacheConfig) -> Self {
+        assert!(
+            config.max_batch_size > 0,
+            "Max batch size must be greater than 0"
+        );
+        assert!(config.max_entries > 0, "Max entries must be greater than 0");
+        Self {
+            source: Arc::new(source),
+            config,
+            state: Arc::new(Mutex::new(CacheState {
+                entries: HashMap::new(),
+                counter: 0,
+            })),
+        }
+    }
+
+    /// Retrieve a list of items, using the cache where possible. Items that are not
+    /// in the cache are retrieved from CDF. The returned list contains one entry per key,
+    /// in the same order, which is `None` if the item does not exist in CDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Keys of the items to retrieve.
+    pub async fn retrieve(&self, keys: &[TKey]) -> Result<Vec<Option<Arc<TValue>>>> {
+        let mut res = Vec::with_capacity(keys.len());
+        let mut to_fetch = Vec::new();
+        let mut seen = HashSet::new();
+        {
+            let now = Instant::now();
+            let mut state = self.state.lock().unwrap();
+            for key in keys {
+                match state.get(key, now) {
+                    CacheLookup::Hit(v) => res.push(Some(v)),
+                    CacheLookup::Missing => res.push(None),
+                    CacheLookup::Miss => {
+                        if seen.insert(key) {
+                            to_fetch.push(key.clone());
+                        }
+                        res.push(None);
+                    }
+                }
+            }
+        }
+        if to_fetch.is_empty() {
+            return Ok(res);
+        }
+
+        let mut fetched = HashMap::new();
+        for chunk in to_fetch.chunks(self.config.max_batch_size) {
+            let items = self.source.load_batch(chunk).await?;
+            let now = Instant::now();
+            let mut state = self.state.lock().unwrap();
+            for item in items {
+                let item = Arc::new(item);
+                for idt in item.identities() {
+                    state.insert(idt.clone(), Some(item.clone()), now + self.config.ttl);
+                    fetched.insert(idt, item.clone());
+                }
+            }
+            if let Some(negative_ttl) = self.config.negative_ttl {
+                for key in chunk {
+                    if !fetched.contains_key(key) {
+                        state.insert(key.clone(), None, now + negative_ttl);
+                    }
+                }
+            }
+            state.evict(self.config.max_entries, now);
+        }
+
+        for (key, r) in keys.iter().zip(res.iter_mut()) {
+            if r.is_none() {
+                if let Some(item) = fetched.get(key) {
+                    *r = Some(item.clone());
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Retrieve a single item, using the cache if possible.
+    /// Returns `None` if the item does not exist in CDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key of the item to retrieve.
+    pub async fn retrieve_one(&self, key: TKey) -> Result<Option<Arc<TValue>>> {
+        Ok(self
+            .retrieve(std::slice::from_ref(&key))
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+
+    /// Look up an item in the cache, without retrieving it from CDF.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key of the item to look up.
+    pub fn get_cached(&self, key: &TKey) -> CacheLookup<Arc<TValue>> {
+        self.state.lock().unwrap().get(key, Instant::now())
+    }
+
+    /// Insert items into the cache, for example after creating or updating them in CDF.
+    /// This replaces any existing entries, including entries marking the items as missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Items to insert.
+    pub fn insert(&self, items: impl IntoIterator<Item = TValue>) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        for item in items {
+            let item = Arc::new(item);
+            for idt in item.identities() {
+                state.invalidate(&idt);
+                state.insert(idt, Some(item.clone()), now + self.config.ttl);
+            }
+        }
+        state.evict(self.config.max_entries, now);
+    }
+
+    /// Remove items from the cache, for example after updating or deleting them in CDF.
+    /// If a key refers to a cached item, the item is removed under all its identities.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Keys of the items to remove.
+    pub fn invalidate<'a>(&self, keys: impl IntoIterator<Item = &'a TKey>)
+    where
+        TKey: 'a,
+    {
+        let mut state = self.state.lock().unwrap();
+        for key in keys {
+            state.invalidate(key);
+        }
+    }
+
+    /// Remove all items from the cache.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    /// Number of entries in the cache, including expired entries that have not yet been removed.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{CacheConfig, CacheLookup, RetrieveCache};
+    use crate::utils::batch_loader::LoadBatch;
+    use crate::{Identity, Result, WithIdentity};
+
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        id: i64,
+        external_id: String,
+    }
+
+    impl WithIdentity<Identity> for Item {
+        fn identities(&self) -> Vec<Identity> {
+            vec![
+                Identity::id(self.id),
+                Identity::external_id(&self.external_id),
+            ]
+        }
+    }
+
+    #[derive(Default)]
+    struct Source {
+        requests: AtomicUsize,
+    }
+
+    impl LoadBatch<Identity, Item> for Arc<Source> {
+        async fn load_batch(&self, keys: &[Identity]) -> Result<Vec<Item>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            // Only IDs below 100 exist.
+            Ok(keys
+                .iter()
+                .filter_map(|k| k.as_id())
+                .filter(|id| *id < 100)
+                .map(|id| Item {
+                    id,
+                    external_id: format!("item-{id}"),
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_negative_caching() {
+        let source = Arc::new(Source::default());
+        let cache = RetrieveCache::new(source.clone(), CacheConfig::default());
+
+        let res = cache
+            .retrieve(&[Identity::id(1), Identity::id(200), Identity::id(1)])
+            .await
+            .unwrap();
+        assert_eq!(1, res[0].as_ref().unwrap().id);
+        assert!(res[1].is_none());
+        assert_eq!(1, res[2].as_ref().unwrap().id);
+        assert_eq!(1, source.requests.load(Ordering::Relaxed));
+
+        // Item is cached under both identities, and the missing ID is cached as missing.
+        let res = cache
+            .retrieve(&[Identity::external_id("item-1"), Identity::id(200)])
+            .await
+            .unwrap();
+        assert_eq!(1, res[0].as_ref().unwrap().id);
+        assert!(res[1].is_none());
+        assert_eq!(1, source.requests.load(Ordering::Relaxed));
+        assert_eq!(CacheLookup::Missing, cache.get_cached(&Identity::id(200)));
+
+        // Invalidating by external ID removes the entry for the internal ID too.
+        cache.invalidate(&[Identity::external_id("item-1")]);
+        assert_eq!(CacheLookup::Miss, cache.get_cached(&Identity::id(1)));
+        cache.retrieve_one(Identity::id(1)).await.unwrap().unwrap();
+        assert_eq!(2, source.requests.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_cache_ttl_and_size() {
+        let source = Arc::new(Source::default());
+        let cache = RetrieveCache::new(
+            source.clone(),
+            CacheConfig {
+                ttl: Duration::from_millis(20),
+                negative_ttl: None,
+                max_entries: 10,
+                max_batch_size: 3,
+            },
+        );
+
+        let keys: Vec<_> = (0..8).map(Identity::id).collect();
+        cache.retrieve(&keys).await.unwrap();
+        assert_eq!(3, source.requests.load(Ordering::Relaxed));
+        assert!(cache.len() <= 10);
+
+        // Missing IDs are not cached.
+        cache.retrieve_one(Identity::id(500)).await.unwrap();
+        assert_eq!(CacheLookup::Miss, cache.get_cached(&Identity::id(500)));
+
+        futures_timer::Delay::new(Duration::from_millis(30)).await;
+        assert_eq!(CacheLookup::Miss, cache.get_cached(&Identity::id(7)));
+    }
+}
@@ -1,12 +1,38 @@
 use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use cognite::{
     assets::{AssetQuery, FilterAssetsRequest},
-    FilterWithRequest, List,
+    files::{
+        AddFile, DownloadProgress, MultiUploadUrls, MultipartUploadState, MultipartUploader,
+        RangedDownloadConfig, ResumableUploadConfig,
+    },
+    models::instances::InstanceId,
+    time_series::{
+        Aggregate, AggregateDatapoint, AggregateDatapoints, CompressionConfig, CsvExportOptions,
+        CsvImportOptions, CsvLayout, DataPointInsertionRequest, DataPointListItem,
+        DataPointListResponse, DatapointDouble, DatapointView, DatapointsCompressor,
+        DatapointsEnumType, DatapointsFilter, DatapointsFlushResult, DatapointsQuery,
+        DatapointsStreamOptions, DatapointsTailOptions, DatapointsUploadQueue,
+        DatapointsUploadQueueConfig, EitherDataPoint, InsertDatapointType, LatestDatapointsQuery,
+        LinkedTimeSeriesSource, ListDatapointType, ListSubscriptionDataRequest, NumericDatapoint,
+        NumericDatapoints, ShardedRetrievalOptions, Status, StringDatapoint, StringDatapoints,
+        SubscriptionDatapoint, SubscriptionPartition, TimeSeriesReference, TimeZoneError,
+        TimestampFormat,
+    },
+    units::UnitConversionError,
+    utils::batch_loader::{BatchLoader, BatchLoaderConfig},
+    ApiVersion, AuthHeaderManager, ClientConfig, CogniteClient, Error, FilterWithRequest, Identity,
+    IdentityOrInstance, List, PaginationCheckpoint,
 };
-use futures::{future, stream, TryStreamExt};
+use futures::{future, stream, StreamExt, TryStreamExt};
+use prost::Message;
 use serde_json::{json, Value};
 use wiremock::{
-    matchers::{body_json_string, body_string, method, path, query_param, query_param_is_missing},
+    matchers::{
+        body_json_string, body_string, header, method, path, query_param, query_param_is_missing,
+    },
     Mock, MockServer, Request, ResponseTemplate,
 };
 
@@ -110,6 +136,148 @@ async fn stream_responses_list() {
     }
 }
 
+#[tokio::test]
+async fn stream_responses_slow_consumer() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    fn gen_asset(id: i64) -> Value {
+        json!({
+            "id": id,
+            "name": "test",
+            "createdTime": 1234,
+            "lastUpdatedTime": 1234
+        })
+    }
+
+    Mock::given(method("GET"))
+        .and(query_param_is_missing("cursor"))
+        .and(path(get_path("", project, "assets")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [gen_asset(1), gen_asset(2), gen_asset(3)],
+            "nextCursor": "cursor1"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(query_param("cursor", "cursor1"))
+        .and(path(get_path("", project, "assets")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [gen_asset(4), gen_asset(5), gen_asset(6)],
+            "nextCursor": null
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = CogniteClient::new_custom_auth(
+        &mock_server.uri(),
+        project,
+        AuthHeaderManager::AuthTicket("my_ticket".to_string()),
+        "rust_sdk_test",
+        Some(ClientConfig {
+            max_retries: 0,
+            timeout_ms: Some(300),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+
+    // Processing each page takes longer than the request timeout, which must not
+    // fail the stream, since each page is read completely before it is returned.
+    let stream = client.assets.list_all_stream(AssetQuery::default());
+    let it: Vec<_> = stream
+        .and_then(|asset| async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            Ok(asset)
+        })
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![1, 2, 3, 4, 5, 6],
+        it.iter().map(|a| a.id).collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn resume_paginator_from_checkpoint() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    fn gen_asset(id: i64) -> Value {
+        json!({
+            "id": id,
+            "name": "test",
+            "createdTime": 1234,
+            "lastUpdatedTime": 1234
+        })
+    }
+
+    Mock::given(method("POST"))
+        .and(body_json_string(json!({}).to_string()))
+        .and(path(get_path("", project, "assets/list")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [gen_asset(1), gen_asset(2)],
+            "nextCursor": "cursor1"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(body_json_string(json!({ "cursor": "cursor1" }).to_string()))
+        .and(path(get_path("", project, "assets/list")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [gen_asset(3)],
+            "nextCursor": null
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+
+    let mut paginator = client
+        .assets
+        .filter_paginator(FilterAssetsRequest::default())
+        .unwrap();
+    let first = paginator.next_page().await.unwrap().unwrap();
+    assert_eq!(2, first.len());
+    let checkpoint = serde_json::to_string(&paginator.checkpoint()).unwrap();
+
+    // Resume from the stored checkpoint with a fresh paginator.
+    let checkpoint: PaginationCheckpoint = serde_json::from_str(&checkpoint).unwrap();
+    assert_eq!(Some("cursor1"), checkpoint.cursor.as_deref());
+    let paginator = client
+        .assets
+        .filter_paginator(FilterAssetsRequest::default())
+        .unwrap()
+        .resume_from(checkpoint)
+        .unwrap();
+    let rest: Vec<_> = paginator.into_stream().try_collect().await.unwrap();
+    assert_eq!(1, rest.len());
+    assert_eq!(3, rest[0].id);
+
+    // A checkpoint for a different filter is rejected.
+    let other = client
+        .assets
+        .filter_paginator(FilterAssetsRequest {
+            limit: Some(10),
+            ..Default::default()
+        })
+        .unwrap();
+    let paginator = client
+        .assets
+        .filter_paginator(FilterAssetsRequest::default())
+        .unwrap();
+    assert!(paginator.resume_from(other.checkpoint()).is_err());
+
+    mock_server.verify().await;
+}
+
 #[test]
 fn test_resource_usage_send() {
     fn assert_send<T: Send>(t: T) -> T {
@@ -121,6 +289,17 @@ fn test_resource_usage_send() {
     // Assert that futures from `Resource` are still send.
     let _ = assert_send(client.assets.list_all(AssetQuery::default()));
     let _ = assert_send(client.time_series.list(None));
+    let _ = assert_send(
+        client
+            .time_series
+            .stream_datapoints(DatapointsFilter::default(), Default::default()),
+    );
+    let source = LinkedTimeSeriesSource::Assets(vec![]);
+    let _ = assert_send(client.time_series.stream_linked_datapoints(
+        &source,
+        DatapointsFilter::default(),
+        Default::default(),
+    ));
 }
 
 #[tokio::test]
@@ -200,3 +379,1928 @@ async fn test_no_auth_header_for_untrusted_urls() {
 
     mock_server.verify().await;
 }
+
+#[tokio::test]
+async fn test_api_version_header() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    let has_no_version_header = |req: &Request| !req.headers.contains_key("cdf-version");
+
+    Mock::given(method("GET"))
+        .and(path(get_path("", project, "assets")))
+        .and(has_no_version_header)
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "items": [] })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(get_path("", project, "assets")))
+        .and(header("cdf-version", "beta"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "items": [] })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(get_path("", project, "assets")))
+        .and(header("cdf-version", "alpha"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "items": [] })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+
+    client.assets.list(None).await.unwrap();
+    let beta_assets = client.assets.with_api_version(ApiVersion::Beta);
+    beta_assets.list(None).await.unwrap();
+
+    // A per-request version overrides the version set on the resource.
+    let _: Value = beta_assets
+        .api_client
+        .get_request("assets")
+        .api_version("alpha")
+        .accept_json()
+        .send()
+        .await
+        .unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn test_batch_loader_single_request() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/byids")))
+        .and(body_json_string(
+            json!({
+                "items": [{ "externalId": "ts1" }, { "externalId": "ts2" }, { "externalId": "ts3" }],
+                "ignoreUnknownIds": true
+            })
+            .to_string(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "id": 1,
+                "externalId": "ts1",
+                "isString": false,
+                "isStep": false,
+                "createdTime": 1234,
+                "lastUpdatedTime": 1234
+            }, {
+                "id": 3,
+                "externalId": "ts3",
+                "isString": false,
+                "isStep": false,
+                "createdTime": 1234,
+                "lastUpdatedTime": 1234
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let loader = BatchLoader::new(client.time_series.clone(), BatchLoaderConfig::default());
+
+    let (r1, r2, r3) = future::join3(
+        loader.load(IdentityOrInstance::external_id("ts1")),
+        loader.load(IdentityOrInstance::external_id("ts2")),
+        loader.load(IdentityOrInstance::external_id("ts3")),
+    )
+    .await;
+    assert_eq!(1, r1.unwrap().unwrap().id);
+    assert!(r2.unwrap().is_none());
+    assert_eq!(3, r3.unwrap().unwrap().id);
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn test_datapoints_upload_queue() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    let sizes = Arc::new(Mutex::new(Vec::new()));
+    let sizes_ref = sizes.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data")))
+        .respond_with(move |req: &Request| {
+            let body = DataPointInsertionRequest::decode(req.body.as_slice()).unwrap();
+            let count: usize = body
+                .items
+                .iter()
+                .map(|i| match i.datapoint_type.as_ref().unwrap() {
+                    InsertDatapointType::NumericDatapoints(d) => d.datapoints.len(),
+                    InsertDatapointType::StringDatapoints(d) => d.datapoints.len(),
+                })
+                .sum();
+            sizes_ref.lock().unwrap().push((body.items.len(), count));
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let flushes = Arc::new(Mutex::new(Vec::new()));
+    let flushes_ref = flushes.clone();
+    let queue = DatapointsUploadQueue::new(
+        client.time_series.clone(),
+        DatapointsUploadQueueConfig {
+            max_queue_size: 150,
+            max_age: None,
+            max_datapoints_per_request: 60,
+            max_time_series_per_request: 2,
+            on_flush: Some(Arc::new(move |r: &DatapointsFlushResult| {
+                flushes_ref
+                    .lock()
+                    .unwrap()
+                    .push((r.uploaded_datapoints, r.is_success()));
+            })),
+            ..Default::default()
+        },
+    );
+
+    let dps = |n: i64| -> Vec<DatapointDouble> {
+        (0..n)
+            .map(|i| DatapointDouble {
+                timestamp: i,
+                value: Some(i as f64),
+                status: None,
+            })
+            .collect()
+    };
+    queue
+        .add(IdentityOrInstance::external_id("ts1"), dps(100))
+        .await
+        .unwrap();
+    queue
+        .add(IdentityOrInstance::external_id("ts2"), dps(20))
+        .await
+        .unwrap();
+    assert_eq!(120, queue.len());
+    // Exceeds the max queue size, so the queue is flushed.
+    queue
+        .add(IdentityOrInstance::from(3), dps(40))
+        .await
+        .unwrap();
+    assert!(queue.is_empty());
+
+    queue
+        .add(IdentityOrInstance::from(3), dps(5))
+        .await
+        .unwrap();
+    let res = queue.close().await;
+    assert_eq!(5, res.uploaded_datapoints);
+
+    assert_eq!(vec![(160, true), (5, true)], *flushes.lock().unwrap());
+    let sizes = sizes.lock().unwrap();
+    assert_eq!(165, sizes.iter().map(|s| s.1).sum::<usize>());
+    for (num_ts, num_dps) in sizes.iter() {
+        assert!(*num_ts <= 2);
+        assert!(*num_dps <= 60);
+    }
+}
+
+#[tokio::test]
+async fn test_datapoints_upload_queue_close_wakes_run() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let queue = DatapointsUploadQueue::new(
+        client.time_series.clone(),
+        DatapointsUploadQueueConfig {
+            max_age: Some(Duration::from_secs(30)),
+            ..Default::default()
+        },
+    );
+
+    let start = std::time::Instant::now();
+    let (_, res) = future::join(queue.run(), async {
+        queue
+            .add(
+                IdentityOrInstance::external_id("ts1"),
+                vec![DatapointDouble {
+                    timestamp: 0,
+                    value: Some(1.0),
+                    status: None,
+                }],
+            )
+            .await
+            .unwrap();
+        queue.close().await
+    })
+    .await;
+
+    // The runner is woken by close, instead of sleeping for the full max_age.
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(1, res.uploaded_datapoints);
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_datapoints_upload_queue_returns_failed_requests() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data")))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": { "code": 400, "message": "Bad request" }
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let queue = DatapointsUploadQueue::new(
+        client.time_series.clone(),
+        DatapointsUploadQueueConfig {
+            max_age: None,
+            ..Default::default()
+        },
+    );
+    queue
+        .add(
+            IdentityOrInstance::external_id("ts1"),
+            vec![DatapointDouble {
+                timestamp: 0,
+                value: Some(1.0),
+                status: None,
+            }],
+        )
+        .await
+        .unwrap();
+    let res = queue.close().await;
+
+    assert!(!res.is_success());
+    assert_eq!(1, res.failed_datapoints);
+    assert_eq!(1, res.failed_requests.len());
+    assert_eq!(1, res.failed_requests[0].items.len());
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_retrieve_datapoints_sharded() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // A time series with one datapoint per millisecond in [0, 1000).
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let requests_ref = requests.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/list")))
+        .respond_with(move |req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let item = &body["items"][0];
+            requests_ref.lock().unwrap().push((
+                item["start"].as_i64().unwrap(),
+                item.get("aggregates").is_some(),
+            ));
+            let start = item["start"].as_i64().unwrap().max(0);
+            let end = item["end"].as_i64().unwrap().min(1000);
+            let limit = item["limit"].as_i64().unwrap();
+            let datapoint_type = if item.get("aggregates").is_some() {
+                let datapoints = (start..end)
+                    .step_by(100)
+                    .map(|ts| AggregateDatapoint {
+                        timestamp: ts,
+                        count: 100.0,
+                        ..Default::default()
+                    })
+                    .collect();
+                ListDatapointType::AggregateDatapoints(AggregateDatapoints { datapoints })
+            } else {
+                let start = item["cursor"]
+                    .as_str()
+                    .map(|c| c.parse().unwrap())
+                    .unwrap_or(start);
+                let datapoints: Vec<_> = (start..end.min(start + limit))
+                    .map(|ts| NumericDatapoint {
+                        timestamp: ts,
+                        value: ts as f64,
+                        ..Default::default()
+                    })
+                    .collect();
+                ListDatapointType::NumericDatapoints(NumericDatapoints { datapoints })
+            };
+            let next_cursor = match &datapoint_type {
+                ListDatapointType::NumericDatapoints(d) => match d.datapoints.last() {
+                    Some(last) if last.timestamp + 1 < end => (last.timestamp + 1).to_string(),
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            };
+            let response = DataPointListResponse {
+                items: vec![DataPointListItem {
+                    id: 1,
+                    next_cursor,
+                    datapoint_type: Some(datapoint_type),
+                    ..Default::default()
+                }],
+            };
+            ResponseTemplate::new(200)
+                .set_body_raw(response.encode_to_vec(), "application/protobuf")
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let datapoints: Vec<_> = client
+        .time_series
+        .retrieve_datapoints_sharded(
+            DatapointsQuery {
+                id: IdentityOrInstance::from(1),
+                ..Default::default()
+            },
+            -500,
+            1500,
+            ShardedRetrievalOptions {
+                datapoints_per_shard: 250,
+                limit_per_request: 40,
+                sample_size: 50,
+                parallelism: 3,
+                ..Default::default()
+            },
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(1000, datapoints.len());
+    for (idx, dp) in datapoints.iter().enumerate() {
+        let EitherDataPoint::Numeric(dp) = dp else {
+            panic!("Expected numeric datapoint");
+        };
+        assert_eq!(idx as i64, dp.timestamp);
+    }
+    // One sample, one count aggregate request, and at least one request per shard.
+    let requests = requests.lock().unwrap();
+    assert!(requests.len() >= 6);
+    // The sample is reused by the first shard, so the start of the range is only fetched once.
+    assert_eq!(1, requests.iter().filter(|r| **r == (-500, false)).count());
+}
+
+#[tokio::test]
+async fn test_stream_subscription_data() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path(
+            "",
+            project,
+            "timeseries/subscriptions/byids",
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "externalId": "my-sub",
+                "partitionCount": 2,
+                "createdTime": 0,
+                "lastUpdatedTime": 0
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // Each partition returns one batch of changes, then nothing.
+    Mock::given(method("POST"))
+        .and(path(get_path(
+            "",
+            project,
+            "timeseries/subscriptions/data/list",
+        )))
+        .respond_with(|req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let partitions = body["partitions"].as_array().unwrap();
+            let fresh = partitions.iter().any(|p| p.get("cursor").is_none());
+            let updates = if fresh {
+                json!([{
+                    "timeSeries": { "id": 1, "externalId": "ts" },
+                    "upserts": [{ "timestamp": 1000, "value": 1.5 }],
+                    "deletes": [{ "inclusiveBegin": 0, "exclusiveEnd": 500 }]
+                }])
+            } else {
+                json!([])
+            };
+            let next: Vec<_> = partitions
+                .iter()
+                .map(|p| json!({ "index": p["index"], "nextCursor": "done" }))
+                .collect();
+            ResponseTemplate::new(200).set_body_json(json!({
+                "updates": updates,
+                "partitions": next,
+                "hasNext": fresh
+            }))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let batches: Vec<_> = client
+        .datapoint_subscriptions
+        .stream_data(ListSubscriptionDataRequest::new("my-sub", vec![]), true)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(2, batches.len());
+    let update = &batches[0].updates[0];
+    assert_eq!(Some("ts"), update.time_series.external_id.as_deref());
+    let SubscriptionDatapoint::Numeric(dp) = &update.upserts[0] else {
+        panic!("Expected numeric datapoint");
+    };
+    assert_eq!(Some(1.5), dp.value);
+    assert_eq!(Some(500), update.deletes[0].exclusive_end);
+
+    // The final batch is empty, but carries the cursors to resume from.
+    assert!(batches[1].is_empty());
+    assert!(!batches[1].has_next);
+    assert_eq!(2, batches[1].partitions.len());
+    for (idx, partition) in batches[1].partitions.iter().enumerate() {
+        assert_eq!(idx as u32, partition.index);
+        assert_eq!(Some("done"), partition.cursor.as_deref());
+    }
+}
+
+#[tokio::test]
+async fn test_stream_subscription_data_yields_cursor_only_batches() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // No changes, but each response advances the cursor.
+    Mock::given(method("POST"))
+        .and(path(get_path(
+            "",
+            project,
+            "timeseries/subscriptions/data/list",
+        )))
+        .respond_with(|req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let cursor = body["partitions"][0]["cursor"].as_str().unwrap_or("0");
+            let next = cursor.parse::<u32>().unwrap() + 1;
+            ResponseTemplate::new(200).set_body_json(json!({
+                "updates": [],
+                "partitions": [{ "index": 0, "nextCursor": next.to_string() }],
+                "hasNext": false
+            }))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let batches: Vec<_> = client
+        .datapoint_subscriptions
+        .stream_data(
+            ListSubscriptionDataRequest::new("my-sub", vec![SubscriptionPartition::new(0)]),
+            false,
+        )
+        .take(2)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(2, batches.len());
+    assert!(batches.iter().all(|b| b.is_empty()));
+    assert_eq!(Some("1"), batches[0].partitions[0].cursor.as_deref());
+    assert_eq!(Some("2"), batches[1].partitions[0].cursor.as_deref());
+}
+
+#[tokio::test]
+async fn test_retrieve_latest_datapoints_bulk() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // Every tenth time series does not exist. Numeric IDs are echoed back,
+    // external IDs are mapped to internal IDs offset by 1000.
+    let requests = Arc::new(Mutex::new(0));
+    let requests_ref = requests.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/latest")))
+        .respond_with(move |req: &Request| {
+            *requests_ref.lock().unwrap() += 1;
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            assert_eq!(Some(true), body["ignoreUnknownIds"].as_bool());
+            let items = body["items"].as_array().unwrap();
+            assert!(items.len() <= 100);
+            let items: Vec<_> = items
+                .iter()
+                .filter_map(|item| {
+                    let (id, external_id) = match item["externalId"].as_str() {
+                        Some(xid) => (1000 + xid[3..].parse::<i64>().unwrap(), Some(xid)),
+                        None => (item["id"].as_i64().unwrap(), None),
+                    };
+                    if id % 10 == 0 {
+                        return None;
+                    }
+                    Some(json!({
+                        "id": id,
+                        "externalId": external_id,
+                        "isString": false,
+                        "datapoints": [{ "timestamp": id, "value": id as f64 }]
+                    }))
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(json!({ "items": items }))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let queries: Vec<_> = (1..=150)
+        .map(|id| LatestDatapointsQuery::new(Identity::from(id), "now"))
+        .chain((1..=100).map(|i| LatestDatapointsQuery::new(format!("ts-{i}"), "now")))
+        .collect();
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let result = client
+        .time_series
+        .retrieve_latest_datapoints_bulk(&queries, true, 2)
+        .await
+        .unwrap();
+
+    assert_eq!(3, *requests.lock().unwrap());
+    assert_eq!(225, result.items.len());
+    assert_eq!(25, result.missing.len());
+    assert!(result.missing.contains(&IdentityOrInstance::from(10)));
+    assert!(result.missing.contains(&IdentityOrInstance::from("ts-20")));
+
+    let latest = &result.items[&IdentityOrInstance::from(7)];
+    assert_eq!(7, latest.id);
+    let latest = &result.items[&IdentityOrInstance::from("ts-7")];
+    assert_eq!(1007, latest.id);
+    assert_eq!(
+        Some(1007.0),
+        latest.datapoint.as_ref().unwrap().numeric().unwrap().value
+    );
+}
+
+#[tokio::test]
+async fn test_retrieve_latest_datapoints_bulk_instance_id() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/latest")))
+        .and(body_json_string(
+            json!({
+                "items": [
+                    { "before": "now", "instanceId": { "space": "sp", "externalId": "ts-a" } },
+                    { "before": "now", "instanceId": { "space": "sp", "externalId": "ts-b" } }
+                ],
+                "ignoreUnknownIds": true
+            })
+            .to_string(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "id": 3,
+                "instanceId": { "space": "sp", "externalId": "ts-a" },
+                "isString": false,
+                "datapoints": [{ "timestamp": 1, "value": 2.5 }]
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let ts_a = IdentityOrInstance::from(InstanceId {
+        space: "sp".to_owned(),
+        external_id: "ts-a".to_owned(),
+    });
+    let ts_b = IdentityOrInstance::from(InstanceId {
+        space: "sp".to_owned(),
+        external_id: "ts-b".to_owned(),
+    });
+    let queries = vec![
+        LatestDatapointsQuery::new(ts_a.clone(), "now"),
+        LatestDatapointsQuery::new(ts_b.clone(), "now"),
+    ];
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let result = client
+        .time_series
+        .retrieve_latest_datapoints_bulk(&queries, true, 1)
+        .await
+        .unwrap();
+
+    assert_eq!(1, result.items.len());
+    let latest = &result.items[&ts_a];
+    assert_eq!(3, latest.id);
+    assert_eq!(
+        Some(2.5),
+        latest.datapoint.as_ref().unwrap().numeric().unwrap().value
+    );
+    assert_eq!(vec![ts_b], result.missing);
+}
+
+#[tokio::test]
+async fn test_retrieve_datapoints_invalid_time_zone() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/list")))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let filter = DatapointsFilter {
+        items: vec![DatapointsQuery {
+            id: IdentityOrInstance::from(1),
+            ..Default::default()
+        }],
+        granularity: Some("5m".parse().unwrap()),
+        time_zone: Some("Europe/Oslo".parse().unwrap()),
+        ..Default::default()
+    };
+    let res = client.time_series.retrieve_datapoints(&filter).await;
+    assert!(matches!(
+        res,
+        Err(Error::TimeZone(
+            TimeZoneError::UnsupportedGranularity { .. }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_replace_datapoints() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let requests_ref = requests.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/delete")))
+        .respond_with(move |req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            requests_ref.lock().unwrap().push(body["items"][0].clone());
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        })
+        .mount(&mock_server)
+        .await;
+    let requests_ref = requests.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data")))
+        .respond_with(move |req: &Request| {
+            let body = DataPointInsertionRequest::decode(req.body.as_slice()).unwrap();
+            let Some(InsertDatapointType::NumericDatapoints(dps)) = &body.items[0].datapoint_type
+            else {
+                panic!("Expected numeric datapoints");
+            };
+            let timestamps: Vec<_> = dps.datapoints.iter().map(|d| d.timestamp).collect();
+            requests_ref.lock().unwrap().push(json!(timestamps));
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let datapoints: Vec<_> = [30, 10, 20]
+        .into_iter()
+        .map(|ts| DatapointDouble {
+            timestamp: ts,
+            value: Some(1.0),
+            status: None,
+        })
+        .collect();
+
+    let err = client
+        .time_series
+        .replace_datapoints(1, 15..100, datapoints.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(15, err.replaced_until);
+    assert_eq!(15, err.deleted_until);
+    assert!(requests.lock().unwrap().is_empty());
+
+    client
+        .time_series
+        .replace_datapoints(1, 0..100, datapoints)
+        .await
+        .unwrap();
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        vec![
+            json!({ "inclusiveBegin": 0, "exclusiveEnd": 100, "id": 1 }),
+            json!([10, 20, 30]),
+        ],
+        *requests
+    );
+}
+
+#[tokio::test]
+async fn test_stream_datapoint_views() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // 25 datapoints, returned 10 at a time.
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/list")))
+        .respond_with(move |req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let item = &body["items"][0];
+            let start = item["cursor"]
+                .as_str()
+                .map(|c| c.parse().unwrap())
+                .unwrap_or(0i64);
+            let end = (start + 10).min(25);
+            let response = DataPointListResponse {
+                items: vec![DataPointListItem {
+                    id: 1,
+                    external_id: "ts".to_owned(),
+                    next_cursor: if end < 25 {
+                        end.to_string()
+                    } else {
+                        String::new()
+                    },
+                    datapoint_type: Some(ListDatapointType::NumericDatapoints(NumericDatapoints {
+                        datapoints: (start..end)
+                            .map(|ts| NumericDatapoint {
+                                timestamp: ts,
+                                value: ts as f64 * 2.0,
+                                ..Default::default()
+                            })
+                            .collect(),
+                    })),
+                    ..Default::default()
+                }],
+            };
+            ResponseTemplate::new(200)
+                .set_body_raw(response.encode_to_vec(), "application/protobuf")
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let filter = DatapointsFilter {
+        items: vec![DatapointsQuery {
+            id: IdentityOrInstance::from("ts"),
+            limit: Some(10),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let batches: Vec<_> = client
+        .time_series
+        .stream_datapoint_views(filter.clone(), Default::default())
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(3, batches.len());
+    let mut expected = 0;
+    for batch in &batches {
+        for (ts, dp) in batch.datapoints() {
+            assert_eq!(1, ts.id());
+            assert_eq!(Some("ts"), ts.external_id());
+            assert_eq!(&IdentityOrInstance::from("ts"), ts.original_id());
+            assert_eq!(
+                DatapointView::Numeric(expected, Some(expected as f64 * 2.0), None),
+                dp
+            );
+            expected += 1;
+        }
+    }
+    assert_eq!(25, expected);
+
+    let owned: Vec<_> = client
+        .time_series
+        .stream_datapoints(filter, Default::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(25, owned.len());
+    assert_eq!(Some(48.0), owned[24].as_numeric().unwrap().value);
+}
+
+#[tokio::test]
+async fn test_datapoints_upload_queue_compression() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    let uploaded = Arc::new(Mutex::new(Vec::new()));
+    let uploaded_ref = uploaded.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data")))
+        .respond_with(move |req: &Request| {
+            let body = DataPointInsertionRequest::decode(req.body.as_slice()).unwrap();
+            for item in body.items {
+                if let Some(InsertDatapointType::NumericDatapoints(d)) = item.datapoint_type {
+                    uploaded_ref
+                        .lock()
+                        .unwrap()
+                        .extend(d.datapoints.iter().map(|dp| dp.timestamp));
+                }
+            }
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    // Only ts1 is compressed.
+    let compressor = DatapointsCompressor::new(None).with_series(
+        IdentityOrInstance::external_id("ts1"),
+        CompressionConfig {
+            swinging_door: Some(0.1),
+            ..Default::default()
+        },
+    );
+    let queue = DatapointsUploadQueue::new(
+        client.time_series.clone(),
+        DatapointsUploadQueueConfig {
+            max_age: None,
+            compression: Some(compressor),
+            ..Default::default()
+        },
+    );
+
+    // A straight line, which compresses to its first and last datapoint.
+    let dps = |range: std::ops::Range<i64>| -> Vec<DatapointDouble> {
+        range
+            .map(|i| DatapointDouble {
+                timestamp: i,
+                value: Some(i as f64),
+                status: None,
+            })
+            .collect()
+    };
+    queue
+        .add(IdentityOrInstance::external_id("ts1"), dps(0..50))
+        .await
+        .unwrap();
+    queue
+        .add(IdentityOrInstance::external_id("ts1"), dps(50..100))
+        .await
+        .unwrap();
+    queue
+        .add(IdentityOrInstance::external_id("ts2"), dps(0..10))
+        .await
+        .unwrap();
+    assert_eq!(11, queue.len());
+
+    let res = queue.close().await;
+    assert_eq!(12, res.uploaded_datapoints);
+    let mut uploaded = uploaded.lock().unwrap().clone();
+    uploaded.sort();
+    assert_eq!(vec![0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 99], uploaded);
+}
+
+#[tokio::test]
+async fn test_tail_datapoints() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // Every poll makes 5 more datapoints available, the third poll fails.
+    let starts = Arc::new(Mutex::new(Vec::new()));
+    let starts_ref = starts.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/list")))
+        .respond_with(move |req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let start = body["items"][0]["start"].as_i64();
+            let mut starts = starts_ref.lock().unwrap();
+            starts.push(start);
+            let poll = starts.len() as i64;
+            if poll == 3 {
+                return ResponseTemplate::new(400)
+                    .set_body_json(json!({ "error": { "code": 400, "message": "Bad" } }));
+            }
+            let response = DataPointListResponse {
+                items: vec![DataPointListItem {
+                    id: 1,
+                    external_id: "ts".to_owned(),
+                    datapoint_type: Some(ListDatapointType::NumericDatapoints(NumericDatapoints {
+                        datapoints: (start.unwrap_or(0)..poll * 5)
+                            .map(|ts| NumericDatapoint {
+                                timestamp: ts,
+                                value: ts as f64,
+                                ..Default::default()
+                            })
+                            .collect(),
+                    })),
+                    ..Default::default()
+                }],
+            };
+            ResponseTemplate::new(200)
+                .set_body_raw(response.encode_to_vec(), "application/protobuf")
+        })
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let results: Vec<_> = client
+        .time_series
+        .tail_datapoints(
+            DatapointsFilter {
+                items: vec![DatapointsQuery {
+                    id: IdentityOrInstance::from("ts"),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            DatapointsTailOptions {
+                poll_interval: Duration::from_millis(10),
+                overlap: Duration::from_millis(2),
+                ..Default::default()
+            },
+        )
+        .take(21)
+        .collect()
+        .await;
+
+    let timestamps: Vec<_> = results
+        .iter()
+        .filter_map(|r| r.as_ref().ok())
+        .map(|dp| dp.as_numeric().unwrap().timestamp)
+        .collect();
+    assert_eq!((0..20).collect::<Vec<_>>(), timestamps);
+    assert_eq!(1, results.iter().filter(|r| r.is_err()).count());
+    assert_eq!(
+        vec![None, Some(3), Some(8), Some(8)],
+        *starts.lock().unwrap()
+    );
+}
+
+fn linked_ts_json(id: i64) -> Value {
+    json!({
+        "id": id,
+        "externalId": format!("ts{id}"),
+        "name": format!("Sensor {id}"),
+        "isString": false,
+        "isStep": false,
+        "createdTime": 1234,
+        "lastUpdatedTime": 1234
+    })
+}
+
+async fn mount_linked_datapoints(mock_server: &MockServer, project: &str) {
+    // One datapoint per time series, with the ID as timestamp.
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/list")))
+        .respond_with(|req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let response = DataPointListResponse {
+                items: body["items"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|item| {
+                        let id = item["id"].as_i64().unwrap();
+                        DataPointListItem {
+                            id,
+                            datapoint_type: Some(ListDatapointType::NumericDatapoints(
+                                NumericDatapoints {
+                                    datapoints: vec![NumericDatapoint {
+                                        timestamp: id,
+                                        value: id as f64,
+                                        ..Default::default()
+                                    }],
+                                },
+                            )),
+                            ..Default::default()
+                        }
+                    })
+                    .collect(),
+            };
+            ResponseTemplate::new(200)
+                .set_body_raw(response.encode_to_vec(), "application/protobuf")
+        })
+        .mount(mock_server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_retrieve_linked_datapoints_assets() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // Time series 2 is linked to both assets, and should only be returned once.
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/list")))
+        .respond_with(|req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let ids = if body["filter"]["assetIds"].is_array() {
+                vec![1, 2]
+            } else {
+                assert_eq!(json!(["pump"]), body["filter"]["assetExternalIds"]);
+                vec![2, 3]
+            };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "items": ids.into_iter().map(linked_ts_json).collect::<Vec<_>>()
+            }))
+        })
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    mount_linked_datapoints(&mock_server, project).await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let source = LinkedTimeSeriesSource::Assets(vec![Identity::from(123), Identity::from("pump")]);
+    let result = client
+        .time_series
+        .retrieve_linked_datapoints(&source, Default::default(), Default::default())
+        .await
+        .unwrap();
+
+    assert_eq!(3, result.len());
+    for (series, id) in result.iter().zip([1, 2, 3]) {
+        assert_eq!(id, series.timeseries.id);
+        assert_eq!(Some(format!("Sensor {id}")), series.timeseries.name);
+        let Some(DatapointsEnumType::NumericDatapoints(dps)) = &series.datapoints else {
+            panic!("Expected numeric datapoints, got {:?}", series.datapoints);
+        };
+        assert_eq!(1, dps.len());
+        assert_eq!(id, dps[0].timestamp);
+    }
+}
+
+#[tokio::test]
+async fn test_stream_linked_datapoints() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/list")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [linked_ts_json(1), linked_ts_json(2)]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    mount_linked_datapoints(&mock_server, project).await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let source = LinkedTimeSeriesSource::AssetSubtrees(vec![Identity::from(123)]);
+    let batches: Vec<_> = client
+        .time_series
+        .stream_linked_datapoints(&source, Default::default(), Default::default())
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(2, batches.len());
+    for (batch, id) in batches.iter().zip([1, 2]) {
+        assert_eq!(id, batch.timeseries.id);
+        assert_eq!(Some(format!("Sensor {id}")), batch.timeseries.name);
+        let Some(DatapointsEnumType::NumericDatapoints(dps)) = &batch.datapoints else {
+            panic!("Expected numeric datapoints, got {:?}", batch.datapoints);
+        };
+        assert_eq!(id, dps[0].timestamp);
+    }
+}
+
+#[tokio::test]
+async fn test_retrieve_linked_datapoints_instance() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    // Linked time series are returned over two pages.
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "models/instances/query")))
+        .respond_with(|req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let through = &body["with"]["timeseries"]["nodes"]["through"];
+            assert_eq!("assets", through["identifier"]);
+            assert_eq!("CogniteTimeSeries", through["view"]["externalId"]);
+            assert_eq!("inwards", body["with"]["timeseries"]["nodes"]["direction"]);
+            let (external_id, cursor) = match body["cursors"]["timeseries"].as_str() {
+                None => ("ts-a", json!({ "timeseries": "next" })),
+                Some("next") => ("ts-b", json!({})),
+                Some(c) => panic!("Unexpected cursor {c}"),
+            };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "items": {
+                    "timeseries": [{
+                        "instanceType": "node",
+                        "space": "my_space",
+                        "externalId": external_id,
+                        "version": 1,
+                        "createdTime": 0,
+                        "lastUpdatedTime": 0
+                    }]
+                },
+                "nextCursor": cursor
+            }))
+        })
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/byids")))
+        .and(body_json_string(
+            json!({
+                "items": [
+                    { "instanceId": { "space": "my_space", "externalId": "ts-a" } },
+                    { "instanceId": { "space": "my_space", "externalId": "ts-b" } }
+                ],
+                "ignoreUnknownIds": true
+            })
+            .to_string(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [linked_ts_json(4), linked_ts_json(5)]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    mount_linked_datapoints(&mock_server, project).await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let source = LinkedTimeSeriesSource::cognite_asset(InstanceId {
+        space: "my_space".to_owned(),
+        external_id: "pump".to_owned(),
+    });
+    let result = client
+        .time_series
+        .retrieve_linked_datapoints(&source, Default::default(), Default::default())
+        .await
+        .unwrap();
+
+    assert_eq!(2, result.len());
+    assert_eq!(4, result[0].timeseries.id);
+    assert_eq!(5, result[1].timeseries.id);
+    assert!(result.iter().all(|s| s.datapoints.is_some()));
+}
+
+async fn mount_file_datapoints(mock_server: &MockServer, project: &str) {
+    // Time series "a" is numeric, with a bad datapoint at 2, "b" is a string time series.
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data/list")))
+        .respond_with(|req: &Request| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap();
+            let items = body["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| match item["externalId"].as_str().unwrap() {
+                    "a" => DataPointListItem {
+                        id: 1,
+                        external_id: "a".to_owned(),
+                        datapoint_type: Some(ListDatapointType::NumericDatapoints(
+                            NumericDatapoints {
+                                datapoints: vec![
+                                    NumericDatapoint {
+                                        timestamp: 1,
+                                        value: 1.5,
+                                        ..Default::default()
+                                    },
+                                    NumericDatapoint {
+                                        timestamp: 2,
+                                        null_value: true,
+                                        status: Some(Status {
+                                            code: 0x8000_0000,
+                                            symbol: "Bad".to_owned(),
+                                        }),
+                                        ..Default::default()
+                                    },
+                                ],
+                            },
+                        )),
+                        ..Default::default()
+                    },
+                    _ => DataPointListItem {
+                        id: 2,
+                        external_id: "b".to_owned(),
+                        is_string: true,
+                        datapoint_type: Some(ListDatapointType::StringDatapoints(
+                            StringDatapoints {
+                                datapoints: vec![
+                                    StringDatapoint {
+                                        timestamp: 2,
+                                        value: "x".to_owned(),
+                                        ..Default::default()
+                                    },
+                                    StringDatapoint {
+                                        timestamp: 3,
+                                        value: "y, z".to_owned(),
+                                        ..Default::default()
+                                    },
+                                ],
+                            },
+                        )),
+                        ..Default::default()
+                    },
+                })
+                .collect();
+            let response = DataPointListResponse { items };
+            ResponseTemplate::new(200)
+                .set_body_raw(response.encode_to_vec(), "application/protobuf")
+        })
+        .mount(mock_server)
+        .await;
+}
+
+/// Record inserted datapoints as `<time series> <timestamp> <value> <status code>`,
+/// one list per request.
+async fn capture_inserted_datapoints(
+    mock_server: &MockServer,
+    project: &str,
+) -> Arc<Mutex<Vec<Vec<String>>>> {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let requests_ref = requests.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/data")))
+        .respond_with(move |req: &Request| {
+            let body = DataPointInsertionRequest::decode(req.body.as_slice()).unwrap();
+            let mut lines = vec![];
+            for item in body.items {
+                let id = match item.time_series_reference.unwrap() {
+                    TimeSeriesReference::Id(id) => id.to_string(),
+                    TimeSeriesReference::ExternalId(xid) => xid,
+                    TimeSeriesReference::InstanceId(i) => format!("{}:{}", i.space, i.external_id),
+                };
+                let status = |s: Option<Status>| s.map(|s| s.code).unwrap_or_default();
+                match item.datapoint_type.unwrap() {
+                    InsertDatapointType::NumericDatapoints(dps) => {
+                        lines.extend(dps.datapoints.into_iter().map(|dp| {
+                            let value = if dp.null_value {
+                                "null".to_owned()
+                            } else {
+                                dp.value.to_string()
+                            };
+                            format!("{id} {} {value} {}", dp.timestamp, status(dp.status))
+                        }))
+                    }
+                    InsertDatapointType::StringDatapoints(dps) => {
+                        lines.extend(dps.datapoints.into_iter().map(|dp| {
+                            let value = if dp.null_value { "null" } else { &dp.value };
+                            format!("{id} {} {value} {}", dp.timestamp, status(dp.status))
+                        }))
+                    }
+                }
+            }
+            requests_ref.lock().unwrap().push(lines);
+            ResponseTemplate::new(200).set_body_json(json!({}))
+        })
+        .mount(mock_server)
+        .await;
+    requests
+}
+
+fn file_datapoints_filter() -> DatapointsFilter {
+    DatapointsFilter {
+        items: vec![
+            DatapointsQuery {
+                id: IdentityOrInstance::from("a"),
+                ..Default::default()
+            },
+            DatapointsQuery {
+                id: IdentityOrInstance::from("b"),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_export_datapoints_csv() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    mount_file_datapoints(&mock_server, project).await;
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+
+    let mut long = vec![];
+    let rows = client
+        .time_series
+        .export_datapoints_csv(file_datapoints_filter(), &mut long, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(4, rows);
+    assert_eq!(
+        "id,external_id,instance_space,instance_external_id,timestamp,value,string_value,status_code\n\
+         1,a,,,1,1.5,,\n\
+         1,a,,,2,,,Bad\n\
+         2,b,,,2,,x,\n\
+         2,b,,,3,,\"y, z\",\n",
+        String::from_utf8(long).unwrap()
+    );
+
+    // Read one time series at a time, so that rows are merged across batches.
+    let mut wide = vec![];
+    let options = CsvExportOptions {
+        layout: CsvLayout::Wide,
+        timestamp_format: TimestampFormat::Rfc3339,
+        wide_status_suffix: Some("_status".to_owned()),
+        stream: DatapointsStreamOptions {
+            batch_size: 1,
+            parallelism: 1,
+        },
+        ..Default::default()
+    };
+    let rows = client
+        .time_series
+        .export_datapoints_csv(file_datapoints_filter(), &mut wide, options)
+        .await
+        .unwrap();
+    assert_eq!(3, rows);
+    assert_eq!(
+        "timestamp,a,a_status,b,b_status\n\
+         1970-01-01T00:00:00.001Z,1.5,,,\n\
+         1970-01-01T00:00:00.002Z,,Bad,x,\n\
+         1970-01-01T00:00:00.003Z,,,\"y, z\",\n",
+        String::from_utf8(wide).unwrap()
+    );
+
+    let aggregates = DatapointsFilter {
+        aggregates: Some(vec![Aggregate::Average]),
+        ..file_datapoints_filter()
+    };
+    assert!(client
+        .time_series
+        .export_datapoints_csv(aggregates, vec![], Default::default())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_import_datapoints_csv() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    let requests = capture_inserted_datapoints(&mock_server, project).await;
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+
+    let long = "timestamp,external_id,instance_space,instance_external_id,id,value,string_value,status_code\n\
+                1,a,,,,1.5,,\n\
+                2,a,,,,,,Bad\n\
+                3,,sp,x,,,hello,\n\
+                4,,,,42,2,,2147483648\n";
+    let count = client
+        .time_series
+        .import_datapoints_csv(
+            long.as_bytes(),
+            CsvImportOptions {
+                batch_size: 2,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(4, count);
+    assert_eq!(
+        vec![
+            vec!["a 1 1.5 0", "a 2 null 2147483648"],
+            vec!["sp:x 3 hello 0", "42 4 2 2147483648"],
+        ],
+        std::mem::take(&mut *requests.lock().unwrap())
+    );
+
+    let wide = "timestamp;a;a_status;b\n1;1.5;;2\n2.5;;Bad;\n";
+    let count = client
+        .time_series
+        .import_datapoints_csv(
+            wide.as_bytes(),
+            CsvImportOptions {
+                layout: CsvLayout::Wide,
+                delimiter: b';',
+                timestamp_format: TimestampFormat::EpochSeconds,
+                wide_status_suffix: Some("_status".to_owned()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(3, count);
+    assert_eq!(
+        vec![vec!["a 1000 1.5 0", "a 2500 null 2147483648", "b 1000 2 0"]],
+        std::mem::take(&mut *requests.lock().unwrap())
+    );
+
+    let invalid = "timestamp,external_id,value\n1,a,1\nnot a timestamp,a,2\n";
+    let err = client
+        .time_series
+        .import_datapoints_csv(invalid.as_bytes(), Default::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Line 3"), "{err}");
+}
+
+#[tokio::test]
+async fn test_import_datapoints_csv_nulls_and_empty_strings() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    let requests = capture_inserted_datapoints(&mock_server, project).await;
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+
+    // The type of "s" is only known from the third row, and later rows are in other batches.
+    // "n" has no values at all, and is inserted as numeric at the end.
+    let long = "timestamp,external_id,value,string_value,status_code\n\
+                1,s,,,Bad\n\
+                2,n,,,Bad\n\
+                3,s,,x,\n\
+                4,s,,,\n\
+                5,s,,,Bad\n\
+                6,n,,,Bad\n";
+    let count = client
+        .time_series
+        .import_datapoints_csv(
+            long.as_bytes(),
+            CsvImportOptions {
+                batch_size: 1,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(6, count);
+    assert_eq!(
+        vec![
+            vec!["s 1 null 2147483648", "s 3 x 0"],
+            vec!["s 4  0"],
+            vec!["s 5 null 2147483648"],
+            vec!["n 2 null 2147483648"],
+            vec!["n 6 null 2147483648"],
+        ],
+        *requests.lock().unwrap()
+    );
+}
+
+#[cfg(feature = "parquet")]
+#[tokio::test]
+async fn test_datapoints_parquet_round_trip() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    mount_file_datapoints(&mock_server, project).await;
+    let requests = capture_inserted_datapoints(&mock_server, project).await;
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+
+    let mut file = vec![];
+    let rows = client
+        .time_series
+        .export_datapoints_parquet(file_datapoints_filter(), &mut file, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(4, rows);
+
+    let count = client
+        .time_series
+        .import_datapoints_parquet(Bytes::from(file), Default::default())
+        .await
+        .unwrap();
+    assert_eq!(4, count);
+    assert_eq!(
+        vec![vec![
+            "a 1 1.5 0",
+            "a 2 null 2147483648",
+            "b 2 x 0",
+            "b 3 y, z 0"
+        ]],
+        *requests.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_units_converter_and_target_unit_validation() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+
+    Mock::given(method("GET"))
+        .and(path(get_path("", project, "units")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "externalId": "temperature:deg_c",
+                "name": "DEG_C",
+                "longName": "degree Celsius",
+                "symbol": "°C",
+                "aliasNames": ["degC"],
+                "quantity": "Temperature",
+                "conversion": { "multiplier": 1.0, "offset": 273.15 },
+                "source": "qudt.org"
+            }, {
+                "externalId": "temperature:deg_f",
+                "name": "DEG_F",
+                "quantity": "Temperature",
+                "conversion": { "multiplier": 0.5555555555555556, "offset": 255.3722222222222 }
+            }, {
+                "externalId": "pressure:bar",
+                "name": "BAR",
+                "quantity": "Pressure",
+                "conversion": { "multiplier": 100000.0, "offset": 0.0 }
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(get_path("", project, "units/systems")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "name": "Imperial",
+                "quantities": [{ "name": "Temperature", "unitExternalId": "temperature:deg_f" }]
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "timeseries/byids")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{
+                "id": 1,
+                "externalId": "temp",
+                "isString": false,
+                "isStep": false,
+                "unitExternalId": "temperature:deg_c",
+                "createdTime": 0,
+                "lastUpdatedTime": 0
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = get_client_for_mocking(&mock_server.uri(), project);
+    let units = client.units.converter().await.unwrap();
+    let f = units
+        .convert(100.0, "temperature:deg_c", "temperature:deg_f")
+        .unwrap();
+    assert!((f - 212.0).abs() < 1e-9);
+
+    let filter = |target_unit: Option<&str>, target_unit_system: Option<&str>| DatapointsFilter {
+        items: vec![DatapointsQuery {
+            id: Identity::from("temp").into(),
+            target_unit: target_unit.map(|t| t.to_owned()),
+            target_unit_system: target_unit_system.map(|t| t.to_owned()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    client
+        .time_series
+        .validate_target_units(&filter(Some("temperature:deg_f"), None), &units)
+        .await
+        .unwrap();
+    client
+        .time_series
+        .validate_target_units(&filter(None, Some("Imperial")), &units)
+        .await
+        .unwrap();
+    let err = client
+        .time_series
+        .validate_target_units(&filter(Some("pressure:bar"), None), &units)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::UnitConversion(UnitConversionError::IncompatibleUnits { .. })
+    ));
+    assert_eq!(
+        "Unit conversion error: Cannot convert from 'temperature:deg_c' (Temperature) to 'pressure:bar' (Pressure)",
+        err.to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_resumable_multipart_upload() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    let uri = mock_server.uri();
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "files/initmultipartupload")))
+        .and(query_param("parts", "3"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 5,
+            "name": "data.bin",
+            "uploaded": false,
+            "createdTime": 0,
+            "lastUpdatedTime": 0,
+            "uploadId": "upload-1",
+            "uploadUrls": (0..3).map(|i| format!("{uri}/upload/{i}")).collect::<Vec<_>>()
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Part 1 fails the first time, simulating a crash during the upload.
+    let uploads = Arc::new(Mutex::new(Vec::<(String, usize)>::new()));
+    let uploads_inner = uploads.clone();
+    Mock::given(method("PUT"))
+        .respond_with(move |req: &Request| {
+            let mut uploads = uploads_inner.lock().unwrap();
+            let part = req.url.path().to_owned();
+            let failed_before = uploads.iter().any(|(p, _)| p == "/upload/1");
+            uploads.push((part.clone(), req.body.len()));
+            if part == "/upload/1" && !failed_before {
+                ResponseTemplate::new(400)
+            } else {
+                ResponseTemplate::new(200)
+            }
+        })
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "files/completemultipartupload")))
+        .and(body_json_string(
+            json!({ "id": 5, "uploadId": "upload-1" }).to_string(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("cognite-upload-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("data.bin");
+    let state_path = dir.join("data.bin.upload");
+    let data: Vec<u8> = (0..11 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&file_path, &data).unwrap();
+
+    let client = get_client_for_mocking(&uri, project);
+    let item = AddFile {
+        name: "data.bin".to_owned(),
+        ..Default::default()
+    };
+    // The 400 response is not transient, so it is not retried.
+    let config = ResumableUploadConfig {
+        parallelism: 1,
+        max_part_retries: 3,
+        initial_retry_delay: Duration::from_millis(1),
+        ..Default::default()
+    };
+    client
+        .files
+        .upload_file_resumable(&file_path, &item, &state_path, config.clone())
+        .await
+        .unwrap_err();
+    let state = MultipartUploadState::load(&state_path)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        vec![0],
+        state.completed_parts.into_iter().collect::<Vec<_>>()
+    );
+
+    let file = client
+        .files
+        .upload_file_resumable(&file_path, &item, &state_path, config)
+        .await
+        .unwrap();
+    assert_eq!(5, file.id);
+    assert!(!state_path.exists());
+    assert_eq!(
+        vec![
+            ("/upload/0".to_owned(), 5 * 1024 * 1024),
+            ("/upload/1".to_owned(), 5 * 1024 * 1024),
+            ("/upload/1".to_owned(), 5 * 1024 * 1024),
+            ("/upload/2".to_owned(), 1024 * 1024),
+        ],
+        *uploads.lock().unwrap()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_multipart_upload_part_stream_uses_part_url() {
+    let mock_server = MockServer::start().await;
+    let uri = mock_server.uri();
+
+    for part in 0..2 {
+        Mock::given(method("PUT"))
+            .and(path(format!("/upload/{part}")))
+            .and(body_string(format!("part {part}")))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    let client = get_client_for_mocking(&uri, "my_project");
+    let uploader = MultipartUploader::new(
+        &client.files,
+        Identity::Id { id: 5 }.into(),
+        MultiUploadUrls {
+            upload_id: "upload-1".to_owned(),
+            upload_urls: (0..2).map(|i| format!("{uri}/upload/{i}")).collect(),
+            instance_id: None,
+        },
+    );
+    for part in [1, 0] {
+        let data = Bytes::from(format!("part {part}"));
+        let size = data.len() as u64;
+        uploader
+            .upload_part_stream(part, stream::iter([Ok::<_, std::io::Error>(data)]), size)
+            .await
+            .unwrap();
+    }
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn test_ranged_resumable_download() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    let uri = mock_server.uri();
+    let data: Vec<u8> = (0..35u8).collect();
+
+    // The first two download links are "v1", which expires before the third chunk.
+    let link_requests = Arc::new(Mutex::new(0));
+    let link_requests_inner = link_requests.clone();
+    let link_uri = uri.clone();
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "files/downloadlink")))
+        .respond_with(move |_: &Request| {
+            let mut count = link_requests_inner.lock().unwrap();
+            *count += 1;
+            let version = if *count <= 2 { "v1" } else { "v2" };
+            ResponseTemplate::new(200).set_body_json(json!({
+                "items": [{ "id": 7, "downloadUrl": format!("{link_uri}/download/{version}") }]
+            }))
+        })
+        .mount(&mock_server)
+        .await;
+
+    let ranges = Arc::new(Mutex::new(Vec::<(String, String)>::new()));
+    let ranges_inner = ranges.clone();
+    let body = data.clone();
+    Mock::given(method("GET"))
+        .respond_with(move |req: &Request| {
+            let range = req.headers["range"].to_str().unwrap().to_owned();
+            let url = req.url.path().to_owned();
+            ranges_inner
+                .lock()
+                .unwrap()
+                .push((url.clone(), range.clone()));
+            let (start, end) = range
+                .strip_prefix("bytes=")
+                .and_then(|r| r.split_once('-'))
+                .unwrap();
+            let start: usize = start.parse().unwrap();
+            let end: usize = end.parse::<usize>().unwrap().min(body.len() - 1);
+            if url == "/download/v1" && start >= 20 {
+                return ResponseTemplate::new(403);
+            }
+            ResponseTemplate::new(206)
+                .insert_header(
+                    "Content-Range",
+                    format!("bytes {start}-{end}/{}", body.len()),
+                )
+                .set_body_bytes(body[start..=end].to_vec())
+        })
+        .mount(&mock_server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("cognite-download-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("data.bin");
+
+    let client = get_client_for_mocking(&uri, project);
+    let progress = Arc::new(Mutex::new(vec![]));
+    let progress_inner = progress.clone();
+    let config = RangedDownloadConfig {
+        chunk_size: 10,
+        parallelism: 1,
+        max_chunk_retries: 0,
+        on_progress: Some(Arc::new(move |p: DownloadProgress| {
+            progress_inner.lock().unwrap().push(p.downloaded)
+        })),
+        ..Default::default()
+    };
+    // Without retries, the expired URL fails the download after two chunks.
+    client
+        .files
+        .download_to_path(Identity::from(7).into(), &file_path, config.clone())
+        .await
+        .unwrap_err();
+    assert!(!file_path.exists());
+
+    let config = RangedDownloadConfig {
+        max_chunk_retries: 2,
+        initial_retry_delay: Duration::from_millis(1),
+        ..config
+    };
+    let size = client
+        .files
+        .download_to_path(Identity::from(7).into(), &file_path, config)
+        .await
+        .unwrap();
+    assert_eq!(35, size);
+    assert_eq!(data, std::fs::read(&file_path).unwrap());
+    assert_eq!(vec!["data.bin"], {
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    });
+    assert_eq!(3, *link_requests.lock().unwrap());
+    assert_eq!(
+        vec![
+            ("/download/v1", "bytes=0-9"),
+            ("/download/v1", "bytes=10-19"),
+            ("/download/v1", "bytes=20-29"),
+            ("/download/v1", "bytes=20-29"),
+            ("/download/v2", "bytes=20-29"),
+            ("/download/v2", "bytes=30-34"),
+        ],
+        ranges
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(u, r)| (u.as_str(), r.as_str()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(vec![10, 20, 20, 30, 35], *progress.lock().unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_ranged_download_discards_changed_file() {
+    let mock_server = MockServer::start().await;
+    let project = "my_project";
+    let uri = mock_server.uri();
+
+    Mock::given(method("POST"))
+        .and(path(get_path("", project, "files/downloadlink")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [{ "id": 7, "downloadUrl": format!("{uri}/download") }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // Version "a" fails after the first chunk, then the file is replaced by version "b",
+    // which has the same size.
+    let versions = [(vec![1u8; 25], "\"a\""), (vec![2u8; 25], "\"b\"")];
+    let version = Arc::new(Mutex::new(0));
+    let version_inner = version.clone();
+    Mock::given(method("GET"))
+        .and(path("/download"))
+        .respond_with(move |req: &Request| {
+            let range = req.headers["range"].to_str().unwrap();
+            let (start, end) = range
+                .strip_prefix("bytes=")
+                .and_then(|r| r.split_once('-'))
+                .unwrap();
+            let start: usize = start.parse().unwrap();
+            let current = *version_inner.lock().unwrap();
+            let (body, etag) = &versions[current];
+            let end: usize = end.parse::<usize>().unwrap().min(body.len() - 1);
+            if current == 0 && start > 0 {
+                return ResponseTemplate::new(404);
+            }
+            ResponseTemplate::new(206)
+                .insert_header(
+                    "Content-Range",
+                    format!("bytes {start}-{end}/{}", body.len()),
+                )
+                .insert_header("ETag", *etag)
+                .set_body_bytes(body[start..=end].to_vec())
+        })
+        .mount(&mock_server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("cognite-download-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("data.bin");
+    let state_path = dir.join("data.bin.part.state");
+
+    let client = get_client_for_mocking(&uri, project);
+    let config = RangedDownloadConfig {
+        chunk_size: 10,
+        parallelism: 1,
+        ..Default::default()
+    };
+    client
+        .files
+        .download_to_path(Identity::from(7).into(), &file_path, config.clone())
+        .await
+        .unwrap_err();
+    assert!(state_path.exists());
+
+    // Resuming detects the new ETag, and discards the partial download.
+    *version.lock().unwrap() = 1;
+    let err = client
+        .files
+        .download_to_path(Identity::from(7).into(), &file_path, config.clone())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("has changed"), "{err}");
+    assert!(!state_path.exists());
+    assert!(!dir.join("data.bin.part").exists());
+
+    let size = client
+        .files
+        .download_to_path(Identity::from(7).into(), &file_path, config)
+        .await
+        .unwrap();
+    assert_eq!(25, size);
+    assert_eq!(vec![2u8; 25], std::fs::read(&file_path).unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -237,7 +237,7 @@ async fn retrieve_latest() {
         .retrieve_latest_datapoints(
             &[LatestDatapointsQuery {
                 before: Some(format!("{}", start + 200_000)),
-                id: Identity::Id { id: ts.id },
+                id: Identity::Id { id: ts.id }.into(),
                 ..Default::default()
             }],
             false,
@@ -337,7 +337,7 @@ async fn create_retrieve_double_datapoints_with_status() {
                 before: Some((start + 10000).to_string()),
                 include_status: Some(true),
                 ignore_bad_data_points: Some(false),
-                id: Identity::from(ts.id),
+                id: Identity::from(ts.id).into(),
                 ..Default::default()
             }],
             false,